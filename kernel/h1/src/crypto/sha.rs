@@ -129,7 +129,9 @@ impl DigestEngine for ShaEngine {
     fn initialize_certificate(&self, certificate_id: u32) -> Result<(), DigestError> {
         let ref regs = unsafe { &*self.regs }.sha;
         regs.itop.set(0); // clear status
-
+        // Certificates produce a SHA-256 sized result, which update and
+        // finalize need to know about.
+        self.current_mode.set(Some(DigestMode::Sha256));
 
         regs.use_cert.set(certificate_id & CertificateMask::CertBits as u32 |
                           CertificateMask::Enable as u32);
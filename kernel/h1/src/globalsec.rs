@@ -53,8 +53,9 @@ register_structs! {
         (0x00e4 => flash_region1_ctrl: ReadWrite<u32, REGION_CTRL::Register>),
         (0x00e8 => flash_region2_ctrl: ReadWrite<u32, REGION_CTRL::Register>),
         (0x00ec => flash_region3_ctrl: ReadWrite<u32, REGION_CTRL::Register>),
+        (0x00f0 => flash_region4_ctrl: ReadWrite<u32, REGION_CTRL::Register>),
 
-        (0x00f0 => _reserved00f0),
+        (0x00f4 => _reserved00f4),
 
         (0x0230 => flash_region0_base_addr: ReadWrite<u32>),
         (0x0234 => flash_region0_size: ReadWrite<u32>),
@@ -64,8 +65,10 @@ register_structs! {
         (0x0244 => flash_region2_size: ReadWrite<u32>),
         (0x0248 => flash_region3_base_addr: ReadWrite<u32>),
         (0x024c => flash_region3_size: ReadWrite<u32>),
+        (0x0250 => flash_region4_base_addr: ReadWrite<u32>),
+        (0x0254 => flash_region4_size: ReadWrite<u32>),
 
-        (0x0250 => @END),
+        (0x0258 => @END),
     }
 }
 
@@ -91,6 +94,9 @@ pub struct Segments {
     pub ro_b: SegmentInfo,
     pub rw_a: SegmentInfo,
     pub rw_b: SegmentInfo,
+    // NVMEM directly follows the RW in each bank.
+    pub nv_a: SegmentInfo,
+    pub nv_b: SegmentInfo,
 }
 
 /// GlobalSec
@@ -163,7 +169,9 @@ impl GlobalSecHardware {
         // - REGION0 : Active RO image, already locked
         // - REGION1 : Active RW image, already locked
         // - REGION2 : inactive RO image
-        // - REGION3 : inactive RW image
+        // - REGION3 : inactive RW image and inactive NVMEM
+        // - REGION4 : personality data, read-only
+        // The value of a SIZE register is one less than the size of the region.
 
         const H1_FLASH_START: u32 = crate::hil::flash::h1_hw::H1_FLASH_START as u32;
 
@@ -182,7 +190,7 @@ impl GlobalSecHardware {
         // Enable the inactive RO for reads and writes.
         self.registers.flash_region2_base_addr.set(
             H1_FLASH_START + self.runtime_segment_info.inactive_ro.address);
-        self.registers.flash_region2_size.set(self.runtime_segment_info.inactive_ro.size);
+        self.registers.flash_region2_size.set(self.runtime_segment_info.inactive_ro.size - 1);
         self.registers.flash_region2_ctrl.write(
             REGION_CTRL::EN::SET +
             REGION_CTRL::RD_EN::SET +
@@ -193,21 +201,36 @@ impl GlobalSecHardware {
             addr if addr == H1_FLASH_START + segments.rw_a.address => {
                 self.runtime_segment_info.active_rw = segments.rw_a;
                 self.runtime_segment_info.inactive_rw = segments.rw_b;
+                self.runtime_segment_info.active_nv = segments.nv_a;
+                self.runtime_segment_info.inactive_nv = segments.nv_b;
             },
             addr if addr == H1_FLASH_START + segments.rw_b.address => {
                 self.runtime_segment_info.active_rw = segments.rw_b;
                 self.runtime_segment_info.inactive_rw = segments.rw_a;
+                self.runtime_segment_info.active_nv = segments.nv_b;
+                self.runtime_segment_info.inactive_nv = segments.nv_a;
             }
             _ => println!("Tock: Unknown flash_region1_base")
         }
-        // Enable the inactive RW for reads and writes.
+        // Enable the inactive RW and the inactive NVMEM directly following it
+        // for reads and writes.
         self.registers.flash_region3_base_addr.set(
             H1_FLASH_START + self.runtime_segment_info.inactive_rw.address);
-        self.registers.flash_region3_size.set(self.runtime_segment_info.inactive_rw.size);
+        self.registers.flash_region3_size.set(
+            self.runtime_segment_info.inactive_rw.size + self.runtime_segment_info.inactive_nv.size - 1);
         self.registers.flash_region3_ctrl.write(
             REGION_CTRL::EN::SET +
             REGION_CTRL::RD_EN::SET +
             REGION_CTRL::WR_EN::SET);
+
+        // Enable the personality data for reads only. It is provisioned before
+        // the application runs, so nothing on the device may change it.
+        self.registers.flash_region4_base_addr.set(
+            H1_FLASH_START + crate::personality::PERSONALITY_ADDRESS as u32);
+        self.registers.flash_region4_size.set(crate::personality::PERSONALITY_SIZE as u32 - 1);
+        self.registers.flash_region4_ctrl.write(
+            REGION_CTRL::EN::SET +
+            REGION_CTRL::RD_EN::SET);
     }
 }
 
@@ -15,9 +15,12 @@
 //! Interface for accessing H1 device personality (individual attestation
 //! data). Called "Personality" to remain consistent with ec-cr52 codebase.
 
+use core::mem::size_of;
 use kernel::ReturnCode;
+use spiutils::driver::personality;
 
 /// Structure of device attestation data.
+/// Userspace reads it with the layout in spiutils::driver::personality.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct PersonalityData {
@@ -27,9 +30,14 @@ pub struct PersonalityData {
     pub pub_y: [u32; 8],
     pub certificate_hash: [u32; 8],
     pub certificate_len: u32,
-    pub certificate: [u8; 2048 - (4 + 5 * 32)],
+    pub certificate: [u8; personality::MAX_CERTIFICATE_LEN],
 }
 
+// Fail to compile if the layout differs from the one userspace uses.
+const _: [(); personality::PERSONALITY_LEN] = [(); size_of::<PersonalityData>()];
+const _: [(); personality::CERTIFICATE_OFFSET] =
+    [(); 5 * size_of::<[u32; 8]>() + size_of::<u32>()];
+
 
 /// Trait for getting and setting device attestation data.
 ///
@@ -47,9 +47,9 @@ pub static mut BUFFER: [u32; PAGE_SIZE_U32] = [0; PAGE_SIZE_U32];
 
 // Personality data is stored as the third-to-last (N-3) page of flash;
 // it is followed by the two pages used as a counter.
-const PERSONALITY_ADDRESS: usize = flash::h1_hw::H1_FLASH_SIZE - (3 * flash::h1_hw::H1_FLASH_PAGE_SIZE) ;
+pub const PERSONALITY_ADDRESS: usize = flash::h1_hw::H1_FLASH_SIZE - (3 * flash::h1_hw::H1_FLASH_PAGE_SIZE) ;
 const PERSONALITY_ADDRESS_U32: usize = PERSONALITY_ADDRESS / 4;
-const PERSONALITY_SIZE: usize = flash::h1_hw::H1_FLASH_PAGE_SIZE;
+pub const PERSONALITY_SIZE: usize = flash::h1_hw::H1_FLASH_PAGE_SIZE;
 const PAGE_SIZE_U32: usize    = flash::h1_hw::H1_FLASH_PAGE_SIZE / 4;
 
 impl<'a> PersonalityDriver<'a> {
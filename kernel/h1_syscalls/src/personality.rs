@@ -20,7 +20,7 @@
 //!   0. check if the driver is present (ReturnCode::SUCCESS if so)
 //!   1. read personality data into a user buffer.
 //!   2. durably write personality data from a user buffer, completion signaled
-//!      by a callback.
+//!      by a callback. Drivers created with `new_read_only` do not support it.
 //!
 //! The driver implements 1 allow:
 //!   0. userspace buffer used for read and write (commands 1 and 2).
@@ -53,7 +53,8 @@ pub struct PersonalitySyscall<'a> {
     device: &'a personality::PersonalityDriver<'a>,
     apps: Grant<AppData>,
     busy: Cell<bool>,
-    current_user: OptionalCell<AppId>
+    current_user: OptionalCell<AppId>,
+    writable: bool,
 }
 
 impl<'a> PersonalitySyscall<'a> {
@@ -63,8 +64,18 @@ impl<'a> PersonalitySyscall<'a> {
             device: device,
             apps: container,
             busy: Cell::new(false),
-            current_user: OptionalCell::empty()
+            current_user: OptionalCell::empty(),
+            writable: true,
+        }
+    }
 
+    /// Create a driver that only reads the personality data, for boards on
+    /// which it is provisioned before the application runs.
+    pub fn new_read_only(device: &'a mut personality::PersonalityDriver<'a>,
+                         container: Grant<AppData>) -> PersonalitySyscall<'a> {
+        PersonalitySyscall {
+            writable: false,
+            ..PersonalitySyscall::new(device, container)
         }
     }
 }
@@ -107,7 +118,9 @@ impl<'a> Driver for PersonalitySyscall<'a> {
                 }
             },
             COMMAND_WRITE => {
-                if self.busy.get() {
+                if !self.writable {
+                    ReturnCode::ENOSUPPORT
+                } else if self.busy.get() {
                     ReturnCode::EBUSY
                 } else {
                     self.apps.enter(app_id, |app_data, _| {
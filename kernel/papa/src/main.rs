@@ -102,6 +102,7 @@ pub struct Papa {
     flash_syscalls: &'static h1_syscalls::flash::FlashSyscalls<'static >,
     fuse_syscalls: &'static h1_syscalls::fuse::FuseSyscall<'static>,
     globalsec_syscalls: &'static h1_syscalls::globalsec::GlobalSecSyscall<'static>,
+    personality: &'static h1_syscalls::personality::PersonalitySyscall<'static>,
     reset_syscalls: &'static h1_syscalls::reset::ResetSyscall<'static>,
 }
 
@@ -305,6 +306,10 @@ pub unsafe fn reset_handler() {
         h1_syscalls::flash::FlashSyscalls::new(flash_user, flash_syscalls_buffer, kernel.create_grant(&grant_cap)));
     flash_user.set_client(flash_syscalls);
 
+    let personality_flash = static_init!(
+        h1::hil::flash::virtual_flash::FlashUser<'static>,
+        h1::hil::flash::virtual_flash::FlashUser::new(flash_mux));
+
     flash.set_client(flash_mux);
 
     let timer_virtual_alarm = static_init!(VirtualMuxAlarm<'static, Timels>,
@@ -349,6 +354,14 @@ pub unsafe fn reset_handler() {
     h1::trng::TRNG0.set_client(entropy_to_random);
     entropy_to_random.set_client(rng);
 
+    let personality = static_init!(
+        h1_syscalls::personality::PersonalitySyscall<'static>,
+        h1_syscalls::personality::PersonalitySyscall::new_read_only(
+            &mut h1::personality::PERSONALITY, kernel.create_grant(&grant_cap)));
+    h1::personality::PERSONALITY.set_flash(personality_flash);
+    h1::personality::PERSONALITY.set_client(personality);
+    personality_flash.set_client(&h1::personality::PERSONALITY);
+
     h1::spi_host::SPI_HOST0.init();
     let h1_spi_host_syscalls = static_init!(
         h1_syscalls::spi_host::SpiHostSyscall<'static>,
@@ -376,11 +389,31 @@ pub unsafe fn reset_handler() {
     );
 
     const H1_FLASH_BANK_SIZE: u32 = h1::hil::flash::h1_hw::H1_FLASH_BANK_SIZE as u32;
+    // The last pages of each bank are reserved. In bank B they hold the
+    // personality data (n-3) and the non-volatile counter (n-2, n-1), which
+    // neither NVMEM nor the inactive RW region may cover. Bank A reserves the
+    // same pages to keep both banks identical.
+    // NVMEM comes right before them and must end before the personality
+    // page, which the assert below checks. NVMEM survives firmware updates,
+    // so moving it orphans the records stored by earlier firmware.
+    // Must match the end of the prog region in chip_layout_a.ld/chip_layout_b.ld.
+    const H1_FLASH_RESERVED_SIZE: u32 =
+        (h1::hil::flash::h1_hw::H1_FLASH_SIZE - h1::personality::PERSONALITY_ADDRESS) as u32;
+    const H1_FLASH_NVMEM_SIZE: u32 = 0x1800;
+    const H1_FLASH_RW_SIZE: u32 =
+        H1_FLASH_BANK_SIZE - 0x4000 - H1_FLASH_NVMEM_SIZE - H1_FLASH_RESERVED_SIZE;
+    const H1_FLASH_NVMEM_OFFSET: u32 =
+        H1_FLASH_BANK_SIZE - H1_FLASH_NVMEM_SIZE - H1_FLASH_RESERVED_SIZE;
+    // Fails to compile if NvB reaches into the personality page.
+    const _: [(); 0] = [(); (H1_FLASH_BANK_SIZE + H1_FLASH_NVMEM_OFFSET + H1_FLASH_NVMEM_SIZE >
+                             h1::personality::PERSONALITY_ADDRESS as u32) as usize];
     h1::globalsec::GLOBALSEC.init(h1::globalsec::Segments {
         ro_a: get_h1_flash_segment_info(SegmentAndLocation::RoA, 0x0, 0x4000),
-        rw_a: get_h1_flash_segment_info(SegmentAndLocation::RwA, 0x4000, H1_FLASH_BANK_SIZE - 0x4000),
+        rw_a: get_h1_flash_segment_info(SegmentAndLocation::RwA, 0x4000, H1_FLASH_RW_SIZE),
+        nv_a: get_h1_flash_segment_info(SegmentAndLocation::NvA, H1_FLASH_NVMEM_OFFSET, H1_FLASH_NVMEM_SIZE),
         ro_b: get_h1_flash_segment_info(SegmentAndLocation::RoB, H1_FLASH_BANK_SIZE, 0x4000),
-        rw_b: get_h1_flash_segment_info(SegmentAndLocation::RwB, H1_FLASH_BANK_SIZE + 0x4000, H1_FLASH_BANK_SIZE - 0x4000),
+        rw_b: get_h1_flash_segment_info(SegmentAndLocation::RwB, H1_FLASH_BANK_SIZE + 0x4000, H1_FLASH_RW_SIZE),
+        nv_b: get_h1_flash_segment_info(SegmentAndLocation::NvB, H1_FLASH_BANK_SIZE + H1_FLASH_NVMEM_OFFSET, H1_FLASH_NVMEM_SIZE),
     });
 
     let globalsec_syscalls = static_init!(
@@ -419,6 +452,7 @@ pub unsafe fn reset_handler() {
         flash_syscalls: flash_syscalls,
         fuse_syscalls: fuse_syscalls,
         globalsec_syscalls: globalsec_syscalls,
+        personality: personality,
         reset_syscalls: reset_syscalls,
     };
 
@@ -471,6 +505,7 @@ impl Platform for Papa {
             h1_syscalls::flash::DRIVER_NUM             => f(Some(self.flash_syscalls)),
             h1_syscalls::fuse::DRIVER_NUM              => f(Some(self.fuse_syscalls)),
             h1_syscalls::globalsec::DRIVER_NUM         => f(Some(self.globalsec_syscalls)),
+            h1_syscalls::personality::DRIVER_NUM       => f(Some(self.personality)),
             h1_syscalls::reset::DRIVER_NUM             => f(Some(self.reset_syscalls)),
             kernel::ipc::DRIVER_NUM                    => f(Some(&self.ipc)),
             _ =>  f(None),
@@ -0,0 +1,43 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::error;
+use spiutils::protocol::error::ContentType;
+use spiutils::protocol::wire::FromWire;
+use spiutils_fuzz::check_round_trip;
+
+fuzz_target!(|data: &[u8]| {
+    check_round_trip!(data, error::Header);
+
+    let mut msg = data;
+    let header = match error::Header::from_wire(&mut msg) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    match header.content {
+        ContentType::BadChecksum => check_round_trip!(msg, error::BadChecksum),
+        ContentType::ContentTypeNotSupported => check_round_trip!(msg, error::ContentTypeNotSupported),
+        ContentType::BadFragment => check_round_trip!(msg, error::BadFragment),
+        ContentType::MalformedPayload => check_round_trip!(msg, error::MalformedPayload),
+        ContentType::UnsupportedOperation => check_round_trip!(msg, error::UnsupportedOperation),
+        ContentType::InternalError => check_round_trip!(msg, error::InternalError),
+        ContentType::Busy => check_round_trip!(msg, error::Busy),
+    }
+});
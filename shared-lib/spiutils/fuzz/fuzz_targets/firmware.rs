@@ -0,0 +1,52 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::firmware;
+use spiutils::protocol::firmware::ContentType;
+use spiutils::protocol::wire::FromWire;
+use spiutils_fuzz::check_round_trip;
+
+fuzz_target!(|data: &[u8]| {
+    check_round_trip!(data, firmware::Header);
+
+    let mut msg = data;
+    let header = match firmware::Header::from_wire(&mut msg) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    match header.content {
+        ContentType::UpdatePrepareRequest => check_round_trip!(msg, firmware::UpdatePrepareRequest),
+        ContentType::UpdatePrepareResponse => check_round_trip!(msg, firmware::UpdatePrepareResponse),
+        ContentType::WriteChunkRequest => check_round_trip!(msg, firmware::WriteChunkRequest),
+        ContentType::WriteChunkResponse => check_round_trip!(msg, firmware::WriteChunkResponse),
+        ContentType::InactiveSegmentsInfoRequest => check_round_trip!(msg, firmware::InactiveSegmentsInfoRequest),
+        ContentType::InactiveSegmentsInfoResponse => check_round_trip!(msg, firmware::InactiveSegmentsInfoResponse),
+        ContentType::RebootRequest => check_round_trip!(msg, firmware::RebootRequest),
+        ContentType::RebootResponse => check_round_trip!(msg, firmware::RebootResponse),
+        ContentType::VerifySegmentRequest => check_round_trip!(msg, firmware::VerifySegmentRequest),
+        ContentType::VerifySegmentResponse => check_round_trip!(msg, firmware::VerifySegmentResponse),
+        ContentType::ReadChunkRequest => check_round_trip!(msg, firmware::ReadChunkRequest),
+        ContentType::ReadChunkResponse => check_round_trip!(msg, firmware::ReadChunkResponse),
+        ContentType::FirmwareInfoRequest => check_round_trip!(msg, firmware::FirmwareInfoRequest),
+        ContentType::FirmwareInfoResponse => check_round_trip!(msg, firmware::FirmwareInfoResponse),
+        ContentType::PendingRebootRequest => check_round_trip!(msg, firmware::PendingRebootRequest),
+        ContentType::PendingRebootResponse => check_round_trip!(msg, firmware::PendingRebootResponse),
+    }
+});
@@ -0,0 +1,43 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::wire::FromWire;
+use spiutils::protocol::measured_boot;
+use spiutils::protocol::measured_boot::ContentType;
+use spiutils_fuzz::check_round_trip;
+
+fuzz_target!(|data: &[u8]| {
+    check_round_trip!(data, measured_boot::Header);
+    check_round_trip!(data, measured_boot::Manifest);
+
+    let mut msg = data;
+    let header = match measured_boot::Header::from_wire(&mut msg) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    match header.content {
+        ContentType::GetManifestRequest => check_round_trip!(msg, measured_boot::GetManifestRequest),
+        ContentType::GetManifestResponse => check_round_trip!(msg, measured_boot::GetManifestResponse),
+        ContentType::SetManifestRequest => check_round_trip!(msg, measured_boot::SetManifestRequest),
+        ContentType::SetManifestResponse => check_round_trip!(msg, measured_boot::SetManifestResponse),
+        ContentType::GetRecoveryStatusRequest => check_round_trip!(msg, measured_boot::GetRecoveryStatusRequest),
+        ContentType::GetRecoveryStatusResponse => check_round_trip!(msg, measured_boot::GetRecoveryStatusResponse),
+    }
+});
@@ -0,0 +1,48 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::payload;
+use spiutils::protocol::wire::FromWire;
+use spiutils_fuzz::check_round_trip;
+
+// The maximum length of a reassembled message, as used by otpilot.
+const MAX_MESSAGE_LEN: usize = 2048;
+
+fuzz_target!(|data: &[u8]| {
+    check_round_trip!(data, payload::Header);
+    check_round_trip!(data, payload::ExtendedHeader);
+    check_round_trip!(data, payload::FragmentHeader);
+
+    // Validate a payload the way otpilot does.
+    let mut content = data;
+    if let Ok(header) = payload::Header::from_wire(&mut content) {
+        if header.content_len as usize <= content.len() {
+            let _ = payload::compute_checksum(&header, content);
+        }
+    }
+
+    let _ = payload::parse_extended(data);
+
+    let mut fragment_data = data;
+    if let Ok(fragment) = payload::FragmentHeader::from_wire(&mut fragment_data) {
+        let mut buf = [0u8; MAX_MESSAGE_LEN];
+        let _ = payload::Reassembler::new().add(&mut buf, &fragment, fragment_data);
+    }
+});
@@ -0,0 +1,32 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::sfdp;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sfdp) = sfdp::parse(data) {
+        // The derived values must not panic either.
+        let _ = sfdp.basic.density_bytes();
+        let _ = sfdp.basic.startup_address_mode();
+        let _ = sfdp.basic.supports_address_mode_switch();
+        let _ = sfdp.basic.smallest_erase_type();
+    }
+});
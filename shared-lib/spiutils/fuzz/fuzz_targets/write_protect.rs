@@ -0,0 +1,41 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use spiutils::protocol::wire::FromWire;
+use spiutils::protocol::write_protect;
+use spiutils::protocol::write_protect::ContentType;
+use spiutils_fuzz::check_round_trip;
+
+fuzz_target!(|data: &[u8]| {
+    check_round_trip!(data, write_protect::Header);
+    check_round_trip!(data, write_protect::Policy);
+
+    let mut msg = data;
+    let header = match write_protect::Header::from_wire(&mut msg) {
+        Ok(header) => header,
+        Err(_) => return,
+    };
+    match header.content {
+        ContentType::GetPolicyRequest => check_round_trip!(msg, write_protect::GetPolicyRequest),
+        ContentType::GetPolicyResponse => check_round_trip!(msg, write_protect::GetPolicyResponse),
+        ContentType::SetPolicyRequest => check_round_trip!(msg, write_protect::SetPolicyRequest),
+        ContentType::SetPolicyResponse => check_round_trip!(msg, write_protect::SetPolicyResponse),
+    }
+});
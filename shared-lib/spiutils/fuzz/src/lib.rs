@@ -0,0 +1,53 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers shared by the spiutils fuzz targets.
+//!
+//! There is one fuzz target per message family. Each target parses the
+//! fuzzer input, serializes every successfully parsed value and checks that
+//! it parses back to the same value. Run a target locally with
+//! `cargo fuzz run <target>` from this directory.
+
+pub use spiutils;
+
+/// Checks the parse -> serialize -> parse round trip of `$ty` on `$data`.
+///
+/// Nothing is checked if `$data` does not parse as `$ty`. Otherwise the
+/// value must serialize to as many bytes as were consumed by parsing and
+/// those bytes must parse back to an equal value.
+#[macro_export]
+macro_rules! check_round_trip {
+    ($data:expr, $ty:ty) => {{
+        use $crate::spiutils::protocol::wire::FromWire;
+        use $crate::spiutils::protocol::wire::ToWire;
+
+        let data: &[u8] = $data;
+        let mut input = data;
+        if let Ok(parsed) = <$ty as FromWire>::from_wire(&mut input) {
+            let parsed_len = data.len() - input.len();
+            let mut buf = vec![0u8; parsed_len];
+            let mut cursor = $crate::spiutils::io::Cursor::new(&mut buf);
+            parsed.to_wire(&mut cursor).expect("serializing a parsed value failed");
+            assert_eq!(cursor.consumed_len(), parsed_len, "serialized length differs");
+
+            let mut output = &buf[..];
+            let reparsed = <$ty as FromWire>::from_wire(&mut output)
+                .expect("parsing a serialized value failed");
+            assert_eq!(parsed, reparsed);
+            assert!(output.is_empty(), "serialized value was not fully parsed");
+        }
+    }};
+}
@@ -0,0 +1,1132 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host-side client for the SPI mailbox.
+//!
+//! A request is written to the mailbox address as a [`payload`] message. The
+//! device processes the request while the BUSY bit is set in its status
+//! register and then makes the response available at the mailbox address.
+//!
+//! [`Transport`] abstracts the SPI bus access, [`Client`] implements the
+//! framing on top of it.
+//!
+//! [`payload`]: ../protocol/payload/index.html
+//! [`Transport`]: trait.Transport.html
+//! [`Client`]: struct.Client.html
+
+use crate::io::Cursor;
+use crate::protocol::capabilities;
+use crate::protocol::error;
+use crate::protocol::firmware;
+use crate::protocol::integrity;
+use crate::protocol::integrity::IntegrityMode;
+use crate::protocol::measured_boot;
+use crate::protocol::payload;
+use crate::protocol::payload::ContentType;
+use crate::protocol::wire::FromWire;
+use crate::protocol::wire::FromWireError;
+use crate::protocol::wire::ToWire;
+use crate::protocol::wire::ToWireError;
+use crate::protocol::write_protect;
+
+use core::convert::TryFrom;
+
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The default time to wait for the device to process a request.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default interval between two polls of the device status.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// An error message sent by the device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeviceError {
+    /// The checksum on the request was invalid.
+    BadChecksum,
+
+    /// The content type of the request is not supported.
+    ContentTypeNotSupported,
+
+    /// A fragment was out of sequence or the reassembled request was too long.
+    BadFragment,
+
+    /// The request could not be parsed.
+    MalformedPayload(error::MalformedPayload),
+
+    /// The operation requested is not supported.
+    UnsupportedOperation(error::UnsupportedOperation),
+
+    /// The device failed to process the request.
+    InternalError(error::InternalError),
+
+    /// The device cannot process the request at this time.
+    Busy(error::Busy),
+}
+
+impl DeviceError {
+    /// Returns the content type of the error message.
+    pub fn content(&self) -> error::ContentType {
+        match self {
+            DeviceError::BadChecksum => error::ContentType::BadChecksum,
+            DeviceError::ContentTypeNotSupported => error::ContentType::ContentTypeNotSupported,
+            DeviceError::BadFragment => error::ContentType::BadFragment,
+            DeviceError::MalformedPayload(_) => error::ContentType::MalformedPayload,
+            DeviceError::UnsupportedOperation(_) => error::ContentType::UnsupportedOperation,
+            DeviceError::InternalError(_) => error::ContentType::InternalError,
+            DeviceError::Busy(_) => error::ContentType::Busy,
+        }
+    }
+}
+
+impl core::fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            DeviceError::MalformedPayload(msg) =>
+                write!(f, "{} (request 0x{:02x})", self.content(), msg.request),
+            DeviceError::UnsupportedOperation(msg) =>
+                write!(f, "{} (request 0x{:02x}, operation 0x{:02x})",
+                    self.content(), msg.request, msg.operation),
+            DeviceError::InternalError(msg) =>
+                write!(f, "{} (request 0x{:02x}, code {})", self.content(), msg.request, msg.code),
+            DeviceError::Busy(msg) =>
+                write!(f, "{} (request 0x{:02x})", self.content(), msg.request),
+            _ => write!(f, "{}", self.content()),
+        }
+    }
+}
+
+/// A client error.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The transport failed.
+    Io(std::io::Error),
+
+    /// A message could not be deserialized.
+    FromWire(FromWireError),
+
+    /// A message could not be serialized.
+    ToWire(ToWireError),
+
+    /// The payload does not fit into the mailbox.
+    PayloadTooLong(usize),
+
+    /// The checksum of a received payload was invalid.
+    BadChecksum,
+
+    /// The device did not clear the BUSY bit in time.
+    Timeout,
+
+    /// The device responded with an error message.
+    Device(DeviceError),
+
+    /// The device responded with an unexpected content type.
+    UnexpectedContentType(ContentType),
+
+    /// The device responded with an unexpected firmware message.
+    UnexpectedFirmwareContentType(firmware::ContentType),
+
+    /// The device responded with an unexpected capabilities message.
+    UnexpectedCapabilitiesContentType(capabilities::ContentType),
+
+    /// The device responded with an unexpected write protection message.
+    UnexpectedWriteProtectContentType(write_protect::ContentType),
+
+    /// The device responded with an unexpected measured boot message.
+    UnexpectedMeasuredBootContentType(measured_boot::ContentType),
+
+    /// A fragment was out of sequence or was not acknowledged.
+    BadFragment,
+
+    /// An extended payload was malformed or failed the integrity check.
+    Extended(payload::ExtendedError),
+
+    /// The device did not answer with the requested integrity mode.
+    IntegrityMismatch(Option<IntegrityMode>),
+}
+
+impl From<std::io::Error> for ClientError {
+    fn from(err: std::io::Error) -> Self {
+        ClientError::Io(err)
+    }
+}
+
+impl From<FromWireError> for ClientError {
+    fn from(err: FromWireError) -> Self {
+        ClientError::FromWire(err)
+    }
+}
+
+impl From<ToWireError> for ClientError {
+    fn from(err: ToWireError) -> Self {
+        ClientError::ToWire(err)
+    }
+}
+
+impl From<payload::ExtendedError> for ClientError {
+    fn from(err: payload::ExtendedError) -> Self {
+        ClientError::Extended(err)
+    }
+}
+
+impl core::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ClientError::Io(err) => write!(f, "transport error: {}", err),
+            ClientError::Device(content) => write!(f, "device error: {}", content),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// The result of a client operation.
+pub type ClientResult<T> = Result<T, ClientError>;
+
+// ----------------------------------------------------------------------------
+
+/// Access to the SPI bus of a device that implements the mailbox.
+pub trait Transport {
+    /// Writes `data` to the device at `address`.
+    ///
+    /// The data must be written in a single SPI transaction.
+    fn write(&mut self, address: u32, data: &[u8]) -> std::io::Result<()>;
+
+    /// Returns true iff the device's BUSY bit is set.
+    fn is_busy(&mut self) -> std::io::Result<bool>;
+
+    /// Reads `buf.len()` bytes from the device at `address`.
+    fn read(&mut self, address: u32, buf: &mut [u8]) -> std::io::Result<()>;
+}
+
+impl<T: Transport + ?Sized> Transport for &'_ mut T {
+    fn write(&mut self, address: u32, data: &[u8]) -> std::io::Result<()> {
+        T::write(*self, address, data)
+    }
+
+    fn is_busy(&mut self) -> std::io::Result<bool> {
+        T::is_busy(*self)
+    }
+
+    fn read(&mut self, address: u32, buf: &mut [u8]) -> std::io::Result<()> {
+        T::read(*self, address, buf)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn write(&mut self, address: u32, data: &[u8]) -> std::io::Result<()> {
+        T::write(self, address, data)
+    }
+
+    fn is_busy(&mut self) -> std::io::Result<bool> {
+        T::is_busy(self)
+    }
+
+    fn read(&mut self, address: u32, buf: &mut [u8]) -> std::io::Result<()> {
+        T::read(self, address, buf)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Wraps `data` into a payload of type `content`, including the checksum.
+pub fn wrap(content: ContentType, data: &[u8]) -> ClientResult<Vec<u8>> {
+    let content_len = u16::try_from(data.len())
+        .map_err(|_| ClientError::PayloadTooLong(data.len()))?;
+    let mut header = payload::Header {
+        content,
+        content_len,
+        checksum: 0,
+    };
+    header.checksum = payload::compute_checksum(&header, data);
+
+    let mut buf = vec![0u8; payload::HEADER_LEN + data.len()];
+    header.to_wire(Cursor::new(&mut buf[..payload::HEADER_LEN]))?;
+    buf[payload::HEADER_LEN..].copy_from_slice(data);
+    Ok(buf)
+}
+
+/// Wraps `data` of type `content` into an `Extended` payload with an
+/// integrity trailer as selected by `mode`.
+pub fn wrap_extended(mode: IntegrityMode, content: ContentType, data: &[u8]) -> ClientResult<Vec<u8>> {
+    let mut extended = Vec::with_capacity(payload::extended_overhead(mode) + data.len());
+    payload::ExtendedHeader {
+        version: payload::EXTENDED_HEADER_VERSION,
+        content,
+        integrity: mode,
+    }.to_wire(crate::io::StdWrite(&mut extended))?;
+    extended.extend_from_slice(data);
+    let trailer = integrity::compute_trailer(mode, &extended);
+    extended.extend_from_slice(&trailer[..mode.trailer_len()]);
+    wrap(ContentType::Extended, &extended)
+}
+
+// Serializes a fragment header followed by the fragment data.
+fn encode_fragment(fragment: &payload::FragmentHeader, data: &[u8]) -> ClientResult<Vec<u8>> {
+    let mut content = Vec::with_capacity(payload::FRAGMENT_HEADER_LEN + data.len());
+    fragment.to_wire(crate::io::StdWrite(&mut content))?;
+    content.extend_from_slice(data);
+    Ok(content)
+}
+
+/// Wraps a single fragment of a larger message into a `Fragment` payload.
+pub fn wrap_fragment(fragment: &payload::FragmentHeader, data: &[u8]) -> ClientResult<Vec<u8>> {
+    wrap(ContentType::Fragment, &encode_fragment(fragment, data)?)
+}
+
+/// Parses a payload and verifies its checksum.
+///
+/// Returns the header and the content following it. Any data following the
+/// content is ignored.
+pub fn unwrap(mut data: &[u8]) -> ClientResult<(payload::Header, &[u8])> {
+    let header = payload::Header::from_wire(&mut data)?;
+    if data.len() < header.content_len as usize {
+        return Err(ClientError::FromWire(FromWireError::OutOfRange));
+    }
+    if header.checksum != payload::compute_checksum(&header, data) {
+        return Err(ClientError::BadChecksum);
+    }
+    Ok((header, &data[..header.content_len as usize]))
+}
+
+/// Parses the content of an `Error` payload.
+pub fn decode_error(mut data: &[u8]) -> ClientResult<DeviceError> {
+    let header = error::Header::from_wire(&mut data)?;
+    Ok(match header.content {
+        error::ContentType::BadChecksum => DeviceError::BadChecksum,
+        error::ContentType::ContentTypeNotSupported => DeviceError::ContentTypeNotSupported,
+        error::ContentType::BadFragment => DeviceError::BadFragment,
+        error::ContentType::MalformedPayload =>
+            DeviceError::MalformedPayload(error::MalformedPayload::from_wire(&mut data)?),
+        error::ContentType::UnsupportedOperation =>
+            DeviceError::UnsupportedOperation(error::UnsupportedOperation::from_wire(&mut data)?),
+        error::ContentType::InternalError =>
+            DeviceError::InternalError(error::InternalError::from_wire(&mut data)?),
+        error::ContentType::Busy =>
+            DeviceError::Busy(error::Busy::from_wire(&mut data)?),
+    })
+}
+
+// Serializes `header` followed by `msg`.
+fn encode_message<H: ToWire, M: ToWire>(header: H, msg: &M) -> ClientResult<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut stdwrite = crate::io::StdWrite(&mut data);
+    header.to_wire(&mut stdwrite)?;
+    msg.to_wire(&mut stdwrite)?;
+    Ok(data)
+}
+
+// Parses a header of type `H` followed by a message of type `M`.
+// `content` gets the content type from the header. Content types other than
+// `expected` are reported with `unexpected`.
+fn decode_message<'a, H, C, M>(mut data: &'a [u8], expected: C, content: impl FnOnce(H) -> C,
+                               unexpected: impl FnOnce(C) -> ClientError) -> ClientResult<M>
+where
+    H: FromWire<'a>,
+    C: PartialEq,
+    M: FromWire<'a>,
+{
+    let actual = content(H::from_wire(&mut data)?);
+    if actual != expected {
+        return Err(unexpected(actual));
+    }
+    Ok(M::from_wire(&mut data)?)
+}
+
+/// Serializes a firmware message including its firmware header.
+pub fn encode_firmware<'a, M: firmware::Message<'a>>(msg: &M) -> ClientResult<Vec<u8>> {
+    encode_message(firmware::Header { content: M::TYPE }, msg)
+}
+
+/// Parses a firmware message of type `M` including its firmware header.
+pub fn decode_firmware<'a, M: firmware::Message<'a>>(data: &'a [u8]) -> ClientResult<M> {
+    decode_message(data, M::TYPE, |header: firmware::Header| header.content,
+                   ClientError::UnexpectedFirmwareContentType)
+}
+
+/// Serializes a capabilities message including its capabilities header.
+pub fn encode_capabilities<'a, M: capabilities::Message<'a>>(msg: &M) -> ClientResult<Vec<u8>> {
+    encode_message(capabilities::Header { content: M::TYPE }, msg)
+}
+
+/// Parses a capabilities message of type `M` including its capabilities header.
+pub fn decode_capabilities<'a, M: capabilities::Message<'a>>(data: &'a [u8]) -> ClientResult<M> {
+    decode_message(data, M::TYPE, |header: capabilities::Header| header.content,
+                   ClientError::UnexpectedCapabilitiesContentType)
+}
+
+/// Serializes a write protection message including its write protection header.
+pub fn encode_write_protect<'a, M: write_protect::Message<'a>>(msg: &M) -> ClientResult<Vec<u8>> {
+    encode_message(write_protect::Header { content: M::TYPE }, msg)
+}
+
+/// Parses a write protection message of type `M` including its write protection header.
+pub fn decode_write_protect<'a, M: write_protect::Message<'a>>(data: &'a [u8]) -> ClientResult<M> {
+    decode_message(data, M::TYPE, |header: write_protect::Header| header.content,
+                   ClientError::UnexpectedWriteProtectContentType)
+}
+
+/// Serializes a measured boot message including its measured boot header.
+pub fn encode_measured_boot<'a, M: measured_boot::Message<'a>>(msg: &M) -> ClientResult<Vec<u8>> {
+    encode_message(measured_boot::Header { content: M::TYPE }, msg)
+}
+
+/// Parses a measured boot message of type `M` including its measured boot header.
+pub fn decode_measured_boot<'a, M: measured_boot::Message<'a>>(data: &'a [u8]) -> ClientResult<M> {
+    decode_message(data, M::TYPE, |header: measured_boot::Header| header.content,
+                   ClientError::UnexpectedMeasuredBootContentType)
+}
+
+// ----------------------------------------------------------------------------
+
+/// A response read from the mailbox.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Response {
+    /// The content type of the response.
+    pub content: ContentType,
+
+    /// The content of the response.
+    pub data: Vec<u8>,
+}
+
+impl Response {
+    /// Returns an error if this is an `Error` response or if the content type
+    /// does not match `expected`.
+    pub fn expect(&self, expected: ContentType) -> ClientResult<&[u8]> {
+        if self.content == ContentType::Error {
+            return Err(ClientError::Device(decode_error(&self.data)?));
+        }
+        if self.content != expected {
+            return Err(ClientError::UnexpectedContentType(self.content));
+        }
+        Ok(&self.data)
+    }
+}
+
+/// A client for the SPI mailbox.
+pub struct Client<T> {
+    transport: T,
+    mailbox_address: u32,
+    mailbox_size: usize,
+    integrity: Option<IntegrityMode>,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl<T: Transport> Client<T> {
+    /// Creates a new client for a mailbox at `mailbox_address` that is
+    /// `mailbox_size` bytes large.
+    pub fn new(transport: T, mailbox_address: u32, mailbox_size: usize) -> Self {
+        Self {
+            transport,
+            mailbox_address,
+            mailbox_size,
+            integrity: None,
+            timeout: DEFAULT_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Sets the time to wait for the device to process a request.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the interval between two polls of the device status.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    /// Sets the integrity check for subsequent requests.
+    ///
+    /// If `None`, requests are only protected by the CRC8 of the payload
+    /// header, which is understood by all devices.
+    pub fn set_integrity(&mut self, integrity: Option<IntegrityMode>) {
+        self.integrity = integrity;
+    }
+
+    /// Returns the underlying transport.
+    pub fn transport(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Returns the maximum content length of a single request.
+    pub fn max_content_len(&self) -> usize {
+        let overhead = self.integrity.map_or(0, payload::extended_overhead);
+        self.mailbox_size.saturating_sub(payload::HEADER_LEN + overhead)
+    }
+
+    fn wait_ready(&mut self) -> ClientResult<()> {
+        let start = Instant::now();
+        while self.transport.is_busy()? {
+            if start.elapsed() > self.timeout {
+                return Err(ClientError::Timeout);
+            }
+            thread::sleep(self.poll_interval);
+        }
+        Ok(())
+    }
+
+    // Writes a single payload to the mailbox and reads back the response payload.
+    // Adds and checks the integrity trailer if enabled.
+    fn exchange(&mut self, content: ContentType, data: &[u8]) -> ClientResult<(ContentType, Vec<u8>)> {
+        let request = match self.integrity {
+            Some(mode) => wrap_extended(mode, content, data)?,
+            None => wrap(content, data)?,
+        };
+
+        self.wait_ready()?;
+        self.transport.write(self.mailbox_address, &request)?;
+        self.wait_ready()?;
+
+        let mut header_buf = [0u8; payload::HEADER_LEN];
+        self.transport.read(self.mailbox_address, &mut header_buf)?;
+        let header = payload::Header::from_wire(&header_buf[..])?;
+        if payload::HEADER_LEN + header.content_len as usize > self.mailbox_size {
+            return Err(ClientError::PayloadTooLong(header.content_len as usize));
+        }
+
+        let mut response = vec![0u8; payload::HEADER_LEN + header.content_len as usize];
+        response[..payload::HEADER_LEN].copy_from_slice(&header_buf);
+        self.transport.read(self.mailbox_address + payload::HEADER_LEN as u32,
+            &mut response[payload::HEADER_LEN..])?;
+
+        let (header, data) = unwrap(&response)?;
+        match (self.integrity, header.content) {
+            (Some(mode), ContentType::Extended) => {
+                let (extended, data) = payload::parse_extended(data)?;
+                if extended.integrity != mode {
+                    return Err(ClientError::IntegrityMismatch(Some(extended.integrity)));
+                }
+                Ok((extended.content, data.to_vec()))
+            }
+            (Some(_), _) => Err(ClientError::IntegrityMismatch(None)),
+            (None, _) => Ok((header.content, data.to_vec())),
+        }
+    }
+
+    // Exchanges a single fragment and returns the fragment it was answered with.
+    fn exchange_fragment(&mut self, fragment: &payload::FragmentHeader, data: &[u8])
+        -> ClientResult<(payload::FragmentHeader, Vec<u8>)> {
+        let (content, response) = self.exchange(ContentType::Fragment, &encode_fragment(fragment, data)?)?;
+        match content {
+            ContentType::Fragment => {
+                let mut data = &response[..];
+                let fragment = payload::FragmentHeader::from_wire(&mut data)?;
+                Ok((fragment, data.to_vec()))
+            }
+            ContentType::Error => Err(ClientError::Device(decode_error(&response)?)),
+            content => Err(ClientError::UnexpectedContentType(content)),
+        }
+    }
+
+    // Sends a request that does not fit into the mailbox as a sequence of
+    // fragments and returns the response to the final fragment.
+    fn send_fragments(&mut self, content: ContentType, data: &[u8])
+        -> ClientResult<(ContentType, Vec<u8>)> {
+        let max_data_len = self.max_content_len().saturating_sub(payload::FRAGMENT_HEADER_LEN);
+        if max_data_len == 0 || payload::Fragmenter::fragment_count(data.len(), max_data_len) > 256 {
+            return Err(ClientError::PayloadTooLong(data.len()));
+        }
+
+        let mut fragmenter = payload::Fragmenter::new();
+        fragmenter.start(content, data.len());
+        while let Some((fragment, range)) = fragmenter.next(max_data_len) {
+            if fragment.is_final() {
+                return self.exchange(ContentType::Fragment, &encode_fragment(&fragment, &data[range])?);
+            }
+
+            let (ack, ack_data) = self.exchange_fragment(&fragment, &data[range])?;
+            if ack != fragment.ack() || !ack_data.is_empty() {
+                return Err(ClientError::BadFragment);
+            }
+        }
+        Err(ClientError::BadFragment)
+    }
+
+    // Acknowledges the fragments of a fragmented response until the final
+    // fragment was received and returns the reassembled response.
+    fn receive_fragments(&mut self, first: &[u8]) -> ClientResult<Response> {
+        let mut data = first;
+        let mut fragment = payload::FragmentHeader::from_wire(&mut data)?;
+        if fragment.sequence != 0 {
+            return Err(ClientError::BadFragment);
+        }
+
+        let mut message = data.to_vec();
+        loop {
+            if fragment.is_final() {
+                return Ok(Response {
+                    content: fragment.content,
+                    data: message,
+                });
+            }
+
+            let expected_sequence = fragment.sequence.checked_add(1)
+                .ok_or(ClientError::BadFragment)?;
+            let (next, data) = self.exchange_fragment(&fragment.ack(), &[])?;
+            if next.content != fragment.content || next.sequence != expected_sequence {
+                return Err(ClientError::BadFragment);
+            }
+            message.extend_from_slice(&data);
+            fragment = next;
+        }
+    }
+
+    /// Sends a request with the given content and returns the response.
+    ///
+    /// Requests and responses that do not fit into the mailbox are split into
+    /// fragments.
+    pub fn transact(&mut self, content: ContentType, data: &[u8]) -> ClientResult<Response> {
+        let (content, response) = if data.len() > self.max_content_len() {
+            self.send_fragments(content, data)?
+        } else {
+            self.exchange(content, data)?
+        };
+
+        if content == ContentType::Fragment {
+            return self.receive_fragments(&response);
+        }
+        Ok(Response {
+            content,
+            data: response,
+        })
+    }
+
+    /// Sends a Manticore request and returns the Manticore response.
+    pub fn manticore(&mut self, request: &[u8]) -> ClientResult<Vec<u8>> {
+        let response = self.transact(ContentType::Manticore, request)?;
+        Ok(response.expect(ContentType::Manticore)?.to_vec())
+    }
+
+    /// Sends a firmware request and returns the firmware response.
+    pub fn firmware<'a, Req, Resp>(&mut self, request: &Req) -> ClientResult<Resp>
+    where
+        Req: firmware::Message<'a>,
+        Resp: for<'r> firmware::Message<'r>,
+    {
+        let response = self.transact(ContentType::Firmware, &encode_firmware(request)?)?;
+        decode_firmware(response.expect(ContentType::Firmware)?)
+    }
+
+    /// Queries the capabilities of the device.
+    pub fn capabilities(&mut self) -> ClientResult<capabilities::CapabilitiesResponse> {
+        let request = encode_capabilities(&capabilities::CapabilitiesRequest {})?;
+        let response = self.transact(ContentType::Capabilities, &request)?;
+        decode_capabilities(response.expect(ContentType::Capabilities)?)
+    }
+
+    /// Queries the write protection policy of the device.
+    pub fn write_protect_policy(&mut self) -> ClientResult<write_protect::GetPolicyResponse> {
+        let request = encode_write_protect(&write_protect::GetPolicyRequest {})?;
+        let response = self.transact(ContentType::WriteProtect, &request)?;
+        decode_write_protect(response.expect(ContentType::WriteProtect)?)
+    }
+
+    /// Provisions a write protection policy.
+    /// The device only accepts it while provisioning is enabled on its console.
+    pub fn set_write_protect_policy(&mut self, policy: write_protect::Policy) -> ClientResult<write_protect::SetPolicyResult> {
+        let request = encode_write_protect(&write_protect::SetPolicyRequest { policy })?;
+        let response = self.transact(ContentType::WriteProtect, &request)?;
+        let response: write_protect::SetPolicyResponse =
+            decode_write_protect(response.expect(ContentType::WriteProtect)?)?;
+        Ok(response.result)
+    }
+
+    /// Queries the measured boot manifest and the result of the most recent
+    /// verification.
+    pub fn measured_boot_manifest(&mut self) -> ClientResult<measured_boot::GetManifestResponse> {
+        let request = encode_measured_boot(&measured_boot::GetManifestRequest {})?;
+        let response = self.transact(ContentType::MeasuredBoot, &request)?;
+        decode_measured_boot(response.expect(ContentType::MeasuredBoot)?)
+    }
+
+    /// Provisions a measured boot manifest.
+    /// The device only accepts it while provisioning is enabled on its console.
+    pub fn set_measured_boot_manifest(&mut self, manifest: measured_boot::Manifest) -> ClientResult<measured_boot::SetManifestResult> {
+        let request = encode_measured_boot(&measured_boot::SetManifestRequest { manifest })?;
+        let response = self.transact(ContentType::MeasuredBoot, &request)?;
+        let response: measured_boot::SetManifestResponse =
+            decode_measured_boot(response.expect(ContentType::MeasuredBoot)?)?;
+        Ok(response.result)
+    }
+
+    /// Queries the status of the most recent recovery of the SPI flash chip.
+    pub fn recovery_status(&mut self) -> ClientResult<measured_boot::GetRecoveryStatusResponse> {
+        let request = encode_measured_boot(&measured_boot::GetRecoveryStatusRequest {})?;
+        let response = self.transact(ContentType::MeasuredBoot, &request)?;
+        decode_measured_boot(response.expect(ContentType::MeasuredBoot)?)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A transport that passes each write to a handler function and serves the
+/// handler's response on subsequent reads.
+///
+/// This allows exercising a [`Client`] without hardware.
+///
+/// [`Client`]: struct.Client.html
+pub struct LoopbackTransport<F> {
+    handler: F,
+    address: u32,
+    response: Vec<u8>,
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> LoopbackTransport<F> {
+    /// Creates a new loopback transport that calls `handler` with the data
+    /// of each write.
+    pub fn new(handler: F) -> Self {
+        Self {
+            handler,
+            address: 0,
+            response: Vec::new(),
+        }
+    }
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8>> Transport for LoopbackTransport<F> {
+    fn write(&mut self, address: u32, data: &[u8]) -> std::io::Result<()> {
+        self.address = address;
+        self.response = (self.handler)(data);
+        Ok(())
+    }
+
+    fn is_busy(&mut self) -> std::io::Result<bool> {
+        Ok(false)
+    }
+
+    fn read(&mut self, address: u32, buf: &mut [u8]) -> std::io::Result<()> {
+        let offset = address.wrapping_sub(self.address) as usize;
+        for (idx, val) in buf.iter_mut().enumerate() {
+            // Unwritten mailbox memory reads as erased.
+            *val = *self.response.get(offset + idx).unwrap_or(&0xff);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAILBOX_ADDRESS: u32 = 0x80000;
+    const MAILBOX_SIZE: usize = 512;
+
+    // Answers every request with an `UpdatePrepareResponse`.
+    fn firmware_handler(request: &[u8]) -> Vec<u8> {
+        let (header, _) = unwrap(request).expect("bad request");
+        assert_eq!(header.content, ContentType::Firmware);
+        let resp = firmware::UpdatePrepareResponse {
+            segment_and_location: firmware::SegmentAndLocation::RwB,
+            max_chunk_length: 128,
+            result: firmware::UpdatePrepareResult::Success,
+        };
+        wrap(ContentType::Firmware, &encode_firmware(&resp).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn wrap_unwrap() {
+        let wrapped = wrap(ContentType::Manticore, &[1, 2, 3]).unwrap();
+        assert_eq!(wrapped.len(), payload::HEADER_LEN + 3);
+
+        let (header, data) = unwrap(&wrapped).unwrap();
+        assert_eq!(header.content, ContentType::Manticore);
+        assert_eq!(data, &[1, 2, 3]);
+
+        let mut corrupted = wrapped.clone();
+        corrupted[payload::HEADER_LEN] ^= 0x01;
+        assert!(matches!(unwrap(&corrupted), Err(ClientError::BadChecksum)));
+
+        assert!(unwrap(&wrapped[..wrapped.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn manticore_loopback() {
+        let transport = LoopbackTransport::new(|request: &[u8]| {
+            let (header, data) = unwrap(request).expect("bad request");
+            let mut reversed = data.to_vec();
+            reversed.reverse();
+            wrap(header.content, &reversed).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        assert_eq!(client.manticore(&[1, 2, 3]).unwrap(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn firmware_loopback() {
+        let mut client = Client::new(LoopbackTransport::new(firmware_handler),
+            MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let req = firmware::UpdatePrepareRequest {
+            segment_and_location: firmware::SegmentAndLocation::RwB,
+        };
+        let resp: firmware::UpdatePrepareResponse = client.firmware(&req).unwrap();
+        assert_eq!(resp.segment_and_location, firmware::SegmentAndLocation::RwB);
+        assert_eq!(resp.max_chunk_length, 128);
+        assert_eq!(resp.result, firmware::UpdatePrepareResult::Success);
+
+        // The handler answers with a different message type.
+        let req = firmware::RebootRequest {
+            time: firmware::RebootTime::Immediate,
+        };
+        let result: ClientResult<firmware::RebootResponse> = client.firmware(&req);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn capabilities_loopback() {
+        let transport = LoopbackTransport::new(|request: &[u8]| {
+            let (header, data) = unwrap(request).expect("bad request");
+            assert_eq!(header.content, ContentType::Capabilities);
+            let _: capabilities::CapabilitiesRequest = decode_capabilities(data).unwrap();
+            let resp = capabilities::CapabilitiesResponse {
+                protocol_version: capabilities::PROTOCOL_VERSION,
+                firmware_content_types: capabilities::firmware_content_type_mask(&[
+                    firmware::ContentType::UpdatePrepareRequest,
+                    firmware::ContentType::WriteChunkRequest,
+                ]),
+                mailbox_size: MAILBOX_SIZE as u32,
+                max_chunk_length: 128,
+            };
+            wrap(ContentType::Capabilities, &encode_capabilities(&resp).unwrap()).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let resp = client.capabilities().unwrap();
+        assert_eq!(resp.protocol_version, capabilities::PROTOCOL_VERSION);
+        assert_eq!(resp.mailbox_size, MAILBOX_SIZE as u32);
+        assert_eq!(resp.max_chunk_length, 128);
+        assert!(resp.supports_firmware(firmware::ContentType::UpdatePrepareRequest));
+        assert!(resp.supports_firmware(firmware::ContentType::WriteChunkRequest));
+        assert!(!resp.supports_firmware(firmware::ContentType::RebootRequest));
+    }
+
+    #[test]
+    fn write_protect_loopback() {
+        let stored = std::rc::Rc::new(std::cell::Cell::new(write_protect::UNRESTRICTED_POLICY));
+        let device_policy = stored.clone();
+        let transport = LoopbackTransport::new(move |request: &[u8]| {
+            let (header, data) = unwrap(request).expect("bad request");
+            assert_eq!(header.content, ContentType::WriteProtect);
+            let response = match write_protect::Header::from_wire(data).unwrap().content {
+                write_protect::ContentType::SetPolicyRequest => {
+                    let req: write_protect::SetPolicyRequest = decode_write_protect(data).unwrap();
+                    let result = if device_policy.get().locked {
+                        write_protect::SetPolicyResult::Locked
+                    } else {
+                        device_policy.set(req.policy);
+                        write_protect::SetPolicyResult::Success
+                    };
+                    encode_write_protect(&write_protect::SetPolicyResponse { result })
+                }
+                _ => {
+                    let _: write_protect::GetPolicyRequest = decode_write_protect(data).unwrap();
+                    encode_write_protect(&write_protect::GetPolicyResponse {
+                        policy: device_policy.get(),
+                        denied_count: 3,
+                    })
+                }
+            };
+            wrap(ContentType::WriteProtect, &response.unwrap()).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let mut policy = write_protect::UNRESTRICTED_POLICY;
+        policy.add_range(write_protect::Range {
+            kind: write_protect::RangeKind::ReadOnly,
+            start: 0x0,
+            size: 0x10000,
+        }).unwrap();
+        policy.locked = true;
+        assert_eq!(client.set_write_protect_policy(policy).unwrap(), write_protect::SetPolicyResult::Success);
+        assert_eq!(stored.get(), policy);
+
+        let resp = client.write_protect_policy().unwrap();
+        assert_eq!(resp.policy, policy);
+        assert_eq!(resp.denied_count, 3);
+
+        let unrestricted = write_protect::UNRESTRICTED_POLICY;
+        assert_eq!(client.set_write_protect_policy(unrestricted).unwrap(), write_protect::SetPolicyResult::Locked);
+    }
+
+    #[test]
+    fn measured_boot_loopback() {
+        let stored = std::rc::Rc::new(std::cell::Cell::new(measured_boot::DISABLED_MANIFEST));
+        let device_manifest = stored.clone();
+        let transport = LoopbackTransport::new(move |request: &[u8]| {
+            let (header, data) = unwrap(request).expect("bad request");
+            assert_eq!(header.content, ContentType::MeasuredBoot);
+            let response = match measured_boot::Header::from_wire(data).unwrap().content {
+                measured_boot::ContentType::SetManifestRequest => {
+                    let req: measured_boot::SetManifestRequest = decode_measured_boot(data).unwrap();
+                    let result = if device_manifest.get().locked {
+                        measured_boot::SetManifestResult::Locked
+                    } else {
+                        device_manifest.set(req.manifest);
+                        measured_boot::SetManifestResult::Success
+                    };
+                    encode_measured_boot(&measured_boot::SetManifestResponse { result })
+                }
+                measured_boot::ContentType::GetRecoveryStatusRequest => {
+                    let _: measured_boot::GetRecoveryStatusRequest = decode_measured_boot(data).unwrap();
+                    encode_measured_boot(&measured_boot::GetRecoveryStatusResponse {
+                        result: measured_boot::RecoveryResult::Success,
+                        attempts: 1,
+                        bytes_copied: 0x10000,
+                        bytes_total: 0x10000,
+                    })
+                }
+                _ => {
+                    let _: measured_boot::GetManifestRequest = decode_measured_boot(data).unwrap();
+                    encode_measured_boot(&measured_boot::GetManifestResponse {
+                        manifest: device_manifest.get(),
+                        result: measured_boot::VerifyResult::Mismatch,
+                        failed_region: 0,
+                    })
+                }
+            };
+            wrap(ContentType::MeasuredBoot, &response.unwrap()).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let mut manifest = measured_boot::DISABLED_MANIFEST;
+        manifest.mode = measured_boot::VerifyMode::Enforce;
+        manifest.add_region(measured_boot::Region {
+            start: 0x0,
+            size: 0x10000,
+            digest: [0xa5; 32],
+        }).unwrap();
+        manifest.locked = true;
+        assert_eq!(client.set_measured_boot_manifest(manifest).unwrap(), measured_boot::SetManifestResult::Success);
+        assert_eq!(stored.get(), manifest);
+
+        let resp = client.measured_boot_manifest().unwrap();
+        assert_eq!(resp.manifest, manifest);
+        assert_eq!(resp.result, measured_boot::VerifyResult::Mismatch);
+        assert_eq!(resp.failed_region, 0);
+
+        let status = client.recovery_status().unwrap();
+        assert_eq!(status.result, measured_boot::RecoveryResult::Success);
+        assert_eq!(status.attempts, 1);
+        assert_eq!(status.bytes_copied, 0x10000);
+
+        let disabled = measured_boot::DISABLED_MANIFEST;
+        assert_eq!(client.set_measured_boot_manifest(disabled).unwrap(), measured_boot::SetManifestResult::Locked);
+    }
+
+    #[test]
+    fn device_error() {
+        let transport = LoopbackTransport::new(|_: &[u8]| {
+            let mut data = Vec::new();
+            error::Header { content: error::ContentType::BadChecksum }
+                .to_wire(crate::io::StdWrite(&mut data)).unwrap();
+            wrap(ContentType::Error, &data).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        match client.manticore(&[0; 4]) {
+            Err(ClientError::Device(DeviceError::BadChecksum)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn device_internal_error() {
+        let transport = LoopbackTransport::new(|_: &[u8]| {
+            let mut data = Vec::new();
+            let mut stdwrite = crate::io::StdWrite(&mut data);
+            error::Header { content: error::ContentType::InternalError }
+                .to_wire(&mut stdwrite).unwrap();
+            error::InternalError {
+                request: ContentType::Firmware as u8,
+                code: error::InternalErrorCode::Driver,
+            }.to_wire(&mut stdwrite).unwrap();
+            wrap(ContentType::Error, &data).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let result: ClientResult<firmware::InactiveSegmentsInfoResponse> =
+            client.firmware(&firmware::InactiveSegmentsInfoRequest {});
+        match result {
+            Err(ClientError::Device(DeviceError::InternalError(msg))) => {
+                assert_eq!(msg.request, ContentType::Firmware as u8);
+                assert_eq!(msg.code, error::InternalErrorCode::Driver);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payload_too_long() {
+        let mut client = Client::new(LoopbackTransport::new(firmware_handler),
+            MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        // The request would need more than 256 fragments.
+        let max_data_len = client.max_content_len() - payload::FRAGMENT_HEADER_LEN;
+        let request = vec![0u8; 256 * max_data_len + 1];
+        assert!(matches!(client.manticore(&request), Err(ClientError::PayloadTooLong(_))));
+    }
+
+    // A device model that reverses fragmented Manticore messages.
+    struct FragmentingDevice {
+        reassembler: payload::Reassembler,
+        rx_buf: Vec<u8>,
+        fragmenter: payload::Fragmenter,
+        tx_buf: Vec<u8>,
+    }
+
+    impl FragmentingDevice {
+        fn new() -> Self {
+            Self {
+                reassembler: payload::Reassembler::new(),
+                rx_buf: vec![0; 4096],
+                fragmenter: payload::Fragmenter::new(),
+                tx_buf: Vec::new(),
+            }
+        }
+
+        fn send_next_fragment(&mut self) -> Vec<u8> {
+            let max_data_len = MAILBOX_SIZE - payload::HEADER_LEN - payload::FRAGMENT_HEADER_LEN;
+            let (fragment, range) = self.fragmenter.next(max_data_len).unwrap();
+            wrap_fragment(&fragment, &self.tx_buf[range]).unwrap()
+        }
+
+        fn handle(&mut self, request: &[u8]) -> Vec<u8> {
+            let (header, mut data) = unwrap(request).expect("bad request");
+            assert_eq!(header.content, ContentType::Fragment);
+            let fragment = payload::FragmentHeader::from_wire(&mut data).unwrap();
+
+            if self.fragmenter.is_ack(&fragment, data) {
+                return self.send_next_fragment();
+            }
+            match self.reassembler.add(&mut self.rx_buf, &fragment, data).unwrap() {
+                None => wrap_fragment(&fragment.ack(), &[]).unwrap(),
+                Some(len) => {
+                    self.tx_buf = self.rx_buf[..len].to_vec();
+                    self.tx_buf.reverse();
+                    self.fragmenter.start(fragment.content, len);
+                    self.send_next_fragment()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fragmented_loopback() {
+        let mut device = FragmentingDevice::new();
+        let transport = LoopbackTransport::new(|request: &[u8]| device.handle(request));
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let request: Vec<u8> = (0..1500u32).map(|val| val as u8).collect();
+        let mut expected = request.clone();
+        expected.reverse();
+        assert_eq!(client.manticore(&request).unwrap(), expected);
+    }
+
+    // Reverses an extended Manticore request and answers in `mode`.
+    fn extended_handler(request: &[u8], mode: IntegrityMode) -> Vec<u8> {
+        let (header, data) = unwrap(request).expect("bad request");
+        assert_eq!(header.content, ContentType::Extended);
+        let (extended, data) = payload::parse_extended(data).expect("bad extended payload");
+        assert_eq!(extended.content, ContentType::Manticore);
+        let mut reversed = data.to_vec();
+        reversed.reverse();
+        wrap_extended(mode, extended.content, &reversed).unwrap()
+    }
+
+    // Answers every request with a plain `Manticore` payload.
+    fn plain_handler(_: &[u8]) -> Vec<u8> {
+        wrap(ContentType::Manticore, &[0]).unwrap()
+    }
+
+    #[test]
+    fn integrity_loopback() {
+        for mode in [IntegrityMode::Crc32, IntegrityMode::Sha256].iter() {
+            let transport = LoopbackTransport::new(|request: &[u8]| extended_handler(request, *mode));
+            let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+            client.set_integrity(Some(*mode));
+            assert_eq!(client.max_content_len(),
+                MAILBOX_SIZE - payload::HEADER_LEN - payload::extended_overhead(*mode));
+
+            assert_eq!(client.manticore(&[1, 2, 3]).unwrap(), vec![3, 2, 1]);
+        }
+    }
+
+    #[test]
+    fn integrity_errors() {
+        // The device answers with a different integrity mode.
+        let transport = LoopbackTransport::new(|request: &[u8]| {
+            extended_handler(request, IntegrityMode::Crc32)
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+        client.set_integrity(Some(IntegrityMode::Sha256));
+        assert!(matches!(client.manticore(&[1, 2, 3]),
+            Err(ClientError::IntegrityMismatch(Some(IntegrityMode::Crc32)))));
+
+        // The device corrupts the trailer.
+        let transport = LoopbackTransport::new(|request: &[u8]| {
+            let response = extended_handler(request, IntegrityMode::Crc32);
+            let (header, data) = unwrap(&response).unwrap();
+            let mut data = data.to_vec();
+            *data.last_mut().unwrap() ^= 0x01;
+            wrap(header.content, &data).unwrap()
+        });
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+        client.set_integrity(Some(IntegrityMode::Crc32));
+        assert!(matches!(client.manticore(&[1, 2, 3]),
+            Err(ClientError::Extended(payload::ExtendedError::BadIntegrity(IntegrityMode::Crc32)))));
+
+        // The device does not understand extended payloads.
+        let transport = LoopbackTransport::new(plain_handler);
+        let mut client = Client::new(transport, MAILBOX_ADDRESS, MAILBOX_SIZE);
+        client.set_integrity(Some(IntegrityMode::Crc32));
+        assert!(matches!(client.manticore(&[1, 2, 3]),
+            Err(ClientError::IntegrityMismatch(None))));
+    }
+
+    #[test]
+    fn reassembly_errors() {
+        let mut reassembler = payload::Reassembler::new();
+        let mut buf = [0u8; 4];
+        let fragment = |sequence, flags| payload::FragmentHeader {
+            content: ContentType::Manticore,
+            sequence,
+            flags,
+        };
+
+        // Fragments must start at 0 and be consecutive.
+        assert_eq!(reassembler.add(&mut buf, &fragment(1, 0), &[1]),
+            Err(payload::ReassemblyError::OutOfSequence));
+        assert_eq!(reassembler.add(&mut buf, &fragment(0, 0), &[1]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(2, 0), &[2]),
+            Err(payload::ReassemblyError::OutOfSequence));
+
+        // The message must fit into the buffer.
+        assert_eq!(reassembler.add(&mut buf, &fragment(0, 0), &[1, 2, 3]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(1, payload::FRAGMENT_FLAG_FINAL), &[4, 5]),
+            Err(payload::ReassemblyError::TooLong));
+
+        assert_eq!(reassembler.add(&mut buf, &fragment(0, 0), &[1, 2]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(1, payload::FRAGMENT_FLAG_FINAL), &[3]),
+            Ok(Some(3)));
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+    }
+}
@@ -16,15 +16,6 @@
 
 //! Data structures related to firmware.
 
-use crate::io::Read;
-use crate::io::Write;
-use crate::protocol::wire::FromWireError;
-use crate::protocol::wire::FromWire;
-use crate::protocol::wire::ToWireError;
-use crate::protocol::wire::ToWire;
-
-use core::mem;
-
 // ----------------------------------------------------------------------------
 
 /// The offset of the BuildInfo from the start of the firmware segment.
@@ -33,47 +24,31 @@ use core::mem;
 pub const BUILD_INFO_OFFSET: usize = 860;
 
 /// The length of a BuildInfo on the wire, in bytes.
-pub const BUILD_INFO_LEN: usize = 3 * mem::size_of::<u32>() + mem::size_of::<u64>();
+pub const BUILD_INFO_LEN: usize = BuildInfo::LEN;
 
-/// Firmware build information.
-/// The fields and serialization of this struct must match the original
-/// `SignedHeader` C-struct used in actual firmware images.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct BuildInfo {
-    /// Time epoch
-    pub epoch: u32,
+wire_struct! {
+    /// Firmware build information.
+    /// The fields and serialization of this struct must match the original
+    /// `SignedHeader` C-struct used in actual firmware images.
+    pub struct BuildInfo {
+        /// Time epoch
+        pub epoch: u32 as le,
 
-    /// Major version
-    pub major: u32,
+        /// Major version
+        pub major: u32 as le,
 
-    /// Minor version
-    pub minor: u32,
-
-    /// Timestamp
-    pub timestamp: u64,
-}
+        /// Minor version
+        pub minor: u32 as le,
 
-impl<'a> FromWire<'a> for BuildInfo {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let epoch = r.read_le::<u32>()?;
-        let major = r.read_le::<u32>()?;
-        let minor = r.read_le::<u32>()?;
-        let timestamp = r.read_le::<u64>()?;
-        Ok(Self {
-            epoch,
-            major,
-            minor,
-            timestamp,
-        })
+        /// Timestamp
+        pub timestamp: u64 as le,
     }
 }
 
-impl ToWire for BuildInfo {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_le(self.epoch)?;
-        w.write_le(self.major)?;
-        w.write_le(self.minor)?;
-        w.write_le(self.timestamp)?;
-        Ok(())
+impl BuildInfo {
+    /// Returns whether the build info is in erased flash, i.e. all bits are set.
+    pub fn is_blank(&self) -> bool {
+        self.epoch == u32::MAX && self.major == u32::MAX && self.minor == u32::MAX
+            && self.timestamp == u64::MAX
     }
 }
@@ -0,0 +1,202 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Multi-precision arithmetic on little-endian 32-bit words.
+//!
+//! `add`, `subtract`, `select` and `montgomery_multiply` run in constant
+//! time, so that they can be used with secret values.
+
+// Load the big-endian `bytes` into the little-endian words `out`.
+pub(crate) fn load(bytes: &[u8], out: &mut [u32]) {
+    for word in out.iter_mut() {
+        *word = 0;
+    }
+    for (word, chunk) in out.iter_mut().zip(bytes.rchunks(4)) {
+        *word = chunk.iter().fold(0, |acc, byte| (acc << 8) | *byte as u32);
+    }
+}
+
+// Store the little-endian words `words` as big-endian `out`.
+pub(crate) fn store(words: &[u32], out: &mut [u8]) {
+    for (word, chunk) in words.iter().zip(out.rchunks_mut(4)) {
+        let bytes = word.to_be_bytes();
+        let len = chunk.len();
+        chunk.copy_from_slice(&bytes[4 - len..]);
+    }
+}
+
+// Whether a >= b. Not constant time.
+pub(crate) fn greater_or_equal(a: &[u32], b: &[u32]) -> bool {
+    for (x, y) in a.iter().zip(b.iter()).rev() {
+        if x != y {
+            return x > y;
+        }
+    }
+    true
+}
+
+// a += b & mask. Returns the carry.
+pub(crate) fn add(a: &mut [u32], b: &[u32], mask: u32) -> u32 {
+    let mut carry = 0u64;
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        let sum = *x as u64 + (*y & mask) as u64 + carry;
+        *x = sum as u32;
+        carry = sum >> 32;
+    }
+    carry as u32
+}
+
+// a -= b. Returns the borrow.
+pub(crate) fn subtract(a: &mut [u32], b: &[u32]) -> u32 {
+    let mut borrow = 0u64;
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        let diff = (*x as u64).wrapping_sub(*y as u64).wrapping_sub(borrow);
+        *x = diff as u32;
+        borrow = (diff >> 32) & 1;
+    }
+    borrow as u32
+}
+
+// a = b if `bit` is 1, otherwise a is unchanged.
+pub(crate) fn select(a: &mut [u32], b: &[u32], bit: u32) {
+    let mask = 0u32.wrapping_sub(bit);
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        *x ^= (*x ^ *y) & mask;
+    }
+}
+
+// Compute -n^-1 mod 2^32 for odd n.
+pub(crate) fn neg_inverse(n: u32) -> u32 {
+    // Each Newton iteration doubles the number of correct low bits, and
+    // n is its own inverse modulo 8.
+    let mut inv = n;
+    for _ in 0..4 {
+        inv = inv.wrapping_mul(2u32.wrapping_sub(n.wrapping_mul(inv)));
+    }
+    inv.wrapping_neg()
+}
+
+// Compute R^2 mod n into `out`, where R = 2^(32 * n.len()). Not constant
+// time.
+pub(crate) fn montgomery_r2(n: &[u32], out: &mut [u32]) {
+    for word in out.iter_mut() {
+        *word = 0;
+    }
+    out[0] = 1;
+    for _ in 0..64 * n.len() {
+        // out = 2 * out mod n. out < n, so a single subtraction suffices.
+        let mut carry = 0;
+        for word in out.iter_mut() {
+            let next = *word >> 31;
+            *word = (*word << 1) | carry;
+            carry = next;
+        }
+        if carry != 0 || greater_or_equal(out, n) {
+            subtract(out, n);
+        }
+    }
+}
+
+// Compute x * y / R mod n into t[..n.len()] (CIOS Montgomery
+// multiplication). x and y must be less than n and t must have at least
+// n.len() + 2 words.
+pub(crate) fn montgomery_multiply(x: &[u32], y: &[u32], n: &[u32], n0inv: u32, t: &mut [u32]) {
+    let len = n.len();
+    for word in t[..len + 2].iter_mut() {
+        *word = 0;
+    }
+    for xi in x.iter() {
+        // t += x[i] * y
+        let mut carry = 0u64;
+        for (tj, yj) in t.iter_mut().zip(y.iter()) {
+            let sum = *tj as u64 + *xi as u64 * *yj as u64 + carry;
+            *tj = sum as u32;
+            carry = sum >> 32;
+        }
+        let sum = t[len] as u64 + carry;
+        t[len] = sum as u32;
+        t[len + 1] = (sum >> 32) as u32;
+
+        // t = (t + m * n) / 2^32
+        let m = t[0].wrapping_mul(n0inv);
+        let mut carry = (t[0] as u64 + m as u64 * n[0] as u64) >> 32;
+        for j in 1..len {
+            let sum = t[j] as u64 + m as u64 * n[j] as u64 + carry;
+            t[j - 1] = sum as u32;
+            carry = sum >> 32;
+        }
+        let sum = t[len] as u64 + carry;
+        t[len - 1] = sum as u32;
+        t[len] = t[len + 1] + (sum >> 32) as u32;
+    }
+
+    // t < 2n, so a single subtraction suffices. It is undone if t < n.
+    let top = t[len];
+    let borrow = subtract(&mut t[..len], n);
+    add(&mut t[..len], n, 0u32.wrapping_sub(borrow & (top ^ 1)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load_store() {
+        let mut words = [0u32; 2];
+        load(&[0x01, 0x02, 0x03, 0x04, 0x05], &mut words);
+        assert_eq!(words, [0x0203_0405, 0x01]);
+
+        let mut bytes = [0u8; 5];
+        store(&words, &mut bytes);
+        assert_eq!(bytes, [0x01, 0x02, 0x03, 0x04, 0x05]);
+    }
+
+    #[test]
+    fn inverse() {
+        for n in [1u32, 3, 0xffff_ffff, 0x8000_0001, 0x1234_5679].iter() {
+            assert_eq!(n.wrapping_mul(neg_inverse(*n)), 0xffff_ffff);
+        }
+    }
+
+    #[test]
+    fn multiply() {
+        // n = 2^64 - 59, R = 2^64
+        let n = [0xffff_ffc5, 0xffff_ffff];
+        let n0inv = neg_inverse(n[0]);
+        let mut r2 = [0u32; 2];
+        montgomery_r2(&n, &mut r2);
+        // R mod n = 59, so R^2 mod n = 59^2.
+        assert_eq!(r2, [59 * 59, 0]);
+
+        // x * R * R^-1 = x, including a result that needs the final
+        // subtraction.
+        let mut t = [0u32; 4];
+        let x = [0xffff_ffc4, 0xffff_ffff];
+        montgomery_multiply(&x, &r2, &n, n0inv, &mut t);
+        let y = [t[0], t[1]];
+        montgomery_multiply(&y, &[1, 0], &n, n0inv, &mut t);
+        assert_eq!(&t[..2], &x);
+    }
+
+    #[test]
+    fn select_words() {
+        let mut a = [1u32, 2];
+        select(&mut a, &[3, 4], 0);
+        assert_eq!(a, [1, 2]);
+        select(&mut a, &[3, 4], 1);
+        assert_eq!(a, [3, 4]);
+    }
+}
@@ -0,0 +1,31 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Software cryptography that does not depend on the H1 crypto hardware.
+
+mod bignum;
+pub mod p256;
+pub mod rsa;
+
+/// Overwrite `buffer` with zeros once it holds no longer needed secrets.
+/// The writes are volatile, so that they are not optimized away even though
+/// the buffer is not read again.
+pub fn wipe<T: Copy + Default>(buffer: &mut [T]) {
+    for val in buffer.iter_mut() {
+        // The pointer comes from a mutable reference, so it is valid for writes.
+        unsafe { core::ptr::write_volatile(val, T::default()); }
+    }
+}
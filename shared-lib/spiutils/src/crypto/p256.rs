@@ -0,0 +1,432 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! P-256 keys and ECDSA signatures (FIPS 186-4).
+//!
+//! This module derives private keys and their public keys and encodes
+//! signatures; the signing itself is done by the callers (the dcrypto engine
+//! on H1). Points are kept in projective coordinates and added with the
+//! complete formulas of Renes, Costello and Batina, and the scalar
+//! multiplication does the same work for every bit of the scalar.
+
+use crate::crypto::bignum::add;
+use crate::crypto::bignum::greater_or_equal;
+use crate::crypto::bignum::load;
+use crate::crypto::bignum::montgomery_multiply;
+use crate::crypto::bignum::montgomery_r2;
+use crate::crypto::bignum::neg_inverse;
+use crate::crypto::bignum::select;
+use crate::crypto::bignum::store;
+use crate::crypto::bignum::subtract;
+use crate::crypto::wipe;
+use crate::protocol::integrity::Sha256;
+
+use core::result::Result;
+
+/// The length of a scalar or a coordinate, in bytes.
+pub const SCALAR_LEN: usize = 32;
+
+/// The maximum length of a DER encoded signature, in bytes.
+pub const MAX_DER_SIGNATURE_LEN: usize = 72;
+
+// The number of words in a scalar or a coordinate.
+const WORDS: usize = SCALAR_LEN / 4;
+
+type Words = [u32; WORDS];
+
+// The field prime p.
+const P: Words = [
+    0xffffffff, 0xffffffff, 0xffffffff, 0x00000000, 0x00000000, 0x00000000, 0x00000001, 0xffffffff,
+];
+
+// The group order n.
+const N: Words = [
+    0xfc632551, 0xf3b9cac2, 0xa7179e84, 0xbce6faad, 0xffffffff, 0xffffffff, 0x00000000, 0xffffffff,
+];
+
+// The curve coefficient b.
+const B: Words = [
+    0x27d2604b, 0x3bce3c3e, 0xcc53b0f6, 0x651d06b0, 0x769886bc, 0xb3ebbd55, 0xaa3a93e7, 0x5ac635d8,
+];
+
+// The base point G.
+const GX: Words = [
+    0xd898c296, 0xf4a13945, 0x2deb33a0, 0x77037d81, 0x63a440f2, 0xf8bce6e5, 0xe12c4247, 0x6b17d1f2,
+];
+const GY: Words = [
+    0x37bf51f5, 0xcbb64068, 0x6b315ece, 0x2bce3357, 0x7c0f9e16, 0x8ee7eb4a, 0xfe1a7f9b, 0x4fe342e2,
+];
+
+/// Errors of P-256 key handling.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum P256Error {
+    /// The private key is not in [1, n - 1].
+    InvalidKey,
+}
+
+// ----------------------------------------------------------------------------
+
+// Arithmetic modulo the odd prime `m`, on values in the Montgomery domain.
+struct Field {
+    m: Words,
+    n0inv: u32,
+    r2: Words,
+}
+
+impl Field {
+    fn new(m: Words) -> Self {
+        let mut r2 = [0; WORDS];
+        montgomery_r2(&m, &mut r2);
+        Self {
+            m,
+            n0inv: neg_inverse(m[0]),
+            r2,
+        }
+    }
+
+    fn mul(&self, x: &Words, y: &Words) -> Words {
+        let mut t = [0u32; WORDS + 2];
+        montgomery_multiply(x, y, &self.m, self.n0inv, &mut t);
+        let mut out = [0; WORDS];
+        out.copy_from_slice(&t[..WORDS]);
+        out
+    }
+
+    fn add(&self, x: &Words, y: &Words) -> Words {
+        let mut out = *x;
+        let carry = add(&mut out, y, !0);
+        let mut reduced = out;
+        let borrow = subtract(&mut reduced, &self.m);
+        select(&mut out, &reduced, carry | (borrow ^ 1));
+        out
+    }
+
+    fn sub(&self, x: &Words, y: &Words) -> Words {
+        let mut out = *x;
+        let borrow = subtract(&mut out, y);
+        add(&mut out, &self.m, 0u32.wrapping_sub(borrow));
+        out
+    }
+
+    // Convert x < m into the Montgomery domain.
+    fn to_mont(&self, x: &Words) -> Words {
+        self.mul(x, &self.r2)
+    }
+
+    // Convert x out of the Montgomery domain.
+    fn to_plain(&self, x: &Words) -> Words {
+        let mut one = [0; WORDS];
+        one[0] = 1;
+        self.mul(x, &one)
+    }
+
+    // Compute x^-1 as x^(m - 2). The exponent is public, so the run time
+    // does not depend on x.
+    fn invert(&self, x: &Words) -> Words {
+        let mut exponent = self.m;
+        subtract(&mut exponent, &[2, 0, 0, 0, 0, 0, 0, 0]);
+        let mut one = [0; WORDS];
+        one[0] = 1;
+        let mut out = self.to_mont(&one);
+        for bit in (0..WORDS * 32).rev() {
+            out = self.mul(&out, &out);
+            if (exponent[bit / 32] >> (bit % 32)) & 1 != 0 {
+                out = self.mul(&out, x);
+            }
+        }
+        out
+    }
+}
+
+// A point in projective coordinates, in the Montgomery domain of p.
+#[derive(Clone, Copy)]
+struct Point {
+    x: Words,
+    y: Words,
+    z: Words,
+}
+
+impl Point {
+    // Add two points (Algorithm 4 of Renes, Costello and Batina, "Complete
+    // addition formulas for prime order elliptic curves"). Also works for
+    // doubling and the point at infinity.
+    fn add(&self, other: &Point, f: &Field, b: &Words) -> Point {
+        let (x1, y1, z1) = (&self.x, &self.y, &self.z);
+        let (x2, y2, z2) = (&other.x, &other.y, &other.z);
+
+        let mut t0 = f.mul(x1, x2);
+        let mut t1 = f.mul(y1, y2);
+        let mut t2 = f.mul(z1, z2);
+        let mut t3 = f.add(x1, y1);
+        let mut t4 = f.add(x2, y2);
+        t3 = f.mul(&t3, &t4);
+        t4 = f.add(&t0, &t1);
+        t3 = f.sub(&t3, &t4);
+        t4 = f.add(y1, z1);
+        let mut x3 = f.add(y2, z2);
+        t4 = f.mul(&t4, &x3);
+        x3 = f.add(&t1, &t2);
+        t4 = f.sub(&t4, &x3);
+        x3 = f.add(x1, z1);
+        let mut y3 = f.add(x2, z2);
+        x3 = f.mul(&x3, &y3);
+        y3 = f.add(&t0, &t2);
+        y3 = f.sub(&x3, &y3);
+        let mut z3 = f.mul(b, &t2);
+        x3 = f.sub(&y3, &z3);
+        z3 = f.add(&x3, &x3);
+        x3 = f.add(&x3, &z3);
+        z3 = f.sub(&t1, &x3);
+        x3 = f.add(&t1, &x3);
+        y3 = f.mul(b, &y3);
+        t1 = f.add(&t2, &t2);
+        t2 = f.add(&t1, &t2);
+        y3 = f.sub(&y3, &t2);
+        y3 = f.sub(&y3, &t0);
+        t1 = f.add(&y3, &y3);
+        y3 = f.add(&t1, &y3);
+        t1 = f.add(&t0, &t0);
+        t0 = f.add(&t1, &t0);
+        t0 = f.sub(&t0, &t2);
+        t1 = f.mul(&t4, &y3);
+        t2 = f.mul(&t0, &y3);
+        y3 = f.mul(&x3, &z3);
+        y3 = f.add(&y3, &t2);
+        x3 = f.mul(&t3, &x3);
+        x3 = f.sub(&x3, &t1);
+        z3 = f.mul(&t4, &z3);
+        t1 = f.mul(&t3, &t0);
+        z3 = f.add(&z3, &t1);
+
+        Point {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+}
+
+// Compute k * G and return its affine coordinates (outside the Montgomery
+// domain). Returns None for the point at infinity.
+fn multiply_base(k: &Words) -> Option<(Words, Words)> {
+    let f = Field::new(P);
+    let b = f.to_mont(&B);
+    let mut one = [0; WORDS];
+    one[0] = 1;
+    let one = f.to_mont(&one);
+    let g = Point {
+        x: f.to_mont(&GX),
+        y: f.to_mont(&GY),
+        z: one,
+    };
+
+    // Double-and-add-always, starting from the point at infinity.
+    let mut r = Point {
+        x: [0; WORDS],
+        y: one,
+        z: [0; WORDS],
+    };
+    for bit in (0..WORDS * 32).rev() {
+        r = r.add(&r, &f, &b);
+        let sum = r.add(&g, &f, &b);
+        let bit = (k[bit / 32] >> (bit % 32)) & 1;
+        select(&mut r.x, &sum.x, bit);
+        select(&mut r.y, &sum.y, bit);
+        select(&mut r.z, &sum.z, bit);
+    }
+
+    if r.z == [0; WORDS] {
+        return None;
+    }
+    let z_inv = f.invert(&r.z);
+    Some((f.to_plain(&f.mul(&r.x, &z_inv)), f.to_plain(&f.mul(&r.y, &z_inv))))
+}
+
+// ----------------------------------------------------------------------------
+
+/// A P-256 public key in affine coordinates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PublicKey {
+    /// The big-endian x coordinate.
+    pub x: [u8; SCALAR_LEN],
+
+    /// The big-endian y coordinate.
+    pub y: [u8; SCALAR_LEN],
+}
+
+/// An ECDSA signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Signature {
+    /// The big-endian r.
+    pub r: [u8; SCALAR_LEN],
+
+    /// The big-endian s.
+    pub s: [u8; SCALAR_LEN],
+}
+
+// Write the DER INTEGER of the big-endian unsigned `value` to `out`.
+// Returns the number of bytes written.
+fn integer_to_der(value: &[u8; SCALAR_LEN], out: &mut [u8]) -> usize {
+    let zeros = value.iter().take_while(|byte| **byte == 0).count();
+    let value = &value[core::cmp::min(zeros, SCALAR_LEN - 1)..];
+    let pad = (value[0] >> 7) as usize;
+    out[0] = 0x02;
+    out[1] = (value.len() + pad) as u8;
+    out[2] = 0;
+    out[2 + pad..2 + pad + value.len()].copy_from_slice(value);
+    2 + pad + value.len()
+}
+
+impl Signature {
+    /// Write the signature as a DER encoded `Ecdsa-Sig-Value` (RFC 3279)
+    /// to `out`. Returns the number of bytes written.
+    pub fn to_der(&self, out: &mut [u8; MAX_DER_SIGNATURE_LEN]) -> usize {
+        let mut len = 2;
+        len += integer_to_der(&self.r, &mut out[len..]);
+        len += integer_to_der(&self.s, &mut out[len..]);
+        out[0] = 0x30;
+        out[1] = (len - 2) as u8;
+        len
+    }
+}
+
+/// A P-256 private key. The key is wiped from memory when it is dropped.
+#[derive(Clone)]
+pub struct PrivateKey {
+    d: Words,
+}
+
+impl Drop for PrivateKey {
+    fn drop(&mut self) {
+        wipe(&mut self.d);
+    }
+}
+
+impl PrivateKey {
+    /// Create a private key from the big-endian scalar `bytes`.
+    pub fn from_bytes(bytes: &[u8; SCALAR_LEN]) -> Result<Self, P256Error> {
+        let mut d = [0; WORDS];
+        load(bytes, &mut d);
+        if d == [0; WORDS] || greater_or_equal(&d, &N) {
+            return Err(P256Error::InvalidKey);
+        }
+        Ok(Self { d })
+    }
+
+    /// Derive a private key from the 32 byte `seed`, the way the H1
+    /// cryptography library does: the seed is rehashed with SHA-256 until
+    /// it is at most n - 2, and the key is the seed plus one.
+    pub fn from_seed(seed: &[u8; SCALAR_LEN]) -> Self {
+        let mut n_minus_one = N;
+        n_minus_one[0] -= 1;
+        let mut seed = *seed;
+        loop {
+            let mut d = [0; WORDS];
+            load(&seed, &mut d);
+            if !greater_or_equal(&d, &n_minus_one) {
+                add(&mut d, &[1, 0, 0, 0, 0, 0, 0, 0], !0);
+                wipe(&mut seed);
+                return Self { d };
+            }
+            seed = Sha256::new().add(&seed).finish();
+        }
+    }
+
+    /// Get the big-endian scalar of the private key.
+    pub fn to_bytes(&self) -> [u8; SCALAR_LEN] {
+        let mut bytes = [0u8; SCALAR_LEN];
+        store(&self.d, &mut bytes);
+        bytes
+    }
+
+    /// Get the public key d * G.
+    pub fn public_key(&self) -> PublicKey {
+        // d is in [1, n - 1], so d * G is never the point at infinity.
+        let (x, y) = multiply_base(&self.d).unwrap_or(([0; WORDS], [0; WORDS]));
+        let mut key = PublicKey {
+            x: [0; SCALAR_LEN],
+            y: [0; SCALAR_LEN],
+        };
+        store(&x, &mut key.x);
+        store(&y, &mut key.y);
+        key
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // RFC 6979 A.2.5.
+    const PRIVATE_KEY: [u8; SCALAR_LEN] = [
+        0xc9, 0xaf, 0xa9, 0xd8, 0x45, 0xba, 0x75, 0x16, 0x6b, 0x5c, 0x21, 0x57, 0x67, 0xb1, 0xd6, 0x93,
+        0x4e, 0x50, 0xc3, 0xdb, 0x36, 0xe8, 0x9b, 0x12, 0x7b, 0x8a, 0x62, 0x2b, 0x12, 0x0f, 0x67, 0x21,
+    ];
+    const PUBLIC_X: [u8; SCALAR_LEN] = [
+        0x60, 0xfe, 0xd4, 0xba, 0x25, 0x5a, 0x9d, 0x31, 0xc9, 0x61, 0xeb, 0x74, 0xc6, 0x35, 0x6d, 0x68,
+        0xc0, 0x49, 0xb8, 0x92, 0x3b, 0x61, 0xfa, 0x6c, 0xe6, 0x69, 0x62, 0x2e, 0x60, 0xf2, 0x9f, 0xb6,
+    ];
+    const PUBLIC_Y: [u8; SCALAR_LEN] = [
+        0x79, 0x03, 0xfe, 0x10, 0x08, 0xb8, 0xbc, 0x99, 0xa4, 0x1a, 0xe9, 0xe9, 0x56, 0x28, 0xbc, 0x64,
+        0xf2, 0xf1, 0xb2, 0x0c, 0x2d, 0x7e, 0x9f, 0x51, 0x77, 0xa3, 0xc2, 0x94, 0xd4, 0x46, 0x22, 0x99,
+    ];
+    const SAMPLE_R: [u8; SCALAR_LEN] = [
+        0xef, 0xd4, 0x8b, 0x2a, 0xac, 0xb6, 0xa8, 0xfd, 0x11, 0x40, 0xdd, 0x9c, 0xd4, 0x5e, 0x81, 0xd6,
+        0x9d, 0x2c, 0x87, 0x7b, 0x56, 0xaa, 0xf9, 0x91, 0xc3, 0x4d, 0x0e, 0xa8, 0x4e, 0xaf, 0x37, 0x16,
+    ];
+    const SAMPLE_S: [u8; SCALAR_LEN] = [
+        0xf7, 0xcb, 0x1c, 0x94, 0x2d, 0x65, 0x7c, 0x41, 0xd4, 0x36, 0xc7, 0xa1, 0xb6, 0xe2, 0x9f, 0x65,
+        0xf3, 0xe9, 0x00, 0xdb, 0xb9, 0xaf, 0xf4, 0x06, 0x4d, 0xc4, 0xab, 0x2f, 0x84, 0x3a, 0xcd, 0xa8,
+    ];
+
+    #[test]
+    fn public_key() {
+        let key = PrivateKey::from_bytes(&PRIVATE_KEY).unwrap();
+        assert_eq!(key.public_key(), PublicKey { x: PUBLIC_X, y: PUBLIC_Y });
+    }
+
+    #[test]
+    fn to_der() {
+        let signature = Signature { r: SAMPLE_R, s: SAMPLE_S };
+
+        let mut der = [0u8; MAX_DER_SIGNATURE_LEN];
+        let len = signature.to_der(&mut der);
+        assert_eq!(len, MAX_DER_SIGNATURE_LEN);
+        assert_eq!(der[..4], [0x30, 70, 0x02, 33]);
+        assert_eq!(der[4..6], [0x00, 0xef]);
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert_eq!(PrivateKey::from_bytes(&[0; SCALAR_LEN]).err(), Some(P256Error::InvalidKey));
+        assert_eq!(PrivateKey::from_bytes(&[0xff; SCALAR_LEN]).err(), Some(P256Error::InvalidKey));
+    }
+
+    #[test]
+    fn from_seed() {
+        // Seeds up to n - 2 are used directly.
+        let mut seed = PRIVATE_KEY;
+        seed[SCALAR_LEN - 1] -= 1;
+        let key = PrivateKey::from_seed(&seed);
+        assert_eq!(key.to_bytes(), PRIVATE_KEY);
+
+        // Larger seeds are rehashed.
+        let seed = [0xff; SCALAR_LEN];
+        let mut expected = Sha256::new().add(&seed).finish();
+        expected[SCALAR_LEN - 1] += 1;
+        assert_eq!(PrivateKey::from_seed(&seed).to_bytes(), expected);
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! RSA PKCS#1 v1.5 signature verification with SHA-256 (RFC 8017).
+//!
+//! Supports 2048 and 3072 bit moduli. The bignum arithmetic uses Montgomery
+//! multiplication on 32-bit words. All intermediate values are kept in a
+//! caller-provided `Workspace`, so that the verification itself only needs
+//! a few words of stack.
+
+use crate::crypto::bignum::greater_or_equal;
+use crate::crypto::bignum::load;
+use crate::crypto::bignum::montgomery_multiply;
+use crate::crypto::bignum::montgomery_r2;
+use crate::crypto::bignum::neg_inverse;
+use crate::protocol::integrity::SHA256_LEN;
+
+use core::result::Result;
+
+/// The length of a 2048 bit modulus, in bytes.
+pub const MODULUS_2048_LEN: usize = 256;
+
+/// The length of a 3072 bit modulus, in bytes.
+pub const MODULUS_3072_LEN: usize = 384;
+
+/// The maximum supported modulus length, in bytes.
+pub const MAX_MODULUS_LEN: usize = MODULUS_3072_LEN;
+
+// The maximum supported modulus length, in words.
+const MAX_WORDS: usize = MAX_MODULUS_LEN / 4;
+
+/// The DER encoded DigestInfo prefix of a SHA-256 digest.
+pub const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+    0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20,
+];
+
+/// Errors while verifying a signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RsaError {
+    /// The modulus length is not supported or the modulus is not odd.
+    UnsupportedModulus,
+
+    /// The public exponent is not odd or less than 3.
+    InvalidExponent,
+
+    /// The signature does not have the length of the modulus or is not
+    /// less than the modulus.
+    InvalidSignature,
+
+    /// The signature does not match the digest.
+    VerifyFailed,
+}
+
+/// An RSA public key.
+#[derive(Clone, Copy)]
+pub struct PublicKey {
+    modulus: [u8; MAX_MODULUS_LEN],
+    modulus_len: usize,
+    exponent: u32,
+}
+
+impl PublicKey {
+    /// Create a public key from the big-endian `modulus` and `exponent`.
+    pub fn new(modulus: &[u8], exponent: u32) -> Result<Self, RsaError> {
+        if modulus.len() != MODULUS_2048_LEN && modulus.len() != MODULUS_3072_LEN {
+            return Err(RsaError::UnsupportedModulus);
+        }
+        if modulus[0] == 0 || modulus[modulus.len() - 1] & 1 == 0 {
+            return Err(RsaError::UnsupportedModulus);
+        }
+        if exponent < 3 || exponent & 1 == 0 {
+            return Err(RsaError::InvalidExponent);
+        }
+        let mut key = PublicKey {
+            modulus: [0; MAX_MODULUS_LEN],
+            modulus_len: modulus.len(),
+            exponent,
+        };
+        key.modulus[..modulus.len()].copy_from_slice(modulus);
+        Ok(key)
+    }
+
+    /// The big-endian modulus.
+    pub fn modulus(&self) -> &[u8] {
+        &self.modulus[..self.modulus_len]
+    }
+
+    /// The length of the modulus, in bytes.
+    pub fn modulus_len(&self) -> usize {
+        self.modulus_len
+    }
+
+    /// The public exponent.
+    pub fn exponent(&self) -> u32 {
+        self.exponent
+    }
+}
+
+/// Scratch memory for `verify`.
+/// This is too large for small stacks and should be kept in static memory
+/// on the device.
+pub struct Workspace {
+    n: [u32; MAX_WORDS],
+    a: [u32; MAX_WORDS],
+    b: [u32; MAX_WORDS],
+    t: [u32; MAX_WORDS + 2],
+}
+
+impl Workspace {
+    /// Create a zeroed workspace.
+    pub const fn new() -> Self {
+        Self {
+            n: [0; MAX_WORDS],
+            a: [0; MAX_WORDS],
+            b: [0; MAX_WORDS],
+            t: [0; MAX_WORDS + 2],
+        }
+    }
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Get byte `idx` of the big-endian representation of the `len` byte number
+// `words`.
+fn byte_at(words: &[u32], len: usize, idx: usize) -> u8 {
+    let pos = len - 1 - idx;
+    (words[pos / 4] >> (8 * (pos % 4))) as u8
+}
+
+/// Verify the PKCS#1 v1.5 `signature` of the SHA-256 `digest` with `key`.
+pub fn verify(
+    key: &PublicKey,
+    signature: &[u8],
+    digest: &[u8; SHA256_LEN],
+    workspace: &mut Workspace,
+) -> Result<(), RsaError> {
+    let len = key.modulus_len();
+    if signature.len() != len {
+        return Err(RsaError::InvalidSignature);
+    }
+    let words = len / 4;
+    let Workspace { n, a, b, t } = workspace;
+    let n = &mut n[..words];
+    let a = &mut a[..words];
+    let b = &mut b[..words];
+
+    load(key.modulus(), n);
+    load(signature, b);
+    if greater_or_equal(b, n) {
+        return Err(RsaError::InvalidSignature);
+    }
+    let n0inv = neg_inverse(n[0]);
+
+    // Convert the signature into the Montgomery domain.
+    montgomery_r2(n, a);
+    montgomery_multiply(b, a, n, n0inv, t);
+    b.copy_from_slice(&t[..words]);
+
+    // Left-to-right square-and-multiply.
+    let exponent = key.exponent();
+    a.copy_from_slice(b);
+    for bit in (0..31 - exponent.leading_zeros()).rev() {
+        montgomery_multiply(a, a, n, n0inv, t);
+        a.copy_from_slice(&t[..words]);
+        if (exponent >> bit) & 1 != 0 {
+            montgomery_multiply(a, b, n, n0inv, t);
+            a.copy_from_slice(&t[..words]);
+        }
+    }
+
+    // Convert the result out of the Montgomery domain.
+    for word in b.iter_mut() {
+        *word = 0;
+    }
+    b[0] = 1;
+    montgomery_multiply(a, b, n, n0inv, t);
+
+    // Compare against EM = 0x00 || 0x01 || PS || 0x00 || DigestInfo || H.
+    let digest_start = len - SHA256_LEN;
+    let info_start = digest_start - SHA256_DIGEST_INFO.len();
+    let mut diff = 0u8;
+    for idx in 0..len {
+        let expected = match idx {
+            0 => 0x00,
+            1 => 0x01,
+            _ if idx < info_start - 1 => 0xff,
+            _ if idx == info_start - 1 => 0x00,
+            _ if idx < digest_start => SHA256_DIGEST_INFO[idx - info_start],
+            _ => digest[idx - digest_start],
+        };
+        diff |= byte_at(&t[..words], len, idx) ^ expected;
+    }
+    if diff != 0 {
+        return Err(RsaError::VerifyFailed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::integrity::Sha256;
+
+    const MESSAGE: &[u8] = b"otpilot manticore";
+
+    // Signatures of MESSAGE with a 2048 bit key (e = 65537) and a 3072 bit
+    // key (e = 3), generated with pyca/cryptography.
+    const MODULUS_2048: [u8; 256] = [
+        0xbc, 0xf9, 0x7f, 0x2e, 0x41, 0x6c, 0x4a, 0xd6, 0x9c, 0x57, 0x68, 0x2c,
+        0x81, 0x4b, 0x80, 0xdf, 0xaa, 0x54, 0x8f, 0x8b, 0x70, 0x9c, 0x2d, 0xd5,
+        0xed, 0xcc, 0x4a, 0x37, 0xa0, 0x69, 0x05, 0xf1, 0xdf, 0x23, 0x38, 0x42,
+        0x9d, 0x7b, 0xdb, 0x31, 0xf3, 0x41, 0x88, 0x9e, 0x0a, 0xa1, 0xc9, 0x56,
+        0x94, 0x98, 0x1a, 0xd3, 0xda, 0xad, 0x42, 0xb2, 0xf3, 0x26, 0x75, 0xc0,
+        0xfe, 0xa6, 0x30, 0x70, 0x47, 0xb6, 0xbf, 0xb5, 0x75, 0x83, 0xa6, 0xf9,
+        0x0e, 0x60, 0xab, 0xd5, 0x2b, 0x92, 0xbe, 0x98, 0x98, 0x23, 0x7a, 0x1b,
+        0xc7, 0x8c, 0x5d, 0x0d, 0xb5, 0xb1, 0xa4, 0x3d, 0x62, 0xd0, 0x37, 0x09,
+        0x9a, 0x73, 0x49, 0x90, 0x28, 0x7c, 0x91, 0x3f, 0xf8, 0x1a, 0xf2, 0xe9,
+        0xaa, 0xe7, 0xd5, 0xd3, 0xaa, 0x4b, 0x20, 0x9e, 0x6e, 0x79, 0xcb, 0xba,
+        0x68, 0xe0, 0x53, 0xda, 0x1b, 0xd4, 0x5d, 0x43, 0x6b, 0x38, 0xe1, 0x8b,
+        0x07, 0x55, 0x0a, 0xa7, 0x08, 0xdd, 0x58, 0x6d, 0x79, 0xdc, 0x41, 0x41,
+        0x0c, 0xe6, 0x85, 0x1d, 0xc3, 0xf2, 0x49, 0x89, 0x5a, 0xb1, 0x21, 0xa1,
+        0x96, 0x01, 0x3e, 0xc2, 0xd8, 0xc8, 0xda, 0x2c, 0x24, 0xa9, 0x0e, 0xf0,
+        0x94, 0x01, 0xd6, 0xc6, 0xcb, 0x4a, 0x85, 0x42, 0x84, 0x93, 0x11, 0x3e,
+        0x24, 0xe9, 0x2a, 0xa7, 0xe1, 0x7d, 0xf4, 0x6a, 0x43, 0xfc, 0x45, 0x00,
+        0x53, 0x33, 0xdb, 0x85, 0xe2, 0x27, 0x1c, 0x46, 0xc8, 0x57, 0x8c, 0x56,
+        0x6d, 0xb6, 0xb0, 0x32, 0xba, 0x8f, 0x3a, 0x25, 0x63, 0x82, 0xe9, 0x2b,
+        0xec, 0x2b, 0x7a, 0x3d, 0x14, 0x60, 0xa2, 0x12, 0xb7, 0xe7, 0xf1, 0x9e,
+        0xa1, 0x3a, 0xe4, 0xcf, 0x3c, 0x06, 0x63, 0xb0, 0xd1, 0x83, 0x45, 0x74,
+        0x6c, 0x12, 0x70, 0xc5, 0x7f, 0xac, 0x27, 0x89, 0x4e, 0x8c, 0x54, 0x77,
+        0x2a, 0x26, 0x16, 0x95,
+    ];
+
+    const SIGNATURE_2048: [u8; 256] = [
+        0x3b, 0xa8, 0xf8, 0x66, 0x6b, 0x06, 0x3a, 0x82, 0x6d, 0xcd, 0x19, 0xf6,
+        0x11, 0xb4, 0xe4, 0xef, 0x0b, 0x05, 0x90, 0x78, 0x11, 0x84, 0xa6, 0xc6,
+        0xdc, 0xdf, 0x16, 0x02, 0xb8, 0xfe, 0xff, 0x70, 0xfb, 0x18, 0x31, 0xef,
+        0xec, 0x43, 0x09, 0x6b, 0xc0, 0x3d, 0xaa, 0xbf, 0x0f, 0x77, 0x19, 0x28,
+        0xb8, 0x84, 0x6b, 0x8b, 0x49, 0x50, 0x89, 0xc2, 0x37, 0xa2, 0xf8, 0xba,
+        0x93, 0x02, 0x36, 0xb4, 0x55, 0xbb, 0x54, 0x66, 0xb8, 0x2a, 0xa2, 0x7f,
+        0x43, 0xdf, 0x5a, 0x44, 0xc3, 0x44, 0x01, 0xaf, 0x11, 0xd6, 0x0c, 0x45,
+        0xd2, 0xf5, 0x0b, 0x6b, 0xbe, 0x3b, 0xaf, 0x64, 0xb8, 0x29, 0x45, 0xcc,
+        0xce, 0x65, 0x67, 0x94, 0x68, 0xcf, 0x48, 0x29, 0xf5, 0x43, 0x9e, 0x3c,
+        0xb3, 0xab, 0x38, 0xd9, 0x2f, 0xae, 0xbb, 0x18, 0x38, 0x0d, 0xa7, 0xcd,
+        0x11, 0x8e, 0x7c, 0x03, 0xfb, 0x40, 0xb7, 0x24, 0x0c, 0x75, 0x4e, 0x01,
+        0x62, 0x36, 0x77, 0x90, 0x1f, 0x8d, 0x72, 0xd8, 0xec, 0x9a, 0x06, 0x73,
+        0x92, 0x3c, 0xfc, 0x1a, 0x7c, 0xca, 0x3e, 0x01, 0xe3, 0x0a, 0x9a, 0x43,
+        0xc9, 0x76, 0xbd, 0x1e, 0x65, 0x9a, 0x6d, 0x97, 0x54, 0x37, 0xb9, 0x9e,
+        0xc5, 0x21, 0x4e, 0x29, 0xe4, 0xed, 0x32, 0xf7, 0x04, 0x56, 0x20, 0xd9,
+        0xd6, 0xa2, 0xde, 0xf7, 0xc9, 0x80, 0x0c, 0xa6, 0x92, 0x5a, 0xf6, 0x38,
+        0xc3, 0x30, 0x63, 0x8f, 0x14, 0xef, 0x1e, 0x3c, 0x3f, 0x4a, 0xa3, 0x9e,
+        0xc7, 0xee, 0x92, 0xa7, 0xaf, 0x2b, 0xfa, 0x7c, 0xdb, 0x50, 0xb4, 0x41,
+        0xcd, 0x35, 0x3d, 0x2e, 0x18, 0xe3, 0x5a, 0xcf, 0xc3, 0xbe, 0x91, 0x37,
+        0x17, 0x3c, 0x0e, 0x64, 0x1b, 0xe9, 0xd0, 0x64, 0x8d, 0xe9, 0xab, 0x36,
+        0xaf, 0x4d, 0xd6, 0x54, 0x40, 0x78, 0x2b, 0xf3, 0x69, 0xfa, 0x0e, 0x78,
+        0xe5, 0xf8, 0xce, 0x27,
+    ];
+
+    const MODULUS_3072: [u8; 384] = [
+        0xc1, 0xbb, 0x16, 0x78, 0xff, 0xad, 0x57, 0x47, 0x5c, 0xda, 0xdc, 0xa0,
+        0x5d, 0xf7, 0x2b, 0x9a, 0x68, 0x62, 0x1a, 0x31, 0x89, 0x99, 0xca, 0xda,
+        0x7a, 0x4d, 0x15, 0xb0, 0xfc, 0xd3, 0xd2, 0x4a, 0x22, 0x6e, 0x91, 0x7f,
+        0x4e, 0x11, 0x56, 0xd8, 0x3c, 0xf6, 0xbb, 0x02, 0x5f, 0xcd, 0x08, 0xf4,
+        0xb3, 0xdd, 0x8b, 0x49, 0xe0, 0x1d, 0x30, 0x20, 0x72, 0x58, 0xf1, 0xb6,
+        0x7c, 0x27, 0xac, 0x46, 0xb4, 0x3b, 0x7e, 0x67, 0x83, 0x2e, 0xca, 0x58,
+        0xda, 0x5c, 0x9f, 0x88, 0x01, 0x2a, 0xe4, 0xdd, 0xe4, 0xd4, 0xdf, 0x9c,
+        0xf7, 0xc8, 0x70, 0x8b, 0x38, 0xa9, 0x50, 0x06, 0x0f, 0xe6, 0x06, 0x76,
+        0x7b, 0xf3, 0xdc, 0xa0, 0x90, 0xd9, 0xa5, 0x9e, 0x6d, 0xae, 0xc4, 0x31,
+        0xaa, 0x03, 0xd8, 0x86, 0x1b, 0xd3, 0xf0, 0x26, 0x59, 0x23, 0xed, 0x8b,
+        0xbb, 0xb4, 0x64, 0xee, 0x88, 0x31, 0x12, 0x5b, 0x83, 0x44, 0x97, 0x57,
+        0x54, 0xcd, 0xef, 0x9b, 0xae, 0x5c, 0xe4, 0xfe, 0x9c, 0x16, 0x16, 0xb4,
+        0xe8, 0x7f, 0x76, 0x8b, 0xb2, 0x1b, 0x63, 0xd3, 0xf5, 0xda, 0x5c, 0x62,
+        0xd1, 0xbc, 0x40, 0xe9, 0xb7, 0x59, 0xea, 0x18, 0x90, 0x6d, 0x1e, 0x9d,
+        0x3a, 0xb7, 0x33, 0x30, 0xed, 0xc1, 0x0a, 0xa0, 0xdd, 0xf7, 0x6c, 0xb5,
+        0xee, 0x18, 0xeb, 0xa3, 0x60, 0x23, 0x09, 0x76, 0xb4, 0xab, 0xd5, 0xad,
+        0xaa, 0x88, 0x66, 0x0e, 0x50, 0x43, 0x37, 0xb9, 0x01, 0x69, 0x62, 0x6f,
+        0x3e, 0x98, 0x20, 0x91, 0x56, 0x95, 0x51, 0x8a, 0x82, 0x2b, 0x17, 0x75,
+        0x38, 0xd5, 0xd4, 0x32, 0xa0, 0x95, 0x70, 0x4d, 0x81, 0xa4, 0x55, 0x3d,
+        0x56, 0x2f, 0xec, 0x86, 0xa6, 0x32, 0xd8, 0x5d, 0x6d, 0x7e, 0x58, 0x67,
+        0x74, 0x86, 0xf1, 0xbf, 0x44, 0x00, 0x08, 0xe1, 0xca, 0x29, 0x63, 0xc3,
+        0xe0, 0x06, 0xbf, 0x33, 0xa7, 0x91, 0x69, 0x3f, 0xc9, 0x42, 0x42, 0x08,
+        0x61, 0xd0, 0x2a, 0x43, 0xc0, 0xdf, 0x32, 0x33, 0x9f, 0xa4, 0xd9, 0xa4,
+        0x8c, 0xb9, 0xac, 0x00, 0xec, 0x2f, 0x3d, 0x94, 0xcc, 0xb0, 0xbe, 0xcc,
+        0xc3, 0x43, 0x0a, 0xdb, 0x1a, 0x2d, 0xc3, 0x19, 0x87, 0xee, 0x8b, 0xee,
+        0x75, 0x95, 0x6a, 0xdd, 0x63, 0x26, 0x04, 0xe7, 0x7c, 0xb0, 0x74, 0x92,
+        0x19, 0xac, 0x17, 0x4c, 0x62, 0xb9, 0x67, 0x6d, 0x6a, 0x43, 0x7d, 0x91,
+        0xbe, 0xf1, 0x30, 0xed, 0x3f, 0xd9, 0xa6, 0xce, 0x25, 0xed, 0xb7, 0x78,
+        0x9b, 0xca, 0x66, 0xe3, 0xaa, 0xce, 0xc5, 0x38, 0x9a, 0xb3, 0x19, 0x02,
+        0x47, 0x1b, 0xfa, 0xe8, 0x20, 0x94, 0x3b, 0xf7, 0x4a, 0x8b, 0xe9, 0x1f,
+        0x88, 0x20, 0x1c, 0x24, 0x59, 0xdf, 0x69, 0x1e, 0xb2, 0x2f, 0x46, 0xbf,
+        0x4e, 0x0d, 0x07, 0xec, 0xf5, 0xad, 0xb6, 0x74, 0xdf, 0xb6, 0x22, 0x69,
+    ];
+
+    const SIGNATURE_3072: [u8; 384] = [
+        0x45, 0xd8, 0x8b, 0xd4, 0xbd, 0x3b, 0x99, 0x7e, 0x08, 0x4d, 0x66, 0x61,
+        0x7f, 0x9f, 0xcd, 0x23, 0x58, 0x78, 0x1e, 0xb5, 0x30, 0x14, 0x07, 0xb8,
+        0x21, 0x66, 0x58, 0xed, 0x23, 0x82, 0xd7, 0x86, 0x97, 0x6d, 0x65, 0xb0,
+        0x2c, 0xa8, 0x79, 0x2e, 0x6b, 0x37, 0x0c, 0xe5, 0xb1, 0x5d, 0x62, 0x39,
+        0xd2, 0x4f, 0x33, 0xe5, 0x73, 0xa4, 0x38, 0x2a, 0xdc, 0x19, 0x74, 0xc0,
+        0x12, 0xf8, 0xcb, 0x6b, 0x05, 0x7d, 0xdd, 0x61, 0x92, 0x91, 0xdc, 0x03,
+        0x4c, 0xfa, 0xbf, 0xcc, 0x66, 0x34, 0xbb, 0x3b, 0xb2, 0xdb, 0x36, 0x32,
+        0xb9, 0xf5, 0x20, 0x20, 0xa3, 0x7b, 0x7b, 0x87, 0xba, 0x38, 0xb6, 0x92,
+        0x24, 0x51, 0x58, 0x3d, 0x49, 0x26, 0xde, 0x53, 0x41, 0x48, 0x33, 0x1f,
+        0x52, 0x63, 0xe2, 0x93, 0xc4, 0x26, 0x60, 0x51, 0xb3, 0x55, 0xbe, 0x54,
+        0x5d, 0x0c, 0xb9, 0x4e, 0x3c, 0x8f, 0x2c, 0x33, 0xe8, 0x58, 0x00, 0xc5,
+        0xc4, 0x68, 0xf2, 0x1d, 0x7f, 0x55, 0xc2, 0x1f, 0xb8, 0xf7, 0x4d, 0x4e,
+        0x2b, 0xd0, 0xc4, 0x15, 0xcb, 0x6c, 0xd2, 0xff, 0xe3, 0x48, 0x65, 0x1b,
+        0x87, 0x57, 0xfc, 0xb2, 0x6e, 0x8f, 0x5b, 0x18, 0x39, 0x80, 0xab, 0x4e,
+        0xf3, 0x66, 0xaa, 0xd2, 0xb4, 0x2e, 0xc7, 0x20, 0x3f, 0x4b, 0x55, 0xf7,
+        0x9f, 0xa9, 0xab, 0x86, 0x9e, 0x2d, 0x8c, 0x32, 0x6b, 0x18, 0x0e, 0xb8,
+        0xb6, 0x40, 0x14, 0x9f, 0xc3, 0x2c, 0x29, 0xb4, 0x6c, 0x96, 0xed, 0xa1,
+        0xc9, 0xd4, 0x5e, 0x92, 0xf6, 0xf2, 0x11, 0xb6, 0x49, 0xe6, 0x44, 0x99,
+        0xd0, 0xa7, 0x60, 0x58, 0x46, 0x50, 0xf4, 0x8f, 0xcf, 0x04, 0xec, 0x5d,
+        0xdb, 0xf3, 0xf4, 0x98, 0x33, 0x28, 0xa7, 0xd3, 0xe9, 0x66, 0x11, 0xc6,
+        0x2d, 0x72, 0x76, 0xd5, 0x8c, 0xbb, 0x5b, 0x65, 0x78, 0x1b, 0x0a, 0xe4,
+        0x91, 0x01, 0x27, 0x30, 0xd3, 0xf3, 0x42, 0x1d, 0xe4, 0x2e, 0xa1, 0x6a,
+        0x8d, 0x9a, 0x75, 0xb8, 0x8a, 0x2f, 0xa7, 0x97, 0x91, 0x97, 0xb5, 0x40,
+        0x78, 0x56, 0x58, 0x81, 0x2c, 0xef, 0xf1, 0xb7, 0xff, 0x3b, 0xbe, 0x7d,
+        0xd9, 0x8c, 0x17, 0x32, 0x56, 0x1c, 0xa7, 0xc1, 0x20, 0x9f, 0x4f, 0x66,
+        0x76, 0x63, 0x74, 0xa7, 0x5f, 0x83, 0xe8, 0xf4, 0x96, 0x61, 0xd3, 0x36,
+        0x85, 0x38, 0xaa, 0xd2, 0x89, 0xc1, 0xb6, 0xeb, 0x7f, 0x6a, 0x08, 0xca,
+        0xf8, 0x70, 0xfa, 0xb3, 0x17, 0x4a, 0xbc, 0xde, 0xa7, 0x9f, 0x03, 0x15,
+        0x90, 0x89, 0xea, 0x34, 0x16, 0xa6, 0xc0, 0xb8, 0x89, 0x20, 0x5d, 0xb9,
+        0x6a, 0xc5, 0xd4, 0x29, 0x91, 0xcd, 0xce, 0x4e, 0x9d, 0x0e, 0xe9, 0x2d,
+        0xfa, 0xd0, 0x77, 0xd1, 0x44, 0xb4, 0x68, 0xc1, 0xcb, 0x17, 0x91, 0x62,
+        0x56, 0xde, 0xca, 0x69, 0x2e, 0x2e, 0xc8, 0x5e, 0xfb, 0x5d, 0x9d, 0x60,
+    ];
+
+    fn digest(message: &[u8]) -> [u8; SHA256_LEN] {
+        Sha256::new().add(message).finish()
+    }
+
+    #[test]
+    fn verify_2048() {
+        let key = PublicKey::new(&MODULUS_2048, 65537).unwrap();
+        let mut workspace = Workspace::new();
+        assert_eq!(verify(&key, &SIGNATURE_2048, &digest(MESSAGE), &mut workspace), Ok(()));
+        assert_eq!(verify(&key, &SIGNATURE_2048, &digest(b"otpilot"), &mut workspace),
+            Err(RsaError::VerifyFailed));
+
+        let mut signature = SIGNATURE_2048;
+        signature[100] ^= 0x01;
+        assert_eq!(verify(&key, &signature, &digest(MESSAGE), &mut workspace),
+            Err(RsaError::VerifyFailed));
+    }
+
+    #[test]
+    fn verify_3072() {
+        let key = PublicKey::new(&MODULUS_3072, 3).unwrap();
+        let mut workspace = Workspace::new();
+        assert_eq!(verify(&key, &SIGNATURE_3072, &digest(MESSAGE), &mut workspace), Ok(()));
+
+        // The right signature for the wrong exponent.
+        let key = PublicKey::new(&MODULUS_3072, 65537).unwrap();
+        assert_eq!(verify(&key, &SIGNATURE_3072, &digest(MESSAGE), &mut workspace),
+            Err(RsaError::VerifyFailed));
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let key = PublicKey::new(&MODULUS_2048, 65537).unwrap();
+        let mut workspace = Workspace::new();
+        assert_eq!(verify(&key, &SIGNATURE_2048[1..], &digest(MESSAGE), &mut workspace),
+            Err(RsaError::InvalidSignature));
+        assert_eq!(verify(&key, &MODULUS_2048, &digest(MESSAGE), &mut workspace),
+            Err(RsaError::InvalidSignature));
+        assert_eq!(verify(&key, &SIGNATURE_3072, &digest(MESSAGE), &mut workspace),
+            Err(RsaError::InvalidSignature));
+    }
+
+    #[test]
+    fn invalid_key() {
+        assert!(PublicKey::new(&MODULUS_2048[..128], 65537).is_err());
+        assert_eq!(PublicKey::new(&MODULUS_2048, 65536).err(), Some(RsaError::InvalidExponent));
+        assert_eq!(PublicKey::new(&MODULUS_2048, 1).err(), Some(RsaError::InvalidExponent));
+
+        let mut modulus = MODULUS_2048;
+        modulus[MODULUS_2048_LEN - 1] &= 0xfe;
+        assert_eq!(PublicKey::new(&modulus, 65537).err(), Some(RsaError::UnsupportedModulus));
+    }
+}
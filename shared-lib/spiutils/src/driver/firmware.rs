@@ -16,19 +16,10 @@
 
 //! Firmware kernel interface.
 
-use crate::io::Read;
-use crate::io::Write;
 use crate::protocol::firmware::SegmentAndLocation;
-use crate::protocol::wire::FromWireError;
-use crate::protocol::wire::FromWire;
-use crate::protocol::wire::ToWireError;
-use crate::protocol::wire::ToWire;
-use crate::protocol::wire::WireEnum;
-
-use core::mem;
 
 /// The length of a SegmentInfo on the wire, in bytes.
-pub const SEGMENT_INFO_LEN: usize = mem::size_of::<u8>() + 4 * mem::size_of::<u32>();
+pub const SEGMENT_INFO_LEN: usize = SegmentInfo::LEN;
 
 /// The "unknown" segment.
 pub const UNKNOWN_SEGMENT: SegmentInfo = SegmentInfo {
@@ -39,58 +30,30 @@ pub const UNKNOWN_SEGMENT: SegmentInfo = SegmentInfo {
     page_count: 0,
 };
 
-/// Information about a segment.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct SegmentInfo {
-    /// The segment and location this info struct pertains to.
-    pub identifier: SegmentAndLocation,
-
-    /// The start address of the segment.
-    pub address: u32,
+wire_struct! {
+    /// Information about a segment.
+    pub struct SegmentInfo {
+        /// The segment and location this info struct pertains to.
+        pub identifier: SegmentAndLocation as wire,
 
-    /// The size of the segment.
-    pub size: u32,
+        /// The start address of the segment.
+        pub address: u32 as be,
 
-    /// The start page of the segment.
-    pub start_page: u32,
+        /// The size of the segment.
+        pub size: u32 as be,
 
-    /// The number of pages in the segment.
-    pub page_count: u32,
-}
+        /// The start page of the segment.
+        pub start_page: u32 as be,
 
-impl<'a> FromWire<'a> for SegmentInfo {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let identifier = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        let address = r.read_be::<u32>()?;
-        let size = r.read_be::<u32>()?;
-        let start_page = r.read_be::<u32>()?;
-        let page_count = r.read_be::<u32>()?;
-        Ok(Self {
-            identifier,
-            address,
-            size,
-            start_page,
-            page_count,
-        })
-    }
-}
-
-impl ToWire for SegmentInfo {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.identifier.to_wire_value())?;
-        w.write_be(self.address)?;
-        w.write_be(self.size)?;
-        w.write_be(self.start_page)?;
-        w.write_be(self.page_count)?;
-        Ok(())
+        /// The number of pages in the segment.
+        pub page_count: u32 as be,
     }
 }
 
 // ----------------------------------------------------------------------------
 
 /// The length of a RuntimeSegmentInfo on the wire, in bytes.
-pub const RUNTIME_SEGMENT_INFO_LEN: usize = 4 * SEGMENT_INFO_LEN;
+pub const RUNTIME_SEGMENT_INFO_LEN: usize = RuntimeSegmentInfo::LEN;
 
 /// The "unknown" runtime segment.
 pub const UNKNOWN_RUNTIME_SEGMENT_INFO: RuntimeSegmentInfo = RuntimeSegmentInfo {
@@ -98,46 +61,30 @@ pub const UNKNOWN_RUNTIME_SEGMENT_INFO: RuntimeSegmentInfo = RuntimeSegmentInfo
     active_rw: UNKNOWN_SEGMENT,
     inactive_ro: UNKNOWN_SEGMENT,
     inactive_rw: UNKNOWN_SEGMENT,
+    active_nv: UNKNOWN_SEGMENT,
+    inactive_nv: UNKNOWN_SEGMENT,
 };
 
-/// Information about segments at runtime.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct RuntimeSegmentInfo {
-    /// The active RO.
-    pub active_ro: SegmentInfo,
+wire_struct! {
+    /// Information about segments at runtime.
+    pub struct RuntimeSegmentInfo {
+        /// The active RO.
+        pub active_ro: SegmentInfo as wire,
 
-    /// The active RW.
-    pub active_rw: SegmentInfo,
+        /// The active RW.
+        pub active_rw: SegmentInfo as wire,
 
-    /// The inactive RO.
-    pub inactive_ro: SegmentInfo,
+        /// The inactive RO.
+        pub inactive_ro: SegmentInfo as wire,
 
-    /// The inactive RW.
-    pub inactive_rw: SegmentInfo,
-}
+        /// The inactive RW.
+        pub inactive_rw: SegmentInfo as wire,
 
-impl<'a> FromWire<'a> for RuntimeSegmentInfo {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let active_ro = SegmentInfo::from_wire(&mut r)?;
-        let active_rw = SegmentInfo::from_wire(&mut r)?;
-        let inactive_ro = SegmentInfo::from_wire(&mut r)?;
-        let inactive_rw = SegmentInfo::from_wire(&mut r)?;
-        Ok(Self {
-            active_ro,
-            active_rw,
-            inactive_ro,
-            inactive_rw,
-        })
-    }
-}
+        /// The NVMEM in the active bank.
+        pub active_nv: SegmentInfo as wire,
 
-impl ToWire for RuntimeSegmentInfo {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        self.active_ro.to_wire(&mut w)?;
-        self.active_rw.to_wire(&mut w)?;
-        self.inactive_ro.to_wire(&mut w)?;
-        self.inactive_rw.to_wire(&mut w)?;
-        Ok(())
+        /// The NVMEM in the inactive bank.
+        pub inactive_nv: SegmentInfo as wire,
     }
 }
 
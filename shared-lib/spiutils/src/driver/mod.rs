@@ -17,5 +17,6 @@
 //! SPI protocol modules.
 
 pub mod firmware;
+pub mod personality;
 pub mod reset;
 pub mod spi_device;
@@ -0,0 +1,64 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Personality kernel interface.
+//!
+//! The personality data holds the device attestation data. It is read as
+//! the kernel's `PersonalityData`, whose layout is defined here. All
+//! numbers are little endian.
+
+/// The length of the personality data, in bytes.
+pub const PERSONALITY_LEN: usize = 2048;
+
+/// The offset of the checksum that binds the certificate to the chip.
+pub const CHECKSUM_OFFSET: usize = 0;
+
+/// The offset of the salt from which the personality key is derived.
+pub const SALT_OFFSET: usize = 32;
+
+/// The offset of the x coordinate of the personality public key.
+pub const PUB_X_OFFSET: usize = 64;
+
+/// The offset of the y coordinate of the personality public key.
+pub const PUB_Y_OFFSET: usize = 96;
+
+/// The offset of the SHA-256 hash of the device certificate.
+pub const CERTIFICATE_HASH_OFFSET: usize = 128;
+
+/// The offset of the length of the device certificate (u32).
+pub const CERTIFICATE_LEN_OFFSET: usize = 160;
+
+/// The offset of the device certificate.
+pub const CERTIFICATE_OFFSET: usize = 164;
+
+/// The maximum length of the device certificate.
+pub const MAX_CERTIFICATE_LEN: usize = PERSONALITY_LEN - CERTIFICATE_OFFSET;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout() {
+        // Five 32-byte fields precede the certificate length.
+        assert_eq!(SALT_OFFSET, CHECKSUM_OFFSET + 32);
+        assert_eq!(PUB_X_OFFSET, SALT_OFFSET + 32);
+        assert_eq!(PUB_Y_OFFSET, PUB_X_OFFSET + 32);
+        assert_eq!(CERTIFICATE_HASH_OFFSET, PUB_Y_OFFSET + 32);
+        assert_eq!(CERTIFICATE_LEN_OFFSET, CERTIFICATE_HASH_OFFSET + 32);
+        assert_eq!(CERTIFICATE_OFFSET, CERTIFICATE_LEN_OFFSET + 4);
+    }
+}
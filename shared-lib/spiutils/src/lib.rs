@@ -20,10 +20,15 @@
 
 //! Utilities for inter-component communication.
 
+// Declared first so that its macros are available in the other modules.
+#[macro_use]
+pub mod protocol;
+
+#[cfg(feature = "std")]
+pub mod client;
+
 pub mod compat;
+pub mod crypto;
 pub mod driver;
 
 pub mod io;
-
-#[macro_use]
-pub mod protocol;
@@ -0,0 +1,126 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Capabilities protocol payload.
+//!
+//! Allows a host to discover which protocol revision and firmware operations
+//! a device supports before using them.
+
+use crate::protocol::firmware;
+use crate::protocol::wire::FromWire;
+use crate::protocol::wire::ToWire;
+use crate::protocol::wire::WireEnum;
+
+/// The revision of the SPI mailbox protocol implemented by this crate.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+wire_enum! {
+    /// The content type.
+    pub enum ContentType: u8 {
+        /// Request the device capabilities
+        CapabilitiesRequest = 0x01,
+
+        /// Response to CapabilitiesRequest
+        CapabilitiesResponse = 0x02,
+    }
+}
+
+wire_struct! {
+    /// A parsed header.
+    pub struct Header {
+        /// The content type following the header.
+        pub content: ContentType as wire,
+    }
+}
+
+/// The length of a capabilities header on the wire, in bytes.
+pub const HEADER_LEN: usize = Header::LEN;
+
+// ----------------------------------------------------------------------------
+
+/// A message.
+///
+/// A message is identified by a [`ContentType`]:
+///
+/// This trait is not implemented by any of the message types
+///
+/// [`ContentType`]: enum.ContentType.html
+pub trait Message<'req>: FromWire<'req> + ToWire {
+    /// The unique [`ContentType`] for this `Message`.
+    ///
+    /// [`ContentType`]: enum.ContentType.html
+    const TYPE: ContentType;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed capabilities request.
+    pub struct CapabilitiesRequest {
+    }
+}
+
+/// The length of a capabilities request on the wire, in bytes.
+pub const CAPABILITIES_REQUEST_LEN: usize = CapabilitiesRequest::LEN;
+
+impl Message<'_> for CapabilitiesRequest {
+    const TYPE: ContentType = ContentType::CapabilitiesRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the bit representing `content` in a firmware content type mask.
+pub fn firmware_content_type_bit(content: firmware::ContentType) -> u64 {
+    1u64.checked_shl(content.to_wire_value() as u32).unwrap_or(0)
+}
+
+/// Returns a firmware content type mask with the bits for all `contents` set.
+pub fn firmware_content_type_mask(contents: &[firmware::ContentType]) -> u64 {
+    contents.iter().fold(0, |mask, content| mask | firmware_content_type_bit(*content))
+}
+
+wire_struct! {
+    /// A parsed capabilities response.
+    pub struct CapabilitiesResponse {
+        /// The revision of the SPI mailbox protocol.
+        pub protocol_version: u16 as be,
+
+        /// The supported firmware content types.
+        /// Bit N is set if the firmware content type with wire value N is
+        /// supported.
+        pub firmware_content_types: u64 as be,
+
+        /// The size of the mailbox, in bytes.
+        pub mailbox_size: u32 as be,
+
+        /// The maximum chunk length per firmware write.
+        pub max_chunk_length: u16 as be,
+    }
+}
+
+/// The length of a capabilities response on the wire, in bytes.
+pub const CAPABILITIES_RESPONSE_LEN: usize = CapabilitiesResponse::LEN;
+
+impl Message<'_> for CapabilitiesResponse {
+    const TYPE: ContentType = ContentType::CapabilitiesResponse;
+}
+
+impl CapabilitiesResponse {
+    /// Returns whether the device supports the firmware content type `content`.
+    pub fn supports_firmware(&self, content: firmware::ContentType) -> bool {
+        self.firmware_content_types & firmware_content_type_bit(content) != 0
+    }
+}
@@ -16,13 +16,8 @@
 
 //! Error protocol messages.
 
-use crate::io::Read;
-use crate::io::Write;
-use crate::protocol::wire::FromWireError;
 use crate::protocol::wire::FromWire;
-use crate::protocol::wire::ToWireError;
 use crate::protocol::wire::ToWire;
-use crate::protocol::wire::WireEnum;
 
 wire_enum! {
     /// The content type.
@@ -32,36 +27,62 @@ wire_enum! {
 
         /// The content type on the message is not supported.
         ContentTypeNotSupported = 0x02,
+
+        /// A fragment was out of sequence or the reassembled message was too long.
+        BadFragment = 0x03,
+
+        /// The request could not be parsed.
+        MalformedPayload = 0x04,
+
+        /// The operation requested by the message is not supported.
+        UnsupportedOperation = 0x05,
+
+        /// The device failed to process the request.
+        InternalError = 0x06,
+
+        /// The device cannot process the request at this time.
+        Busy = 0x07,
     }
 }
 
-/// A parsed header.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct Header {
-    /// The content type following the header.
-    pub content: ContentType,
-}
+/// The `request` value used if the failed payload is too short to carry a
+/// content type.
+///
+/// Errors of SPI flash commands outside of the mailbox are not reported with
+/// error messages, since those would overwrite a pending mailbox response.
+pub const NO_REQUEST: u8 = 0x00;
 
-/// The length of a firmware header on the wire, in bytes.
-pub const HEADER_LEN: usize = 1;
-
-impl<'a> FromWire<'a> for Header {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let content_u8 = r.read_be::<u8>()?;
-        let content = ContentType::from_wire_value(content_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            content,
-        })
+wire_enum! {
+    /// The cause of an `InternalError`.
+    pub enum InternalErrorCode: u8 {
+        /// The response could not be serialized.
+        Serialization = 0x01,
+
+        /// A kernel driver returned an error.
+        Driver = 0x02,
+
+        /// The Manticore handler failed to process the request.
+        Manticore = 0x03,
+
+        /// The host acknowledged a fragment that was not sent.
+        NoPendingFragment = 0x04,
+
+        /// A formatting error occurred.
+        Format = 0x05,
     }
 }
 
-impl ToWire for Header {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.content.to_wire_value())?;
-        Ok(())
+wire_struct! {
+    /// A parsed header.
+    pub struct Header {
+        /// The content type following the header.
+        pub content: ContentType as wire,
     }
 }
 
+/// The length of a firmware header on the wire, in bytes.
+pub const HEADER_LEN: usize = Header::LEN;
+
 // ----------------------------------------------------------------------------
 
 /// A message.
@@ -80,53 +101,119 @@ pub trait Message<'req>: FromWire<'req> + ToWire {
 
 // ----------------------------------------------------------------------------
 
-/// A parsed `bad checksum` message.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct BadChecksum {
+wire_struct! {
+    /// A parsed `bad checksum` message.
+    pub struct BadChecksum {
+    }
 }
 
 /// The length of a `bad checksum` message on the wire, in bytes.
-pub const BAD_CHECKSUM_LEN: usize = 0;
+pub const BAD_CHECKSUM_LEN: usize = BadChecksum::LEN;
 
 impl Message<'_> for BadChecksum {
     const TYPE: ContentType = ContentType::BadChecksum;
 }
 
-impl<'a> FromWire<'a> for BadChecksum {
-    fn from_wire<R: Read<'a>>(mut _r: R) -> Result<Self, FromWireError> {
-        Ok(Self {})
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed `ContentTypeNotSupported` message.
+    pub struct ContentTypeNotSupported {
+    }
+}
+
+/// The length of a `ContentTypeNotSupported` message on the wire, in bytes.
+pub const CONTENT_TYPE_NOT_SUPPORTED_LEN: usize = ContentTypeNotSupported::LEN;
+
+impl Message<'_> for ContentTypeNotSupported {
+    const TYPE: ContentType = ContentType::ContentTypeNotSupported;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed `BadFragment` message.
+    pub struct BadFragment {
     }
 }
 
-impl ToWire for BadChecksum {
-    fn to_wire<W: Write>(&self, mut _w: W) -> Result<(), ToWireError> {
-        Ok(())
+/// The length of a `BadFragment` message on the wire, in bytes.
+pub const BAD_FRAGMENT_LEN: usize = BadFragment::LEN;
+
+impl Message<'_> for BadFragment {
+    const TYPE: ContentType = ContentType::BadFragment;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed `MalformedPayload` message.
+    pub struct MalformedPayload {
+        /// The payload content type of the failed request.
+        pub request: u8 as be,
     }
 }
 
+/// The length of a `MalformedPayload` message on the wire, in bytes.
+pub const MALFORMED_PAYLOAD_LEN: usize = MalformedPayload::LEN;
+
+impl Message<'_> for MalformedPayload {
+    const TYPE: ContentType = ContentType::MalformedPayload;
+}
+
 // ----------------------------------------------------------------------------
 
-/// A parsed `ContentTypeNotSupported` message.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct ContentTypeNotSupported {
+wire_struct! {
+    /// A parsed `UnsupportedOperation` message.
+    pub struct UnsupportedOperation {
+        /// The payload content type of the failed request.
+        pub request: u8 as be,
+
+        /// The unsupported operation: the content type within the payload.
+        pub operation: u8 as be,
+    }
 }
 
-/// The length of a `ContentTypeNotSupported` message on the wire, in bytes.
-pub const CONTENT_TYPE_NOT_SUPPORTED_LEN: usize = 0;
+/// The length of a `UnsupportedOperation` message on the wire, in bytes.
+pub const UNSUPPORTED_OPERATION_LEN: usize = UnsupportedOperation::LEN;
 
-impl Message<'_> for ContentTypeNotSupported {
-    const TYPE: ContentType = ContentType::ContentTypeNotSupported;
+impl Message<'_> for UnsupportedOperation {
+    const TYPE: ContentType = ContentType::UnsupportedOperation;
 }
 
-impl<'a> FromWire<'a> for ContentTypeNotSupported {
-    fn from_wire<R: Read<'a>>(mut _r: R) -> Result<Self, FromWireError> {
-        Ok(Self {})
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed `InternalError` message.
+    pub struct InternalError {
+        /// The payload content type of the failed request.
+        pub request: u8 as be,
+
+        /// The cause of the error.
+        pub code: InternalErrorCode as wire,
     }
 }
 
-impl ToWire for ContentTypeNotSupported {
-    fn to_wire<W: Write>(&self, mut _w: W) -> Result<(), ToWireError> {
-        Ok(())
+/// The length of a `InternalError` message on the wire, in bytes.
+pub const INTERNAL_ERROR_LEN: usize = InternalError::LEN;
+
+impl Message<'_> for InternalError {
+    const TYPE: ContentType = ContentType::InternalError;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed `Busy` message.
+    pub struct Busy {
+        /// The payload content type of the rejected request.
+        pub request: u8 as be,
     }
 }
 
+/// The length of a `Busy` message on the wire, in bytes.
+pub const BUSY_LEN: usize = Busy::LEN;
+
+impl Message<'_> for Busy {
+    const TYPE: ContentType = ContentType::Busy;
+}
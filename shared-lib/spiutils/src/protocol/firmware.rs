@@ -17,16 +17,10 @@
 //! Firmware protocol payload.
 
 use crate::compat::firmware::BuildInfo;
-use crate::compat::firmware::BUILD_INFO_LEN;
 use crate::driver::firmware::SegmentInfo;
-use crate::driver::firmware::SEGMENT_INFO_LEN;
-use crate::io::Read;
-use crate::io::Write;
-use crate::protocol::wire::FromWireError;
+use crate::protocol::integrity::SHA256_LEN;
 use crate::protocol::wire::FromWire;
-use crate::protocol::wire::ToWireError;
 use crate::protocol::wire::ToWire;
-use crate::protocol::wire::WireEnum;
 
 wire_enum! {
     /// The content type.
@@ -54,36 +48,44 @@ wire_enum! {
 
         /// Response to RebootRequest
         RebootResponse = 0x08,
-    }
-}
 
-/// A parsed header.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct Header {
-    /// The content type following the header.
-    pub content: ContentType,
-}
+        /// Request to verify the digest of a segment
+        VerifySegmentRequest = 0x09,
 
-/// The length of a firmware header on the wire, in bytes.
-pub const HEADER_LEN: usize = 1;
-
-impl<'a> FromWire<'a> for Header {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let content_u8 = r.read_be::<u8>()?;
-        let content = ContentType::from_wire_value(content_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            content,
-        })
+        /// Response to VerifySegmentRequest
+        VerifySegmentResponse = 0x0a,
+
+        /// Request to read a chunk of firmware
+        ReadChunkRequest = 0x0b,
+
+        /// Response to ReadChunkRequest
+        ReadChunkResponse = 0x0c,
+
+        /// Request firmware information on all segments
+        FirmwareInfoRequest = 0x0d,
+
+        /// Response to FirmwareInfoRequest
+        FirmwareInfoResponse = 0x0e,
+
+        /// Request to query or cancel a pending reboot
+        PendingRebootRequest = 0x0f,
+
+        /// Response to PendingRebootRequest
+        PendingRebootResponse = 0x10,
     }
 }
 
-impl ToWire for Header {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.content.to_wire_value())?;
-        Ok(())
+wire_struct! {
+    /// A parsed header.
+    pub struct Header {
+        /// The content type following the header.
+        pub content: ContentType as wire,
     }
 }
 
+/// The length of a firmware header on the wire, in bytes.
+pub const HEADER_LEN: usize = Header::LEN;
+
 // ----------------------------------------------------------------------------
 
 /// A message.
@@ -119,141 +121,83 @@ wire_enum! {
 
         /// RW in location B
         RwB = 0x04,
+
+        /// NVMEM in location A
+        NvA = 0x05,
+
+        /// NVMEM in location B
+        NvB = 0x06,
     }
 }
 
 // ----------------------------------------------------------------------------
 
-/// A parsed inactive segments info request.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct InactiveSegmentsInfoRequest {
+wire_struct! {
+    /// A parsed inactive segments info request.
+    pub struct InactiveSegmentsInfoRequest {
+    }
 }
 
 /// The length of an inactive segments info request on the wire, in bytes.
-pub const INACTIVE_SEGMENTS_INFO_REQUEST_LEN: usize = 0;
+pub const INACTIVE_SEGMENTS_INFO_REQUEST_LEN: usize = InactiveSegmentsInfoRequest::LEN;
 
 impl Message<'_> for InactiveSegmentsInfoRequest {
     const TYPE: ContentType = ContentType::InactiveSegmentsInfoRequest;
 }
 
-impl<'a> FromWire<'a> for InactiveSegmentsInfoRequest {
-    fn from_wire<R: Read<'a>>(mut _r: R) -> Result<Self, FromWireError> {
-        Ok(Self {})
-    }
-}
-
-impl ToWire for InactiveSegmentsInfoRequest {
-    fn to_wire<W: Write>(&self, mut _w: W) -> Result<(), ToWireError> {
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
-/// A parsed inactive segments info response.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct InactiveSegmentsInfoResponse {
-    /// The inactive RO.
-    pub ro: SegmentInfo,
+wire_struct! {
+    /// A parsed inactive segments info response.
+    pub struct InactiveSegmentsInfoResponse {
+        /// The inactive RO.
+        pub ro: SegmentInfo as wire,
 
-    /// The inactive RW.
-    pub rw: SegmentInfo,
+        /// The inactive RW.
+        pub rw: SegmentInfo as wire,
+    }
 }
 
 /// The length of an inactive segments info response on the wire, in bytes.
-pub const INACTIVE_SEGMENTS_INFO_RESPONSE_LEN: usize = 2 * SEGMENT_INFO_LEN;
+pub const INACTIVE_SEGMENTS_INFO_RESPONSE_LEN: usize = InactiveSegmentsInfoResponse::LEN;
 
 impl Message<'_> for InactiveSegmentsInfoResponse {
     const TYPE: ContentType = ContentType::InactiveSegmentsInfoResponse;
 }
 
-impl<'a> FromWire<'a> for InactiveSegmentsInfoResponse {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let ro = SegmentInfo::from_wire(&mut r)?;
-        let rw = SegmentInfo::from_wire(&mut r)?;
-        Ok(Self {
-            ro,
-            rw,
-        })
-    }
-}
-
-impl ToWire for InactiveSegmentsInfoResponse {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        self.ro.to_wire(&mut w)?;
-        self.rw.to_wire(&mut w)?;
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
-/// A parsed firmware info message.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct FirmwareInfo {
-    /// The segment and location.
-    pub segment_and_location: SegmentAndLocation,
-
-    /// The build information.
-    pub build_info: BuildInfo,
-}
+wire_struct! {
+    /// A parsed firmware info message.
+    pub struct FirmwareInfo {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
 
-/// The length of a firmware info struct on the wire, in bytes.
-pub const FIRMWARE_INFO_LEN: usize = 1 + BUILD_INFO_LEN;
-
-impl<'a> FromWire<'a> for FirmwareInfo {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let segment_and_location = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        let build_info = BuildInfo::from_wire(r)?;
-        Ok(Self {
-            segment_and_location,
-            build_info,
-        })
+        /// The build information.
+        pub build_info: BuildInfo as wire,
     }
 }
 
-impl ToWire for FirmwareInfo {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.segment_and_location.to_wire_value())?;
-        self.build_info.to_wire(w)?;
-        Ok(())
-    }
-}
+/// The length of a firmware info struct on the wire, in bytes.
+pub const FIRMWARE_INFO_LEN: usize = FirmwareInfo::LEN;
 
 // ----------------------------------------------------------------------------
 
-/// A parsed update prepare request.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct UpdatePrepareRequest {
-    /// The segment and location.
-    pub segment_and_location: SegmentAndLocation,
+wire_struct! {
+    /// A parsed update prepare request.
+    pub struct UpdatePrepareRequest {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
+    }
 }
 
 /// The length of a update prepare request on the wire, in bytes.
-pub const UPDATE_PREPARE_REQUEST_LEN: usize = 1;
+pub const UPDATE_PREPARE_REQUEST_LEN: usize = UpdatePrepareRequest::LEN;
 
 impl Message<'_> for UpdatePrepareRequest {
     const TYPE: ContentType = ContentType::UpdatePrepareRequest;
 }
 
-impl<'a> FromWire<'a> for UpdatePrepareRequest {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let segment_and_location = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            segment_and_location,
-        })
-    }
-}
-
-impl ToWire for UpdatePrepareRequest {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.segment_and_location.to_wire_value())?;
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
 wire_enum! {
@@ -270,96 +214,50 @@ wire_enum! {
     }
 }
 
-/// A parsed update prepare response.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct UpdatePrepareResponse {
-    /// The segment and location.
-    pub segment_and_location: SegmentAndLocation,
+wire_struct! {
+    /// A parsed update prepare response.
+    pub struct UpdatePrepareResponse {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
 
-    /// The maximum chunk length per write.
-    pub max_chunk_length: u16,
+        /// The maximum chunk length per write.
+        pub max_chunk_length: u16 as be,
 
-    /// The result of the update prepare request.
-    pub result: UpdatePrepareResult,
+        /// The result of the update prepare request.
+        pub result: UpdatePrepareResult as wire,
+    }
 }
 
 /// The length of a update prepare response on the wire, in bytes.
-pub const UPDATE_PREPARE_RESPONSE_LEN: usize = 4;
+pub const UPDATE_PREPARE_RESPONSE_LEN: usize = UpdatePrepareResponse::LEN;
 
 impl Message<'_> for UpdatePrepareResponse {
     const TYPE: ContentType = ContentType::UpdatePrepareResponse;
 }
 
-impl<'a> FromWire<'a> for UpdatePrepareResponse {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let segment_and_location = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        let max_chunk_length = r.read_be::<u16>()?;
-        let result_u8 = r.read_be::<u8>()?;
-        let result = UpdatePrepareResult::from_wire_value(result_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            segment_and_location,
-            max_chunk_length,
-            result,
-        })
-    }
-}
-
-impl ToWire for UpdatePrepareResponse {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.segment_and_location.to_wire_value())?;
-        w.write_be(self.max_chunk_length)?;
-        w.write_be(self.result.to_wire_value())?;
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
-/// A parsed write chunk request.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct WriteChunkRequest<'a> {
-    /// The segment and location.
-    pub segment_and_location: SegmentAndLocation,
+wire_struct! {
+    /// A parsed write chunk request.
+    pub struct WriteChunkRequest<'a> {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
 
-    /// The offset within the segment.
-    pub offset: u32,
+        /// The offset within the segment.
+        pub offset: u32 as be,
 
-    /// The data to write
-    pub data: &'a [u8],
+        /// The data to write
+        pub data: &'a [u8] as rest,
+    }
 }
 
 /// The length of a write chunk request on the wire, in bytes.
-pub const WRITE_CHUNK_REQUEST_LEN: usize = 5;
+pub const WRITE_CHUNK_REQUEST_LEN: usize = WriteChunkRequest::LEN;
 
 impl<'a> Message<'a> for WriteChunkRequest<'a> {
     const TYPE: ContentType = ContentType::WriteChunkRequest;
 }
 
-impl<'a> FromWire<'a> for WriteChunkRequest<'a> {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let segment_and_location = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        let offset = r.read_be::<u32>()?;
-        let data_len = r.remaining_data();
-        let data = r.read_bytes(data_len)?;
-        Ok(Self {
-            segment_and_location,
-            offset,
-            data,
-        })
-    }
-}
-
-impl ToWire for WriteChunkRequest<'_> {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.segment_and_location.to_wire_value())?;
-        w.write_be(self.offset)?;
-        w.write_bytes(self.data)?;
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
 wire_enum! {
@@ -385,50 +283,27 @@ wire_enum! {
     }
 }
 
-/// A parsed write chunk response.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct WriteChunkResponse {
-    /// The segment and location.
-    pub segment_and_location: SegmentAndLocation,
+wire_struct! {
+    /// A parsed write chunk response.
+    pub struct WriteChunkResponse {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
 
-    /// The offset within the segment,
-    pub offset: u32,
+        /// The offset within the segment,
+        pub offset: u32 as be,
 
-    /// The result of the write chunk request.
-    pub result: WriteChunkResult,
+        /// The result of the write chunk request.
+        pub result: WriteChunkResult as wire,
+    }
 }
 
 /// The length of an write chunk response on the wire, in bytes.
-pub const WRITE_CHUNK_RESPONSE_LEN: usize = 6;
+pub const WRITE_CHUNK_RESPONSE_LEN: usize = WriteChunkResponse::LEN;
 
 impl Message<'_> for WriteChunkResponse {
     const TYPE: ContentType = ContentType::WriteChunkResponse;
 }
 
-impl<'a> FromWire<'a> for WriteChunkResponse {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let sal_u8 = r.read_be::<u8>()?;
-        let segment_and_location = SegmentAndLocation::from_wire_value(sal_u8).ok_or(FromWireError::OutOfRange)?;
-        let offset = r.read_be::<u32>()?;
-        let result_u8 = r.read_be::<u8>()?;
-        let result = WriteChunkResult::from_wire_value(result_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            segment_and_location,
-            offset,
-            result,
-        })
-    }
-}
-
-impl ToWire for WriteChunkResponse {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.segment_and_location.to_wire_value())?;
-        w.write_be(self.offset)?;
-        w.write_be(self.result.to_wire_value())?;
-        Ok(())
-    }
-}
-
 // ----------------------------------------------------------------------------
 
 wire_enum! {
@@ -442,84 +317,313 @@ wire_enum! {
     }
 }
 
-/// A parsed reboot request.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct RebootRequest {
-    /// When to reboot.
-    pub time: RebootTime,
+wire_struct! {
+    /// A parsed reboot request.
+    pub struct RebootRequest {
+        /// When to reboot.
+        pub time: RebootTime as wire,
+    }
 }
 
 /// The length of a reboot request on the wire, in bytes.
-pub const REBOOT_REQUEST_LEN: usize = 1;
+pub const REBOOT_REQUEST_LEN: usize = RebootRequest::LEN;
 
 impl Message<'_> for RebootRequest {
     const TYPE: ContentType = ContentType::RebootRequest;
 }
 
-impl<'a> FromWire<'a> for RebootRequest {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let time_u8 = r.read_be::<u8>()?;
-        let time = RebootTime::from_wire_value(time_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            time,
-        })
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The result of a reboot request.
+    pub enum RebootResult: u8 {
+        /// Success
+        Success = 0x00,
+
+        /// Unspecified error
+        Error = 0x01,
+    }
+}
+
+wire_struct! {
+    /// A parsed reboot response.
+    pub struct RebootResponse {
+        /// When to reboot from the request.
+        pub time: RebootTime as wire,
+
+        /// The result of the reboot request.
+        pub result: RebootResult as wire,
     }
 }
 
-impl ToWire for RebootRequest {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.time.to_wire_value())?;
-        Ok(())
+/// The length of a reboot response on the wire, in bytes.
+pub const REBOOT_RESPONSE_LEN: usize = RebootResponse::LEN;
+
+impl Message<'_> for RebootResponse {
+    const TYPE: ContentType = ContentType::RebootResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed verify segment request.
+    pub struct VerifySegmentRequest {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
+
+        /// The number of bytes to hash, starting at the beginning of the segment.
+        pub length: u32 as be,
+
+        /// The expected SHA-256 digest.
+        pub digest: [u8; SHA256_LEN] as bytes,
     }
 }
 
+/// The length of a verify segment request on the wire, in bytes.
+pub const VERIFY_SEGMENT_REQUEST_LEN: usize = VerifySegmentRequest::LEN;
+
+impl Message<'_> for VerifySegmentRequest {
+    const TYPE: ContentType = ContentType::VerifySegmentRequest;
+}
+
 // ----------------------------------------------------------------------------
 
 wire_enum! {
-    /// The result of a reboot request.
-    pub enum RebootResult: u8 {
+    /// The result of a verify segment request.
+    pub enum VerifySegmentResult: u8 {
+        /// The computed digest matches the expected digest
+        Match = 0x00,
+
+        /// Unspecified error
+        Error = 0x01,
+
+        /// Invalid segment and/or location
+        InvalidSegmentAndLocation = 0x02,
+
+        /// Invalid length
+        InvalidLength = 0x03,
+
+        /// The computed digest does not match the expected digest
+        Mismatch = 0x04,
+    }
+}
+
+wire_struct! {
+    /// A parsed verify segment response.
+    pub struct VerifySegmentResponse {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
+
+        /// The number of bytes hashed.
+        pub length: u32 as be,
+
+        /// The result of the verify segment request.
+        pub result: VerifySegmentResult as wire,
+
+        /// The computed SHA-256 digest. All zeros if none was computed.
+        pub digest: [u8; SHA256_LEN] as bytes,
+    }
+}
+
+/// The length of a verify segment response on the wire, in bytes.
+pub const VERIFY_SEGMENT_RESPONSE_LEN: usize = VerifySegmentResponse::LEN;
+
+impl Message<'_> for VerifySegmentResponse {
+    const TYPE: ContentType = ContentType::VerifySegmentResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed read chunk request.
+    pub struct ReadChunkRequest {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
+
+        /// The offset within the segment.
+        pub offset: u32 as be,
+
+        /// The number of bytes to read.
+        pub length: u16 as be,
+    }
+}
+
+/// The length of a read chunk request on the wire, in bytes.
+pub const READ_CHUNK_REQUEST_LEN: usize = ReadChunkRequest::LEN;
+
+impl Message<'_> for ReadChunkRequest {
+    const TYPE: ContentType = ContentType::ReadChunkRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The result of a read chunk request.
+    pub enum ReadChunkResult: u8 {
         /// Success
         Success = 0x00,
 
         /// Unspecified error
         Error = 0x01,
+
+        /// Invalid segment and/or location
+        InvalidSegmentAndLocation = 0x02,
+
+        /// Invalid offset
+        InvalidOffset = 0x03,
+
+        /// The segment may not be read
+        AccessDenied = 0x04,
     }
 }
 
-/// A parsed reboot response.
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-pub struct RebootResponse {
-    /// When to reboot from the request.
-    pub time: RebootTime,
+wire_struct! {
+    /// A parsed read chunk response.
+    pub struct ReadChunkResponse<'a> {
+        /// The segment and location.
+        pub segment_and_location: SegmentAndLocation as wire,
 
-    /// The result of the reboot request.
-    pub result: RebootResult,
+        /// The offset within the segment.
+        pub offset: u32 as be,
+
+        /// The result of the read chunk request.
+        pub result: ReadChunkResult as wire,
+
+        /// The data read.
+        /// May be shorter than requested at the end of the segment or if the
+        /// requested length does not fit into the mailbox.
+        pub data: &'a [u8] as rest,
+    }
 }
 
-/// The length of a reboot response on the wire, in bytes.
-pub const REBOOT_RESPONSE_LEN: usize = 2;
+/// The length of a read chunk response on the wire, in bytes.
+pub const READ_CHUNK_RESPONSE_LEN: usize = ReadChunkResponse::LEN;
 
-impl Message<'_> for RebootResponse {
-    const TYPE: ContentType = ContentType::RebootResponse;
+impl<'a> Message<'a> for ReadChunkResponse<'a> {
+    const TYPE: ContentType = ContentType::ReadChunkResponse;
 }
 
-impl<'a> FromWire<'a> for RebootResponse {
-    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
-        let time_u8 = r.read_be::<u8>()?;
-        let time = RebootTime::from_wire_value(time_u8).ok_or(FromWireError::OutOfRange)?;
-        let result_u8 = r.read_be::<u8>()?;
-        let result = RebootResult::from_wire_value(result_u8).ok_or(FromWireError::OutOfRange)?;
-        Ok(Self {
-            time,
-            result,
-        })
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed firmware info request.
+    pub struct FirmwareInfoRequest {
     }
 }
 
-impl ToWire for RebootResponse {
-    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
-        w.write_be(self.time.to_wire_value())?;
-        w.write_be(self.result.to_wire_value())?;
-        Ok(())
+/// The length of a firmware info request on the wire, in bytes.
+pub const FIRMWARE_INFO_REQUEST_LEN: usize = FirmwareInfoRequest::LEN;
+
+impl Message<'_> for FirmwareInfoRequest {
+    const TYPE: ContentType = ContentType::FirmwareInfoRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The status of the firmware info of a segment.
+    pub enum FirmwareInfoStatus: u8 {
+        /// The build information was read from the segment
+        Valid = 0x00,
+
+        /// The segment is erased
+        Blank = 0x01,
+
+        /// The build information could not be read
+        Unreadable = 0x02,
+    }
+}
+
+wire_struct! {
+    /// The firmware info of a segment along with its status.
+    pub struct FirmwareInfoEntry {
+        /// The status of the firmware info.
+        pub status: FirmwareInfoStatus as wire,
+
+        /// The firmware info. The build information is only meaningful if the
+        /// status is `Valid`.
+        pub info: FirmwareInfo as wire,
+    }
+}
+
+/// The length of a firmware info entry on the wire, in bytes.
+pub const FIRMWARE_INFO_ENTRY_LEN: usize = FirmwareInfoEntry::LEN;
+
+wire_struct! {
+    /// A parsed firmware info response.
+    pub struct FirmwareInfoResponse {
+        /// The active RO.
+        pub active_ro: FirmwareInfoEntry as wire,
+
+        /// The active RW.
+        pub active_rw: FirmwareInfoEntry as wire,
+
+        /// The inactive RO.
+        pub inactive_ro: FirmwareInfoEntry as wire,
+
+        /// The inactive RW.
+        pub inactive_rw: FirmwareInfoEntry as wire,
+    }
+}
+
+/// The length of a firmware info response on the wire, in bytes.
+pub const FIRMWARE_INFO_RESPONSE_LEN: usize = FirmwareInfoResponse::LEN;
+
+impl Message<'_> for FirmwareInfoResponse {
+    const TYPE: ContentType = ContentType::FirmwareInfoResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// What to do with a pending reboot.
+    pub enum PendingRebootAction: u8 {
+        /// Only query the state
+        Query = 0x00,
+
+        /// Cancel the pending reboot
+        Cancel = 0x01,
     }
 }
+
+wire_struct! {
+    /// A parsed pending reboot request.
+    pub struct PendingRebootRequest {
+        /// What to do with a pending reboot.
+        pub action: PendingRebootAction as wire,
+    }
+}
+
+/// The length of a pending reboot request on the wire, in bytes.
+pub const PENDING_REBOOT_REQUEST_LEN: usize = PendingRebootRequest::LEN;
+
+impl Message<'_> for PendingRebootRequest {
+    const TYPE: ContentType = ContentType::PendingRebootRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+/// The `timeout_msecs` of a pending reboot without a timeout.
+pub const NO_REBOOT_TIMEOUT: u32 = 0xffffffff;
+
+wire_struct! {
+    /// A parsed pending reboot response.
+    pub struct PendingRebootResponse {
+        /// The action from the request.
+        pub action: PendingRebootAction as wire,
+
+        /// Whether a reboot was pending before the action was performed.
+        pub pending: bool as bool,
+
+        /// The time until the pending reboot is executed without a reset,
+        /// in msecs. `NO_REBOOT_TIMEOUT` if there is no timeout.
+        pub timeout_msecs: u32 as be,
+    }
+}
+
+/// The length of a pending reboot response on the wire, in bytes.
+pub const PENDING_REBOOT_RESPONSE_LEN: usize = PendingRebootResponse::LEN;
+
+impl Message<'_> for PendingRebootResponse {
+    const TYPE: ContentType = ContentType::PendingRebootResponse;
+}
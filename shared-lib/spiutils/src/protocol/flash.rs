@@ -88,6 +88,15 @@ wire_enum! {
         /// Resumes write. Software should set WSP or WSE = 0.
         WriteResume = 0x30,
 
+        /// Returns contents of the second status register.
+        ReadStatusRegister2 = 0x35,
+
+        /// Returns contents of the third status register.
+        ReadStatusRegister3 = 0x15,
+
+        /// Returns contents of the flag status register.
+        ReadFlagStatusRegister = 0x70,
+
         ////////////////////////////////////////////////////////////
         // Erase and program commands
 
@@ -114,6 +123,15 @@ wire_enum! {
         /// Must be implemented in software. HW sets BUSY bit.
         PageProgram = 0x02,
 
+        /// Similar to PageProgram but uses explicit 4 byte addressing.
+        PageProgram4B = 0x12,
+
+        /// Similar to SectorErase but uses explicit 4 byte addressing.
+        SectorErase4B = 0x21,
+
+        /// Similar to BlockErase64KB but uses explicit 4 byte addressing.
+        BlockErase64KB4B = 0xdc,
+
         ////////////////////////////////////////////////////////////
         // ID commands
 
@@ -123,6 +141,8 @@ wire_enum! {
 
         /// Retrieves SFDP as configured in sfdp registers.
         /// Implemented in hardware.
+        /// The 3 byte address is followed by 8 dummy cycles (JESD216), which
+        /// `Header` consumes and emits as a dummy byte.
         ReadSfdp = 0x5a,
 
         ////////////////////////////////////////////////////////////
@@ -143,6 +163,20 @@ wire_enum! {
         /// Similar to FastRead with output on both MISO and MOSI.
         FastReadDualOutput = 0x3b,
 
+        /// Similar to FastRead with output on all four IO lines.
+        FastReadQuadOutput = 0x6b,
+
+        /// Similar to FastReadQuadOutput but the address is also sent on
+        /// all four IO lines. The address is followed by 6 dummy cycles,
+        /// including 2 cycles of mode bits.
+        FastReadQuadIO = 0xeb,
+
+        /// Similar to FastReadQuadOutput but uses explicit 4 byte addressing.
+        FastReadQuadOutput4B = 0x6c,
+
+        /// Similar to FastReadQuadIO but uses explicit 4 byte addressing.
+        FastReadQuadIO4B = 0xec,
+
         ////////////////////////////////////////////////////////////
         // Address mode commands
 
@@ -153,6 +187,18 @@ wire_enum! {
         /// Disable 4 byte address mode and revert to 3 byte address mode.
         /// Must be implemented in software.
         Exit4ByteAddressMode = 0xe9,
+
+        ////////////////////////////////////////////////////////////
+        // Reset and power commands
+
+        /// Enables a subsequent Reset.
+        ResetEnable = 0x66,
+
+        /// Resets the device if directly preceded by ResetEnable.
+        Reset = 0x99,
+
+        /// Puts the device into deep power down mode.
+        DeepPowerDown = 0xb9,
     }
 }
 
@@ -164,33 +210,109 @@ impl<'a> OpCode {
             Self::BlockErase32KB => true,
             Self::BlockErase64KB => true,
             Self::PageProgram => true,
+            Self::PageProgram4B => true,
+            Self::SectorErase4B => true,
+            Self::BlockErase64KB4B => true,
             Self::ReadSfdp => true,
             Self::NormalRead => true,
             Self::FastRead => true,
             Self::FastRead4B => true,
             Self::FastReadDualOutput => true,
+            Self::FastReadQuadOutput => true,
+            Self::FastReadQuadIO => true,
+            Self::FastReadQuadOutput4B => true,
+            Self::FastReadQuadIO4B => true,
             _ => false,
         }
     }
 
-    /// Returns true iff the OpCode requires a dummy byte.
-    pub fn has_dummy_byte(&self) -> bool {
+    /// Returns true iff the OpCode uses a 4 byte address regardless of the
+    /// current address mode.
+    pub fn has_4b_address(&self) -> bool {
         match self {
-            Self::FastRead => true,
             Self::FastRead4B => true,
-            Self::FastReadDualOutput => true,
+            Self::PageProgram4B => true,
+            Self::SectorErase4B => true,
+            Self::BlockErase64KB4B => true,
+            Self::FastReadQuadOutput4B => true,
+            Self::FastReadQuadIO4B => true,
             _ => false,
         }
     }
 
+    /// Returns the address mode used by the OpCode if the device is in
+    /// address mode `current`.
+    pub fn address_mode(&self, current: AddressMode) -> AddressMode {
+        match self {
+            // SFDP always uses a 3 byte address (JESD216).
+            Self::ReadSfdp => AddressMode::ThreeByte,
+            _ if self.has_4b_address() => AddressMode::FourByte,
+            _ => current,
+        }
+    }
+
+    /// Returns true iff the OpCode requires dummy cycles.
+    pub fn has_dummy_byte(&self) -> bool {
+        self.dummy_cycles() > 0
+    }
+
+    /// Returns the number of dummy cycles between the address and the data.
+    ///
+    /// Note: ReadSfdp has 8 dummy cycles, so `Header` consumes one byte after
+    /// its address. Earlier versions did not, and took that byte as the first
+    /// data byte.
+    pub fn dummy_cycles(&self) -> usize {
+        match self {
+            Self::ReadSfdp => 8,
+            Self::FastRead => 8,
+            Self::FastRead4B => 8,
+            Self::FastReadDualOutput => 8,
+            Self::FastReadQuadOutput => 8,
+            Self::FastReadQuadOutput4B => 8,
+            Self::FastReadQuadIO => 6,
+            Self::FastReadQuadIO4B => 6,
+            _ => 0,
+        }
+    }
+
+    /// Returns the number of IO lines used for the address and dummy cycles.
+    pub fn address_lines(&self) -> usize {
+        match self {
+            Self::FastReadQuadIO => 4,
+            Self::FastReadQuadIO4B => 4,
+            _ => 1,
+        }
+    }
+
+    /// Returns the number of bytes the dummy cycles occupy in a transaction
+    /// captured as a byte stream.
+    pub fn dummy_bytes(&self) -> usize {
+        self.dummy_cycles() * self.address_lines() / 8
+    }
+
     /// Returns true iff the OpCode requires data.
     pub fn has_data(&self) -> bool {
         match self {
             Self::PageProgram => true,
+            Self::PageProgram4B => true,
             _ => false,
         }
     }
 
+    /// Returns the number of bytes erased by the OpCode, starting at its
+    /// address rounded down to that size.
+    /// Returns None if the OpCode does not erase a block of fixed size.
+    pub fn erase_size(&self) -> Option<u32> {
+        match self {
+            Self::SectorErase => Some(4 * 1024),
+            Self::SectorErase4B => Some(4 * 1024),
+            Self::BlockErase32KB => Some(32 * 1024),
+            Self::BlockErase64KB => Some(64 * 1024),
+            Self::BlockErase64KB4B => Some(64 * 1024),
+            _ => None,
+        }
+    }
+
     /// Returns true iff the OpCode requires for the BUSY bit to clear.
     pub fn wait_busy_clear(&self) -> bool {
         match self {
@@ -202,6 +324,9 @@ impl<'a> OpCode {
             Self::ChipErase => true,
             Self::ChipErase2 => true,
             Self::PageProgram => true,
+            Self::PageProgram4B => true,
+            Self::SectorErase4B => true,
+            Self::BlockErase64KB4B => true,
             _ => false,
         }
     }
@@ -268,7 +393,7 @@ where AddrType: Address {
             false => None,
         };
 
-        if opcode.has_dummy_byte() {
+        for _ in 0..opcode.dummy_bytes() {
             // We don't actually care about the value, we just need to consume it.
             let _ = r.read_be::<u8>()?;
         }
@@ -288,7 +413,7 @@ where AddrType: Address {
             }
             w.write_be(self.address.unwrap())?;
         }
-        if self.opcode.has_dummy_byte() {
+        for _ in 0..self.opcode.dummy_bytes() {
             w.write_be(DUMMY_BYTE_VALUE)?;
         }
 
@@ -320,3 +445,90 @@ impl ToWire for Header<u32> {
     }
 }
 
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::Cursor;
+
+    #[test]
+    fn address_and_dummy() {
+        assert!(OpCode::ReadSfdp.has_address());
+        assert_eq!(OpCode::ReadSfdp.address_mode(AddressMode::FourByte), AddressMode::ThreeByte);
+        assert_eq!(OpCode::ReadSfdp.dummy_bytes(), 1);
+
+        for opcode in [OpCode::PageProgram4B, OpCode::SectorErase4B, OpCode::BlockErase64KB4B,
+                       OpCode::FastReadQuadOutput4B, OpCode::FastReadQuadIO4B].iter() {
+            assert!(opcode.has_address());
+            assert_eq!(opcode.address_mode(AddressMode::ThreeByte), AddressMode::FourByte);
+        }
+        assert_eq!(OpCode::FastReadQuadOutput.address_mode(AddressMode::ThreeByte), AddressMode::ThreeByte);
+        assert_eq!(OpCode::FastReadQuadOutput.address_mode(AddressMode::FourByte), AddressMode::FourByte);
+
+        assert_eq!(OpCode::FastReadQuadOutput.dummy_bytes(), 1);
+        assert_eq!(OpCode::FastReadQuadOutput4B.dummy_bytes(), 1);
+        // 6 cycles on 4 lines.
+        assert_eq!(OpCode::FastReadQuadIO.dummy_bytes(), 3);
+        assert_eq!(OpCode::FastReadQuadIO4B.dummy_bytes(), 3);
+        assert_eq!(OpCode::PageProgram4B.dummy_bytes(), 0);
+        assert!(!OpCode::PageProgram4B.has_dummy_byte());
+
+        for opcode in [OpCode::ReadStatusRegister2, OpCode::ReadStatusRegister3,
+                       OpCode::ReadFlagStatusRegister, OpCode::ResetEnable, OpCode::Reset,
+                       OpCode::DeepPowerDown].iter() {
+            assert!(!opcode.has_address());
+            assert!(!opcode.has_dummy_byte());
+            assert!(!opcode.has_data());
+        }
+    }
+
+    #[test]
+    fn read_sfdp_header() {
+        let mut data: &[u8] = &[0x5a, 0x00, 0x00, 0x30, 0xff, 0x53];
+        let header = Header::<ux::u24>::from_wire(&mut data).unwrap();
+        assert_eq!(header.opcode, OpCode::ReadSfdp);
+        assert_eq!(header.get_address(), Some(0x30));
+        // The dummy byte was consumed.
+        assert_eq!(data, &[0x53]);
+
+        let mut buf = [0u8; 8];
+        let mut cursor = Cursor::new(&mut buf);
+        header.to_wire(&mut cursor).unwrap();
+        assert_eq!(cursor.consumed_len(), 5);
+        assert_eq!(&buf[..5], &[0x5a, 0x00, 0x00, 0x30, 0xff]);
+    }
+
+    #[test]
+    fn quad_io_header() {
+        let mut data: &[u8] = &[0xec, 0x01, 0x02, 0x03, 0x04, 0xa0, 0xff, 0xff, 0x11];
+        let header = Header::<u32>::from_wire(&mut data).unwrap();
+        assert_eq!(header.opcode, OpCode::FastReadQuadIO4B);
+        assert_eq!(header.get_address(), Some(0x01020304));
+        assert_eq!(data, &[0x11]);
+
+        let mut buf = [0u8; 16];
+        let mut cursor = Cursor::new(&mut buf);
+        header.to_wire(&mut cursor).unwrap();
+        assert_eq!(cursor.consumed_len(), 8);
+    }
+
+    #[test]
+    fn program_4b_header() {
+        let mut data: &[u8] = &[0x12, 0x00, 0x01, 0x00, 0x00, 0xaa, 0xbb];
+        let header = Header::<u32>::from_wire(&mut data).unwrap();
+        assert_eq!(header.opcode, OpCode::PageProgram4B);
+        assert_eq!(header.get_address(), Some(0x10000));
+        assert_eq!(data, &[0xaa, 0xbb]);
+
+        // Too short for the dummy bytes.
+        let mut data: &[u8] = &[0xeb, 0x00, 0x00, 0x00, 0xff];
+        assert!(Header::<ux::u24>::from_wire(&mut data).is_err());
+    }
+
+    #[test]
+    fn missing_address() {
+        let header = Header::<u32> { opcode: OpCode::SectorErase4B, address: None };
+        let mut buf = [0u8; 8];
+        assert!(header.to_wire(Cursor::new(&mut buf)).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Integrity checks for extended payloads.
+
+/// The length of a CRC-32 trailer, in bytes.
+pub const CRC32_LEN: usize = 4;
+
+/// The length of a SHA-256 digest, in bytes.
+pub const SHA256_LEN: usize = 32;
+
+/// The maximum length of an integrity trailer, in bytes.
+pub const MAX_TRAILER_LEN: usize = SHA256_LEN;
+
+wire_enum! {
+    /// The integrity check appended to an extended payload.
+    pub enum IntegrityMode: u8 {
+        /// CRC-32 (IEEE 802.3), big-endian
+        Crc32 = 0x01,
+
+        /// SHA-256 digest
+        Sha256 = 0x02,
+    }
+}
+
+impl IntegrityMode {
+    /// Returns the length of the trailer for this mode, in bytes.
+    pub fn trailer_len(&self) -> usize {
+        match self {
+            IntegrityMode::Crc32 => CRC32_LEN,
+            IntegrityMode::Sha256 => SHA256_LEN,
+        }
+    }
+}
+
+/// Computes the trailer for `data` in the specified `mode`.
+///
+/// Only the first `mode.trailer_len()` bytes of the result are valid.
+pub fn compute_trailer(mode: IntegrityMode, data: &[u8]) -> [u8; MAX_TRAILER_LEN] {
+    let mut trailer = [0u8; MAX_TRAILER_LEN];
+    match mode {
+        IntegrityMode::Crc32 => {
+            let crc = Crc32::init().add(data).get();
+            trailer[..CRC32_LEN].copy_from_slice(&crc.to_be_bytes());
+        }
+        IntegrityMode::Sha256 => {
+            trailer = Sha256::new().add(data).finish();
+        }
+    }
+    trailer
+}
+
+/// Returns whether `trailer` is the valid trailer for `data` in the specified `mode`.
+pub fn verify_trailer(mode: IntegrityMode, data: &[u8], trailer: &[u8]) -> bool {
+    let expected = compute_trailer(mode, data);
+    trailer == &expected[..mode.trailer_len()]
+}
+
+// ----------------------------------------------------------------------------
+
+/// Data for CRC-32 implementation.
+pub struct Crc32 {
+    crc: u32,
+}
+
+/// The CRC-32 implementation.
+/// Uses the reflected IEEE 802.3 polynomial (0xedb88320). The result matches
+/// the commonly used CRC-32 of zlib and Ethernet.
+impl Crc32 {
+    /// Initialize CRC-32 data.
+    pub fn init() -> Self {
+        Self {
+            crc: 0xffff_ffff,
+        }
+    }
+
+    /// Get the calculated CRC-32 checksum.
+    pub fn get(&self) -> u32 {
+        !self.crc
+    }
+
+    /// Adds the specified data to the CRC-32 checksum.
+    pub fn add(&mut self, data: &[u8]) -> &mut Self {
+        for byte in data {
+            self.crc ^= *byte as u32;
+            for _ in 0..8 {
+                let mask = (self.crc & 1).wrapping_neg();
+                self.crc = (self.crc >> 1) ^ (0xedb8_8320 & mask);
+            }
+        }
+
+        self
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// The SHA-256 round constants.
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+// The SHA-256 initial hash value.
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// The SHA-256 block size, in bytes.
+const SHA256_BLOCK_LEN: usize = 64;
+
+/// Data for SHA-256 implementation.
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; SHA256_BLOCK_LEN],
+    block_len: usize,
+    total_len: u64,
+}
+
+/// A software SHA-256 implementation (FIPS 180-4).
+impl Sha256 {
+    /// Initialize SHA-256 data.
+    pub fn new() -> Self {
+        Self {
+            state: SHA256_H,
+            block: [0; SHA256_BLOCK_LEN],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    /// Adds the specified data to the digest.
+    pub fn add(&mut self, mut data: &[u8]) -> &mut Self {
+        self.total_len = self.total_len.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let len = core::cmp::min(SHA256_BLOCK_LEN - self.block_len, data.len());
+            self.block[self.block_len..self.block_len + len].copy_from_slice(&data[..len]);
+            self.block_len += len;
+            data = &data[len..];
+
+            if self.block_len == SHA256_BLOCK_LEN {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+
+        self
+    }
+
+    /// Finishes the digest and returns it.
+    pub fn finish(&mut self) -> [u8; SHA256_LEN] {
+        let bit_len = self.total_len.wrapping_mul(8);
+
+        // Append the 0x80 marker, pad with zeros and append the bit length.
+        self.block[self.block_len] = 0x80;
+        self.block_len += 1;
+        if self.block_len > SHA256_BLOCK_LEN - 8 {
+            for val in self.block[self.block_len..].iter_mut() {
+                *val = 0;
+            }
+            self.compress();
+            self.block_len = 0;
+        }
+        for val in self.block[self.block_len..SHA256_BLOCK_LEN - 8].iter_mut() {
+            *val = 0;
+        }
+        self.block[SHA256_BLOCK_LEN - 8..].copy_from_slice(&bit_len.to_be_bytes());
+        self.compress();
+
+        let mut digest = [0u8; SHA256_LEN];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    // Processes the full block in `self.block`.
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (idx, chunk) in self.block.chunks(4).enumerate() {
+            w[idx] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for idx in 16..64 {
+            let s0 = w[idx - 15].rotate_right(7) ^ w[idx - 15].rotate_right(18) ^ (w[idx - 15] >> 3);
+            let s1 = w[idx - 2].rotate_right(17) ^ w[idx - 2].rotate_right(19) ^ (w[idx - 2] >> 10);
+            w[idx] = w[idx - 16].wrapping_add(s0).wrapping_add(w[idx - 7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for idx in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(SHA256_K[idx]).wrapping_add(w[idx]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, val) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h].iter()) {
+            *state = state.wrapping_add(*val);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc32() {
+        assert_eq!(Crc32::init().get(), 0);
+        assert_eq!(Crc32::init().add(b"123456789").get(), 0xcbf43926);
+        assert_eq!(Crc32::init().add(b"1234").add(b"56789").get(), 0xcbf43926);
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(Sha256::new().finish()[..4], [0xe3, 0xb0, 0xc4, 0x42]);
+        assert_eq!(Sha256::new().add(b"abc").finish(), [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23,
+            0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+        ]);
+
+        // Two blocks, added in pieces that straddle the block boundary.
+        let data = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(Sha256::new().add(&data[..40]).add(&data[40..]).finish(), [
+            0x24, 0x8d, 0x6a, 0x61, 0xd2, 0x06, 0x38, 0xb8, 0xe5, 0xc0, 0x26, 0x93, 0x0c, 0x3e, 0x60, 0x39,
+            0xa3, 0x3c, 0xe4, 0x59, 0x64, 0xff, 0x21, 0x67, 0xf6, 0xec, 0xed, 0xd4, 0x19, 0xdb, 0x06, 0xc1,
+        ]);
+    }
+
+    #[test]
+    fn trailer() {
+        let crc = compute_trailer(IntegrityMode::Crc32, b"123456789");
+        assert_eq!(&crc[..CRC32_LEN], &[0xcb, 0xf4, 0x39, 0x26]);
+        assert!(verify_trailer(IntegrityMode::Crc32, b"123456789", &crc[..CRC32_LEN]));
+        assert!(!verify_trailer(IntegrityMode::Crc32, b"123456780", &crc[..CRC32_LEN]));
+
+        let digest = compute_trailer(IntegrityMode::Sha256, b"abc");
+        assert!(verify_trailer(IntegrityMode::Sha256, b"abc", &digest));
+        assert!(!verify_trailer(IntegrityMode::Sha256, b"abc", &digest[..CRC32_LEN]));
+    }
+}
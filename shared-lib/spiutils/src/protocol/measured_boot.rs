@@ -0,0 +1,585 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Measured boot protocol payload.
+//!
+//! A measured boot manifest lists regions of the SPI flash chip and their
+//! expected SHA-256 digests. The device measures these regions while the BMC
+//! is held in reset and compares the result against the manifest. The
+//! manifest is provisioned via the mailbox and persisted by the device. The
+//! device only accepts a new manifest while provisioning has been enabled on
+//! its console.
+//!
+//! A manifest may also specify a recovery region that holds a known-good
+//! copy of the image. If verification fails, the device copies the recovery
+//! region over the image and verifies it again.
+
+use crate::io::Read;
+use crate::io::Write;
+use crate::protocol::integrity::SHA256_LEN;
+use crate::protocol::wire::FromWire;
+use crate::protocol::wire::FromWireError;
+use crate::protocol::wire::ToWire;
+use crate::protocol::wire::ToWireError;
+
+wire_enum! {
+    /// The content type.
+    pub enum ContentType: u8 {
+        /// Request the measured boot manifest and status
+        GetManifestRequest = 0x01,
+
+        /// Response to GetManifestRequest
+        GetManifestResponse = 0x02,
+
+        /// Request to provision a measured boot manifest
+        SetManifestRequest = 0x03,
+
+        /// Response to SetManifestRequest
+        SetManifestResponse = 0x04,
+
+        /// Request the status of the most recent recovery
+        GetRecoveryStatusRequest = 0x05,
+
+        /// Response to GetRecoveryStatusRequest
+        GetRecoveryStatusResponse = 0x06,
+    }
+}
+
+wire_struct! {
+    /// A parsed header.
+    pub struct Header {
+        /// The content type following the header.
+        pub content: ContentType as wire,
+    }
+}
+
+/// The length of a measured boot header on the wire, in bytes.
+pub const HEADER_LEN: usize = Header::LEN;
+
+// ----------------------------------------------------------------------------
+
+/// A message.
+///
+/// A message is identified by a [`ContentType`]:
+///
+/// This trait is not implemented by any of the message types
+///
+/// [`ContentType`]: enum.ContentType.html
+pub trait Message<'req>: FromWire<'req> + ToWire {
+    /// The unique [`ContentType`] for this `Message`.
+    ///
+    /// [`ContentType`]: enum.ContentType.html
+    const TYPE: ContentType;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// What to do with the result of the verification.
+    pub enum VerifyMode: u8 {
+        /// The SPI flash chip is not verified.
+        Disabled = 0x00,
+
+        /// The result is logged, but the BMC is always let out of reset.
+        LogOnly = 0x01,
+
+        /// The BMC is only let out of reset if all regions match.
+        Enforce = 0x02,
+    }
+}
+
+wire_struct! {
+    /// A measured region of the SPI flash chip.
+    pub struct Region {
+        /// The start address of the region.
+        pub start: u32 as be,
+
+        /// The size of the region, in bytes.
+        pub size: u32 as be,
+
+        /// The expected SHA-256 digest of the region.
+        pub digest: [u8; SHA256_LEN] as bytes,
+    }
+}
+
+/// The length of a region on the wire, in bytes.
+pub const REGION_LEN: usize = Region::LEN;
+
+wire_struct! {
+    /// A recovery region of the SPI flash chip.
+    pub struct Recovery {
+        /// The start address of the known-good copy of the image.
+        pub source: u32 as be,
+
+        /// The start address of the image.
+        pub destination: u32 as be,
+
+        /// The size of the image, in bytes.
+        /// A size of zero disables recovery.
+        pub size: u32 as be,
+    }
+}
+
+/// The length of a recovery region on the wire, in bytes.
+pub const RECOVERY_LEN: usize = Recovery::LEN;
+
+/// The alignment of the addresses and the size of a recovery region.
+/// This is the size of the smallest erase command of common SPI flash chips.
+pub const RECOVERY_ALIGNMENT: u32 = 0x1000;
+
+/// The recovery region that disables recovery.
+pub const NO_RECOVERY: Recovery = Recovery {
+    source: 0,
+    destination: 0,
+    size: 0,
+};
+
+impl Recovery {
+    /// Returns true iff recovery is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.size != 0
+    }
+
+    /// Returns true iff the recovery region is disabled or its source and
+    /// destination are aligned, do not overlap and fit into the 32-bit
+    /// address space.
+    pub fn is_valid(&self) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let aligned = |val: u32| val & (RECOVERY_ALIGNMENT - 1) == 0;
+        let source_end = self.source as u64 + self.size as u64;
+        let destination_end = self.destination as u64 + self.size as u64;
+        aligned(self.source) && aligned(self.destination) && aligned(self.size)
+            && source_end <= 1u64 << 32 && destination_end <= 1u64 << 32
+            && (source_end <= self.destination as u64 || destination_end <= self.source as u64)
+    }
+
+    /// Returns the region of the known-good copy that corresponds to
+    /// `region`, or None if `region` is not entirely within the image.
+    pub fn source_region(&self, region: &Region) -> Option<Region> {
+        let offset = region.start.checked_sub(self.destination)?;
+        if offset as u64 + region.size as u64 > self.size as u64 {
+            return None;
+        }
+        Some(Region {
+            start: self.source + offset,
+            ..*region
+        })
+    }
+}
+
+/// The maximum number of regions in a manifest.
+pub const MAX_REGIONS: usize = 4;
+
+/// An unused entry in a manifest's region table.
+const UNUSED_REGION: Region = Region {
+    start: 0,
+    size: 0,
+    digest: [0; SHA256_LEN],
+};
+
+/// A measured boot manifest.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Manifest {
+    /// What to do with the result of the verification.
+    pub mode: VerifyMode,
+
+    /// Whether the manifest is locked.
+    /// A locked manifest cannot be replaced.
+    pub locked: bool,
+
+    recovery: Recovery,
+    region_count: u8,
+    regions: [Region; MAX_REGIONS],
+}
+
+/// The manifest that disables verification.
+pub const DISABLED_MANIFEST: Manifest = Manifest {
+    mode: VerifyMode::Disabled,
+    locked: false,
+    recovery: NO_RECOVERY,
+    region_count: 0,
+    regions: [UNUSED_REGION; MAX_REGIONS],
+};
+
+/// Error returned when a region cannot be added to a manifest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RegionError {
+    /// The manifest already has `MAX_REGIONS` regions.
+    TooManyRegions,
+
+    /// The region is empty or extends beyond the 32-bit address space.
+    InvalidRegion,
+
+    /// The recovery region is misaligned, overlaps itself or extends beyond
+    /// the 32-bit address space.
+    InvalidRecovery,
+}
+
+impl Manifest {
+    /// The length of a manifest on the wire, in bytes.
+    /// Unused region table entries are included.
+    pub const LEN: usize = 4 + RECOVERY_LEN + MAX_REGIONS * REGION_LEN;
+
+    /// Returns the regions of the manifest.
+    pub fn regions(&self) -> &[Region] {
+        &self.regions[..self.region_count as usize]
+    }
+
+    /// Adds a region to the manifest.
+    pub fn add_region(&mut self, region: Region) -> Result<(), RegionError> {
+        if region.size == 0 || region.start as u64 + region.size as u64 > 1u64 << 32 {
+            return Err(RegionError::InvalidRegion);
+        }
+        let idx = self.region_count as usize;
+        if idx >= MAX_REGIONS {
+            return Err(RegionError::TooManyRegions);
+        }
+        self.regions[idx] = region;
+        self.region_count += 1;
+        Ok(())
+    }
+
+    /// Returns the recovery region of the manifest.
+    pub fn recovery(&self) -> &Recovery {
+        &self.recovery
+    }
+
+    /// Sets the recovery region of the manifest.
+    pub fn set_recovery(&mut self, recovery: Recovery) -> Result<(), RegionError> {
+        if !recovery.is_valid() {
+            return Err(RegionError::InvalidRecovery);
+        }
+        self.recovery = recovery;
+        Ok(())
+    }
+}
+
+// Flags in the second byte of a manifest on the wire.
+const FLAG_LOCKED: u8 = 0x01;
+
+impl<'a> FromWire<'a> for Manifest {
+    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
+        let mode = VerifyMode::from_wire(&mut r)?;
+        let flags = r.read_be::<u8>()?;
+        if flags & !FLAG_LOCKED != 0 {
+            return Err(FromWireError::OutOfRange);
+        }
+        let mut manifest = Manifest {
+            mode,
+            locked: flags & FLAG_LOCKED != 0,
+            ..DISABLED_MANIFEST
+        };
+        let region_count = r.read_be::<u8>()? as usize;
+        let _reserved = r.read_be::<u8>()?;
+        if region_count > MAX_REGIONS {
+            return Err(FromWireError::OutOfRange);
+        }
+        let recovery = Recovery::from_wire(&mut r)?;
+        manifest.set_recovery(recovery).map_err(|_| FromWireError::OutOfRange)?;
+        for idx in 0..MAX_REGIONS {
+            let region = Region::from_wire(&mut r)?;
+            if idx < region_count {
+                manifest.add_region(region).map_err(|_| FromWireError::OutOfRange)?;
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+impl ToWire for Manifest {
+    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
+        self.mode.to_wire(&mut w)?;
+        w.write_be(if self.locked { FLAG_LOCKED } else { 0u8 })?;
+        w.write_be(self.region_count)?;
+        w.write_be(0u8)?;
+        self.recovery.to_wire(&mut w)?;
+        for region in self.regions.iter() {
+            region.to_wire(&mut w)?;
+        }
+        Ok(())
+    }
+}
+
+/// The length of a manifest on the wire, in bytes.
+pub const MANIFEST_LEN: usize = Manifest::LEN;
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The result of the most recent verification.
+    pub enum VerifyResult: u8 {
+        /// The SPI flash chip has not been verified since boot.
+        Unknown = 0xff,
+
+        /// All regions match the manifest.
+        Match = 0x00,
+
+        /// At least one region does not match the manifest.
+        Mismatch = 0x01,
+
+        /// A region could not be measured.
+        Error = 0x02,
+
+        /// Verification is disabled.
+        Disabled = 0x03,
+    }
+}
+
+/// The value of `failed_region` if no region failed.
+pub const NO_FAILED_REGION: u8 = 0xff;
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed get manifest request.
+    pub struct GetManifestRequest {
+    }
+}
+
+/// The length of a get manifest request on the wire, in bytes.
+pub const GET_MANIFEST_REQUEST_LEN: usize = GetManifestRequest::LEN;
+
+impl Message<'_> for GetManifestRequest {
+    const TYPE: ContentType = ContentType::GetManifestRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed get manifest response.
+    pub struct GetManifestResponse {
+        /// The active manifest.
+        pub manifest: Manifest as wire,
+
+        /// The result of the most recent verification.
+        pub result: VerifyResult as wire,
+
+        /// The index of the first region that did not match or could not be
+        /// measured, or `NO_FAILED_REGION`.
+        pub failed_region: u8 as be,
+    }
+}
+
+/// The length of a get manifest response on the wire, in bytes.
+pub const GET_MANIFEST_RESPONSE_LEN: usize = GetManifestResponse::LEN;
+
+impl Message<'_> for GetManifestResponse {
+    const TYPE: ContentType = ContentType::GetManifestResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed set manifest request.
+    pub struct SetManifestRequest {
+        /// The manifest to provision.
+        pub manifest: Manifest as wire,
+    }
+}
+
+/// The length of a set manifest request on the wire, in bytes.
+pub const SET_MANIFEST_REQUEST_LEN: usize = SetManifestRequest::LEN;
+
+impl Message<'_> for SetManifestRequest {
+    const TYPE: ContentType = ContentType::SetManifestRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// Set manifest result.
+    pub enum SetManifestResult: u8 {
+        /// Unknown result
+        Unknown = 0xff,
+
+        /// The manifest was persisted and is active.
+        Success = 0x00,
+
+        /// The active manifest is locked.
+        Locked = 0x01,
+
+        /// The manifest could not be persisted.
+        StorageError = 0x02,
+
+        /// Provisioning has not been enabled on the device console.
+        NotProvisioning = 0x03,
+    }
+}
+
+wire_struct! {
+    /// A parsed set manifest response.
+    pub struct SetManifestResponse {
+        /// The result of the set manifest operation.
+        pub result: SetManifestResult as wire,
+    }
+}
+
+/// The length of a set manifest response on the wire, in bytes.
+pub const SET_MANIFEST_RESPONSE_LEN: usize = SetManifestResponse::LEN;
+
+impl Message<'_> for SetManifestResponse {
+    const TYPE: ContentType = ContentType::SetManifestResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed get recovery status request.
+    pub struct GetRecoveryStatusRequest {
+    }
+}
+
+/// The length of a get recovery status request on the wire, in bytes.
+pub const GET_RECOVERY_STATUS_REQUEST_LEN: usize = GetRecoveryStatusRequest::LEN;
+
+impl Message<'_> for GetRecoveryStatusRequest {
+    const TYPE: ContentType = ContentType::GetRecoveryStatusRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The result of the most recent recovery.
+    pub enum RecoveryResult: u8 {
+        /// No recovery was attempted since boot.
+        Unknown = 0xff,
+
+        /// The image was recovered and matches the manifest.
+        Success = 0x00,
+
+        /// Verification failed, but the manifest has no recovery region.
+        NotConfigured = 0x01,
+
+        /// The known-good copy does not match the manifest.
+        /// The image was not modified.
+        InvalidSource = 0x02,
+
+        /// Reading, erasing or programming the SPI flash chip failed.
+        FlashError = 0x03,
+
+        /// The recovered image does not match the manifest.
+        VerifyFailed = 0x04,
+    }
+}
+
+wire_struct! {
+    /// A parsed get recovery status response.
+    pub struct GetRecoveryStatusResponse {
+        /// The result of the most recent recovery.
+        pub result: RecoveryResult as wire,
+
+        /// The number of recoveries attempted since boot.
+        pub attempts: u32 as be,
+
+        /// The number of bytes copied by the most recent recovery.
+        pub bytes_copied: u32 as be,
+
+        /// The number of bytes to copy in the most recent recovery.
+        pub bytes_total: u32 as be,
+    }
+}
+
+/// The length of a get recovery status response on the wire, in bytes.
+pub const GET_RECOVERY_STATUS_RESPONSE_LEN: usize = GetRecoveryStatusResponse::LEN;
+
+impl Message<'_> for GetRecoveryStatusResponse {
+    const TYPE: ContentType = ContentType::GetRecoveryStatusResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        let mut manifest = Manifest { mode: VerifyMode::Enforce, ..DISABLED_MANIFEST };
+        manifest.add_region(Region { start: 0x0, size: 0x10000, digest: [0xa5; SHA256_LEN] }).unwrap();
+        manifest.add_region(Region { start: 0x100000, size: 0x1000, digest: [0x5a; SHA256_LEN] }).unwrap();
+        manifest.set_recovery(Recovery { source: 0x800000, destination: 0x0, size: 0x200000 }).unwrap();
+        manifest
+    }
+
+    #[test]
+    fn recovery() {
+        let recovery = Recovery { source: 0x800000, destination: 0x0, size: 0x200000 };
+        assert!(recovery.is_valid());
+        assert!(NO_RECOVERY.is_valid());
+        assert!(!NO_RECOVERY.is_enabled());
+        assert!(!Recovery { size: 0x1800, ..recovery }.is_valid());
+        assert!(!Recovery { source: 0x100000, ..recovery }.is_valid());
+        assert!(!Recovery { source: 0xfff0_0000, ..recovery }.is_valid());
+        assert!(Recovery { source: 0xffe0_0000, ..recovery }.is_valid());
+
+        let region = Region { start: 0x100000, size: 0x1000, digest: [0x5a; SHA256_LEN] };
+        assert_eq!(recovery.source_region(&region),
+            Some(Region { start: 0x900000, ..region }));
+        assert_eq!(recovery.source_region(&Region { start: 0x1ff800, ..region }), None);
+        assert_eq!(Recovery { destination: 0x200000, ..recovery }.source_region(&region), None);
+
+        let mut manifest = DISABLED_MANIFEST;
+        assert_eq!(manifest.set_recovery(Recovery { destination: 0x900000, ..recovery }),
+            Err(RegionError::InvalidRecovery));
+        assert_eq!(manifest.recovery(), &NO_RECOVERY);
+    }
+
+    #[test]
+    fn add_region() {
+        let mut manifest = DISABLED_MANIFEST;
+        assert_eq!(manifest.add_region(Region { start: 0x0, size: 0, digest: [0; SHA256_LEN] }),
+            Err(RegionError::InvalidRegion));
+        assert_eq!(manifest.add_region(Region { start: 0xffff_f000, size: 0x2000, digest: [0; SHA256_LEN] }),
+            Err(RegionError::InvalidRegion));
+        for idx in 0..MAX_REGIONS {
+            manifest.add_region(Region { start: idx as u32 * 0x1000, size: 0x1000, digest: [0; SHA256_LEN] }).unwrap();
+        }
+        assert_eq!(manifest.add_region(Region { start: 0x0, size: 0x1000, digest: [0; SHA256_LEN] }),
+            Err(RegionError::TooManyRegions));
+        assert_eq!(manifest.regions().len(), MAX_REGIONS);
+    }
+
+    #[test]
+    fn round_trip() {
+        let manifest = Manifest { locked: true, ..manifest() };
+        let mut buf = [0u8; MANIFEST_LEN];
+        manifest.to_wire(buf.as_mut()).unwrap();
+        assert_eq!(buf[..4], [0x02, FLAG_LOCKED, 2, 0]);
+        assert_eq!(buf[4..16], [0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00]);
+        assert_eq!(buf[16..24], [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(buf[24..24 + SHA256_LEN], [0xa5; SHA256_LEN]);
+        assert_eq!(Manifest::from_wire(buf.as_ref()).unwrap(), manifest);
+
+        // Invalid modes, flags, region count and regions are rejected.
+        let mut bad = buf;
+        bad[0] = 0x03;
+        assert!(Manifest::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[1] = 0x80;
+        assert!(Manifest::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[2] = MAX_REGIONS as u8 + 1;
+        assert!(Manifest::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[4..8].copy_from_slice(&[0, 0, 0x10, 0]);
+        assert!(Manifest::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[16 + REGION_LEN + 4..16 + REGION_LEN + 8].copy_from_slice(&[0, 0, 0, 0]);
+        assert!(Manifest::from_wire(bad.as_ref()).is_err());
+        assert!(Manifest::from_wire(&buf[..MANIFEST_LEN - 1]).is_err());
+    }
+}
@@ -19,7 +19,12 @@
 #[macro_use]
 pub mod wire;
 
+pub mod capabilities;
 pub mod error;
 pub mod firmware;
 pub mod flash;
+pub mod integrity;
+pub mod measured_boot;
 pub mod payload;
+pub mod sfdp;
+pub mod write_protect;
@@ -22,8 +22,12 @@ use crate::protocol::wire::FromWireError;
 use crate::protocol::wire::FromWire;
 use crate::protocol::wire::ToWireError;
 use crate::protocol::wire::ToWire;
+use crate::protocol::integrity;
+use crate::protocol::integrity::IntegrityMode;
 use crate::protocol::wire::WireEnum;
 
+use core::ops::Range;
+
 /// Data for CRC8 implementation.
 struct Crc8 {
     crc: u16,
@@ -82,6 +86,21 @@ wire_enum! {
 
         /// Firmware
         Firmware = 0x02,
+
+        /// Capabilities
+        Capabilities = 0x03,
+
+        /// A fragment of a message that does not fit into a single payload
+        Fragment = 0x04,
+
+        /// A payload with an extended header and an integrity trailer
+        Extended = 0x05,
+
+        /// Write protection
+        WriteProtect = 0x06,
+
+        /// Measured boot
+        MeasuredBoot = 0x07,
     }
 }
 
@@ -124,3 +143,456 @@ impl ToWire for Header {
         Ok(())
     }
 }
+
+// ----------------------------------------------------------------------------
+
+/// The version of the extended header.
+pub const EXTENDED_HEADER_VERSION: u8 = 1;
+
+wire_struct! {
+    /// A parsed extended header.
+    ///
+    /// An `Extended` payload consists of this header, the content and a
+    /// trailer as selected by `integrity`. The trailer covers the extended
+    /// header and the content. The device answers an `Extended` request with
+    /// an `Extended` response using the same integrity mode.
+    pub struct ExtendedHeader {
+        /// The version of the extended header.
+        pub version: u8 as be,
+
+        /// The content type following the extended header.
+        pub content: ContentType as wire,
+
+        /// The integrity trailer following the content.
+        pub integrity: IntegrityMode as wire,
+    }
+}
+
+/// The length of an extended header on the wire, in bytes.
+pub const EXTENDED_HEADER_LEN: usize = ExtendedHeader::LEN;
+
+/// Returns the number of bytes an extended header with the integrity `mode`
+/// adds to the content.
+pub fn extended_overhead(mode: IntegrityMode) -> usize {
+    EXTENDED_HEADER_LEN + mode.trailer_len()
+}
+
+/// An error while parsing an `Extended` payload.
+#[derive(Clone, Copy, Debug)]
+pub enum ExtendedError {
+    /// The extended header could not be parsed.
+    FromWire(FromWireError),
+
+    /// The version of the extended header is not supported.
+    UnsupportedVersion(u8),
+
+    /// The integrity trailer does not match.
+    BadIntegrity(IntegrityMode),
+}
+
+impl From<FromWireError> for ExtendedError {
+    fn from(err: FromWireError) -> Self {
+        ExtendedError::FromWire(err)
+    }
+}
+
+/// Parses the content of an `Extended` payload and verifies its trailer.
+///
+/// Returns the extended header and the content between it and the trailer.
+pub fn parse_extended(data: &[u8]) -> Result<(ExtendedHeader, &[u8]), ExtendedError> {
+    let mut r = data;
+    let header = ExtendedHeader::from_wire(&mut r)?;
+    if header.version != EXTENDED_HEADER_VERSION {
+        return Err(ExtendedError::UnsupportedVersion(header.version));
+    }
+
+    let trailer_len = header.integrity.trailer_len();
+    if data.len() < EXTENDED_HEADER_LEN + trailer_len {
+        return Err(ExtendedError::FromWire(FromWireError::OutOfRange));
+    }
+    let trailer_start = data.len() - trailer_len;
+    if !integrity::verify_trailer(header.integrity, &data[..trailer_start], &data[trailer_start..]) {
+        return Err(ExtendedError::BadIntegrity(header.integrity));
+    }
+    Ok((header, &data[EXTENDED_HEADER_LEN..trailer_start]))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Flag marking the last fragment of a message.
+pub const FRAGMENT_FLAG_FINAL: u8 = 0x01;
+
+wire_struct! {
+    /// A parsed fragment header.
+    ///
+    /// A `Fragment` payload consists of this header followed by the fragment
+    /// data. Each fragment that is not marked as final is acknowledged by the
+    /// receiver with an empty fragment carrying the same content type and
+    /// sequence number.
+    pub struct FragmentHeader {
+        /// The content type of the fragmented message.
+        pub content: ContentType as wire,
+
+        /// The index of this fragment within the message, starting at 0.
+        pub sequence: u8 as be,
+
+        /// Flags, see `FRAGMENT_FLAG_*`.
+        pub flags: u8 as be,
+    }
+}
+
+/// The length of a fragment header on the wire, in bytes.
+pub const FRAGMENT_HEADER_LEN: usize = FragmentHeader::LEN;
+
+impl FragmentHeader {
+    /// Returns whether this is the last fragment of a message.
+    pub fn is_final(&self) -> bool {
+        self.flags & FRAGMENT_FLAG_FINAL != 0
+    }
+
+    /// Returns the header of the empty fragment acknowledging this fragment.
+    pub fn ack(&self) -> Self {
+        Self {
+            content: self.content,
+            sequence: self.sequence,
+            flags: 0,
+        }
+    }
+}
+
+/// An error while reassembling a fragmented message.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ReassemblyError {
+    /// The fragment is not the next one in sequence.
+    OutOfSequence,
+
+    /// The content type differs from that of the previous fragments.
+    ContentMismatch,
+
+    /// The message does not fit into the reassembly buffer.
+    TooLong,
+}
+
+/// Reassembles fragments into a message.
+///
+/// The reassembler only keeps track of the state. The message is assembled
+/// in a buffer provided by the caller, which must be the same for all
+/// fragments of a message.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Reassembler {
+    content: Option<ContentType>,
+    len: usize,
+    next_sequence: u8,
+}
+
+impl Reassembler {
+    /// Creates a new reassembler.
+    pub const fn new() -> Self {
+        Self {
+            content: None,
+            len: 0,
+            next_sequence: 0,
+        }
+    }
+
+    /// Discards any partially reassembled message.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Adds the fragment with the specified `header` and `data` to `buf`.
+    ///
+    /// A fragment with sequence number 0 starts a new message. Returns the
+    /// length of the message in `buf` once the final fragment was added.
+    pub fn add(&mut self, buf: &mut [u8], header: &FragmentHeader, data: &[u8])
+        -> Result<Option<usize>, ReassemblyError> {
+        if header.sequence == 0 {
+            self.reset();
+            self.content = Some(header.content);
+        }
+
+        let result = self.append(buf, header, data);
+        if result != Ok(None) {
+            self.reset();
+        }
+        result
+    }
+
+    fn append(&mut self, buf: &mut [u8], header: &FragmentHeader, data: &[u8])
+        -> Result<Option<usize>, ReassemblyError> {
+        if self.content.is_none() || header.sequence != self.next_sequence {
+            return Err(ReassemblyError::OutOfSequence);
+        }
+        if self.content != Some(header.content) {
+            return Err(ReassemblyError::ContentMismatch);
+        }
+
+        let end = self.len + data.len();
+        buf.get_mut(self.len..end)
+            .ok_or(ReassemblyError::TooLong)?
+            .copy_from_slice(data);
+        self.len = end;
+
+        if header.is_final() {
+            return Ok(Some(self.len));
+        }
+        self.next_sequence = self.next_sequence.checked_add(1)
+            .ok_or(ReassemblyError::TooLong)?;
+        Ok(None)
+    }
+}
+
+/// Splits a message into fragments.
+///
+/// The fragmenter only keeps track of the state. The caller keeps the
+/// message and sends the bytes in the range returned for each fragment.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Fragmenter {
+    content: Option<ContentType>,
+    len: usize,
+    offset: usize,
+    next_sequence: u8,
+}
+
+impl Fragmenter {
+    /// Creates a new fragmenter.
+    pub const fn new() -> Self {
+        Self {
+            content: None,
+            len: 0,
+            offset: 0,
+            next_sequence: 0,
+        }
+    }
+
+    /// Discards the message that is being fragmented.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Starts fragmenting a message of type `content` that is `len` bytes long.
+    pub fn start(&mut self, content: ContentType, len: usize) {
+        *self = Self {
+            content: Some(content),
+            len,
+            offset: 0,
+            next_sequence: 0,
+        };
+    }
+
+    /// Returns whether not all fragments of the message were returned yet.
+    pub fn is_active(&self) -> bool {
+        self.content.is_some()
+    }
+
+    /// Returns the number of fragments of a message that is `len` bytes long
+    /// if each fragment carries up to `max_data_len` bytes.
+    pub fn fragment_count(len: usize, max_data_len: usize) -> usize {
+        if len == 0 {
+            1
+        } else {
+            (len - 1) / max_data_len + 1
+        }
+    }
+
+    /// Returns whether `header` and `data` acknowledge the last fragment.
+    pub fn is_ack(&self, header: &FragmentHeader, data: &[u8]) -> bool {
+        match self.content {
+            Some(content) => content == header.content
+                && !header.is_final()
+                && data.is_empty()
+                && header.sequence.wrapping_add(1) == self.next_sequence,
+            None => false,
+        }
+    }
+
+    /// Returns the header and the message range of the next fragment, which
+    /// carries at most `max_data_len` bytes. `max_data_len` must not be 0.
+    ///
+    /// Returns `None` once the final fragment was returned.
+    pub fn next(&mut self, max_data_len: usize) -> Option<(FragmentHeader, Range<usize>)> {
+        let content = self.content?;
+        let start = self.offset;
+        let end = start + core::cmp::min(self.len - start, max_data_len);
+        let is_final = end == self.len;
+
+        let header = FragmentHeader {
+            content,
+            sequence: self.next_sequence,
+            flags: if is_final { FRAGMENT_FLAG_FINAL } else { 0 },
+        };
+
+        self.offset = end;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        if is_final {
+            self.content = None;
+        }
+        Some((header, start..end))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fragment(content: ContentType, sequence: u8, is_final: bool) -> FragmentHeader {
+        FragmentHeader {
+            content,
+            sequence,
+            flags: if is_final { FRAGMENT_FLAG_FINAL } else { 0 },
+        }
+    }
+
+    // Builds the content of an `Extended` payload carrying `content`.
+    fn extended(mode: IntegrityMode, content: &[u8]) -> Vec<u8> {
+        let mut data = vec![0u8; EXTENDED_HEADER_LEN];
+        ExtendedHeader {
+            version: EXTENDED_HEADER_VERSION,
+            content: ContentType::Firmware,
+            integrity: mode,
+        }.to_wire(&mut data[..]).unwrap();
+        data.extend_from_slice(content);
+        let trailer = integrity::compute_trailer(mode, &data);
+        data.extend_from_slice(&trailer[..mode.trailer_len()]);
+        data
+    }
+
+    #[test]
+    fn parse_extended_valid() {
+        let data = extended(IntegrityMode::Crc32, &[1, 2, 3]);
+        let (header, content) = parse_extended(&data).unwrap();
+        assert_eq!(header.content, ContentType::Firmware);
+        assert_eq!(header.integrity, IntegrityMode::Crc32);
+        assert_eq!(content, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn parse_extended_truncated() {
+        let data = extended(IntegrityMode::Sha256, &[]);
+        assert!(matches!(parse_extended(&data[..EXTENDED_HEADER_LEN - 1]),
+            Err(ExtendedError::FromWire(_))));
+        assert!(matches!(parse_extended(&data[..data.len() - 1]),
+            Err(ExtendedError::FromWire(FromWireError::OutOfRange))));
+    }
+
+    #[test]
+    fn parse_extended_corrupt() {
+        let mut data = extended(IntegrityMode::Crc32, &[1, 2, 3]);
+        data[EXTENDED_HEADER_LEN] ^= 1;
+        assert!(matches!(parse_extended(&data),
+            Err(ExtendedError::BadIntegrity(IntegrityMode::Crc32))));
+
+        let mut data = extended(IntegrityMode::Crc32, &[1, 2, 3]);
+        data[0] = EXTENDED_HEADER_VERSION + 1;
+        assert!(matches!(parse_extended(&data),
+            Err(ExtendedError::UnsupportedVersion(version)) if version == EXTENDED_HEADER_VERSION + 1));
+    }
+
+    #[test]
+    fn reassemble_in_order() {
+        let mut buf = [0u8; 8];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1, 2, 3]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, false), &[4, 5]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 2, true), &[6]), Ok(Some(6)));
+        assert_eq!(&buf[..6], &[1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let mut buf = [0u8; 8];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 2, true), &[3]),
+                   Err(ReassemblyError::OutOfSequence));
+
+        // The partial message was discarded.
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, true), &[2]),
+                   Err(ReassemblyError::OutOfSequence));
+    }
+
+    #[test]
+    fn reassemble_duplicate() {
+        let mut buf = [0u8; 8];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, false), &[2]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, false), &[2]),
+                   Err(ReassemblyError::OutOfSequence));
+    }
+
+    #[test]
+    fn reassemble_missing_first() {
+        let mut buf = [0u8; 8];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, true), &[2]),
+                   Err(ReassemblyError::OutOfSequence));
+
+        // A new first fragment restarts the message.
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, true), &[7]), Ok(Some(1)));
+        assert_eq!(buf[0], 7);
+    }
+
+    #[test]
+    fn reassemble_content_mismatch() {
+        let mut buf = [0u8; 8];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Manticore, 1, true), &[2]),
+                   Err(ReassemblyError::ContentMismatch));
+    }
+
+    #[test]
+    fn reassemble_oversize() {
+        let mut buf = [0u8; 4];
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 0, false), &[1, 2, 3]), Ok(None));
+        assert_eq!(reassembler.add(&mut buf, &fragment(ContentType::Firmware, 1, true), &[4, 5]),
+                   Err(ReassemblyError::TooLong));
+        assert_eq!(reassembler, Reassembler::new());
+    }
+
+    #[test]
+    fn fragment_message() {
+        let mut fragmenter = Fragmenter::new();
+        assert!(!fragmenter.is_active());
+        assert_eq!(fragmenter.next(4), None);
+
+        fragmenter.start(ContentType::Manticore, 10);
+        assert_eq!(Fragmenter::fragment_count(10, 4), 3);
+        assert_eq!(fragmenter.next(4), Some((fragment(ContentType::Manticore, 0, false), 0..4)));
+        assert!(fragmenter.is_ack(&fragment(ContentType::Manticore, 0, false), &[]));
+        assert!(!fragmenter.is_ack(&fragment(ContentType::Manticore, 1, false), &[]));
+        assert!(!fragmenter.is_ack(&fragment(ContentType::Firmware, 0, false), &[]));
+        assert!(!fragmenter.is_ack(&fragment(ContentType::Manticore, 0, false), &[0]));
+        assert_eq!(fragmenter.next(4), Some((fragment(ContentType::Manticore, 1, false), 4..8)));
+        assert_eq!(fragmenter.next(4), Some((fragment(ContentType::Manticore, 2, true), 8..10)));
+        assert!(!fragmenter.is_active());
+        assert_eq!(fragmenter.next(4), None);
+    }
+
+    #[test]
+    fn fragment_empty_message() {
+        let mut fragmenter = Fragmenter::new();
+        fragmenter.start(ContentType::Firmware, 0);
+        assert_eq!(Fragmenter::fragment_count(0, 4), 1);
+        assert_eq!(fragmenter.next(4), Some((fragment(ContentType::Firmware, 0, true), 0..0)));
+        assert_eq!(fragmenter.next(4), None);
+    }
+
+    #[test]
+    fn fragment_round_trip() {
+        let message: Vec<u8> = (0..100).collect();
+        let mut buf = [0u8; 128];
+        let mut fragmenter = Fragmenter::new();
+        let mut reassembler = Reassembler::new();
+        fragmenter.start(ContentType::Firmware, message.len());
+        let mut result = None;
+        while let Some((header, range)) = fragmenter.next(7) {
+            result = reassembler.add(&mut buf, &header, &message[range]).unwrap();
+        }
+        assert_eq!(result, Some(message.len()));
+        assert_eq!(&buf[..message.len()], &message[..]);
+    }
+}
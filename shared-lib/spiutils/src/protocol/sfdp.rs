@@ -0,0 +1,501 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serial Flash Discoverable Parameters (SFDP).
+//!
+//! Parses the SFDP header, the JEDEC basic flash parameter table
+//! (JESD216, JESD216A and JESD216B) and the Google parameter table.
+
+use crate::protocol::flash::AddressMode;
+
+use core::result::Result;
+
+/// The SFDP signature ("SFDP" in little-endian byte order).
+pub const SIGNATURE: u32 = 0x5044_4653;
+
+/// The length of the SFDP header, in bytes.
+pub const HEADER_LEN: usize = 8;
+
+/// The length of a parameter header, in bytes.
+pub const PARAMETER_HEADER_LEN: usize = 8;
+
+/// The only supported SFDP major revision.
+pub const MAJOR_REVISION: u8 = 0x01;
+
+/// The ID of the JEDEC basic flash parameter table.
+pub const BASIC_PARAMETER_TABLE_ID: u16 = 0xff00;
+
+/// The minimum length of the basic flash parameter table (JESD216), in DWORDs.
+pub const BASIC_PARAMETER_TABLE_MIN_LEN: usize = 9;
+
+/// The ID of the Google parameter table (manufacturer ID 0x26 in bank 9).
+pub const GOOGLE_PARAMETER_TABLE_ID: u16 = 0x0926;
+
+/// The length of the Google parameter table, in DWORDs.
+pub const GOOGLE_PARAMETER_TABLE_LEN: usize = 4;
+
+/// The signature of the Google parameter table ("GOOG" in little-endian
+/// byte order).
+pub const GOOGLE_SIGNATURE: u32 = 0x474f_4f47;
+
+/// The number of erase types in the basic flash parameter table.
+pub const ERASE_TYPE_COUNT: usize = 4;
+
+/// Errors while parsing SFDP data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SfdpError {
+    /// The data does not start with the SFDP signature.
+    BadSignature,
+
+    /// The SFDP major revision is not supported.
+    UnsupportedRevision,
+
+    /// A header or a required table extends beyond the data.
+    Truncated,
+
+    /// There is no usable basic flash parameter table.
+    MissingBasicParameterTable,
+
+    /// The flash memory density cannot be represented.
+    InvalidDensity,
+
+    /// The Google parameter table does not start with its signature.
+    BadGoogleSignature,
+}
+
+/// The address lengths supported by the flash device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AddressBytes {
+    /// 3 byte addressing only.
+    ThreeByteOnly,
+
+    /// Defaults to 3 byte addressing, enters 4 byte addressing on command.
+    ThreeOrFourByte,
+
+    /// 4 byte addressing only.
+    FourByteOnly,
+}
+
+/// An erase type supported by the flash device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EraseType {
+    /// The size of the erased region, in bytes.
+    pub size: u32,
+
+    /// The erase op code.
+    pub opcode: u8,
+}
+
+/// The parsed JEDEC basic flash parameter table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BasicParameters {
+    /// The table's major revision.
+    pub major_revision: u8,
+
+    /// The table's minor revision.
+    pub minor_revision: u8,
+
+    /// The flash memory density, in bits.
+    pub density_bits: u64,
+
+    /// The address lengths supported by the flash device.
+    pub address_bytes: AddressBytes,
+
+    /// The supported erase types.
+    pub erase_types: [Option<EraseType>; ERASE_TYPE_COUNT],
+
+    /// The page size, in bytes.
+    /// Only present in JESD216A and later.
+    pub page_size: Option<u32>,
+
+    /// Whether 4 byte address mode is entered with instruction B7h.
+    /// Only present in JESD216A and later.
+    pub enter_4b_instruction: bool,
+
+    /// Whether the dedicated 4 byte address instruction set is supported.
+    /// Only present in JESD216A and later.
+    pub has_4b_instructions: bool,
+}
+
+impl BasicParameters {
+    /// Returns the flash memory density in bytes.
+    pub fn density_bytes(&self) -> u64 {
+        self.density_bits / 8
+    }
+
+    /// Returns true iff the flash device supports 4 byte addresses.
+    pub fn supports_4b(&self) -> bool {
+        self.address_bytes != AddressBytes::ThreeByteOnly
+    }
+
+    /// Returns true iff the host can switch between 3 and 4 byte address mode
+    /// with the Enter/Exit4ByteAddressMode op codes.
+    pub fn supports_address_mode_switch(&self) -> bool {
+        self.address_bytes == AddressBytes::ThreeOrFourByte && self.enter_4b_instruction
+    }
+
+    /// Returns the address mode of the flash device after power up.
+    pub fn startup_address_mode(&self) -> AddressMode {
+        match self.address_bytes {
+            AddressBytes::FourByteOnly => AddressMode::FourByte,
+            _ => AddressMode::ThreeByte,
+        }
+    }
+
+    /// Returns the smallest supported erase type.
+    pub fn smallest_erase_type(&self) -> Option<EraseType> {
+        self.erase_types.iter()
+            .filter_map(|erase_type| *erase_type)
+            .min_by_key(|erase_type| erase_type.size)
+    }
+}
+
+/// The parsed Google parameter table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GoogleParameters {
+    /// The table's major revision.
+    pub major_revision: u8,
+
+    /// The table's minor revision.
+    pub minor_revision: u8,
+
+    /// The address of the mailbox.
+    pub mailbox_offset: u32,
+
+    /// The size of the mailbox, in bytes.
+    pub mailbox_size: u32,
+
+    /// The Google capabilities.
+    pub capabilities: u32,
+}
+
+/// Parsed SFDP data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Sfdp {
+    /// The SFDP major revision.
+    pub major_revision: u8,
+
+    /// The SFDP minor revision.
+    pub minor_revision: u8,
+
+    /// The basic flash parameter table.
+    pub basic: BasicParameters,
+
+    /// The Google parameter table, if present.
+    pub google: Option<GoogleParameters>,
+}
+
+// A parsed parameter header.
+struct ParameterHeader {
+    id: u16,
+    major_revision: u8,
+    minor_revision: u8,
+    len: usize,
+    pointer: usize,
+}
+
+// Reads the little-endian DWORD at `offset`.
+fn read_dword(data: &[u8], offset: usize) -> Result<u32, SfdpError> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or(SfdpError::Truncated)
+}
+
+// Extracts `len` bits of `dword` starting at bit `shift`.
+fn bits(dword: u32, shift: u32, len: u32) -> u32 {
+    (dword >> shift) & ((1 << len) - 1)
+}
+
+fn parse_parameter_header(data: &[u8], offset: usize) -> Result<ParameterHeader, SfdpError> {
+    let first = read_dword(data, offset)?;
+    let second = read_dword(data, offset + 4)?;
+    Ok(ParameterHeader {
+        id: (bits(second, 24, 8) << 8 | bits(first, 0, 8)) as u16,
+        minor_revision: bits(first, 8, 8) as u8,
+        major_revision: bits(first, 16, 8) as u8,
+        len: bits(first, 24, 8) as usize,
+        pointer: bits(second, 0, 24) as usize,
+    })
+}
+
+// Returns the table referenced by `header`.
+fn get_table<'a>(data: &'a [u8], header: &ParameterHeader) -> Result<&'a [u8], SfdpError> {
+    data.get(header.pointer..header.pointer + header.len * 4)
+        .ok_or(SfdpError::Truncated)
+}
+
+fn parse_basic_parameters(header: &ParameterHeader, table: &[u8]) -> Result<BasicParameters, SfdpError> {
+    // 1st DWORD: Address bytes
+    let dword = read_dword(table, 0)?;
+    let address_bytes = match bits(dword, 17, 2) {
+        1 => AddressBytes::ThreeOrFourByte,
+        2 => AddressBytes::FourByteOnly,
+        // 0x3 is reserved. Stick to 3 byte addresses.
+        _ => AddressBytes::ThreeByteOnly,
+    };
+
+    // 2nd DWORD: Flash memory density
+    let dword = read_dword(table, 4)?;
+    let density_bits = if bits(dword, 31, 1) == 0 {
+        u64::from(dword) + 1
+    } else {
+        let exponent = bits(dword, 0, 31);
+        if exponent >= 64 {
+            return Err(SfdpError::InvalidDensity);
+        }
+        1u64 << exponent
+    };
+
+    // 8th and 9th DWORD: Erase types
+    let mut erase_types = [None; ERASE_TYPE_COUNT];
+    for (idx, erase_type) in erase_types.iter_mut().enumerate() {
+        let dword = read_dword(table, 7 * 4 + idx / 2 * 4)?;
+        let shift = (idx as u32 % 2) * 16;
+        let size_exponent = bits(dword, shift, 8);
+        if size_exponent != 0 && size_exponent < 32 {
+            *erase_type = Some(EraseType {
+                size: 1 << size_exponent,
+                opcode: bits(dword, shift + 8, 8) as u8,
+            });
+        }
+    }
+
+    // 11th DWORD (JESD216A): Page size
+    let page_size = match read_dword(table, 10 * 4) {
+        Ok(dword) => Some(1 << bits(dword, 4, 4)),
+        Err(_) => None,
+    };
+
+    // 16th DWORD (JESD216A): Enter 4-Byte Addressing
+    let (enter_4b_instruction, has_4b_instructions) = match read_dword(table, 15 * 4) {
+        Ok(dword) => {
+            let enter_4b = bits(dword, 24, 8);
+            (enter_4b & 0x3 != 0, enter_4b & 0x20 != 0)
+        }
+        Err(_) => (false, false),
+    };
+
+    Ok(BasicParameters {
+        major_revision: header.major_revision,
+        minor_revision: header.minor_revision,
+        density_bits,
+        address_bytes,
+        erase_types,
+        page_size,
+        enter_4b_instruction,
+        has_4b_instructions,
+    })
+}
+
+fn parse_google_parameters(header: &ParameterHeader, table: &[u8]) -> Result<GoogleParameters, SfdpError> {
+    if read_dword(table, 0)? != GOOGLE_SIGNATURE {
+        return Err(SfdpError::BadGoogleSignature);
+    }
+
+    Ok(GoogleParameters {
+        major_revision: header.major_revision,
+        minor_revision: header.minor_revision,
+        mailbox_offset: read_dword(table, 4)?,
+        mailbox_size: read_dword(table, 8)?,
+        capabilities: read_dword(table, 12)?,
+    })
+}
+
+/// Parses the SFDP data in `data`, starting at SFDP address 0.
+///
+/// If there are several basic flash parameter tables, the one with the
+/// highest revision is used. Tables other than the basic flash parameter
+/// table and the Google parameter table are ignored and may lie beyond the
+/// end of `data`.
+pub fn parse(data: &[u8]) -> Result<Sfdp, SfdpError> {
+    if read_dword(data, 0)? != SIGNATURE {
+        return Err(SfdpError::BadSignature);
+    }
+
+    let dword = read_dword(data, 4)?;
+    let minor_revision = bits(dword, 0, 8) as u8;
+    let major_revision = bits(dword, 8, 8) as u8;
+    if major_revision != MAJOR_REVISION {
+        return Err(SfdpError::UnsupportedRevision);
+    }
+
+    let parameter_header_count = bits(dword, 16, 8) as usize + 1;
+    let mut basic: Option<BasicParameters> = None;
+    let mut google = None;
+    for idx in 0..parameter_header_count {
+        let header = parse_parameter_header(data, HEADER_LEN + idx * PARAMETER_HEADER_LEN)?;
+        match header.id {
+            BASIC_PARAMETER_TABLE_ID if header.len >= BASIC_PARAMETER_TABLE_MIN_LEN => {
+                if header.major_revision != MAJOR_REVISION {
+                    continue;
+                }
+                if let Some(current) = basic {
+                    if current.minor_revision >= header.minor_revision {
+                        continue;
+                    }
+                }
+                basic = Some(parse_basic_parameters(&header, get_table(data, &header)?)?);
+            }
+            GOOGLE_PARAMETER_TABLE_ID if header.len >= GOOGLE_PARAMETER_TABLE_LEN => {
+                google = Some(parse_google_parameters(&header, get_table(data, &header)?)?);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Sfdp {
+        major_revision,
+        minor_revision,
+        basic: basic.ok_or(SfdpError::MissingBasicParameterTable)?,
+        google,
+    })
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A JESD216B SFDP of a 256 Mbit part with a basic flash parameter table,
+    // a 4-byte address instruction table and a Google parameter table.
+    const SFDP: [u8; 0xa0] = [
+        // SFDP header: JESD216B, 3 parameter headers
+        0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x02, 0xff,
+        // Basic flash parameter table v1.6, 16 DWORDs at 0x30
+        0x00, 0x06, 0x01, 0x10, 0x30, 0x00, 0x00, 0xff,
+        // 4-byte address instruction table v1.0, 2 DWORDs at 0x70
+        0x84, 0x00, 0x01, 0x02, 0x70, 0x00, 0x00, 0xff,
+        // Google parameter table v1.0, 4 DWORDs at 0x78
+        0x26, 0x00, 0x01, 0x04, 0x78, 0x00, 0x00, 0x09,
+        // Padding
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+
+        // Basic flash parameter table
+        // 1st DWORD: 4 KiB erase, 3 or 4 byte addresses
+        0xe5, 0x20, 0xfb, 0xff,
+        // 2nd DWORD: 256 Mbit
+        0xff, 0xff, 0xff, 0x0f,
+        // 3rd to 7th DWORD: Fast reads
+        0x44, 0xeb, 0x08, 0x6b, 0x08, 0x3b, 0x42, 0xbb,
+        0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+        0xff, 0xff, 0x40, 0xeb,
+        // 8th and 9th DWORD: 4 KiB, 32 KiB and 64 KiB erase
+        0x0c, 0x20, 0x0f, 0x52, 0x10, 0xd8, 0x00, 0xff,
+        // 10th to 15th DWORD
+        0x23, 0x72, 0xf5, 0x00, 0x82, 0xed, 0x04, 0xb7,
+        0x44, 0x83, 0x38, 0x44, 0x30, 0xb0, 0x30, 0xb0,
+        0xf7, 0xc4, 0xd5, 0x5c, 0x00, 0xbe, 0x29, 0xff,
+        // 16th DWORD: Enter 4B with B7h, dedicated 4B instructions
+        0xf0, 0x50, 0xf9, 0x21,
+
+        // 4-byte address instruction table
+        0x7f, 0xef, 0xff, 0xff, 0x21, 0x5c, 0xdc, 0xff,
+
+        // Google parameter table
+        0x47, 0x4f, 0x4f, 0x47,
+        0x00, 0x00, 0x08, 0x00,
+        0x00, 0x02, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00,
+
+        // Padding
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    ];
+
+    #[test]
+    fn basic_parameters() {
+        let sfdp = parse(&SFDP).unwrap();
+        assert_eq!((sfdp.major_revision, sfdp.minor_revision), (1, 6));
+
+        let basic = sfdp.basic;
+        assert_eq!(basic.density_bits, 256 * 1024 * 1024);
+        assert_eq!(basic.density_bytes(), 32 * 1024 * 1024);
+        assert_eq!(basic.address_bytes, AddressBytes::ThreeOrFourByte);
+        assert_eq!(basic.startup_address_mode(), AddressMode::ThreeByte);
+        assert!(basic.supports_4b());
+        assert!(basic.supports_address_mode_switch());
+        assert!(basic.has_4b_instructions);
+        assert_eq!(basic.page_size, Some(256));
+        assert_eq!(basic.erase_types, [
+            Some(EraseType { size: 4 * 1024, opcode: 0x20 }),
+            Some(EraseType { size: 32 * 1024, opcode: 0x52 }),
+            Some(EraseType { size: 64 * 1024, opcode: 0xd8 }),
+            None,
+        ]);
+        assert_eq!(basic.smallest_erase_type(), Some(EraseType { size: 4 * 1024, opcode: 0x20 }));
+    }
+
+    #[test]
+    fn google_parameters() {
+        assert_eq!(parse(&SFDP).unwrap().google, Some(GoogleParameters {
+            major_revision: 1,
+            minor_revision: 0,
+            mailbox_offset: 0x80000,
+            mailbox_size: 512,
+            capabilities: 1,
+        }));
+
+        let mut data = SFDP;
+        data[0x78] = 0;
+        assert_eq!(parse(&data), Err(SfdpError::BadGoogleSignature));
+
+        // Without the Google parameter header.
+        data[6] = 1;
+        assert_eq!(parse(&data).unwrap().google, None);
+    }
+
+    #[test]
+    fn jesd216() {
+        // A JESD216 basic flash parameter table with only 9 DWORDs, 4 byte
+        // addresses only and a density above 2 gibibits.
+        let mut data = SFDP;
+        data[6] = 0x00;
+        data[9] = 0x00;
+        data[11] = 0x09;
+        data[0x32] = 0xfd;
+        data[0x34..0x38].copy_from_slice(&[0x20, 0x00, 0x00, 0x80]);
+        let basic = parse(&data[..0x54]).unwrap().basic;
+        assert_eq!(basic.density_bytes(), 512 * 1024 * 1024);
+        assert_eq!(basic.startup_address_mode(), AddressMode::FourByte);
+        assert!(!basic.supports_address_mode_switch());
+        assert_eq!(basic.page_size, None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse(&SFDP[..4]), Err(SfdpError::Truncated));
+        assert_eq!(parse(&SFDP[..0x50]), Err(SfdpError::Truncated));
+
+        let mut data = SFDP;
+        data[0] = b's';
+        assert_eq!(parse(&data), Err(SfdpError::BadSignature));
+
+        let mut data = SFDP;
+        data[5] = 0x02;
+        assert_eq!(parse(&data), Err(SfdpError::UnsupportedRevision));
+
+        let mut data = SFDP;
+        data[11] = 0x08;
+        assert_eq!(parse(&data), Err(SfdpError::MissingBasicParameterTable));
+
+        let mut data = SFDP;
+        data[0x34..0x38].copy_from_slice(&[0x40, 0x00, 0x00, 0x80]);
+        assert_eq!(parse(&data), Err(SfdpError::InvalidDensity));
+    }
+}
@@ -90,7 +90,7 @@ impl From<io::Error> for ToWireError {
 ///
 /// Also, the following identity must hold for all types T:
 /// ```
-/// # use manticore::protocol::wire::WireEnum;
+/// # use spiutils::protocol::wire::WireEnum;
 /// # fn test<T: WireEnum + Copy + PartialEq + std::fmt::Debug>(x: T) {
 /// assert_eq!(T::from_name(T::name(x)), Some(x));
 /// # }
@@ -169,6 +169,11 @@ macro_rules! wire_enum {
            )*
         }
 
+        impl $name {
+            /// The length of this enum on the wire, in bytes.
+            pub const LEN: usize = core::mem::size_of::<$wire>();
+        }
+
         impl $crate::protocol::wire::WireEnum for $name {
             type Wire = $wire;
             fn to_wire_value(self) -> Self::Wire {
@@ -224,6 +229,133 @@ macro_rules! wire_enum {
     }
 }
 
+/// A conveinence macro for generating structs that implement `FromWire` and
+/// `ToWire`.
+///
+/// Syntax is as follows:
+/// ```text
+/// wire_struct! {
+///     /// This is my struct.
+///     pub struct MyStruct<'a> {
+///         /// A big-endian integer.
+///         pub offset: u32 as be,
+///         /// A little-endian integer.
+///         pub epoch: u32 as le,
+///         /// A boolean, encoded as a single byte.
+///         pub enabled: bool as bool,
+///         /// A fixed-size byte array.
+///         pub digest: [u8; 32] as bytes,
+///         /// A type that implements `FromWire` and `ToWire` and has a `LEN`.
+///         pub content: MyEnum as wire,
+///         /// All remaining data. Only valid as the last field.
+///         pub data: &'a [u8] as rest,
+///     }
+/// }
+/// ```
+/// The fields are serialized in the order in which they are declared. The
+/// lifetime parameter is only needed for `rest` fields.
+///
+/// This macro will generate implementations of `FromWire` and `ToWire` for
+/// the above struct, as well as a `LEN` constant with the length of the
+/// struct on the wire, excluding any `rest` field.
+macro_rules! wire_struct {
+    (@len be, $ty:ty) => { core::mem::size_of::<$ty>() };
+    (@len le, $ty:ty) => { core::mem::size_of::<$ty>() };
+    (@len bool, $ty:ty) => { 1 };
+    (@len bytes, $ty:ty) => { core::mem::size_of::<$ty>() };
+    (@len wire, $ty:ty) => { <$ty>::LEN };
+    (@len rest, $ty:ty) => { 0 };
+
+    (@read $r:ident, be, $ty:ty) => { $r.read_be::<$ty>()? };
+    (@read $r:ident, le, $ty:ty) => { $r.read_le::<$ty>()? };
+    (@read $r:ident, bool, $ty:ty) => { $r.read_be::<u8>()? != 0 };
+    (@read $r:ident, bytes, $ty:ty) => {{
+        let mut val: $ty = [0u8; core::mem::size_of::<$ty>()];
+        val.copy_from_slice($r.read_bytes(core::mem::size_of::<$ty>())?);
+        val
+    }};
+    (@read $r:ident, wire, $ty:ty) => {
+        <$ty as $crate::protocol::wire::FromWire>::from_wire(&mut $r)?
+    };
+    (@read $r:ident, rest, $ty:ty) => {{
+        let data_len = $r.remaining_data();
+        $r.read_bytes(data_len)?
+    }};
+
+    (@write $w:ident, $val:expr, be) => { $w.write_be($val)? };
+    (@write $w:ident, $val:expr, le) => { $w.write_le($val)? };
+    (@write $w:ident, $val:expr, bool) => { $w.write_be(if $val { 1 } else { 0u8 })? };
+    (@write $w:ident, $val:expr, bytes) => { $w.write_bytes(&$val)? };
+    (@write $w:ident, $val:expr, wire) => {
+        $crate::protocol::wire::ToWire::to_wire(&$val, &mut $w)?
+    };
+    (@write $w:ident, $val:expr, rest) => { $w.write_bytes($val)? };
+
+    (@impl $name:ident, $wire_lt:lifetime, ($($generics:tt)*), ($($args:tt)*) {
+        $($field:ident: $ty:ty as $kind:ident,)*
+    }) => {
+        impl<$($generics)*> $name<$($args)*> {
+            /// The length of this struct on the wire, in bytes.
+            pub const LEN: usize = 0 $(+ wire_struct!(@len $kind, $ty))*;
+        }
+
+        impl<$wire_lt> $crate::protocol::wire::FromWire<$wire_lt> for $name<$($args)*> {
+            #[allow(unused_mut, unused_variables)]
+            fn from_wire<R: $crate::io::Read<$wire_lt>>(mut r: R)
+                -> Result<Self, $crate::protocol::wire::FromWireError> {
+                $(let $field = wire_struct!(@read r, $kind, $ty);)*
+                Ok(Self {
+                    $($field,)*
+                })
+            }
+        }
+
+        impl<$($generics)*> $crate::protocol::wire::ToWire for $name<$($args)*> {
+            #[allow(unused_mut, unused_variables)]
+            fn to_wire<W: $crate::io::Write>(&self, mut w: W)
+                -> Result<(), $crate::protocol::wire::ToWireError> {
+                $(wire_struct!(@write w, self.$field, $kind);)*
+                Ok(())
+            }
+        }
+    };
+
+    (@struct ($(#[$meta:meta])*) $vis:vis $name:ident ($($generics:tt)*) {
+        $($(#[$meta_field:meta])* $field_vis:vis $field:ident: $ty:ty,)*
+    }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        $vis struct $name<$($generics)*> {
+            $(
+                $(#[$meta_field])*
+                $field_vis $field: $ty,
+            )*
+        }
+    };
+
+    ($(#[$meta:meta])* $vis:vis struct $name:ident<$lt:lifetime> {
+        $($(#[$meta_field:meta])* $field_vis:vis $field:ident: $ty:ty as $kind:ident,)*
+    }) => {
+        wire_struct!(@struct ($(#[$meta])*) $vis $name ($lt) {
+            $($(#[$meta_field])* $field_vis $field: $ty,)*
+        });
+        wire_struct!(@impl $name, $lt, ($lt), ($lt) {
+            $($field: $ty as $kind,)*
+        });
+    };
+
+    ($(#[$meta:meta])* $vis:vis struct $name:ident {
+        $($(#[$meta_field:meta])* $field_vis:vis $field:ident: $ty:ty as $kind:ident,)*
+    }) => {
+        wire_struct!(@struct ($(#[$meta])*) $vis $name () {
+            $($(#[$meta_field])* $field_vis $field: $ty,)*
+        });
+        wire_struct!(@impl $name, 'wire, (), () {
+            $($field: $ty as $kind,)*
+        });
+    };
+}
+
 #[cfg(test)]
 mod test {
     wire_enum! {
@@ -262,4 +394,67 @@ mod test {
         assert_eq!(DemoEnum::First.name(), "First");
         assert_eq!(DemoEnum::Second.name(), "Second");
     }
+
+    wire_struct! {
+        /// A struct for testing.
+        pub struct DemoStruct<'a> {
+            /// A big-endian field.
+            pub be_field: u16 as be,
+
+            /// A little-endian field.
+            pub le_field: u32 as le,
+
+            /// A boolean field.
+            pub bool_field: bool as bool,
+
+            /// A byte array field.
+            pub bytes_field: [u8; 3] as bytes,
+
+            /// An enum field.
+            pub enum_field: DemoEnum as wire,
+
+            /// The remaining data.
+            pub data: &'a [u8] as rest,
+        }
+    }
+
+    #[test]
+    fn struct_len() {
+        assert_eq!(DemoEnum::LEN, 1);
+        assert_eq!(DemoStruct::LEN, 11);
+    }
+
+    #[test]
+    fn struct_round_trip() {
+        use crate::protocol::wire::*;
+
+        let value = DemoStruct {
+            be_field: 0x0102,
+            le_field: 0x03040506,
+            bool_field: true,
+            bytes_field: [0x0a, 0x0b, 0x0c],
+            enum_field: DemoEnum::Second,
+            data: &[0xaa, 0xbb],
+        };
+
+        let mut buf = [0u8; 16];
+        let mut cursor = &mut buf[..];
+        value.to_wire(&mut cursor).expect("to_wire failed");
+        let remaining = cursor.len();
+        let written = buf.len() - remaining;
+        assert_eq!(&buf[..written], &[0x01, 0x02, 0x06, 0x05, 0x04, 0x03, 0x01, 0x0a, 0x0b, 0x0c, 0x02, 0xaa, 0xbb]);
+
+        let mut data = &buf[..written];
+        let parsed = DemoStruct::from_wire(&mut data).expect("from_wire failed");
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn struct_out_of_range() {
+        use crate::protocol::wire::*;
+
+        let buf = [0x01, 0x02, 0x06, 0x05, 0x04, 0x03, 0x00, 0x0a, 0x0b, 0x0c, 0x7f];
+        let mut data = &buf[..];
+        assert!(DemoStruct::from_wire(&mut data).is_err());
+    }
 }
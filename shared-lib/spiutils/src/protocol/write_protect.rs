@@ -0,0 +1,417 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Write protection protocol payload.
+//!
+//! A write protection policy restricts which PageProgram and erase commands
+//! the device passes through to the SPI flash chip. The policy is
+//! provisioned via the mailbox and persisted by the device. The device only
+//! accepts a new policy while provisioning has been enabled on its console.
+
+use crate::io::Read;
+use crate::io::Write;
+use crate::protocol::wire::FromWire;
+use crate::protocol::wire::FromWireError;
+use crate::protocol::wire::ToWire;
+use crate::protocol::wire::ToWireError;
+
+wire_enum! {
+    /// The content type.
+    pub enum ContentType: u8 {
+        /// Request the write protection policy
+        GetPolicyRequest = 0x01,
+
+        /// Response to GetPolicyRequest
+        GetPolicyResponse = 0x02,
+
+        /// Request to provision a write protection policy
+        SetPolicyRequest = 0x03,
+
+        /// Response to SetPolicyRequest
+        SetPolicyResponse = 0x04,
+    }
+}
+
+wire_struct! {
+    /// A parsed header.
+    pub struct Header {
+        /// The content type following the header.
+        pub content: ContentType as wire,
+    }
+}
+
+/// The length of a write protection header on the wire, in bytes.
+pub const HEADER_LEN: usize = Header::LEN;
+
+// ----------------------------------------------------------------------------
+
+/// A message.
+///
+/// A message is identified by a [`ContentType`]:
+///
+/// This trait is not implemented by any of the message types
+///
+/// [`ContentType`]: enum.ContentType.html
+pub trait Message<'req>: FromWire<'req> + ToWire {
+    /// The unique [`ContentType`] for this `Message`.
+    ///
+    /// [`ContentType`]: enum.ContentType.html
+    const TYPE: ContentType;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// The kind of protection applied to a range.
+    pub enum RangeKind: u8 {
+        /// Neither PageProgram nor erase commands are allowed.
+        ReadOnly = 0x01,
+
+        /// PageProgram commands are allowed, erase commands are not.
+        /// This does not make the range write-once: programming can still
+        /// clear further bits of data that has already been programmed.
+        ProgramOnly = 0x02,
+    }
+}
+
+wire_struct! {
+    /// A protected range of the SPI flash chip.
+    pub struct Range {
+        /// The kind of protection.
+        pub kind: RangeKind as wire,
+
+        /// The start address of the range.
+        pub start: u32 as be,
+
+        /// The size of the range, in bytes.
+        pub size: u32 as be,
+    }
+}
+
+/// The length of a range on the wire, in bytes.
+pub const RANGE_LEN: usize = Range::LEN;
+
+impl Range {
+    /// Returns true iff the range overlaps `size` bytes at `start`.
+    pub fn overlaps(&self, start: u32, size: u32) -> bool {
+        let range_end = self.start as u64 + self.size as u64;
+        let end = start as u64 + size as u64;
+        (start as u64) < range_end && (self.start as u64) < end
+    }
+}
+
+/// The maximum number of ranges in a policy.
+pub const MAX_RANGES: usize = 8;
+
+/// An unused entry in a policy's range table.
+const UNUSED_RANGE: Range = Range {
+    kind: RangeKind::ReadOnly,
+    start: 0,
+    size: 0,
+};
+
+/// A write protection policy.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Policy {
+    /// Whether ChipErase is denied.
+    /// ChipErase is also denied if the policy has any ranges.
+    pub deny_chip_erase: bool,
+
+    /// Whether the policy is locked.
+    /// A locked policy cannot be replaced.
+    pub locked: bool,
+
+    range_count: u8,
+    ranges: [Range; MAX_RANGES],
+}
+
+/// The policy that allows all commands.
+pub const UNRESTRICTED_POLICY: Policy = Policy {
+    deny_chip_erase: false,
+    locked: false,
+    range_count: 0,
+    ranges: [UNUSED_RANGE; MAX_RANGES],
+};
+
+/// Error returned when a range cannot be added to a policy.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RangeError {
+    /// The policy already has `MAX_RANGES` ranges.
+    TooManyRanges,
+
+    /// The range is empty or extends beyond the 32-bit address space.
+    InvalidRange,
+}
+
+impl Policy {
+    /// The length of a policy on the wire, in bytes.
+    /// Unused range table entries are included.
+    pub const LEN: usize = 3 + MAX_RANGES * RANGE_LEN;
+
+    /// Returns the ranges of the policy.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges[..self.range_count as usize]
+    }
+
+    /// Adds a range to the policy.
+    pub fn add_range(&mut self, range: Range) -> Result<(), RangeError> {
+        if range.size == 0 || range.start as u64 + range.size as u64 > 1u64 << 32 {
+            return Err(RangeError::InvalidRange);
+        }
+        let idx = self.range_count as usize;
+        if idx >= MAX_RANGES {
+            return Err(RangeError::TooManyRanges);
+        }
+        self.ranges[idx] = range;
+        self.range_count += 1;
+        Ok(())
+    }
+
+    /// Returns true iff a PageProgram of `size` bytes at `start` is allowed.
+    pub fn allows_program(&self, start: u32, size: u32) -> bool {
+        !self.ranges().iter()
+            .any(|range| range.kind == RangeKind::ReadOnly && range.overlaps(start, size))
+    }
+
+    /// Returns true iff erasing `size` bytes at `start` is allowed.
+    pub fn allows_erase(&self, start: u32, size: u32) -> bool {
+        !self.ranges().iter().any(|range| range.overlaps(start, size))
+    }
+
+    /// Returns true iff ChipErase is allowed.
+    pub fn allows_chip_erase(&self) -> bool {
+        !self.deny_chip_erase && self.range_count == 0
+    }
+}
+
+// Flags in the first byte of a policy on the wire.
+const FLAG_DENY_CHIP_ERASE: u8 = 0x01;
+const FLAG_LOCKED: u8 = 0x02;
+
+impl<'a> FromWire<'a> for Policy {
+    fn from_wire<R: Read<'a>>(mut r: R) -> Result<Self, FromWireError> {
+        let flags = r.read_be::<u8>()?;
+        if flags & !(FLAG_DENY_CHIP_ERASE | FLAG_LOCKED) != 0 {
+            return Err(FromWireError::OutOfRange);
+        }
+        let mut policy = Policy {
+            deny_chip_erase: flags & FLAG_DENY_CHIP_ERASE != 0,
+            locked: flags & FLAG_LOCKED != 0,
+            ..UNRESTRICTED_POLICY
+        };
+        let range_count = r.read_be::<u8>()? as usize;
+        let _reserved = r.read_be::<u8>()?;
+        if range_count > MAX_RANGES {
+            return Err(FromWireError::OutOfRange);
+        }
+        for idx in 0..MAX_RANGES {
+            let range = Range::from_wire(&mut r)?;
+            if idx < range_count {
+                policy.add_range(range).map_err(|_| FromWireError::OutOfRange)?;
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl ToWire for Policy {
+    fn to_wire<W: Write>(&self, mut w: W) -> Result<(), ToWireError> {
+        let mut flags = 0u8;
+        if self.deny_chip_erase {
+            flags |= FLAG_DENY_CHIP_ERASE;
+        }
+        if self.locked {
+            flags |= FLAG_LOCKED;
+        }
+        w.write_be(flags)?;
+        w.write_be(self.range_count)?;
+        w.write_be(0u8)?;
+        for range in self.ranges.iter() {
+            range.to_wire(&mut w)?;
+        }
+        Ok(())
+    }
+}
+
+/// The length of a policy on the wire, in bytes.
+pub const POLICY_LEN: usize = Policy::LEN;
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed get policy request.
+    pub struct GetPolicyRequest {
+    }
+}
+
+/// The length of a get policy request on the wire, in bytes.
+pub const GET_POLICY_REQUEST_LEN: usize = GetPolicyRequest::LEN;
+
+impl Message<'_> for GetPolicyRequest {
+    const TYPE: ContentType = ContentType::GetPolicyRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed get policy response.
+    pub struct GetPolicyResponse {
+        /// The active policy.
+        pub policy: Policy as wire,
+
+        /// The number of commands denied by the policy since boot.
+        pub denied_count: u32 as be,
+    }
+}
+
+/// The length of a get policy response on the wire, in bytes.
+pub const GET_POLICY_RESPONSE_LEN: usize = GetPolicyResponse::LEN;
+
+impl Message<'_> for GetPolicyResponse {
+    const TYPE: ContentType = ContentType::GetPolicyResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_struct! {
+    /// A parsed set policy request.
+    pub struct SetPolicyRequest {
+        /// The policy to provision.
+        pub policy: Policy as wire,
+    }
+}
+
+/// The length of a set policy request on the wire, in bytes.
+pub const SET_POLICY_REQUEST_LEN: usize = SetPolicyRequest::LEN;
+
+impl Message<'_> for SetPolicyRequest {
+    const TYPE: ContentType = ContentType::SetPolicyRequest;
+}
+
+// ----------------------------------------------------------------------------
+
+wire_enum! {
+    /// Set policy result.
+    pub enum SetPolicyResult: u8 {
+        /// Unknown result
+        Unknown = 0xff,
+
+        /// The policy was persisted and is active.
+        Success = 0x00,
+
+        /// The active policy is locked.
+        Locked = 0x01,
+
+        /// The policy could not be persisted.
+        StorageError = 0x02,
+
+        /// Provisioning has not been enabled on the device console.
+        NotProvisioning = 0x03,
+    }
+}
+
+wire_struct! {
+    /// A parsed set policy response.
+    pub struct SetPolicyResponse {
+        /// The result of the set policy operation.
+        pub result: SetPolicyResult as wire,
+    }
+}
+
+/// The length of a set policy response on the wire, in bytes.
+pub const SET_POLICY_RESPONSE_LEN: usize = SetPolicyResponse::LEN;
+
+impl Message<'_> for SetPolicyResponse {
+    const TYPE: ContentType = ContentType::SetPolicyResponse;
+}
+
+// ----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn policy() -> Policy {
+        let mut policy = UNRESTRICTED_POLICY;
+        policy.add_range(Range { kind: RangeKind::ReadOnly, start: 0x0, size: 0x10000 }).unwrap();
+        policy.add_range(Range { kind: RangeKind::ProgramOnly, start: 0x20000, size: 0x1000 }).unwrap();
+        policy
+    }
+
+    #[test]
+    fn ranges() {
+        let policy = policy();
+
+        assert!(!policy.allows_program(0xff00, 0x100));
+        assert!(policy.allows_program(0x10000, 0x100));
+        assert!(!policy.allows_program(0xfffc, 0x100));
+        assert!(policy.allows_program(0x20000, 0x100));
+
+        assert!(!policy.allows_erase(0x0, 0x1000));
+        assert!(policy.allows_erase(0x10000, 0x10000));
+        assert!(!policy.allows_erase(0x20000, 0x1000));
+        assert!(!policy.allows_erase(0x10000, 0x20000));
+        assert!(policy.allows_erase(0x21000, 0x1000));
+
+        assert!(!policy.allows_chip_erase());
+        assert!(UNRESTRICTED_POLICY.allows_chip_erase());
+        assert!(UNRESTRICTED_POLICY.allows_erase(0x0, 0x1000));
+        assert!(!Policy { deny_chip_erase: true, ..UNRESTRICTED_POLICY }.allows_chip_erase());
+
+        // A range may end at the top of the address space.
+        let mut top = UNRESTRICTED_POLICY;
+        top.add_range(Range { kind: RangeKind::ReadOnly, start: 0xffff_f000, size: 0x1000 }).unwrap();
+        assert!(!top.allows_program(0xffff_ff00, 0x100));
+        assert!(top.allows_program(0xffff_e000, 0x1000));
+    }
+
+    #[test]
+    fn add_range() {
+        let mut policy = UNRESTRICTED_POLICY;
+        assert_eq!(policy.add_range(Range { kind: RangeKind::ReadOnly, start: 0x0, size: 0 }),
+            Err(RangeError::InvalidRange));
+        assert_eq!(policy.add_range(Range { kind: RangeKind::ReadOnly, start: 0xffff_f000, size: 0x2000 }),
+            Err(RangeError::InvalidRange));
+        for idx in 0..MAX_RANGES {
+            policy.add_range(Range { kind: RangeKind::ReadOnly, start: idx as u32 * 0x1000, size: 0x1000 }).unwrap();
+        }
+        assert_eq!(policy.add_range(Range { kind: RangeKind::ReadOnly, start: 0x0, size: 0x1000 }),
+            Err(RangeError::TooManyRanges));
+        assert_eq!(policy.ranges().len(), MAX_RANGES);
+    }
+
+    #[test]
+    fn round_trip() {
+        let policy = Policy { locked: true, ..policy() };
+        let mut buf = [0u8; POLICY_LEN];
+        policy.to_wire(buf.as_mut()).unwrap();
+        assert_eq!(buf[..3], [FLAG_LOCKED, 2, 0]);
+        assert_eq!(buf[3..3 + RANGE_LEN], [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(Policy::from_wire(buf.as_ref()).unwrap(), policy);
+
+        // Invalid flags, range count and ranges are rejected.
+        let mut bad = buf;
+        bad[0] = 0x80;
+        assert!(Policy::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[1] = MAX_RANGES as u8 + 1;
+        assert!(Policy::from_wire(bad.as_ref()).is_err());
+        let mut bad = buf;
+        bad[3 + 5..3 + 9].copy_from_slice(&[0, 0, 0, 0]);
+        assert!(Policy::from_wire(bad.as_ref()).is_err());
+        assert!(Policy::from_wire(&buf[..POLICY_LEN - 1]).is_err());
+    }
+}
@@ -14,88 +14,680 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+mod trace;
+mod transport;
+mod update;
+
 use clap::App;
 use clap::AppSettings;
 use clap::Arg;
+use clap::ArgMatches;
 use clap::SubCommand;
 
-use core::convert::TryFrom;
-
+use spiutils::client;
 use spiutils::io::StdWrite;
 use spiutils::io::Write;
+use spiutils::protocol::capabilities;
+use spiutils::protocol::firmware;
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::integrity::IntegrityMode;
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::measured_boot;
 use spiutils::protocol::payload;
 use spiutils::protocol::wire::FromWire;
-use spiutils::protocol::wire::ToWire;
+use spiutils::protocol::wire::FromWireError;
+use spiutils::protocol::wire::WireEnum;
+use spiutils::protocol::write_protect;
 
+use std::cmp::min;
 use std::fs::OpenOptions;
 use std::io::Read as _;
+use std::process::exit;
 
-fn wrap(input_file: &str, output_file: &str) {
+use transport::FileTransport;
+use transport::SocketBus;
+use transport::SpiFlashTransport;
+use transport::SpidevBus;
+
+fn read_file(input_file: &str) -> Vec<u8> {
     let mut input = OpenOptions::new()
         .read(true)
-        .open(&input_file)
+        .open(input_file)
         .expect("failed to open input file");
-    let mut output = OpenOptions::new()
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(&output_file)
-        .expect("failed to open output file");
 
     let mut read_buf = Vec::new();
     input
         .read_to_end(&mut read_buf)
         .expect("couldn't read from file");
-
-    let header = payload::Header {
-        content: payload::ContentType::Manticore,
-        content_len: u16::try_from(read_buf.len()).unwrap(),
-        checksum: 0,
-    };
-
-    let mut stdwrite = StdWrite(&mut output);
-    header
-        .to_wire(&mut stdwrite)
-        .expect("failed to write header");
-    stdwrite
-        .write_bytes(&read_buf.as_slice())
-        .expect("failed to write payload");
+    read_buf
 }
 
-fn unwrap(input_file: &str, output_file: &str) {
-    let mut input = OpenOptions::new()
-        .read(true)
-        .open(&input_file)
-        .expect("failed to open input file");
+fn write_file(output_file: &str, data: &[u8]) {
     let mut output = OpenOptions::new()
         .write(true)
         .truncate(true)
         .create(true)
-        .open(&output_file)
+        .open(output_file)
         .expect("failed to open output file");
 
-    let mut read_buf = Vec::new();
-    input
-        .read_to_end(&mut read_buf)
-        .expect("couldn't read from file");
+    let mut stdwrite = StdWrite(&mut output);
+    stdwrite
+        .write_bytes(data)
+        .expect("failed to write output file");
+}
+
+fn wrap(input_file: &str, output_file: &str, content: payload::ContentType) {
+    let read_buf = read_file(input_file);
+
+    let wrapped = client::wrap(content, &read_buf).expect("failed to wrap payload");
+    write_file(output_file, &wrapped);
+}
+
+// Decodes the capabilities message following the capabilities header.
+fn decode_capabilities(content: capabilities::ContentType, mut data: &[u8]) -> Result<String, FromWireError> {
+    let r = &mut data;
+    Ok(match content {
+        capabilities::ContentType::CapabilitiesRequest =>
+            format!("{:?}", capabilities::CapabilitiesRequest::from_wire(r)?),
+        capabilities::ContentType::CapabilitiesResponse =>
+            format!("{:?}", capabilities::CapabilitiesResponse::from_wire(r)?),
+    })
+}
+
+// Decodes the write protection message following the write protection header.
+fn decode_write_protect(content: write_protect::ContentType, mut data: &[u8]) -> Result<String, FromWireError> {
+    let r = &mut data;
+    Ok(match content {
+        write_protect::ContentType::GetPolicyRequest =>
+            format!("{:?}", write_protect::GetPolicyRequest::from_wire(r)?),
+        write_protect::ContentType::GetPolicyResponse =>
+            format!("{:?}", write_protect::GetPolicyResponse::from_wire(r)?),
+        write_protect::ContentType::SetPolicyRequest =>
+            format!("{:?}", write_protect::SetPolicyRequest::from_wire(r)?),
+        write_protect::ContentType::SetPolicyResponse =>
+            format!("{:?}", write_protect::SetPolicyResponse::from_wire(r)?),
+    })
+}
+
+// Decodes the measured boot message following the measured boot header.
+fn decode_measured_boot(content: measured_boot::ContentType, mut data: &[u8]) -> Result<String, FromWireError> {
+    let r = &mut data;
+    Ok(match content {
+        measured_boot::ContentType::GetManifestRequest =>
+            format!("{:?}", measured_boot::GetManifestRequest::from_wire(r)?),
+        measured_boot::ContentType::GetManifestResponse =>
+            format!("{:?}", measured_boot::GetManifestResponse::from_wire(r)?),
+        measured_boot::ContentType::SetManifestRequest =>
+            format!("{:?}", measured_boot::SetManifestRequest::from_wire(r)?),
+        measured_boot::ContentType::SetManifestResponse =>
+            format!("{:?}", measured_boot::SetManifestResponse::from_wire(r)?),
+        measured_boot::ContentType::GetRecoveryStatusRequest =>
+            format!("{:?}", measured_boot::GetRecoveryStatusRequest::from_wire(r)?),
+        measured_boot::ContentType::GetRecoveryStatusResponse =>
+            format!("{:?}", measured_boot::GetRecoveryStatusResponse::from_wire(r)?),
+    })
+}
+
+// Decodes the firmware message following the firmware header.
+fn decode_firmware(content: firmware::ContentType, mut data: &[u8]) -> Result<String, FromWireError> {
+    let r = &mut data;
+    Ok(match content {
+        firmware::ContentType::UpdatePrepareRequest =>
+            format!("{:?}", firmware::UpdatePrepareRequest::from_wire(r)?),
+        firmware::ContentType::UpdatePrepareResponse =>
+            format!("{:?}", firmware::UpdatePrepareResponse::from_wire(r)?),
+        firmware::ContentType::WriteChunkRequest =>
+            format!("{:?}", firmware::WriteChunkRequest::from_wire(r)?),
+        firmware::ContentType::WriteChunkResponse =>
+            format!("{:?}", firmware::WriteChunkResponse::from_wire(r)?),
+        firmware::ContentType::InactiveSegmentsInfoRequest =>
+            format!("{:?}", firmware::InactiveSegmentsInfoRequest::from_wire(r)?),
+        firmware::ContentType::InactiveSegmentsInfoResponse =>
+            format!("{:?}", firmware::InactiveSegmentsInfoResponse::from_wire(r)?),
+        firmware::ContentType::RebootRequest =>
+            format!("{:?}", firmware::RebootRequest::from_wire(r)?),
+        firmware::ContentType::RebootResponse =>
+            format!("{:?}", firmware::RebootResponse::from_wire(r)?),
+        firmware::ContentType::VerifySegmentRequest =>
+            format!("{:?}", firmware::VerifySegmentRequest::from_wire(r)?),
+        firmware::ContentType::VerifySegmentResponse =>
+            format!("{:?}", firmware::VerifySegmentResponse::from_wire(r)?),
+        firmware::ContentType::ReadChunkRequest =>
+            format!("{:?}", firmware::ReadChunkRequest::from_wire(r)?),
+        firmware::ContentType::ReadChunkResponse =>
+            format!("{:?}", firmware::ReadChunkResponse::from_wire(r)?),
+        firmware::ContentType::FirmwareInfoRequest =>
+            format!("{:?}", firmware::FirmwareInfoRequest::from_wire(r)?),
+        firmware::ContentType::FirmwareInfoResponse =>
+            format!("{:?}", firmware::FirmwareInfoResponse::from_wire(r)?),
+        firmware::ContentType::PendingRebootRequest =>
+            format!("{:?}", firmware::PendingRebootRequest::from_wire(r)?),
+        firmware::ContentType::PendingRebootResponse =>
+            format!("{:?}", firmware::PendingRebootResponse::from_wire(r)?),
+    })
+}
+
+// Describes the content of a payload with one line per nested message.
+fn describe_content(content: payload::ContentType, data: &[u8]) -> Result<Vec<String>, String> {
+    Ok(match content {
+        payload::ContentType::Manticore => Vec::new(),
+        payload::ContentType::Error => {
+            let error = client::decode_error(data)
+                .map_err(|err| format!("failed to decode error: {:?}", err))?;
+            vec![format!("error={}", error)]
+        }
+        payload::ContentType::Firmware => {
+            let mut fw_data = data;
+            let fw_header = firmware::Header::from_wire(&mut fw_data)
+                .map_err(|err| format!("failed to decode firmware header: {:?}", err))?;
+            let message = decode_firmware(fw_header.content, fw_data)
+                .map_err(|err| format!("failed to decode firmware message: {:?}", err))?;
+            vec![format!("firmware={} {}", fw_header.content, message)]
+        }
+        payload::ContentType::Capabilities => {
+            let mut cap_data = data;
+            let cap_header = capabilities::Header::from_wire(&mut cap_data)
+                .map_err(|err| format!("failed to decode capabilities header: {:?}", err))?;
+            let message = decode_capabilities(cap_header.content, cap_data)
+                .map_err(|err| format!("failed to decode capabilities message: {:?}", err))?;
+            vec![format!("capabilities={} {}", cap_header.content, message)]
+        }
+        payload::ContentType::WriteProtect => {
+            let mut wp_data = data;
+            let wp_header = write_protect::Header::from_wire(&mut wp_data)
+                .map_err(|err| format!("failed to decode write protection header: {:?}", err))?;
+            let message = decode_write_protect(wp_header.content, wp_data)
+                .map_err(|err| format!("failed to decode write protection message: {:?}", err))?;
+            vec![format!("write_protect={} {}", wp_header.content, message)]
+        }
+        payload::ContentType::MeasuredBoot => {
+            let mut mb_data = data;
+            let mb_header = measured_boot::Header::from_wire(&mut mb_data)
+                .map_err(|err| format!("failed to decode measured boot header: {:?}", err))?;
+            let message = decode_measured_boot(mb_header.content, mb_data)
+                .map_err(|err| format!("failed to decode measured boot message: {:?}", err))?;
+            vec![format!("measured_boot={} {}", mb_header.content, message)]
+        }
+        payload::ContentType::Fragment => {
+            let mut fragment_data = data;
+            let fragment = payload::FragmentHeader::from_wire(&mut fragment_data)
+                .map_err(|err| format!("failed to decode fragment header: {:?}", err))?;
+            vec![format!("fragment={} sequence={} final={} len={}", fragment.content,
+                fragment.sequence, fragment.is_final(), fragment_data.len())]
+        }
+        payload::ContentType::Extended => {
+            let (extended, extended_data) = payload::parse_extended(data)
+                .map_err(|err| format!("failed to decode extended payload: {:?}", err))?;
+            let mut lines = vec![format!("extended={} version={} integrity={}", extended.content,
+                extended.version, extended.integrity)];
+            lines.extend(describe_content(extended.content, extended_data)?);
+            lines
+        }
+    })
+}
+
+fn print_content(content: payload::ContentType, data: &[u8]) {
+    let lines = describe_content(content, data).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+fn unwrap(input_file: &str, output_file: &str) {
+    let read_buf = read_file(input_file);
+
+    let (header, data) = client::unwrap(&read_buf).expect("failed to unwrap payload");
+    println!("content={} content_len={} checksum=0x{:02x}",
+        header.content, header.content_len, header.checksum);
+    print_content(header.content, data);
+
+    write_file(output_file, data);
+}
+
+// Parses a numeric argument that may be given in hex with a 0x prefix.
+fn parse_number(value: &str) -> u32 {
+    let result = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    result.unwrap_or_else(|_| {
+        eprintln!("invalid number: {}", value);
+        exit(1);
+    })
+}
+
+fn open_transport(matches: &ArgMatches) -> std::io::Result<Box<dyn client::Transport>> {
+    let address_mode = if matches.is_present("four-byte") {
+        AddressMode::FourByte
+    } else {
+        AddressMode::ThreeByte
+    };
+    Ok(match matches.value_of("transport").unwrap() {
+        "spidev" => {
+            let bus = SpidevBus::open(
+                matches.value_of("device").unwrap(),
+                parse_number(matches.value_of("speed").unwrap()))?;
+            Box::new(SpiFlashTransport::new(bus, address_mode))
+        }
+        "socket" => {
+            let bus = SocketBus::connect(matches.value_of("socket").unwrap())?;
+            Box::new(SpiFlashTransport::new(bus, address_mode))
+        }
+        "file" => Box::new(FileTransport::open(
+            matches.value_of("tx").expect("--tx is required for the file transport"),
+            matches.value_of("rx").expect("--rx is required for the file transport"))?),
+        _ => unreachable!(),
+    })
+}
+
+fn open_client(matches: &ArgMatches) -> client::Client<Box<dyn client::Transport>> {
+    let transport = open_transport(matches).unwrap_or_else(|err| {
+        eprintln!("failed to open transport: {}", err);
+        exit(1);
+    });
+    let mut client = client::Client::new(
+        transport,
+        parse_number(matches.value_of("mailbox-address").unwrap()),
+        parse_number(matches.value_of("mailbox-size").unwrap()) as usize);
+    client.set_integrity(match matches.value_of("integrity").unwrap() {
+        "none" => None,
+        "Crc32" => Some(IntegrityMode::Crc32),
+        "Sha256" => Some(IntegrityMode::Sha256),
+        _ => unreachable!(),
+    });
+    client
+}
+
+fn capabilities(matches: &ArgMatches) {
+    let mut client = open_client(matches);
+    let response = client.capabilities().unwrap_or_else(|err| {
+        eprintln!("capabilities request failed: {}", err);
+        exit(1);
+    });
+
+    println!("protocol_version={}", response.protocol_version);
+    println!("mailbox_size={}", response.mailbox_size);
+    println!("max_chunk_length={}", response.max_chunk_length);
+    for value in 0..64u8 {
+        if let Some(content) = firmware::ContentType::from_wire_value(value) {
+            if response.supports_firmware(content) {
+                println!("firmware={}", content);
+            }
+        }
+    }
+}
+
+// Parses an argument of the form START:SIZE.
+fn parse_start_size(value: &str) -> (u32, u32) {
+    let mut parts = value.splitn(2, ':');
+    let start = parts.next().unwrap();
+    let size = parts.next().unwrap_or_else(|| {
+        eprintln!("invalid range, expected START:SIZE: {}", value);
+        exit(1);
+    });
+    (parse_number(start), parse_number(size))
+}
+
+// Parses a range argument of the form START:SIZE.
+fn parse_range(kind: write_protect::RangeKind, value: &str) -> write_protect::Range {
+    let (start, size) = parse_start_size(value);
+    write_protect::Range {
+        kind,
+        start,
+        size,
+    }
+}
+
+fn print_policy(policy: &write_protect::Policy) {
+    println!("locked={}", policy.locked);
+    println!("chip_erase={}", if policy.allows_chip_erase() { "allowed" } else { "denied" });
+    for range in policy.ranges() {
+        println!("range={} start=0x{:x} size=0x{:x}", range.kind, range.start, range.size);
+    }
+}
+
+fn write_protect_policy(matches: &ArgMatches) {
+    let mut client = open_client(matches);
+
+    if matches.is_present("set") {
+        let mut policy = write_protect::UNRESTRICTED_POLICY;
+        policy.deny_chip_erase = matches.is_present("deny-chip-erase");
+        policy.locked = matches.is_present("lock");
+        let ranges = [
+            ("read-only", write_protect::RangeKind::ReadOnly),
+            ("program-only", write_protect::RangeKind::ProgramOnly),
+        ];
+        for (name, kind) in ranges.iter() {
+            for value in matches.values_of(name).into_iter().flatten() {
+                if let Err(err) = policy.add_range(parse_range(*kind, value)) {
+                    eprintln!("invalid range {}: {:?}", value, err);
+                    exit(1);
+                }
+            }
+        }
+
+        let result = client.set_write_protect_policy(policy).unwrap_or_else(|err| {
+            eprintln!("set policy request failed: {}", err);
+            exit(1);
+        });
+        if result != write_protect::SetPolicyResult::Success {
+            eprintln!("set policy failed: {}", result);
+            exit(1);
+        }
+    }
+
+    let response = client.write_protect_policy().unwrap_or_else(|err| {
+        eprintln!("get policy request failed: {}", err);
+        exit(1);
+    });
+    print_policy(&response.policy);
+    println!("denied_count={}", response.denied_count);
+}
+
+// Parses a region argument of the form START:SIZE and measures it in `image`.
+fn parse_region(value: &str, image: &[u8]) -> measured_boot::Region {
+    let (start, size) = parse_start_size(value);
+    let end = start as usize + size as usize;
+    if end > image.len() {
+        eprintln!("region {} extends beyond the image ({} bytes)", value, image.len());
+        exit(1);
+    }
+    measured_boot::Region {
+        start,
+        size,
+        digest: Sha256::new().add(&image[start as usize..end]).finish(),
+    }
+}
+
+// Parses a recovery argument of the form SOURCE:DESTINATION:SIZE.
+fn parse_recovery(value: &str) -> measured_boot::Recovery {
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() != 3 {
+        eprintln!("invalid recovery, expected SOURCE:DESTINATION:SIZE: {}", value);
+        exit(1);
+    }
+    measured_boot::Recovery {
+        source: parse_number(parts[0]),
+        destination: parse_number(parts[1]),
+        size: parse_number(parts[2]),
+    }
+}
+
+fn print_manifest(manifest: &measured_boot::Manifest) {
+    println!("mode={}", manifest.mode);
+    println!("locked={}", manifest.locked);
+    let recovery = manifest.recovery();
+    if recovery.is_enabled() {
+        println!("recovery source=0x{:x} destination=0x{:x} size=0x{:x}",
+            recovery.source, recovery.destination, recovery.size);
+    }
+    for region in manifest.regions() {
+        let digest: String = region.digest.iter().map(|b| format!("{:02x}", b)).collect();
+        println!("region start=0x{:x} size=0x{:x} digest={}", region.start, region.size, digest);
+    }
+}
+
+fn measured_boot_manifest(matches: &ArgMatches) {
+    let mut client = open_client(matches);
+
+    if matches.is_present("set") {
+        let mut manifest = measured_boot::DISABLED_MANIFEST;
+        manifest.mode = match matches.value_of("mode").unwrap_or("Enforce") {
+            "Disabled" => measured_boot::VerifyMode::Disabled,
+            "LogOnly" => measured_boot::VerifyMode::LogOnly,
+            _ => measured_boot::VerifyMode::Enforce,
+        };
+        manifest.locked = matches.is_present("lock");
+        if let Some(value) = matches.value_of("recovery") {
+            if let Err(err) = manifest.set_recovery(parse_recovery(value)) {
+                eprintln!("invalid recovery {}: {:?}", value, err);
+                exit(1);
+            }
+        }
+        if let Some(image_file) = matches.value_of("image") {
+            let image = read_file(image_file);
+            for value in matches.values_of("region").into_iter().flatten() {
+                if let Err(err) = manifest.add_region(parse_region(value, &image)) {
+                    eprintln!("invalid region {}: {:?}", value, err);
+                    exit(1);
+                }
+            }
+        }
+
+        let result = client.set_measured_boot_manifest(manifest).unwrap_or_else(|err| {
+            eprintln!("set manifest request failed: {}", err);
+            exit(1);
+        });
+        if result != measured_boot::SetManifestResult::Success {
+            eprintln!("set manifest failed: {}", result);
+            exit(1);
+        }
+    }
+
+    let response = client.measured_boot_manifest().unwrap_or_else(|err| {
+        eprintln!("get manifest request failed: {}", err);
+        exit(1);
+    });
+    print_manifest(&response.manifest);
+    println!("result={}", response.result);
+    if response.failed_region != measured_boot::NO_FAILED_REGION {
+        println!("failed_region={}", response.failed_region);
+    }
+
+    let status = client.recovery_status().unwrap_or_else(|err| {
+        eprintln!("get recovery status request failed: {}", err);
+        exit(1);
+    });
+    println!("recovery_result={} attempts={} copied=0x{:x}/0x{:x}",
+        status.result, status.attempts, status.bytes_copied, status.bytes_total);
+}
+
+fn print_firmware_info(name: &str, entry: &firmware::FirmwareInfoEntry) {
+    let build_info = &entry.info.build_info;
+    match entry.status {
+        firmware::FirmwareInfoStatus::Valid => println!(
+            "{}={} epoch={} version={}.{} timestamp={}", name, entry.info.segment_and_location,
+            build_info.epoch, build_info.major, build_info.minor, build_info.timestamp),
+        status => println!("{}={} {}", name, entry.info.segment_and_location, status),
+    }
+}
+
+fn info(matches: &ArgMatches) {
+    let mut client = open_client(matches);
+    let response: firmware::FirmwareInfoResponse =
+        client.firmware(&firmware::FirmwareInfoRequest {}).unwrap_or_else(|err| {
+            eprintln!("firmware info request failed: {}", err);
+            exit(1);
+        });
+
+    print_firmware_info("active_ro", &response.active_ro);
+    print_firmware_info("active_rw", &response.active_rw);
+    print_firmware_info("inactive_ro", &response.inactive_ro);
+    print_firmware_info("inactive_rw", &response.inactive_rw);
+}
+
+fn pending_reboot(matches: &ArgMatches) {
+    let mut client = open_client(matches);
+    let action = if matches.is_present("cancel") {
+        firmware::PendingRebootAction::Cancel
+    } else {
+        firmware::PendingRebootAction::Query
+    };
+    let response: firmware::PendingRebootResponse =
+        client.firmware(&firmware::PendingRebootRequest { action }).unwrap_or_else(|err| {
+            eprintln!("pending reboot request failed: {}", err);
+            exit(1);
+        });
+
+    if !response.pending {
+        println!("pending=false");
+    } else if response.timeout_msecs == firmware::NO_REBOOT_TIMEOUT {
+        println!("pending=true timeout=none");
+    } else {
+        println!("pending=true timeout_msecs={}", response.timeout_msecs);
+    }
+    if response.pending && action == firmware::PendingRebootAction::Cancel {
+        println!("cancelled");
+    }
+}
+
+fn update(matches: &ArgMatches) {
+    let image = read_file(matches.value_of("image").unwrap());
+    let mut client = open_client(matches);
 
-    let mut read_buf_slice = read_buf.as_slice();
-    println!("read_buf_slice.len={}", read_buf_slice.len());
-    let header = payload::Header::from_wire(&mut read_buf_slice).expect("failed to read header");
+    let reboot_time = match matches.value_of("reboot").unwrap() {
+        "none" => None,
+        time => Some(time.parse().unwrap()),
+    };
+    let options = update::UpdateOptions {
+        segment: matches.value_of("segment").unwrap().parse().unwrap(),
+        max_retries: parse_number(matches.value_of("retries").unwrap()) as usize,
+        reboot_time,
+        verify: !matches.is_present("no-verify"),
+    };
+
+    if let Err(err) = update::update(&mut client, &image, &options) {
+        eprintln!("update failed: {}", err);
+        exit(1);
+    }
+}
+
+// Reads a single chunk and returns the offset, result and data of the response.
+fn read_chunk<T: client::Transport>(client: &mut client::Client<T>, request: &firmware::ReadChunkRequest)
+    -> client::ClientResult<(u32, firmware::ReadChunkResult, Vec<u8>)> {
+    let response = client.transact(payload::ContentType::Firmware, &client::encode_firmware(request)?)?;
+    let chunk: firmware::ReadChunkResponse =
+        client::decode_firmware(response.expect(payload::ContentType::Firmware)?)?;
+    Ok((chunk.offset, chunk.result, chunk.data.to_vec()))
+}
+
+fn read(matches: &ArgMatches) {
+    let segment: firmware::SegmentAndLocation = matches.value_of("segment").unwrap().parse().unwrap();
+    let offset = parse_number(matches.value_of("offset").unwrap());
+    let length = parse_number(matches.value_of("length").unwrap()) as usize;
+    let mut client = open_client(matches);
+
+    let mut data = Vec::with_capacity(length);
+    while data.len() < length {
+        let request = firmware::ReadChunkRequest {
+            segment_and_location: segment,
+            offset: offset + data.len() as u32,
+            length: min(length - data.len(), u16::MAX as usize) as u16,
+        };
+        let (response_offset, result, chunk) = read_chunk(&mut client, &request).unwrap_or_else(|err| {
+            eprintln!("read chunk request failed: {}", err);
+            exit(1);
+        });
+        if response_offset != request.offset {
+            eprintln!("unexpected response for offset 0x{:x}", request.offset);
+            exit(1);
+        }
+        if result != firmware::ReadChunkResult::Success {
+            eprintln!("read at offset 0x{:x} failed: {}", request.offset, result);
+            exit(1);
+        }
+        if chunk.is_empty() {
+            eprintln!("read at offset 0x{:x} returned no data", request.offset);
+            exit(1);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    write_file(matches.value_of("output").unwrap(), &data);
+}
 
-    match header.content {
-        payload::ContentType::Manticore => {
-            let mut stdwrite = StdWrite(&mut output);
-            stdwrite
-                .write_bytes(&mut &read_buf_slice[..header.content_len as usize])
-                .expect("failed to write payload");
+fn decode_trace(matches: &ArgMatches) {
+    let input_file = matches.value_of("input").unwrap();
+    let transactions = match matches.value_of("format").unwrap() {
+        "binary" => trace::parse_binary(&read_file(input_file)),
+        "csv" => {
+            let text = String::from_utf8(read_file(input_file)).unwrap_or_else(|_| {
+                eprintln!("{}: not a text file", input_file);
+                exit(1);
+            });
+            trace::parse_csv(&text)
         }
-        _ => {
-            panic!("Unsupported content type {:?}", header.content);
+        _ => unreachable!(),
+    }.unwrap_or_else(|err| {
+        eprintln!("{}: {}", input_file, err);
+        exit(1);
+    });
+
+    let address_mode = if matches.is_present("four-byte") {
+        AddressMode::FourByte
+    } else {
+        AddressMode::ThreeByte
+    };
+    let mut decoder = trace::Decoder::new(
+        address_mode,
+        parse_number(matches.value_of("mailbox-address").unwrap()),
+        parse_number(matches.value_of("mailbox-size").unwrap()));
+    for (idx, transaction) in transactions.iter().enumerate() {
+        let lines = decoder.decode(transaction);
+        println!("{:6}: {}", idx, lines[0]);
+        for line in &lines[1..] {
+            println!("{:6}  {}", "", line);
         }
     }
 }
 
+// Arguments for selecting and configuring the transport to the device.
+fn transport_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("transport")
+            .long("transport")
+            .help("transport to use for communicating with the device")
+            .possible_values(&["spidev", "socket", "file"])
+            .default_value("spidev")
+            .takes_value(true),
+        Arg::with_name("device")
+            .long("device")
+            .help("spidev device for the spidev transport")
+            .default_value("/dev/spidev0.0")
+            .takes_value(true),
+        Arg::with_name("speed")
+            .long("speed")
+            .help("SPI clock speed in Hz for the spidev transport")
+            .default_value("1000000")
+            .takes_value(true),
+        Arg::with_name("socket")
+            .long("socket")
+            .help("Unix socket of the otpilot emulator for the socket transport")
+            .default_value("/tmp/otpilot_emulator.sock")
+            .takes_value(true),
+        Arg::with_name("four-byte")
+            .long("four-byte")
+            .help("use 4-byte addressing for the spidev and socket transports"),
+        Arg::with_name("tx")
+            .long("tx")
+            .help("request output file or pipe for the file transport")
+            .takes_value(true),
+        Arg::with_name("rx")
+            .long("rx")
+            .help("response input file or pipe for the file transport")
+            .takes_value(true),
+        Arg::with_name("mailbox-address")
+            .long("mailbox-address")
+            .help("address of the SPI mailbox")
+            .default_value("0x80000")
+            .takes_value(true),
+        Arg::with_name("mailbox-size")
+            .long("mailbox-size")
+            .help("size of the SPI mailbox in bytes")
+            .default_value("512")
+            .takes_value(true),
+        Arg::with_name("integrity")
+            .long("integrity")
+            .help("integrity check to add to requests and expect on responses")
+            .possible_values(&["none", "Crc32", "Sha256"])
+            .default_value("none")
+            .takes_value(true),
+    ]
+}
+
 fn main() {
     let app = App::new("SPI Transport Tool")
         .version("0.1")
@@ -127,11 +719,20 @@ fn main() {
                         .help("output file for wrapped message")
                         .required(true)
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("content")
+                        .short("t")
+                        .long("content-type")
+                        .help("content type of the message")
+                        .possible_values(&["Error", "Manticore", "Firmware", "Capabilities", "WriteProtect", "MeasuredBoot"])
+                        .default_value("Manticore")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
             SubCommand::with_name("unwrap")
-                .about("Unwrap a message")
+                .about("Unwrap a message and verify its checksum")
                 .arg(
                     Arg::with_name("input")
                         .short("i")
@@ -148,6 +749,226 @@ fn main() {
                         .required(true)
                         .takes_value(true),
                 ),
+        )
+        .subcommand(
+            SubCommand::with_name("update")
+                .about("Write a firmware image to an inactive segment")
+                .arg(
+                    Arg::with_name("image")
+                        .long("image")
+                        .help("firmware image to write")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("segment")
+                        .long("segment")
+                        .help("segment and location to write the image to")
+                        .possible_values(&["RoA", "RoB", "RwA", "RwB"])
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("reboot")
+                        .long("reboot")
+                        .help("when to reboot after the update")
+                        .possible_values(&["Immediate", "Delayed", "none"])
+                        .default_value("Immediate")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("retries")
+                        .long("retries")
+                        .help("number of retries for a chunk that failed to compare")
+                        .default_value("3")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("no-verify")
+                        .long("no-verify")
+                        .help("skip verifying the digest of the written segment"),
+                )
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Read back data from a firmware segment")
+                .arg(
+                    Arg::with_name("segment")
+                        .long("segment")
+                        .help("segment and location to read from")
+                        .possible_values(&["RoA", "RoB", "RwA", "RwB"])
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("offset")
+                        .long("offset")
+                        .help("offset within the segment")
+                        .default_value("0")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("length")
+                        .long("length")
+                        .help("number of bytes to read")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("file to write the data to")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("pending-reboot")
+                .about("Query or cancel a delayed reboot")
+                .arg(
+                    Arg::with_name("cancel")
+                        .long("cancel")
+                        .help("cancel the pending reboot"),
+                )
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Query the build information of all firmware segments")
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("decode-trace")
+                .about("Decode a capture of SPI flash transactions")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .help("capture file with one SPI transaction per chip select")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("format of the capture file")
+                        .possible_values(&["binary", "csv"])
+                        .default_value("binary")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("four-byte")
+                        .long("four-byte")
+                        .help("assume 4-byte addressing at the start of the capture"),
+                )
+                .arg(
+                    Arg::with_name("mailbox-address")
+                        .long("mailbox-address")
+                        .help("address of the SPI mailbox")
+                        .default_value("0x80000")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("mailbox-size")
+                        .long("mailbox-size")
+                        .help("size of the SPI mailbox in bytes")
+                        .default_value("512")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("capabilities")
+                .about("Query the capabilities of the device")
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("write-protect")
+                .about("Query or provision the SPI flash write protection policy")
+                .arg(
+                    Arg::with_name("set")
+                        .long("set")
+                        .help("provision a policy built from the other options; requires provisioning to be enabled on the device console"),
+                )
+                .arg(
+                    Arg::with_name("read-only")
+                        .long("read-only")
+                        .help("range START:SIZE that denies PageProgram and erase")
+                        .requires("set")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("program-only")
+                        .long("program-only")
+                        .help("range START:SIZE that denies erase but allows PageProgram")
+                        .requires("set")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("deny-chip-erase")
+                        .long("deny-chip-erase")
+                        .help("deny ChipErase even without ranges")
+                        .requires("set"),
+                )
+                .arg(
+                    Arg::with_name("lock")
+                        .long("lock")
+                        .help("prevent the policy from being replaced")
+                        .requires("set"),
+                )
+                .args(&transport_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("measured-boot")
+                .about("Query or provision the measured boot manifest")
+                .arg(
+                    Arg::with_name("set")
+                        .long("set")
+                        .help("provision a manifest built from the other options; requires provisioning to be enabled on the device console"),
+                )
+                .arg(
+                    Arg::with_name("mode")
+                        .long("mode")
+                        .help("what to do if the SPI flash chip does not match (default: Enforce)")
+                        .requires("set")
+                        .possible_values(&["Disabled", "LogOnly", "Enforce"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("image")
+                        .long("image")
+                        .help("known-good SPI flash image to measure the regions in")
+                        .requires("set")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("region")
+                        .long("region")
+                        .help("region START:SIZE to measure")
+                        .requires("image")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("recovery")
+                        .long("recovery")
+                        .help("copy SOURCE:DESTINATION:SIZE to restore the image if verification fails")
+                        .requires("set")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("lock")
+                        .long("lock")
+                        .help("prevent the manifest from being replaced")
+                        .requires("set"),
+                )
+                .args(&transport_args()),
         );
     let matches = app.get_matches();
 
@@ -155,11 +976,28 @@ fn main() {
         wrap(
             matches.value_of("input").unwrap(),
             matches.value_of("output").unwrap(),
+            matches.value_of("content").unwrap().parse().unwrap(),
         );
     } else if let Some(matches) = matches.subcommand_matches("unwrap") {
         unwrap(
             matches.value_of("input").unwrap(),
             matches.value_of("output").unwrap(),
         );
+    } else if let Some(matches) = matches.subcommand_matches("update") {
+        update(matches);
+    } else if let Some(matches) = matches.subcommand_matches("read") {
+        read(matches);
+    } else if let Some(matches) = matches.subcommand_matches("pending-reboot") {
+        pending_reboot(matches);
+    } else if let Some(matches) = matches.subcommand_matches("info") {
+        info(matches);
+    } else if let Some(matches) = matches.subcommand_matches("decode-trace") {
+        decode_trace(matches);
+    } else if let Some(matches) = matches.subcommand_matches("capabilities") {
+        capabilities(matches);
+    } else if let Some(matches) = matches.subcommand_matches("write-protect") {
+        write_protect_policy(matches);
+    } else if let Some(matches) = matches.subcommand_matches("measured-boot") {
+        measured_boot_manifest(matches);
     }
 }
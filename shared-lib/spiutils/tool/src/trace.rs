@@ -0,0 +1,454 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decoder for captured SPI flash transactions.
+//!
+//! A capture is a sequence of transactions delimited by chip select. Two
+//! capture formats are supported:
+//!
+//! * A binary dump in which each transaction is a 32-bit little-endian
+//!   length, followed by that many MOSI bytes and then that many MISO bytes.
+//! * A CSV export of a logic analyzer's SPI decoder with one row per byte
+//!   and the columns `Packet ID`, `MOSI` and optionally `MISO`. Consecutive
+//!   rows with the same packet ID form a transaction.
+//!
+//! Writes to and reads from the mailbox are further decoded as payloads.
+
+use crate::describe_content;
+
+use spiutils::protocol::flash;
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::payload;
+use spiutils::protocol::wire::FromWire;
+use spiutils::protocol::wire::FromWireError;
+use spiutils::protocol::wire::WireEnum;
+
+use std::convert::TryInto;
+
+// The BUSY bit in the status register.
+const STATUS_BUSY: u8 = 0x01;
+
+// The WEL bit in the status register.
+const STATUS_WEL: u8 = 0x02;
+
+// The length of a JEDEC ID, in bytes.
+const JEDEC_ID_LEN: usize = 3;
+
+#[derive(Debug)]
+pub enum TraceError {
+    Truncated { offset: usize },
+    Csv { line: usize, reason: String },
+}
+
+impl core::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TraceError::Truncated { offset } =>
+                write!(f, "truncated transaction at offset 0x{:x}", offset),
+            TraceError::Csv { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+pub type TraceResult<T> = Result<T, TraceError>;
+
+/// A single SPI transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transaction {
+    /// The bytes sent by the host.
+    pub mosi: Vec<u8>,
+
+    /// The bytes sent by the device. Empty if they were not captured.
+    pub miso: Vec<u8>,
+}
+
+/// Parses a binary capture.
+pub fn parse_binary(mut data: &[u8]) -> TraceResult<Vec<Transaction>> {
+    let total_len = data.len();
+    let mut transactions = Vec::new();
+    while !data.is_empty() {
+        let offset = total_len - data.len();
+        if data.len() < 4 {
+            return Err(TraceError::Truncated { offset });
+        }
+        let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        data = &data[4..];
+        if data.len() / 2 < len {
+            return Err(TraceError::Truncated { offset });
+        }
+        transactions.push(Transaction {
+            mosi: data[..len].to_vec(),
+            miso: data[len..2 * len].to_vec(),
+        });
+        data = &data[2 * len..];
+    }
+    Ok(transactions)
+}
+
+// Parses a byte value from a CSV field, e.g. "0x9F" or "159".
+fn parse_csv_byte(field: &str) -> Option<u8> {
+    match field.strip_prefix("0x").or_else(|| field.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16).ok(),
+        None => field.parse().ok(),
+    }
+}
+
+/// Parses a CSV capture.
+pub fn parse_csv(text: &str) -> TraceResult<Vec<Transaction>> {
+    let mut lines = text.lines().enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let (header_line, header) = lines.next()
+        .ok_or(TraceError::Csv { line: 1, reason: "missing header".to_string() })?;
+    let columns: Vec<String> = header.split(',')
+        .map(|column| column.trim().trim_matches('"').to_ascii_lowercase())
+        .collect();
+    let find_column = |name: &str| columns.iter().position(|column| column == name);
+    let missing_column = |name: &str| TraceError::Csv {
+        line: header_line,
+        reason: format!("missing column \"{}\"", name),
+    };
+    let packet_column = find_column("packet id").ok_or_else(|| missing_column("Packet ID"))?;
+    let mosi_column = find_column("mosi").ok_or_else(|| missing_column("MOSI"))?;
+    let miso_column = find_column("miso");
+
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut current_packet = None;
+    for (line, row) in lines {
+        let fields: Vec<&str> = row.split(',')
+            .map(|field| field.trim().trim_matches('"'))
+            .collect();
+        let field = |column: usize| fields.get(column).copied().unwrap_or("");
+        let invalid_value = |column: usize| TraceError::Csv {
+            line,
+            reason: format!("invalid value \"{}\"", field(column)),
+        };
+
+        // Bytes without a packet ID were transferred while CS was deasserted.
+        let packet = field(packet_column);
+        if packet.is_empty() {
+            continue;
+        }
+        if current_packet != Some(packet) {
+            current_packet = Some(packet);
+            transactions.push(Transaction::default());
+        }
+        let transaction = transactions.last_mut().unwrap();
+
+        transaction.mosi.push(parse_csv_byte(field(mosi_column))
+            .ok_or_else(|| invalid_value(mosi_column))?);
+        if let Some(miso_column) = miso_column {
+            transaction.miso.push(parse_csv_byte(field(miso_column))
+                .ok_or_else(|| invalid_value(miso_column))?);
+        }
+    }
+    Ok(transactions)
+}
+
+// ----------------------------------------------------------------------------
+
+/// Decodes transactions into annotated SPI flash commands.
+pub struct Decoder {
+    address_mode: AddressMode,
+    mailbox_address: u32,
+    mailbox_size: u32,
+
+    // The mailbox contents read since the last mailbox write, starting at
+    // the mailbox address.
+    mailbox_read: Vec<u8>,
+}
+
+impl Decoder {
+    /// Creates a decoder that starts in `address_mode`.
+    pub fn new(address_mode: AddressMode, mailbox_address: u32, mailbox_size: u32) -> Self {
+        Self {
+            address_mode,
+            mailbox_address,
+            mailbox_size,
+            mailbox_read: Vec::new(),
+        }
+    }
+
+    fn is_mailbox_address(&self, address: u32) -> bool {
+        address >= self.mailbox_address && address - self.mailbox_address < self.mailbox_size
+    }
+
+    // Parses the SPI flash header and returns the op code, the address and the header length.
+    fn parse_header(&self, mosi: &[u8]) -> Result<(OpCode, Option<u32>, usize), FromWireError> {
+        // Some op codes use the same address length in both modes.
+        let address_mode = OpCode::from_wire_value(mosi[0])
+            .map_or(self.address_mode, |opcode| opcode.address_mode(self.address_mode));
+        let mut data = mosi;
+        let (opcode, address) = match address_mode {
+            AddressMode::ThreeByte => {
+                let header = flash::Header::<ux::u24>::from_wire(&mut data)?;
+                (header.opcode, header.get_address())
+            }
+            AddressMode::FourByte => {
+                let header = flash::Header::<u32>::from_wire(&mut data)?;
+                (header.opcode, header.get_address())
+            }
+        };
+        Ok((opcode, address, mosi.len() - data.len()))
+    }
+
+    /// Decodes a transaction and returns one line for the command and one
+    /// line for each message decoded from its data.
+    pub fn decode(&mut self, transaction: &Transaction) -> Vec<String> {
+        let mosi = &transaction.mosi;
+        if mosi.is_empty() {
+            return vec!["empty transaction".to_string()];
+        }
+
+        let (opcode, address, header_len) = match self.parse_header(mosi) {
+            Ok(header) => header,
+            Err(FromWireError::OutOfRange) => {
+                return vec![format!("unknown opcode 0x{:02x} len={}", mosi[0], mosi.len())];
+            }
+            Err(_) => {
+                return vec![format!("truncated opcode 0x{:02x} len={}", mosi[0], mosi.len())];
+            }
+        };
+        let mut lines = Vec::new();
+        let mut command = match address {
+            Some(address) => format!("{} addr=0x{:08x}", opcode, address),
+            None => format!("{}", opcode),
+        };
+        let data_len = mosi.len() - header_len;
+        if data_len > 0 {
+            command += &format!(" len={}", data_len);
+        }
+
+        match opcode {
+            OpCode::Enter4ByteAddressMode => {
+                self.address_mode = AddressMode::FourByte;
+                command += " -> 4B";
+            }
+            OpCode::Exit4ByteAddressMode => {
+                self.address_mode = AddressMode::ThreeByte;
+                command += " -> 3B";
+            }
+            OpCode::ReadStatusRegister => {
+                if let Some(status) = transaction.miso.get(header_len) {
+                    command += &format!(" status=0x{:02x}", status);
+                    if status & STATUS_BUSY != 0 {
+                        command += " BUSY";
+                    }
+                    if status & STATUS_WEL != 0 {
+                        command += " WEL";
+                    }
+                }
+            }
+            OpCode::ReadJedec => {
+                if let Some(id) = transaction.miso.get(header_len..header_len + JEDEC_ID_LEN) {
+                    command += &format!(" id={:02x}{:02x}{:02x}", id[0], id[1], id[2]);
+                }
+            }
+            OpCode::PageProgram | OpCode::PageProgram4B => {
+                if let Some(address) = address.filter(|&address| self.is_mailbox_address(address)) {
+                    command += " [mailbox]";
+                    self.mailbox_read.clear();
+                    if address == self.mailbox_address {
+                        lines.extend(describe_payload(&mosi[header_len..]));
+                    }
+                }
+            }
+            OpCode::NormalRead | OpCode::FastRead | OpCode::FastRead4B | OpCode::FastReadDualOutput
+                | OpCode::FastReadQuadOutput | OpCode::FastReadQuadIO
+                | OpCode::FastReadQuadOutput4B | OpCode::FastReadQuadIO4B => {
+                if let Some(address) = address.filter(|&address| self.is_mailbox_address(address)) {
+                    command += " [mailbox]";
+                    let data = transaction.miso.get(header_len..).unwrap_or(&[]);
+                    lines.extend(self.add_mailbox_read(address - self.mailbox_address, data));
+                }
+            }
+            _ => {}
+        }
+
+        lines.insert(0, command);
+        lines
+    }
+
+    // Adds data read at `offset` within the mailbox to the mailbox contents
+    // and decodes the response once it is complete.
+    fn add_mailbox_read(&mut self, offset: u32, data: &[u8]) -> Vec<String> {
+        let offset = offset as usize;
+        if offset == 0 {
+            self.mailbox_read.clear();
+        } else if offset != self.mailbox_read.len() {
+            // A read that does not continue the previous one.
+            return Vec::new();
+        }
+        self.mailbox_read.extend_from_slice(data);
+
+        let mut content = &self.mailbox_read[..];
+        match payload::Header::from_wire(&mut content) {
+            Ok(header) if header.content_len as usize <= content.len() => {
+                let lines = describe_payload(&self.mailbox_read);
+                self.mailbox_read.clear();
+                lines
+            }
+            Ok(_) => Vec::new(),
+            Err(_) if self.mailbox_read.len() >= payload::HEADER_LEN => {
+                self.mailbox_read.clear();
+                vec!["malformed payload header".to_string()]
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+// Describes a payload and its content with one line per message.
+fn describe_payload(data: &[u8]) -> Vec<String> {
+    let mut content = data;
+    let header = match payload::Header::from_wire(&mut content) {
+        Ok(header) => header,
+        Err(_) => return vec!["malformed payload header".to_string()],
+    };
+    let mut lines = vec![format!("payload content={} content_len={} checksum=0x{:02x}",
+        header.content, header.content_len, header.checksum)];
+    if header.content_len as usize > content.len() {
+        lines.push(format!("truncated payload: {} of {} bytes", content.len(), header.content_len));
+        return lines;
+    }
+
+    let content = &content[..header.content_len as usize];
+    let checksum = payload::compute_checksum(&header, content);
+    if header.checksum != checksum {
+        lines.push(format!("bad checksum, expected 0x{:02x}", checksum));
+    }
+    match describe_content(header.content, content) {
+        Ok(content_lines) => lines.extend(content_lines),
+        Err(err) => lines.push(err),
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use spiutils::client;
+    use spiutils::protocol::firmware;
+    use spiutils::protocol::payload::ContentType;
+
+    const MAILBOX_ADDRESS: u32 = 0x80000;
+    const MAILBOX_SIZE: u32 = 512;
+
+    fn transaction(mosi: &[u8], miso: &[u8]) -> Transaction {
+        Transaction {
+            mosi: mosi.to_vec(),
+            miso: miso.to_vec(),
+        }
+    }
+
+    // Returns a read transaction at `address` whose MISO bytes contain `data`.
+    fn read(address: &[u8], data: &[u8]) -> Transaction {
+        let mut mosi = vec![OpCode::NormalRead as u8];
+        mosi.extend_from_slice(address);
+        let mut miso = vec![0xff; mosi.len()];
+        miso.extend_from_slice(data);
+        mosi.resize(miso.len(), 0xff);
+        Transaction { mosi, miso }
+    }
+
+    #[test]
+    fn binary() {
+        let data = [
+            1, 0, 0, 0, 0x06, 0xff,
+            2, 0, 0, 0, 0x05, 0xff, 0xff, 0x03,
+        ];
+        assert_eq!(parse_binary(&data).unwrap(), vec![
+            transaction(&[0x06], &[0xff]),
+            transaction(&[0x05, 0xff], &[0xff, 0x03]),
+        ]);
+        assert!(matches!(parse_binary(&data[..data.len() - 1]),
+            Err(TraceError::Truncated { offset: 6 })));
+    }
+
+    #[test]
+    fn csv() {
+        let text = "Time [s],Packet ID,MOSI,MISO\n\
+            0.1,0,0x06,0xFF\n\
+            0.2,,0x00,0x00\n\
+            0.3,1,0x05,0xFF\n\
+            0.4,1,0xFF,0x03\n";
+        assert_eq!(parse_csv(text).unwrap(), vec![
+            transaction(&[0x06], &[0xff]),
+            transaction(&[0x05, 0xff], &[0xff, 0x03]),
+        ]);
+
+        assert!(matches!(parse_csv("Time [s],MOSI\n"), Err(TraceError::Csv { line: 1, .. })));
+        assert!(matches!(parse_csv("Packet ID,MOSI\n0,0x100\n"), Err(TraceError::Csv { line: 2, .. })));
+    }
+
+    #[test]
+    fn address_mode() {
+        let mut decoder = Decoder::new(AddressMode::ThreeByte, MAILBOX_ADDRESS, MAILBOX_SIZE);
+        assert_eq!(decoder.decode(&transaction(&[0x03, 0x01, 0x02, 0x03, 0xff], &[])),
+            vec!["NormalRead addr=0x00010203 len=1"]);
+        assert_eq!(decoder.decode(&transaction(&[0xb7], &[])),
+            vec!["Enter4ByteAddressMode -> 4B"]);
+        assert_eq!(decoder.decode(&transaction(&[0x03, 0x01, 0x02, 0x03, 0x04], &[])),
+            vec!["NormalRead addr=0x01020304"]);
+        assert_eq!(decoder.decode(&transaction(&[0xe9], &[])),
+            vec!["Exit4ByteAddressMode -> 3B"]);
+        assert_eq!(decoder.decode(&transaction(&[0x12, 0x01, 0x02, 0x03, 0x04, 0x00], &[])),
+            vec!["PageProgram4B addr=0x01020304 len=1"]);
+        assert_eq!(decoder.decode(&transaction(&[0xeb, 0x01, 0x02, 0x03, 0xff, 0xff, 0xff, 0x00], &[])),
+            vec!["FastReadQuadIO addr=0x00010203 len=1"]);
+        assert_eq!(decoder.decode(&transaction(&[0x05, 0xff], &[0xff, 0x03])),
+            vec!["ReadStatusRegister len=1 status=0x03 BUSY WEL"]);
+        assert_eq!(decoder.decode(&transaction(&[0x42], &[])),
+            vec!["unknown opcode 0x42 len=1"]);
+    }
+
+    #[test]
+    fn mailbox() {
+        let mut decoder = Decoder::new(AddressMode::ThreeByte, MAILBOX_ADDRESS, MAILBOX_SIZE);
+
+        let request = client::encode_firmware(&firmware::RebootRequest {
+            time: firmware::RebootTime::Delayed,
+        }).unwrap();
+        let mut mosi = vec![OpCode::PageProgram as u8, 0x08, 0x00, 0x00];
+        mosi.extend_from_slice(&client::wrap(ContentType::Firmware, &request).unwrap());
+        let lines = decoder.decode(&transaction(&mosi, &[]));
+        assert_eq!(lines[0], format!("PageProgram addr=0x00080000 len={} [mailbox]", mosi.len() - 4));
+        assert!(lines[1].starts_with("payload content=Firmware"));
+        assert_eq!(lines[2], "firmware=RebootRequest RebootRequest { time: Delayed }");
+
+        // The response is read in two parts, as done by the client.
+        let response = client::encode_firmware(&firmware::RebootResponse {
+            time: firmware::RebootTime::Delayed,
+            result: firmware::RebootResult::Success,
+        }).unwrap();
+        let response = client::wrap(ContentType::Firmware, &response).unwrap();
+        let lines = decoder.decode(&read(&[0x08, 0x00, 0x00], &response[..payload::HEADER_LEN]));
+        assert_eq!(lines, vec!["NormalRead addr=0x00080000 len=4 [mailbox]"]);
+        let lines = decoder.decode(&read(&[0x08, 0x00, 0x04], &response[payload::HEADER_LEN..]));
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("payload content=Firmware"));
+        assert!(lines[2].starts_with("firmware=RebootResponse"));
+
+        // A corrupted response is still decoded, but flagged.
+        let mut corrupted = response.clone();
+        corrupted[payload::HEADER_LEN + 1] ^= 0x01;
+        let lines = decoder.decode(&read(&[0x08, 0x00, 0x00], &corrupted));
+        assert!(lines[2].starts_with("bad checksum"));
+    }
+}
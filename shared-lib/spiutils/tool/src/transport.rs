@@ -0,0 +1,263 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transports for the SPI mailbox client.
+
+use core::convert::TryFrom;
+
+use spiutils::client::Transport;
+use spiutils::io::Cursor;
+use spiutils::protocol::flash;
+use spiutils::protocol::flash::Address;
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::flash::OpCode;
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+// The BUSY bit in the status register.
+const STATUS_BUSY: u8 = 0x01;
+
+// Serializes an SPI flash header for `opcode` and `address`.
+fn flash_header(opcode: OpCode, address: Option<u32>, address_mode: AddressMode) -> io::Result<Vec<u8>> {
+    fn to_vec<A: Address>(opcode: OpCode, address: Option<u32>) -> io::Result<Vec<u8>> {
+        let address = match address {
+            Some(val) => Some(<A as Address>::try_from(val)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "address out of range"))?),
+            None => None,
+        };
+        let header = flash::Header::<A> {
+            opcode,
+            address,
+        };
+        let mut buf = [0u8; 16];
+        let mut cursor = Cursor::new(&mut buf);
+        header.to_wire(&mut cursor)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", err)))?;
+        Ok(cursor.consumed_bytes().to_vec())
+    }
+
+    match address_mode {
+        AddressMode::ThreeByte => to_vec::<ux::u24>(opcode, address),
+        AddressMode::FourByte => to_vec::<u32>(opcode, address),
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A full-duplex SPI bus.
+pub trait SpiBus {
+    /// Sends `tx` and returns the bytes received at the same time.
+    fn transfer(&mut self, tx: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// A transport that talks to the mailbox with SPI flash commands.
+pub struct SpiFlashTransport<B: SpiBus> {
+    bus: B,
+    address_mode: AddressMode,
+}
+
+impl<B: SpiBus> SpiFlashTransport<B> {
+    /// Creates a transport on `bus` that uses `address_mode` for addresses.
+    pub fn new(bus: B, address_mode: AddressMode) -> Self {
+        Self {
+            bus,
+            address_mode,
+        }
+    }
+}
+
+impl<B: SpiBus> Transport for SpiFlashTransport<B> {
+    fn write(&mut self, address: u32, data: &[u8]) -> io::Result<()> {
+        self.bus.transfer(&flash_header(OpCode::WriteEnable, None, self.address_mode)?)?;
+
+        let mut tx = flash_header(OpCode::PageProgram, Some(address), self.address_mode)?;
+        tx.extend_from_slice(data);
+        self.bus.transfer(&tx)?;
+        Ok(())
+    }
+
+    fn is_busy(&mut self) -> io::Result<bool> {
+        let mut tx = flash_header(OpCode::ReadStatusRegister, None, self.address_mode)?;
+        tx.push(0xff);
+        let rx = self.bus.transfer(&tx)?;
+        Ok(rx[rx.len() - 1] & STATUS_BUSY != 0)
+    }
+
+    fn read(&mut self, address: u32, buf: &mut [u8]) -> io::Result<()> {
+        let mut tx = flash_header(OpCode::NormalRead, Some(address), self.address_mode)?;
+        let header_len = tx.len();
+        tx.resize(header_len + buf.len(), 0xff);
+        let rx = self.bus.transfer(&tx)?;
+        if rx.len() != tx.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "short SPI transfer"));
+        }
+        buf.copy_from_slice(&rx[header_len..]);
+        Ok(())
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// Mirrors `struct spi_ioc_transfer` from <linux/spi/spidev.h>.
+#[repr(C)]
+#[derive(Default)]
+struct SpiIocTransfer {
+    tx_buf: u64,
+    rx_buf: u64,
+    len: u32,
+    speed_hz: u32,
+    delay_usecs: u16,
+    bits_per_word: u8,
+    cs_change: u8,
+    tx_nbits: u8,
+    rx_nbits: u8,
+    word_delay_usecs: u8,
+    pad: u8,
+}
+
+// SPI_IOC_MESSAGE(1) = _IOW(SPI_IOC_MAGIC, 0, char[sizeof(struct spi_ioc_transfer)])
+const SPI_IOC_MESSAGE_1: libc::c_ulong = (1 << 30)
+    | ((core::mem::size_of::<SpiIocTransfer>() as libc::c_ulong) << 16)
+    | ((b'k' as libc::c_ulong) << 8);
+
+/// An SPI bus using a Linux spidev device.
+pub struct SpidevBus {
+    device: File,
+    speed_hz: u32,
+}
+
+impl SpidevBus {
+    /// Opens the spidev device at `path`.
+    pub fn open(path: &str, speed_hz: u32) -> io::Result<Self> {
+        let device = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+        Ok(Self {
+            device,
+            speed_hz,
+        })
+    }
+}
+
+impl SpiBus for SpidevBus {
+    fn transfer(&mut self, tx: &[u8]) -> io::Result<Vec<u8>> {
+        let mut rx = vec![0u8; tx.len()];
+        let transfer = SpiIocTransfer {
+            tx_buf: tx.as_ptr() as u64,
+            rx_buf: rx.as_mut_ptr() as u64,
+            len: <u32 as TryFrom<usize>>::try_from(tx.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "transfer too long"))?,
+            speed_hz: self.speed_hz,
+            bits_per_word: 8,
+            ..Default::default()
+        };
+        let result = unsafe {
+            libc::ioctl(self.device.as_raw_fd(), SPI_IOC_MESSAGE_1 as _, &transfer)
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(rx)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// An SPI bus connected to the otpilot emulator via a Unix socket.
+///
+/// Each transfer is sent as a 32-bit little-endian length followed by the
+/// transmitted bytes. The emulator replies in the same format with the
+/// received bytes.
+pub struct SocketBus {
+    stream: UnixStream,
+}
+
+impl SocketBus {
+    /// Connects to the emulator listening at `path`.
+    pub fn connect(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            stream: UnixStream::connect(path)?,
+        })
+    }
+}
+
+impl SpiBus for SocketBus {
+    fn transfer(&mut self, tx: &[u8]) -> io::Result<Vec<u8>> {
+        let len = <u32 as TryFrom<usize>>::try_from(tx.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "transfer too long"))?;
+        self.stream.write_all(&len.to_le_bytes())?;
+        self.stream.write_all(tx)?;
+
+        let mut len_buf = [0u8; 4];
+        self.stream.read_exact(&mut len_buf)?;
+        let mut rx = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+        self.stream.read_exact(&mut rx)?;
+        Ok(rx)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A transport that writes requests to one file and reads responses from
+/// another.
+///
+/// Each request is written as a complete payload. Responses are read
+/// sequentially, so the response file must contain complete payloads. This
+/// works with regular files as well as named pipes.
+pub struct FileTransport {
+    tx: File,
+    rx: File,
+}
+
+impl FileTransport {
+    /// Opens the request file `tx_path` and the response file `rx_path`.
+    pub fn open(tx_path: &str, rx_path: &str) -> io::Result<Self> {
+        let tx = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(tx_path)?;
+        let rx = OpenOptions::new()
+            .read(true)
+            .open(rx_path)?;
+        Ok(Self {
+            tx,
+            rx,
+        })
+    }
+}
+
+impl Transport for FileTransport {
+    fn write(&mut self, _address: u32, data: &[u8]) -> io::Result<()> {
+        self.tx.write_all(data)?;
+        self.tx.flush()
+    }
+
+    fn is_busy(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    fn read(&mut self, _address: u32, buf: &mut [u8]) -> io::Result<()> {
+        self.rx.read_exact(buf)
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Firmware update over the SPI mailbox.
+
+use core::cmp::min;
+
+use spiutils::client::Client;
+use spiutils::client::ClientError;
+use spiutils::client::Transport;
+use spiutils::protocol::firmware;
+use spiutils::protocol::firmware::SegmentAndLocation;
+use spiutils::protocol::integrity::Sha256;
+
+// Flash writes on the device must be a multiple of this many bytes.
+const WRITE_ALIGNMENT: usize = 4;
+
+// The value of erased flash, used for padding the last chunk.
+const ERASED_VALUE: u8 = 0xff;
+
+#[derive(Debug)]
+pub enum UpdateError {
+    Client(ClientError),
+    SegmentNotInactive(SegmentAndLocation),
+    ImageTooLarge { image_len: usize, segment_size: u32 },
+    ChunkLengthTooSmall(usize),
+    Prepare(firmware::UpdatePrepareResult),
+    WriteChunk { offset: u32, result: firmware::WriteChunkResult },
+    UnexpectedResponse,
+    Verify(firmware::VerifySegmentResult),
+    Reboot(firmware::RebootResult),
+}
+
+impl From<ClientError> for UpdateError {
+    fn from(err: ClientError) -> Self {
+        UpdateError::Client(err)
+    }
+}
+
+impl core::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            UpdateError::Client(err) => write!(f, "{}", err),
+            UpdateError::SegmentNotInactive(segment) =>
+                write!(f, "segment {} is not inactive", segment),
+            UpdateError::ImageTooLarge { image_len, segment_size } =>
+                write!(f, "image of 0x{:x} bytes exceeds segment size 0x{:x}", image_len, segment_size),
+            UpdateError::ChunkLengthTooSmall(len) =>
+                write!(f, "max chunk length {} is too small", len),
+            UpdateError::Prepare(result) => write!(f, "update prepare failed: {}", result),
+            UpdateError::WriteChunk { offset, result } =>
+                write!(f, "write chunk at offset 0x{:x} failed: {}", offset, result),
+            UpdateError::UnexpectedResponse => write!(f, "unexpected response"),
+            UpdateError::Verify(result) => write!(f, "verify segment failed: {}", result),
+            UpdateError::Reboot(result) => write!(f, "reboot failed: {}", result),
+        }
+    }
+}
+
+pub type UpdateResult<T> = Result<T, UpdateError>;
+
+pub struct UpdateOptions {
+    /// The segment and location to write the image to.
+    pub segment: SegmentAndLocation,
+
+    /// How often to retry a chunk that failed the post-write compare.
+    pub max_retries: usize,
+
+    /// When to reboot after the update. No reboot is requested if `None`.
+    pub reboot_time: Option<firmware::RebootTime>,
+
+    /// Whether to compare the digest of the written segment against the image.
+    pub verify: bool,
+}
+
+// Returns the size of the specified segment iff it is inactive.
+fn get_inactive_segment_size<T: Transport>(client: &mut Client<T>, segment: SegmentAndLocation) -> UpdateResult<u32> {
+    let info: firmware::InactiveSegmentsInfoResponse =
+        client.firmware(&firmware::InactiveSegmentsInfoRequest {})?;
+    if info.ro.identifier == segment {
+        Ok(info.ro.size)
+    } else if info.rw.identifier == segment {
+        Ok(info.rw.size)
+    } else {
+        Err(UpdateError::SegmentNotInactive(segment))
+    }
+}
+
+fn prepare<T: Transport>(client: &mut Client<T>, segment: SegmentAndLocation) -> UpdateResult<usize> {
+    let request = firmware::UpdatePrepareRequest {
+        segment_and_location: segment,
+    };
+    let response: firmware::UpdatePrepareResponse = client.firmware(&request)?;
+    if response.segment_and_location != segment {
+        return Err(UpdateError::UnexpectedResponse);
+    }
+    if response.result != firmware::UpdatePrepareResult::Success {
+        return Err(UpdateError::Prepare(response.result));
+    }
+
+    // The chunk must also fit into a single mailbox transaction.
+    let max_content_len = client.max_content_len()
+        .saturating_sub(firmware::HEADER_LEN + firmware::WRITE_CHUNK_REQUEST_LEN);
+    let chunk_len = min(response.max_chunk_length as usize, max_content_len);
+    let chunk_len = chunk_len - chunk_len % WRITE_ALIGNMENT;
+    if chunk_len == 0 {
+        return Err(UpdateError::ChunkLengthTooSmall(response.max_chunk_length as usize));
+    }
+    Ok(chunk_len)
+}
+
+fn write_chunk<T: Transport>(client: &mut Client<T>, segment: SegmentAndLocation, offset: u32,
+    data: &[u8], max_retries: usize) -> UpdateResult<()> {
+    let request = firmware::WriteChunkRequest {
+        segment_and_location: segment,
+        offset,
+        data,
+    };
+
+    let mut attempt = 0;
+    loop {
+        let response: firmware::WriteChunkResponse = client.firmware(&request)?;
+        if response.segment_and_location != segment || response.offset != offset {
+            return Err(UpdateError::UnexpectedResponse);
+        }
+        match response.result {
+            firmware::WriteChunkResult::Success => return Ok(()),
+            firmware::WriteChunkResult::CompareFailed if attempt < max_retries => {
+                attempt += 1;
+                eprintln!("compare failed at offset 0x{:x}, retrying ({}/{})",
+                    offset, attempt, max_retries);
+            }
+            result => return Err(UpdateError::WriteChunk { offset, result }),
+        }
+    }
+}
+
+fn verify<T: Transport>(client: &mut Client<T>, segment: SegmentAndLocation, image: &[u8]) -> UpdateResult<()> {
+    let request = firmware::VerifySegmentRequest {
+        segment_and_location: segment,
+        length: image.len() as u32,
+        digest: Sha256::new().add(image).finish(),
+    };
+    let response: firmware::VerifySegmentResponse = client.firmware(&request)?;
+    if response.segment_and_location != segment || response.length != request.length {
+        return Err(UpdateError::UnexpectedResponse);
+    }
+    if response.result != firmware::VerifySegmentResult::Match {
+        return Err(UpdateError::Verify(response.result));
+    }
+    Ok(())
+}
+
+fn reboot<T: Transport>(client: &mut Client<T>, time: firmware::RebootTime) -> UpdateResult<()> {
+    let response: firmware::RebootResponse =
+        client.firmware(&firmware::RebootRequest { time })?;
+    if response.time != time {
+        return Err(UpdateError::UnexpectedResponse);
+    }
+    if response.result != firmware::RebootResult::Success {
+        return Err(UpdateError::Reboot(response.result));
+    }
+    Ok(())
+}
+
+/// Writes `image` to the device and optionally requests a reboot.
+pub fn update<T: Transport>(client: &mut Client<T>, image: &[u8], options: &UpdateOptions) -> UpdateResult<()> {
+    let segment_size = get_inactive_segment_size(client, options.segment)?;
+    if image.len() > segment_size as usize {
+        return Err(UpdateError::ImageTooLarge { image_len: image.len(), segment_size });
+    }
+
+    println!("Preparing {} for update", options.segment);
+    let chunk_len = prepare(client, options.segment)?;
+
+    let mut chunk = Vec::with_capacity(chunk_len);
+    for (idx, data) in image.chunks(chunk_len).enumerate() {
+        let offset = (idx * chunk_len) as u32;
+
+        chunk.clear();
+        chunk.extend_from_slice(data);
+        while chunk.len() % WRITE_ALIGNMENT != 0 {
+            chunk.push(ERASED_VALUE);
+        }
+
+        write_chunk(client, options.segment, offset, &chunk, options.max_retries)?;
+        println!("Wrote 0x{:x}/0x{:x} bytes", offset as usize + data.len(), image.len());
+    }
+
+    if options.verify {
+        println!("Verifying {}", options.segment);
+        verify(client, options.segment, image)?;
+    }
+
+    if let Some(time) = options.reboot_time {
+        println!("Requesting {} reboot", time);
+        reboot(client, time)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use spiutils::client;
+    use spiutils::client::LoopbackTransport;
+    use spiutils::driver::firmware::SegmentInfo;
+    use spiutils::protocol::payload::ContentType;
+    use spiutils::protocol::wire::FromWire;
+
+    const SEGMENT_SIZE: u32 = 0x1000;
+
+    // A minimal device model that fails the first compare at `fail_offset`
+    // and silently corrupts the byte at `corrupt_offset` when it is written.
+    struct Device {
+        image: Vec<u8>,
+        fail_offset: Option<u32>,
+        corrupt_offset: Option<usize>,
+    }
+
+    impl Device {
+        fn handle(&mut self, request: &[u8]) -> Vec<u8> {
+            let (_, data) = client::unwrap(request).unwrap();
+            let mut data = data;
+            let header = firmware::Header::from_wire(&mut data).unwrap();
+            let response = match header.content {
+                firmware::ContentType::InactiveSegmentsInfoRequest => {
+                    let segment = |identifier| SegmentInfo {
+                        identifier,
+                        address: 0,
+                        size: SEGMENT_SIZE,
+                        start_page: 0,
+                        page_count: 0,
+                    };
+                    client::encode_firmware(&firmware::InactiveSegmentsInfoResponse {
+                        ro: segment(SegmentAndLocation::RoB),
+                        rw: segment(SegmentAndLocation::RwB),
+                    })
+                }
+                firmware::ContentType::UpdatePrepareRequest => {
+                    let req = firmware::UpdatePrepareRequest::from_wire(&mut data).unwrap();
+                    client::encode_firmware(&firmware::UpdatePrepareResponse {
+                        segment_and_location: req.segment_and_location,
+                        max_chunk_length: 128,
+                        result: firmware::UpdatePrepareResult::Success,
+                    })
+                }
+                firmware::ContentType::WriteChunkRequest => {
+                    let req = firmware::WriteChunkRequest::from_wire(&mut data).unwrap();
+                    let result = if self.fail_offset == Some(req.offset) {
+                        self.fail_offset = None;
+                        firmware::WriteChunkResult::CompareFailed
+                    } else {
+                        let start = req.offset as usize;
+                        self.image[start..start + req.data.len()].copy_from_slice(req.data);
+                        if let Some(offset) = self.corrupt_offset {
+                            if (start..start + req.data.len()).contains(&offset) {
+                                self.image[offset] ^= 0x01;
+                            }
+                        }
+                        firmware::WriteChunkResult::Success
+                    };
+                    client::encode_firmware(&firmware::WriteChunkResponse {
+                        segment_and_location: req.segment_and_location,
+                        offset: req.offset,
+                        result,
+                    })
+                }
+                firmware::ContentType::VerifySegmentRequest => {
+                    let req = firmware::VerifySegmentRequest::from_wire(&mut data).unwrap();
+                    let digest = Sha256::new().add(&self.image[..req.length as usize]).finish();
+                    let result = if digest == req.digest {
+                        firmware::VerifySegmentResult::Match
+                    } else {
+                        firmware::VerifySegmentResult::Mismatch
+                    };
+                    client::encode_firmware(&firmware::VerifySegmentResponse {
+                        segment_and_location: req.segment_and_location,
+                        length: req.length,
+                        result,
+                        digest,
+                    })
+                }
+                firmware::ContentType::RebootRequest => {
+                    let req = firmware::RebootRequest::from_wire(&mut data).unwrap();
+                    client::encode_firmware(&firmware::RebootResponse {
+                        time: req.time,
+                        result: firmware::RebootResult::Success,
+                    })
+                }
+                _ => panic!("unexpected request {:?}", header.content),
+            };
+            client::wrap(ContentType::Firmware, &response.unwrap()).unwrap()
+        }
+    }
+
+    #[test]
+    fn update_with_retry() {
+        let image: Vec<u8> = (0..301u32).map(|val| val as u8).collect();
+        let mut device = Device {
+            image: vec![ERASED_VALUE; SEGMENT_SIZE as usize],
+            fail_offset: Some(128),
+            corrupt_offset: None,
+        };
+
+        {
+            let transport = LoopbackTransport::new(|request: &[u8]| device.handle(request));
+            let mut client = Client::new(transport, 0x80000, 512);
+            let options = UpdateOptions {
+                segment: SegmentAndLocation::RwB,
+                max_retries: 1,
+                reboot_time: Some(firmware::RebootTime::Immediate),
+                verify: true,
+            };
+            update(&mut client, &image, &options).unwrap();
+        }
+
+        assert_eq!(&device.image[..image.len()], &image[..]);
+        assert_eq!(device.image[image.len()], ERASED_VALUE);
+        assert_eq!(device.fail_offset, None);
+    }
+
+    #[test]
+    fn image_too_large() {
+        let image = vec![0u8; SEGMENT_SIZE as usize + 1];
+        let mut device = Device {
+            image: vec![ERASED_VALUE; SEGMENT_SIZE as usize],
+            fail_offset: None,
+            corrupt_offset: None,
+        };
+
+        let transport = LoopbackTransport::new(|request: &[u8]| device.handle(request));
+        let mut client = Client::new(transport, 0x80000, 512);
+        let options = UpdateOptions {
+            segment: SegmentAndLocation::RwB,
+            max_retries: 0,
+            reboot_time: None,
+            verify: true,
+        };
+        match update(&mut client, &image, &options) {
+            Err(UpdateError::ImageTooLarge { .. }) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_mismatch() {
+        let image = vec![0x5au8; 256];
+        let mut device = Device {
+            image: vec![ERASED_VALUE; SEGMENT_SIZE as usize],
+            fail_offset: None,
+            corrupt_offset: Some(200),
+        };
+
+        let transport = LoopbackTransport::new(|request: &[u8]| device.handle(request));
+        let mut client = Client::new(transport, 0x80000, 512);
+        let options = UpdateOptions {
+            segment: SegmentAndLocation::RwB,
+            max_retries: 0,
+            reboot_time: None,
+            verify: true,
+        };
+        match update(&mut client, &image, &options) {
+            Err(UpdateError::Verify(firmware::VerifySegmentResult::Mismatch)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
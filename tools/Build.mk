@@ -13,14 +13,14 @@
 # limitations under the License.
 
 .PHONY: tools/build
-tools/build: cargo_version_check sandbox_setup
+tools/build: cargo_version_check sandbox_setup tools/submodule_check
 	cd tools && $(BWRAP) cargo build --offline --release
 
 .PHONY: tools/build-signed
 tools/build-signed: tools/build
 
 .PHONY: tools/check
-tools/check: cargo_version_check sandbox_setup
+tools/check: cargo_version_check sandbox_setup tools/submodule_check
 	cd tools && $(BWRAP) cargo check --offline --release
 
 .PHONY: tools/clean
@@ -31,9 +31,22 @@ tools/clean:
 tools/devicetests:
 
 .PHONY: tools/doc
-tools/doc: cargo_version_check sandbox_setup
+tools/doc: cargo_version_check sandbox_setup tools/submodule_check
 	cd tools && $(BWRAP) cargo doc --offline --release
 
 .PHONY: tools/localtests
-tools/localtests: cargo_version_check sandbox_setup
+tools/localtests: cargo_version_check sandbox_setup tools/submodule_check
 	cd tools && $(BWRAP) cargo test --offline --release
+
+# otpilot_emulator compiles the otpilot sources, which depend on manticore.
+# Cargo cannot even load the tools workspace without the submodule, so fail
+# early with a hint instead.
+.PHONY: tools/submodule_check
+tools/submodule_check:
+	@if [ ! -f third_party/manticore/Cargo.toml ] ; \
+		then echo "#######################################################################"; \
+		     echo "# third_party/manticore is missing. otpilot_emulator needs it:"; \
+		     echo "#   git submodule update --init third_party/manticore"; \
+		     echo "#######################################################################"; \
+		     exit 1; \
+		fi
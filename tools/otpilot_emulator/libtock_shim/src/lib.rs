@@ -0,0 +1,34 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The subset of the libtock API that the otpilot sources shared with the
+//! emulator depend on, implemented on top of std.
+
+pub use std::println;
+
+pub mod result {
+    /// Errors reported by the emulated drivers.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum TockError {
+        /// Data did not have the expected format.
+        Format,
+
+        /// The emulated driver does not support the operation.
+        Other,
+    }
+
+    pub type TockResult<T> = Result<T, TockError>;
+}
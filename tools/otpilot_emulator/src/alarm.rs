@@ -0,0 +1,67 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Replacement for the Tock alarm driver based on the host clock.
+//!
+//! Only the clock is emulated. The emulator checks for a timed out reboot
+//! between transactions instead of waiting for an alarm.
+
+use libtock::result::TockResult;
+
+use std::time::Instant;
+
+/// The emulated clock frequency (1 tick per microsecond).
+const CLOCK_FREQUENCY: usize = 1_000_000;
+
+pub trait Alarm {
+    /// Get clock frequency in Hz.
+    fn get_clock_frequency(&self) -> usize;
+
+    /// Get the current value of the alarm clock in ticks.
+    fn get_ticks(&self) -> TockResult<usize>;
+}
+
+// Get the static Alarm object.
+pub fn get() -> &'static dyn Alarm {
+    ALARM.with(|alarm| *alarm)
+}
+
+struct FakeAlarm {
+    start: Instant,
+}
+
+thread_local! {
+    static ALARM: &'static FakeAlarm = Box::leak(Box::new(FakeAlarm {
+        start: Instant::now(),
+    }));
+}
+
+impl FakeAlarm {
+    fn now(&self) -> usize {
+        self.start.elapsed().as_micros() as usize
+    }
+}
+
+impl Alarm for FakeAlarm {
+    fn get_clock_frequency(&self) -> usize {
+        CLOCK_FREQUENCY
+    }
+
+    fn get_ticks(&self) -> TockResult<usize> {
+        Ok(self.now())
+    }
+}
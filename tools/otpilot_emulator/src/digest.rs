@@ -0,0 +1,86 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//! Replacement for the H1 digest driver based on the software SHA-256.
+//!
+//! The key ladder has its own replacement, so certificate steps are not
+//! emulated.
+
+use core::cell::RefCell;
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::integrity::SHA256_LEN;
+
+// The digest modes of the kernel digest driver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DigestMode {
+    Sha256 = 1,
+}
+
+pub trait Digest {
+    /// Start a new digest in the specified mode.
+    fn initialize(&self, mode: DigestMode) -> TockResult<()>;
+
+    /// Add the first `len` bytes of `buffer` to the digest.
+    fn update(&self, buffer: &mut[u8], len: usize) -> TockResult<()>;
+
+    /// Finish the digest and write it to output.
+    fn finalize(&self, output: &mut[u8]) -> TockResult<()>;
+}
+
+// Get the static Digest object.
+pub fn get() -> &'static dyn Digest {
+    DIGEST.with(|digest| *digest)
+}
+
+struct FakeDigest {
+    // The digest in progress, if the engine is in use.
+    state: RefCell<Option<Sha256>>,
+}
+
+thread_local! {
+    static DIGEST: &'static FakeDigest = Box::leak(Box::new(FakeDigest {
+        state: RefCell::new(None),
+    }));
+}
+
+impl Digest for FakeDigest {
+    fn initialize(&self, mode: DigestMode) -> TockResult<()> {
+        let mut state = self.state.borrow_mut();
+        if state.is_some() {
+            // The kernel driver reports the engine as busy.
+            return Err(TockError::Other);
+        }
+        match mode {
+            DigestMode::Sha256 => *state = Some(Sha256::new()),
+        }
+        Ok(())
+    }
+
+    fn update(&self, buffer: &mut[u8], len: usize) -> TockResult<()> {
+        let data = buffer.get(..len).ok_or(TockError::Format)?;
+        self.state.borrow_mut().as_mut().ok_or(TockError::Other)?.add(data);
+        Ok(())
+    }
+
+    fn finalize(&self, output: &mut[u8]) -> TockResult<()> {
+        let mut sha = self.state.borrow_mut().take().ok_or(TockError::Other)?;
+        output.get_mut(..SHA256_LEN).ok_or(TockError::Format)?.copy_from_slice(&sha.finish());
+        Ok(())
+    }
+}
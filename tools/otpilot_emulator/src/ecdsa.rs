@@ -0,0 +1,207 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Replacement for the dcrypto ECDSA P-256 signer in software.
+//!
+//! Nonces are derived deterministically from the private key and the digest
+//! (RFC 6979), so signing does not need an entropy source. The arithmetic
+//! modulo the group order is simple and slow, and makes no attempt to run
+//! in constant time.
+
+use libtock::result::TockResult;
+
+use spiutils::crypto::p256::PrivateKey;
+use spiutils::crypto::p256::Signature;
+use spiutils::crypto::p256::SCALAR_LEN;
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::integrity::SHA256_LEN;
+
+// The HMAC-SHA256 block size, in bytes.
+const HMAC_BLOCK_LEN: usize = 64;
+
+// A big-endian number modulo the group order.
+type Scalar = [u8; SCALAR_LEN];
+
+// The group order n.
+const N: Scalar = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+pub trait Ecdsa {
+    /// Sign the SHA-256 digest with key.
+    fn sign(&self, key: &PrivateKey, digest: &[u8; SHA256_LEN]) -> TockResult<Signature>;
+}
+
+// Get the static Ecdsa object.
+pub fn get() -> &'static dyn Ecdsa {
+    &ECDSA
+}
+
+struct SoftwareEcdsa;
+
+static ECDSA: SoftwareEcdsa = SoftwareEcdsa;
+
+impl Ecdsa for SoftwareEcdsa {
+    fn sign(&self, key: &PrivateKey, digest: &[u8; SHA256_LEN]) -> TockResult<Signature> {
+        Ok(sign(key, digest))
+    }
+}
+
+// Compute x - y and return the result and whether it borrowed.
+fn subtract(x: &Scalar, y: &Scalar) -> (Scalar, bool) {
+    let mut out = [0u8; SCALAR_LEN];
+    let mut borrow = 0i16;
+    for index in (0..SCALAR_LEN).rev() {
+        let diff = x[index] as i16 - y[index] as i16 - borrow;
+        out[index] = diff as u8;
+        borrow = (diff < 0) as i16;
+    }
+    (out, borrow != 0)
+}
+
+// Reduce x < 2n modulo n.
+fn reduce(x: &Scalar) -> Scalar {
+    if *x >= N {
+        subtract(x, &N).0
+    } else {
+        *x
+    }
+}
+
+// Compute (x + y) mod n for x, y < n.
+fn add_mod(x: &Scalar, y: &Scalar) -> Scalar {
+    let mut out = [0u8; SCALAR_LEN];
+    let mut carry = 0u16;
+    for index in (0..SCALAR_LEN).rev() {
+        let sum = x[index] as u16 + y[index] as u16 + carry;
+        out[index] = sum as u8;
+        carry = sum >> 8;
+    }
+    // The sum is below 2n, so subtracting n once is enough.
+    if carry != 0 || out >= N {
+        out = subtract(&out, &N).0;
+    }
+    out
+}
+
+// Compute (x * y) mod n for x, y < n by doubling and adding.
+fn mul_mod(x: &Scalar, y: &Scalar) -> Scalar {
+    let mut out = [0u8; SCALAR_LEN];
+    for byte in y.iter() {
+        for bit in (0..8).rev() {
+            out = add_mod(&out, &out);
+            if (byte >> bit) & 1 != 0 {
+                out = add_mod(&out, x);
+            }
+        }
+    }
+    out
+}
+
+// Compute x^-1 mod n as x^(n - 2).
+fn invert(x: &Scalar) -> Scalar {
+    // The last byte of n is larger than 2, so this does not borrow.
+    let mut exponent = N;
+    exponent[SCALAR_LEN - 1] -= 2;
+    let mut out = [0u8; SCALAR_LEN];
+    out[SCALAR_LEN - 1] = 1;
+    for byte in exponent.iter() {
+        for bit in (0..8).rev() {
+            out = mul_mod(&out, &out);
+            if (byte >> bit) & 1 != 0 {
+                out = mul_mod(&out, x);
+            }
+        }
+    }
+    out
+}
+
+// Compute HMAC-SHA256 (RFC 2104) of the concatenated `parts`.
+fn hmac(key: &[u8; SHA256_LEN], parts: &[&[u8]]) -> [u8; SHA256_LEN] {
+    let mut pad = [0x36u8; HMAC_BLOCK_LEN];
+    for (val, byte) in pad.iter_mut().zip(key.iter()) {
+        *val ^= byte;
+    }
+    let mut inner = Sha256::new();
+    inner.add(&pad);
+    for part in parts {
+        inner.add(part);
+    }
+    let inner = inner.finish();
+
+    for val in pad.iter_mut() {
+        *val ^= 0x36 ^ 0x5c;
+    }
+    Sha256::new().add(&pad).add(&inner).finish()
+}
+
+// Sign the SHA-256 `digest` with `key`.
+fn sign(key: &PrivateKey, digest: &[u8; SHA256_LEN]) -> Signature {
+    let d = key.to_bytes();
+    let e = reduce(digest);
+
+    // Derive the nonce (RFC 6979 section 3.2).
+    let mut v = [0x01u8; SHA256_LEN];
+    let mut k = [0x00u8; SHA256_LEN];
+    k = hmac(&k, &[&v, &[0x00], &d, &e]);
+    v = hmac(&k, &[&v]);
+    k = hmac(&k, &[&v, &[0x01], &d, &e]);
+    v = hmac(&k, &[&v]);
+
+    loop {
+        v = hmac(&k, &[&v]);
+        // Candidates outside of [1, n - 1] are not valid keys either.
+        if let Ok(nonce) = PrivateKey::from_bytes(&v) {
+            let r = reduce(&nonce.public_key().x);
+            if r != [0; SCALAR_LEN] {
+                // s = k^-1 * (e + r * d) mod n
+                let s = mul_mod(&invert(&v), &add_mod(&e, &mul_mod(&r, &d)));
+                if s != [0; SCALAR_LEN] {
+                    return Signature { r, s };
+                }
+            }
+        }
+        k = hmac(&k, &[&v, &[0x00]]);
+        v = hmac(&k, &[&v]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // RFC 6979 A.2.5.
+    const PRIVATE_KEY: [u8; SCALAR_LEN] = [
+        0xc9, 0xaf, 0xa9, 0xd8, 0x45, 0xba, 0x75, 0x16, 0x6b, 0x5c, 0x21, 0x57, 0x67, 0xb1, 0xd6, 0x93,
+        0x4e, 0x50, 0xc3, 0xdb, 0x36, 0xe8, 0x9b, 0x12, 0x7b, 0x8a, 0x62, 0x2b, 0x12, 0x0f, 0x67, 0x21,
+    ];
+    const SAMPLE_R: [u8; SCALAR_LEN] = [
+        0xef, 0xd4, 0x8b, 0x2a, 0xac, 0xb6, 0xa8, 0xfd, 0x11, 0x40, 0xdd, 0x9c, 0xd4, 0x5e, 0x81, 0xd6,
+        0x9d, 0x2c, 0x87, 0x7b, 0x56, 0xaa, 0xf9, 0x91, 0xc3, 0x4d, 0x0e, 0xa8, 0x4e, 0xaf, 0x37, 0x16,
+    ];
+    const SAMPLE_S: [u8; SCALAR_LEN] = [
+        0xf7, 0xcb, 0x1c, 0x94, 0x2d, 0x65, 0x7c, 0x41, 0xd4, 0x36, 0xc7, 0xa1, 0xb6, 0xe2, 0x9f, 0x65,
+        0xf3, 0xe9, 0x00, 0xdb, 0xb9, 0xaf, 0xf4, 0x06, 0x4d, 0xc4, 0xab, 0x2f, 0x84, 0x3a, 0xcd, 0xa8,
+    ];
+
+    #[test]
+    fn sign_sample() {
+        let key = PrivateKey::from_bytes(&PRIVATE_KEY).unwrap();
+        let digest = Sha256::new().add(b"sample").finish();
+        assert_eq!(sign(&key, &digest), Signature { r: SAMPLE_R, s: SAMPLE_S });
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Emulation of the SPI bus between the host and the H1.
+//!
+//! Commands that H1 handles in hardware are emulated here. All other
+//! commands are passed to the `SpiProcessor`, mirroring the main loop of
+//! otpilot.
+
+use crate::spi_device;
+use crate::spi_device::SpiDevice;
+use crate::spi_host;
+use crate::spi_processor::SpiProcessor;
+use crate::spi_processor::SPI_MAILBOX_ADDRESS;
+
+use libtock::println;
+
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::wire::WireEnum;
+
+// The number of address bytes for the specified address mode.
+fn address_len(address_mode: AddressMode) -> usize {
+    match address_mode {
+        AddressMode::ThreeByte => 3,
+        AddressMode::FourByte => 4,
+    }
+}
+
+// Parse a big-endian address of `len` bytes from the start of `data`.
+fn parse_address(data: &[u8], len: usize) -> Option<u32> {
+    data.get(..len)
+        .map(|bytes| bytes.iter().fold(0u32, |addr, byte| (addr << 8) | *byte as u32))
+}
+
+pub struct Emulator<'a> {
+    processor: SpiProcessor<'a>,
+}
+
+impl<'a> Emulator<'a> {
+    pub fn new(processor: SpiProcessor<'a>) -> Self {
+        Self {
+            processor,
+        }
+    }
+
+    /// Execute a full-duplex SPI transaction and return the received bytes.
+    pub fn transfer(&mut self, tx: &[u8]) -> Vec<u8> {
+        // The emulator only runs between transactions, so that's when a
+        // timed out reboot is executed.
+        if self.processor.reboot.alarm_expired().is_err() {
+            println!("Emulator: alarm handling failed");
+        }
+
+        let mut rx = vec![0xff; tx.len()];
+        if tx.is_empty() {
+            return rx;
+        }
+
+        let device = spi_device::get_fake();
+        let opcode = match OpCode::from_wire_value(tx[0]) {
+            Some(opcode) => opcode,
+            None => {
+                println!("Emulator: unknown opcode 0x{:02x}", tx[0]);
+                return rx;
+            }
+        };
+
+        match opcode {
+            OpCode::ReadStatusRegister => {
+                for val in rx[1..].iter_mut() {
+                    *val = device.get_status();
+                }
+            }
+            OpCode::ReadStatusRegister2 | OpCode::ReadStatusRegister3 => {
+                for val in rx[1..].iter_mut() {
+                    *val = 0;
+                }
+            }
+            OpCode::ReadFlagStatusRegister => {
+                // Report the program/erase controller as ready.
+                for val in rx[1..].iter_mut() {
+                    *val = 0x80;
+                }
+            }
+            OpCode::WriteEnable => device.set_write_enable(true),
+            OpCode::WriteDisable => device.set_write_enable(false),
+            OpCode::ReadJedec => device.read_jedec_id(&mut rx[1..]),
+            OpCode::ReadSfdp => {
+                // SFDP always uses a 3-byte address followed by a dummy byte.
+                if let Some(addr) = parse_address(&tx[1..], 3) {
+                    if tx.len() > 5 {
+                        device.read_sfdp(addr as usize, &mut rx[5..]);
+                    }
+                }
+            }
+            OpCode::NormalRead | OpCode::FastRead | OpCode::FastRead4B | OpCode::FastReadDualOutput
+                | OpCode::FastReadQuadOutput | OpCode::FastReadQuadIO
+                | OpCode::FastReadQuadOutput4B | OpCode::FastReadQuadIO4B => {
+                // Multi-line reads are emulated as if they were single-line
+                // byte streams.
+                let addr_len = address_len(opcode.address_mode(device.get_address_mode()));
+                let data_start = 1 + addr_len + opcode.dummy_bytes();
+                if let Some(addr) = parse_address(&tx[1..], addr_len) {
+                    if tx.len() > data_start {
+                        self.read(addr, &mut rx[data_start..]);
+                    }
+                }
+            }
+            OpCode::Enter4ByteAddressMode | OpCode::Exit4ByteAddressMode => {
+                let address_mode = if opcode == OpCode::Enter4ByteAddressMode {
+                    AddressMode::FourByte
+                } else {
+                    AddressMode::ThreeByte
+                };
+                // Passthrough forwards the command to the SPI flash as well.
+                device.set_address_mode(address_mode);
+                spi_host::get_fake().set_address_mode(address_mode);
+            }
+            _ => self.process(tx),
+        }
+
+        rx
+    }
+
+    // Read from the mailbox or the SPI flash, byte by byte, since a read may
+    // cross the mailbox boundaries.
+    fn read(&self, addr: u32, buffer: &mut [u8]) {
+        for (idx, val) in buffer.iter_mut().enumerate() {
+            let byte_addr = addr.wrapping_add(idx as u32);
+            let offset = byte_addr.wrapping_sub(SPI_MAILBOX_ADDRESS) as usize;
+            if byte_addr >= SPI_MAILBOX_ADDRESS && offset < spi_device::MAX_READ_BUFFER_SIZE {
+                spi_device::get_fake().read_mailbox(offset, core::slice::from_mut(val));
+            } else {
+                spi_host::get_fake().read(byte_addr, core::slice::from_mut(val));
+            }
+        }
+    }
+
+    // Hand a command to the SpiProcessor like the otpilot main loop.
+    fn process(&mut self, tx: &[u8]) {
+        spi_device::get_fake().set_busy();
+        match self.processor.process_spi_packet(tx) {
+            Ok(()) => {}
+            Err(why) => {
+                println!("SPI processor: Error {:?}", why);
+                if spi_device::get().is_busy_set() {
+                    if spi_device::get().end_transaction_with_status(true, false).is_err() {
+                        println!("SPI device: end_transaction error.");
+                    }
+                } else {
+                    spi_device::get().end_transaction();
+                }
+            }
+        }
+    }
+}
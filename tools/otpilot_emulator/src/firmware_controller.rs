@@ -0,0 +1,120 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Synchronous replacement for the otpilot firmware controller that operates
+//! on the in-memory flash.
+
+use crate::flash;
+
+use core::cmp::min;
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::compat::firmware::BUILD_INFO_LEN;
+use spiutils::compat::firmware::BUILD_INFO_OFFSET;
+use spiutils::compat::firmware::BuildInfo;
+use spiutils::driver::firmware::SegmentInfo;
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::integrity::SHA256_LEN;
+use spiutils::protocol::wire::FromWire;
+
+#[derive(Copy, Clone, Debug)]
+pub enum FirmwareControllerError {
+    Tock,
+    OutOfRange,
+}
+
+impl From<TockError> for FirmwareControllerError {
+    fn from(_err: TockError) -> Self {
+        FirmwareControllerError::Tock
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+
+pub struct FirmwareController;
+
+pub type FirmwareControllerResult<T> = Result<T, FirmwareControllerError>;
+
+impl FirmwareController {
+
+    pub fn new() -> FirmwareController {
+        FirmwareController
+    }
+
+    pub fn erase_segment(&mut self, segment: SegmentInfo) -> FirmwareControllerResult<()> {
+        for page in segment.start_page..segment.start_page + segment.page_count {
+            flash::get().erase(page as usize)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_and_verify_segment_chunk(&mut self, segment: SegmentInfo, offset: usize, data: &[u8]) -> FirmwareControllerResult<bool> {
+        if data.len() > flash::MAX_BUFFER_LENGTH || offset + data.len() > segment.size as usize {
+            return Err(FirmwareControllerError::OutOfRange);
+        }
+        let flash_offset = segment.address as usize + offset;
+
+        let mut write_buf = [0u8; flash::MAX_BUFFER_LENGTH];
+        write_buf[..data.len()].copy_from_slice(data);
+        flash::get().write(flash_offset, &mut write_buf, data.len())?;
+
+        let mut read_buf = [0u8; flash::MAX_BUFFER_LENGTH];
+        flash::get().read(flash_offset, &mut read_buf, data.len())?;
+        Ok(read_buf[..data.len()] == *data)
+    }
+
+    pub fn read_segment_chunk(&self, segment: SegmentInfo, offset: usize, data: &mut [u8]) -> FirmwareControllerResult<()> {
+        if offset + data.len() > segment.size as usize {
+            return Err(FirmwareControllerError::OutOfRange);
+        }
+
+        for (idx, chunk) in data.chunks_mut(flash::MAX_BUFFER_LENGTH).enumerate() {
+            let flash_offset = segment.address as usize + offset + idx * flash::MAX_BUFFER_LENGTH;
+            let chunk_length = chunk.len();
+            flash::get().read(flash_offset, chunk, chunk_length)?;
+        }
+        Ok(())
+    }
+
+    pub fn compute_segment_digest(&self, segment: SegmentInfo, length: usize) -> FirmwareControllerResult<[u8; SHA256_LEN]> {
+        if length > segment.size as usize {
+            return Err(FirmwareControllerError::OutOfRange);
+        }
+
+        let mut digest = Sha256::new();
+        let mut read_buf = [0u8; flash::MAX_BUFFER_LENGTH];
+        for offset in (0..length).step_by(flash::MAX_BUFFER_LENGTH) {
+            let chunk_length = min(length - offset, flash::MAX_BUFFER_LENGTH);
+            flash::get().read(segment.address as usize + offset, &mut read_buf, chunk_length)?;
+            digest.add(&read_buf[..chunk_length]);
+        }
+        Ok(digest.finish())
+    }
+
+    pub fn get_max_write_chunk_length(&self) -> usize {
+        flash::MAX_BUFFER_LENGTH
+    }
+}
+
+pub fn get_build_info(segment: SegmentInfo) -> TockResult<BuildInfo> {
+    let mut buf = [0u8; BUILD_INFO_LEN];
+    flash::get().read(segment.address as usize + BUILD_INFO_OFFSET, &mut buf, BUILD_INFO_LEN)?;
+
+    BuildInfo::from_wire(buf.as_ref()).map_err(|_| TockError::Format)
+}
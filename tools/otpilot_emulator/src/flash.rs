@@ -0,0 +1,123 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! In-memory replacement for the H1 internal flash driver.
+
+use core::cell::RefCell;
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+pub const MAX_BUFFER_LENGTH: usize = 128;
+
+/// The size of a flash page.
+pub const PAGE_SIZE: usize = 2048;
+
+/// The size of the internal flash (two banks of 256 KiB each).
+pub const FLASH_SIZE: usize = 512 * 1024;
+
+pub trait Flash {
+    /// Read `len` bytes at `offset` into `buffer`.
+    fn read(&self, offset: usize, buffer: &mut[u8], len: usize) -> TockResult<()>;
+
+    /// Write `len` bytes from `buffer` to `offset`.
+    /// As with real flash, writing can only clear bits.
+    fn write(&self, offset: usize, buffer: &mut[u8], len: usize) -> TockResult<()>;
+
+    /// Erase the page with the specified number.
+    fn erase(&self, page: usize) -> TockResult<()>;
+
+    /// Wait until the operation is done.
+    /// Operations complete before `write` and `erase` return.
+    fn wait_operation_done(&self);
+
+    /// Get the result of the last operation.
+    fn get_operation_result(&self) -> isize;
+
+    fn clear_operation(&self);
+}
+
+// Get the static Flash object.
+pub fn get() -> &'static dyn Flash {
+    get_fake()
+}
+
+pub struct FakeFlash {
+    data: RefCell<Vec<u8>>,
+}
+
+thread_local! {
+    static FLASH: &'static FakeFlash = Box::leak(Box::new(FakeFlash {
+        data: RefCell::new(vec![0xff; FLASH_SIZE]),
+    }));
+}
+
+// Get the static FakeFlash object.
+pub fn get_fake() -> &'static FakeFlash {
+    FLASH.with(|flash| *flash)
+}
+
+impl FakeFlash {
+    /// Copy `data` to `offset` without the restrictions of a flash write.
+    pub fn load(&self, offset: usize, data: &[u8]) -> TockResult<()> {
+        let mut flash = self.data.borrow_mut();
+        let target = flash.get_mut(offset..offset + data.len()).ok_or(TockError::Format)?;
+        target.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl Flash for FakeFlash {
+    fn read(&self, offset: usize, buffer: &mut[u8], len: usize) -> TockResult<()> {
+        if len > MAX_BUFFER_LENGTH || len > buffer.len() {
+            return Err(TockError::Format);
+        }
+        let flash = self.data.borrow();
+        let source = flash.get(offset..offset + len).ok_or(TockError::Format)?;
+        buffer[..len].copy_from_slice(source);
+        Ok(())
+    }
+
+    fn write(&self, offset: usize, buffer: &mut[u8], len: usize) -> TockResult<()> {
+        if len > MAX_BUFFER_LENGTH || len > buffer.len() {
+            return Err(TockError::Format);
+        }
+        let mut flash = self.data.borrow_mut();
+        let target = flash.get_mut(offset..offset + len).ok_or(TockError::Format)?;
+        for (dst, src) in target.iter_mut().zip(buffer[..len].iter()) {
+            *dst &= *src;
+        }
+        Ok(())
+    }
+
+    fn erase(&self, page: usize) -> TockResult<()> {
+        let offset = page * PAGE_SIZE;
+        let mut flash = self.data.borrow_mut();
+        let target = flash.get_mut(offset..offset + PAGE_SIZE).ok_or(TockError::Format)?;
+        for val in target.iter_mut() {
+            *val = 0xff;
+        }
+        Ok(())
+    }
+
+    fn wait_operation_done(&self) {}
+
+    fn get_operation_result(&self) -> isize {
+        0
+    }
+
+    fn clear_operation(&self) {}
+}
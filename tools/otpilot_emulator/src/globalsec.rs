@@ -0,0 +1,117 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Static replacement for the H1 global security driver.
+//!
+//! The emulated device always runs from the A segments, so the B segments
+//! are the inactive ones.
+
+use crate::flash;
+
+use spiutils::driver::firmware::SegmentInfo;
+use spiutils::protocol::firmware::SegmentAndLocation;
+
+// Size of an RO segment.
+const RO_SIZE: u32 = 0x4000;
+
+// Size of an RW segment.
+const RW_SIZE: u32 = 0x39000;
+
+// Size of an NVMEM segment.
+const NV_SIZE: u32 = 0x1800;
+
+// Size of the reserved pages at the end of each bank.
+const RESERVED_SIZE: u32 = 0x1800;
+
+// Size of a flash bank, each of which holds one RO and one RW segment,
+// followed by NVMEM and the reserved pages.
+const BANK_SIZE: u32 = RO_SIZE + RW_SIZE + NV_SIZE + RESERVED_SIZE;
+
+pub trait GlobalSec {
+    /// Get segment information for active RO.
+    fn get_active_ro(&self) -> SegmentInfo;
+
+    /// Get segment information for active RW.
+    fn get_active_rw(&self) -> SegmentInfo;
+
+    /// Get segment information for inactive RO.
+    fn get_inactive_ro(&self) -> SegmentInfo;
+
+    /// Get segment information for inactive RW.
+    fn get_inactive_rw(&self) -> SegmentInfo;
+
+    /// Get segment information for the NVMEM in the active bank.
+    fn get_active_nv(&self) -> SegmentInfo;
+
+    /// Get segment information for the NVMEM in the inactive bank.
+    fn get_inactive_nv(&self) -> SegmentInfo;
+}
+
+// Get the static GlobalSec object.
+pub fn get() -> &'static dyn GlobalSec {
+    &GLOBALSEC
+}
+
+struct GlobalSecImpl;
+
+static GLOBALSEC: GlobalSecImpl = GlobalSecImpl;
+
+const fn segment(identifier: SegmentAndLocation, address: u32, size: u32) -> SegmentInfo {
+    SegmentInfo {
+        identifier,
+        address,
+        size,
+        start_page: address / flash::PAGE_SIZE as u32,
+        page_count: size / flash::PAGE_SIZE as u32,
+    }
+}
+
+/// All segments of the emulated device.
+pub const SEGMENTS: [SegmentInfo; 6] = [
+    segment(SegmentAndLocation::RoA, 0, RO_SIZE),
+    segment(SegmentAndLocation::RwA, RO_SIZE, RW_SIZE),
+    segment(SegmentAndLocation::RoB, BANK_SIZE, RO_SIZE),
+    segment(SegmentAndLocation::RwB, BANK_SIZE + RO_SIZE, RW_SIZE),
+    segment(SegmentAndLocation::NvA, RO_SIZE + RW_SIZE, NV_SIZE),
+    segment(SegmentAndLocation::NvB, BANK_SIZE + RO_SIZE + RW_SIZE, NV_SIZE),
+];
+
+impl GlobalSec for GlobalSecImpl {
+    fn get_active_ro(&self) -> SegmentInfo {
+        SEGMENTS[0]
+    }
+
+    fn get_active_rw(&self) -> SegmentInfo {
+        SEGMENTS[1]
+    }
+
+    fn get_inactive_ro(&self) -> SegmentInfo {
+        SEGMENTS[2]
+    }
+
+    fn get_inactive_rw(&self) -> SegmentInfo {
+        SEGMENTS[3]
+    }
+
+    fn get_active_nv(&self) -> SegmentInfo {
+        SEGMENTS[4]
+    }
+
+    fn get_inactive_nv(&self) -> SegmentInfo {
+        SEGMENTS[5]
+    }
+}
@@ -0,0 +1,45 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//! Replacement for the H1 key ladder based on a fixed device secret.
+
+use libtock::result::TockResult;
+
+use spiutils::protocol::integrity::Sha256;
+
+pub const SECRET_LEN: usize = 32;
+
+// The secret of the emulated chip.
+const DEVICE_SECRET: &[u8] = b"otpilot_emulator device secret";
+
+pub trait KeyLadder {
+    /// Derive the attestation secret for input.
+    fn derive_attest(&self, input: &[u8; SECRET_LEN]) -> TockResult<[u8; SECRET_LEN]>;
+}
+
+// Get the static KeyLadder object.
+pub fn get() -> &'static dyn KeyLadder {
+    &KEY_LADDER
+}
+
+struct FakeKeyLadder;
+
+static KEY_LADDER: FakeKeyLadder = FakeKeyLadder;
+
+impl KeyLadder for FakeKeyLadder {
+    fn derive_attest(&self, input: &[u8; SECRET_LEN]) -> TockResult<[u8; SECRET_LEN]> {
+        Ok(Sha256::new().add(DEVICE_SECRET).add(input).finish())
+    }
+}
@@ -0,0 +1,379 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! otpilot_emulator runs the SPI mailbox handling of otpilot on a Linux host.
+//!
+//! The real `spi_processor`, `manticore_support`, `attestation`,
+//! `firmware_update`, `measured_boot`, `reboot_scheduler`, `reset_counter`,
+//! `sfdp`, `nvmem` and `write_protect` modules of otpilot are compiled
+//! against in-memory replacements of the H1 drivers. Clients
+//! connect to a Unix socket and exchange SPI transactions with the emulator.
+//! Each transaction is a frame consisting of a 32-bit little-endian length
+//! followed by the bytes sent on MOSI. The emulator answers with a frame of
+//! the same length containing the bytes received on MISO.
+
+mod alarm;
+#[path = "../../../userspace/otpilot/src/attestation.rs"]
+mod attestation;
+mod digest;
+mod ecdsa;
+mod emulator;
+mod firmware_controller;
+#[path = "../../../userspace/otpilot/src/firmware_update.rs"]
+mod firmware_update;
+mod flash;
+mod globalsec;
+mod key_ladder;
+#[path = "../../../userspace/otpilot/src/manticore_support.rs"]
+mod manticore_support;
+#[path = "../../../userspace/otpilot/src/measured_boot.rs"]
+mod measured_boot;
+#[path = "../../../userspace/otpilot/src/nvmem.rs"]
+mod nvmem;
+mod personality;
+#[path = "../../../userspace/otpilot/src/reboot_scheduler.rs"]
+mod reboot_scheduler;
+mod reset;
+#[path = "../../../userspace/otpilot/src/reset_counter.rs"]
+mod reset_counter;
+mod rng;
+#[path = "../../../userspace/otpilot/src/sfdp.rs"]
+mod sfdp;
+mod spi_device;
+mod spi_host;
+mod spi_host_h1;
+#[path = "../../../userspace/otpilot/src/spi_processor.rs"]
+mod spi_processor;
+#[path = "../../../userspace/otpilot/src/write_protect.rs"]
+mod write_protect;
+
+use crate::attestation::Attestation;
+use crate::emulator::Emulator;
+use crate::firmware_update::FirmwareUpdate;
+use crate::measured_boot::MeasuredBoot;
+use crate::reboot_scheduler::RebootScheduler;
+use crate::spi_processor::SegmentReadAccess;
+use crate::spi_processor::SpiProcessor;
+use crate::write_protect::WriteProtect;
+
+use clap::App;
+use clap::Arg;
+use clap::ArgMatches;
+
+use spiutils::driver::firmware::SegmentInfo;
+use spiutils::io::Cursor;
+use spiutils::protocol::error;
+use spiutils::protocol::firmware::SegmentAndLocation;
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::measured_boot::RECOVERY_ALIGNMENT;
+use spiutils::protocol::payload;
+use spiutils::protocol::wire::ToWire;
+
+use std::cmp::min;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::process::exit;
+
+// Time after which a delayed reboot is executed if no reset is emulated.
+const DELAYED_REBOOT_TIMEOUT_MSECS: Option<u32> = Some(60 * 1000);
+
+// The maximum length of a single SPI transaction.
+const MAX_TRANSFER_LEN: usize = 64 * 1024;
+
+// Command line options for the initial segment contents.
+const SEGMENT_OPTIONS: [(&str, SegmentAndLocation); 6] = [
+    ("ro-a", SegmentAndLocation::RoA),
+    ("rw-a", SegmentAndLocation::RwA),
+    ("ro-b", SegmentAndLocation::RoB),
+    ("rw-b", SegmentAndLocation::RwB),
+    ("nv-a", SegmentAndLocation::NvA),
+    ("nv-b", SegmentAndLocation::NvB),
+];
+
+const BANNER: &str = concat!(env!("CARGO_PKG_NAME"), ' ', env!("CARGO_PKG_VERSION"));
+
+// Load the file at `path` into the specified segment.
+fn load_segment(path: &str, segment: SegmentInfo) -> Result<(), String> {
+    let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+    if data.len() > segment.size as usize {
+        return Err(format!("{}: image of 0x{:x} bytes exceeds {} size 0x{:x}",
+            path, data.len(), segment.identifier, segment.size));
+    }
+    flash::get_fake().load(segment.address as usize, &data)
+        .map_err(|_| format!("{}: failed to load image", path))
+}
+
+// Create the SPI flash image, optionally initialized from the file at `path`.
+fn load_spi_flash(path: Option<&str>) -> Result<(), String> {
+    let mut image = match path {
+        Some(path) => fs::read(path).map_err(|err| format!("{}: {}", path, err))?,
+        None => Vec::new(),
+    };
+    if image.len() > spi_processor::SPI_FLASH_SIZE as usize {
+        return Err(format!("SPI flash image exceeds 0x{:x} bytes", spi_processor::SPI_FLASH_SIZE));
+    }
+    image.resize(spi_processor::SPI_FLASH_SIZE as usize, 0xff);
+    spi_host::get_fake().set_image(image);
+    Ok(())
+}
+
+// Get the build info for the specified segment and serialize it to the
+// provided buffer.
+fn store_build_info(segment_info: SegmentInfo, buf: &mut [u8]) {
+    if let Ok(build_info) = firmware_controller::get_build_info(segment_info) {
+        if build_info.to_wire(Cursor::new(buf)).is_err() {
+            println!("Could not serialize {} build info", segment_info.identifier);
+        }
+    }
+}
+
+// The otpilot console commands to run at startup, since the emulator has no
+// console.
+struct ConsoleCommands {
+    // Accept provisioning requests via the mailbox.
+    provisioning: bool,
+
+    // Unlock the measured boot manifest and the write protection policy.
+    unlock: bool,
+}
+
+fn setup_spi_device() -> Result<(), String> {
+    // OpenTitan JEDEC ID, as used by otpilot.
+    spi_device::get().set_jedec_id(&mut [0x26, 0x31, 0x19])
+        .map_err(|_| "failed to set JEDEC ID")?;
+
+    let mut sfdp = [0xff; 128];
+    sfdp::get_table(
+        &mut sfdp,
+        spi_processor::SPI_FLASH_SIZE as u64 * 8, // image_size_bits
+        spi_device::get().get_address_mode(), // startup_address_mode
+        spi_device::get().get_address_mode() == AddressMode::ThreeByte, // support_address_mode_switch
+        spi_processor::SPI_MAILBOX_ADDRESS, // mailbox_offset
+        spi_device::MAX_READ_BUFFER_SIZE as u32, // mailbox_size
+        0 // google_capabilities
+        ).map_err(|_| "failed to build SFDP table")?;
+    spi_device::get().set_sfdp(&mut sfdp)
+        .map_err(|_| "failed to set SFDP table")?;
+    Ok(())
+}
+
+// Load the initial flash contents and return the device ID.
+fn setup(matches: &ArgMatches) -> Result<u64, String> {
+    load_spi_flash(matches.value_of("spi-flash"))?;
+
+    for (name, identifier) in SEGMENT_OPTIONS.iter() {
+        if let Some(path) = matches.value_of(name) {
+            let segment = globalsec::SEGMENTS.iter()
+                .find(|segment| segment.identifier == *identifier)
+                .unwrap();
+            load_segment(path, *segment)?;
+        }
+    }
+
+    let dev_id = matches.value_of("dev-id").unwrap();
+    u64::from_str_radix(dev_id.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid device ID: {}", dev_id))
+}
+
+// Serve SPI transactions from a single client until it disconnects.
+fn handle_client(mut stream: UnixStream, emulator: &mut Emulator) -> io::Result<()> {
+    loop {
+        let mut len_buf = [0u8; 4];
+        match stream.read_exact(&mut len_buf) {
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let len = u32::from_le_bytes(len_buf) as usize;
+        if len > MAX_TRANSFER_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "transaction too long"));
+        }
+
+        let mut tx = vec![0u8; len];
+        stream.read_exact(&mut tx)?;
+        let rx = emulator.transfer(&tx);
+        stream.write_all(&len_buf)?;
+        stream.write_all(&rx)?;
+    }
+}
+
+fn run(socket_path: &str, dev_id: u64, print_flash_headers: bool, console: ConsoleCommands) -> Result<(), String> {
+    setup_spi_device()?;
+
+    let mut identity = manticore_support::Identity {
+        version: [0; 32],
+        ro_version: [0; 32],
+        rw_version: [0; 32],
+        device_id: [0; 64],
+    };
+
+    let banner_bytes = BANNER.as_bytes();
+    let max_len = min(identity.version.len(), banner_bytes.len());
+    identity.version[..max_len].copy_from_slice(&banner_bytes[..max_len]);
+
+    store_build_info(globalsec::get().get_active_ro(), &mut identity.ro_version);
+    store_build_info(globalsec::get().get_active_rw(), &mut identity.rw_version);
+
+    let dev_id_bytes = dev_id.to_be_bytes();
+    identity.device_id[..dev_id_bytes.len()].copy_from_slice(&dev_id_bytes);
+
+    let reboot_scheduler = RebootScheduler::new(DELAYED_REBOOT_TIMEOUT_MSECS);
+    let measured_boot = MeasuredBoot::load();
+    let write_protect = WriteProtect::load();
+    if console.unlock {
+        println!("Unlocking measured boot manifest: {:?}", measured_boot.unlock());
+        println!("Unlocking write protection policy: {:?}", write_protect.unlock());
+    }
+    if console.provisioning {
+        measured_boot.enable_provisioning();
+        write_protect.enable_provisioning();
+    }
+
+    // Verify and recover the SPI flash image as otpilot does before letting
+    // the BMC out of reset.
+    let measure = |start: u32, size: u32| {
+        let mut data = vec![0u8; size as usize];
+        spi_host::get_fake().read(start, &mut data);
+        Ok(Sha256::new().add(&data).finish())
+    };
+    let copy = |source: u32, destination: u32, size: u32| {
+        let mut data = vec![0u8; size as usize];
+        spi_host::get_fake().read(source, &mut data);
+        for offset in (0..size).step_by(RECOVERY_ALIGNMENT as usize) {
+            spi_host::get_fake().erase((destination + offset) as usize, RECOVERY_ALIGNMENT as usize)?;
+        }
+        spi_host::get_fake().program(destination as usize, &data)
+    };
+    let bmc_allowed = !measured_boot.is_enabled() || measured_boot.verify(measure)
+        || measured_boot.recover(measure, copy);
+    println!("BMC {}", if bmc_allowed { "released" } else { "held in reset" });
+
+    let resets_since_power_on = reset_counter::update()
+        .map_err(|_| "could not update the reset count".to_string())?;
+    let manticore_reset = manticore_support::Reset::new(resets_since_power_on);
+
+    let firmware = firmware_controller::FirmwareController::new();
+    let active_rw = globalsec::get().get_active_rw();
+    let pmr0 = firmware.compute_segment_digest(active_rw, active_rw.size as usize)
+        .map_err(|_| "could not compute PMR0".to_string())?;
+    let attestation = Attestation::load(pmr0);
+    let firmware_update = FirmwareUpdate::new(&reboot_scheduler);
+
+    let mut emulator = Emulator::new(SpiProcessor {
+        manticore_handler: manticore_support::Handler::new(
+            &identity, &manticore_reset, &attestation, &firmware_update),
+        print_flash_headers,
+        firmware,
+        update: &firmware_update,
+        read_access: SegmentReadAccess {
+            active_ro: true,
+            active_rw: true,
+            inactive_ro: true,
+            inactive_rw: true,
+        },
+        reboot: &reboot_scheduler,
+        reassembler: payload::Reassembler::new(),
+        fragmenter: payload::Fragmenter::new(),
+        integrity: None,
+        request: error::NO_REQUEST,
+        write_protect: &write_protect,
+        measured_boot: &measured_boot,
+    });
+
+    // Remove a stale socket from a previous run.
+    let _ = fs::remove_file(socket_path);
+    let listener = UnixListener::bind(socket_path)
+        .map_err(|err| format!("{}: {}", socket_path, err))?;
+    println!("Listening on {}", socket_path);
+
+    for stream in listener.incoming() {
+        let stream = stream.map_err(|err| err.to_string())?;
+        println!("Client connected");
+        if let Err(err) = handle_client(stream, &mut emulator) {
+            println!("Client error: {}", err);
+        }
+        println!("Client disconnected");
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut app = App::new("otpilot emulator")
+        .version("0.1")
+        .author("lowRISC contributors")
+        .about("Emulates the otpilot SPI mailbox on a Unix socket")
+        .arg(
+            Arg::with_name("v")
+                .short("v")
+                .help("Print incoming SPI flash headers"),
+        )
+        .arg(
+            Arg::with_name("socket")
+                .short("s")
+                .long("socket")
+                .help("path of the Unix socket to listen on")
+                .default_value("/tmp/otpilot_emulator.sock")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("spi-flash")
+                .long("spi-flash")
+                .help("file with the initial SPI flash contents")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("provisioning")
+                .long("provisioning")
+                .help("accept provisioning requests, as if enabled on the console"),
+        )
+        .arg(
+            Arg::with_name("unlock")
+                .long("unlock")
+                .help("unlock the measured boot manifest and write protection policy, as if done on the console"),
+        )
+        .arg(
+            Arg::with_name("dev-id")
+                .long("dev-id")
+                .help("device ID reported to Manticore, in hex")
+                .default_value("0")
+                .takes_value(true),
+        );
+    for (name, _) in SEGMENT_OPTIONS.iter() {
+        app = app.arg(
+            Arg::with_name(name)
+                .long(name)
+                .help("file with the initial segment contents")
+                .takes_value(true),
+        );
+    }
+    let matches = app.get_matches();
+
+    if let Err(err) = setup(&matches).and_then(|dev_id| {
+        let console = ConsoleCommands {
+            provisioning: matches.is_present("provisioning"),
+            unlock: matches.is_present("unlock"),
+        };
+        run(matches.value_of("socket").unwrap(), dev_id, matches.is_present("v"), console)
+    }) {
+        eprintln!("Error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//! Replacement for the H1 personality driver.
+//!
+//! The personality data is provisioned with a key derived from the fake key
+//! ladder. The certificate is a placeholder that contains the public key,
+//! not an X.509 certificate.
+
+use crate::key_ladder;
+
+use libtock::result::TockResult;
+
+use spiutils::crypto::p256::PrivateKey;
+use spiutils::driver::personality::*;
+use spiutils::protocol::integrity::Sha256;
+
+// The salt of the personality key.
+const SALT: [u8; 32] = [0x5a; 32];
+
+// The prefix of the placeholder certificate.
+const CERTIFICATE_PREFIX: &[u8] = b"otpilot_emulator device certificate";
+
+pub trait Personality {
+    /// Read the personality data into buffer.
+    fn read(&self, buffer: &mut[u8; PERSONALITY_LEN]) -> TockResult<()>;
+}
+
+// Get the static Personality object.
+pub fn get() -> &'static dyn Personality {
+    &PERSONALITY
+}
+
+struct FakePersonality;
+
+static PERSONALITY: FakePersonality = FakePersonality;
+
+impl Personality for FakePersonality {
+    fn read(&self, buffer: &mut[u8; PERSONALITY_LEN]) -> TockResult<()> {
+        let seed = key_ladder::get().derive_attest(&SALT)?;
+        let public_key = PrivateKey::from_seed(&seed).public_key();
+
+        let mut certificate = CERTIFICATE_PREFIX.to_vec();
+        certificate.extend_from_slice(&public_key.x);
+        certificate.extend_from_slice(&public_key.y);
+        let certificate_hash = Sha256::new().add(&certificate).finish();
+
+        // Unused bytes stay erased.
+        for byte in buffer.iter_mut() {
+            *byte = 0xff;
+        }
+        let checksum = key_ladder::get().derive_attest(&certificate_hash)?;
+        buffer[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 32].copy_from_slice(&checksum);
+        buffer[SALT_OFFSET..SALT_OFFSET + 32].copy_from_slice(&SALT);
+        // The public key coordinates are stored as little-endian numbers.
+        for (index, byte) in public_key.x.iter().rev().enumerate() {
+            buffer[PUB_X_OFFSET + index] = *byte;
+        }
+        for (index, byte) in public_key.y.iter().rev().enumerate() {
+            buffer[PUB_Y_OFFSET + index] = *byte;
+        }
+        buffer[CERTIFICATE_HASH_OFFSET..CERTIFICATE_HASH_OFFSET + 32].copy_from_slice(&certificate_hash);
+        buffer[CERTIFICATE_LEN_OFFSET..CERTIFICATE_OFFSET]
+            .copy_from_slice(&(certificate.len() as u32).to_le_bytes());
+        buffer[CERTIFICATE_OFFSET..CERTIFICATE_OFFSET + certificate.len()].copy_from_slice(&certificate);
+        Ok(())
+    }
+}
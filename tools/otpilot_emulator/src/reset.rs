@@ -0,0 +1,63 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Replacement for the H1 reset driver that only logs reset requests.
+//! Every start of the emulator is a power-on reset.
+
+use libtock::println;
+use libtock::result::TockResult;
+
+use spiutils::driver::reset::ResetSource;
+
+pub trait Reset {
+    /// Reset the chip.
+    fn reset(&self) -> TockResult<()>;
+
+    /// Get reset source.
+    fn get_reset_source(&self) -> TockResult<ResetSource>;
+}
+
+// Get the static Reset object.
+pub fn get() -> &'static dyn Reset {
+    &RESET
+}
+
+struct ResetImpl;
+
+static RESET: ResetImpl = ResetImpl;
+
+impl Reset for ResetImpl {
+    fn reset(&self) -> TockResult<()> {
+        // The emulator keeps running so that the client can observe the
+        // response to its reboot request.
+        println!("Reset: chip reset requested");
+        Ok(())
+    }
+
+    fn get_reset_source(&self) -> TockResult<ResetSource> {
+        Ok(ResetSource {
+            power_on_reset: true,
+            low_power_reset: false,
+            watchdog_reset: false,
+            lockup_reset: false,
+            sysreset: false,
+            software_reset: false,
+            fast_burnout_circuit: false,
+            security_breach_reset: false,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+//! Replacement for the H1 random number generator based on /dev/urandom.
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use std::fs::File;
+use std::io::Read;
+
+pub trait Rng {
+    /// Fill buffer with random bytes.
+    fn fill(&self, buffer: &mut[u8]) -> TockResult<()>;
+}
+
+// Get the static Rng object.
+pub fn get() -> &'static dyn Rng {
+    &RNG
+}
+
+struct FakeRng;
+
+static RNG: FakeRng = FakeRng;
+
+impl Rng for FakeRng {
+    fn fill(&self, buffer: &mut[u8]) -> TockResult<()> {
+        File::open("/dev/urandom")
+            .and_then(|mut file| file.read_exact(buffer))
+            .map_err(|_| TockError::Other)
+    }
+}
@@ -0,0 +1,197 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Replacement for the SPI device driver.
+//!
+//! The methods of `SpiDevice` are used by the otpilot code, while the
+//! inherent methods of `FakeSpiDevice` emulate the parts of the SPI device
+//! that are implemented in hardware.
+
+use core::cell::Cell;
+use core::cell::RefCell;
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::protocol::flash::AddressMode;
+
+/// The size of the mailbox buffer that is returned to the SPI host.
+pub const MAX_READ_BUFFER_SIZE: usize = 512;
+
+// Status register bits.
+const STATUS_BUSY: u8 = 0x01;
+const STATUS_WRITE_ENABLE: u8 = 0x02;
+
+pub trait SpiDevice {
+    /// Check whether the BUSY bit is set.
+    fn is_busy_set(&self) -> bool;
+
+    /// Check whether the WRITE ENABLE bit is set.
+    fn is_write_enable_set(&self) -> bool;
+
+    /// End a transaction.
+    fn end_transaction(&self);
+
+    /// End a transaction and clear the specified status bits.
+    fn end_transaction_with_status(&self, clear_busy: bool, clear_write_enable: bool) -> TockResult<()>;
+
+    /// End a transaction, send data to the SPI host and clear the specified
+    /// status bits.
+    fn end_transaction_with_data(&self, write_buffer: &mut[u8], clear_busy: bool, clear_write_enable: bool)
+        -> TockResult<()>;
+
+    /// Get the current address mode.
+    fn get_address_mode(&self) -> AddressMode;
+
+    /// Set the JEDEC ID returned to the SPI host.
+    fn set_jedec_id(&self, data: &mut[u8]) -> TockResult<()>;
+
+    /// Set the SFDP table returned to the SPI host.
+    fn set_sfdp(&self, data: &mut[u8]) -> TockResult<()>;
+}
+
+// Get the static SpiDevice object.
+pub fn get() -> &'static dyn SpiDevice {
+    get_fake()
+}
+
+pub struct FakeSpiDevice {
+    is_busy_set: Cell<bool>,
+    is_write_enable_set: Cell<bool>,
+    address_mode: Cell<AddressMode>,
+    mailbox: RefCell<[u8; MAX_READ_BUFFER_SIZE]>,
+    jedec_id: RefCell<Vec<u8>>,
+    sfdp: RefCell<Vec<u8>>,
+}
+
+thread_local! {
+    static SPI_DEVICE: &'static FakeSpiDevice = Box::leak(Box::new(FakeSpiDevice {
+        is_busy_set: Cell::new(false),
+        is_write_enable_set: Cell::new(false),
+        address_mode: Cell::new(AddressMode::ThreeByte),
+        mailbox: RefCell::new([0xff; MAX_READ_BUFFER_SIZE]),
+        jedec_id: RefCell::new(Vec::new()),
+        sfdp: RefCell::new(Vec::new()),
+    }));
+}
+
+// Get the static FakeSpiDevice object.
+pub fn get_fake() -> &'static FakeSpiDevice {
+    SPI_DEVICE.with(|device| *device)
+}
+
+// Copy `source` starting at `offset` to `buffer`, padding with 0xff.
+fn copy_padded(source: &[u8], offset: usize, buffer: &mut [u8]) {
+    for (idx, val) in buffer.iter_mut().enumerate() {
+        *val = *source.get(offset + idx).unwrap_or(&0xff);
+    }
+}
+
+impl FakeSpiDevice {
+    /// Get the status register value.
+    pub fn get_status(&self) -> u8 {
+        let mut status = 0;
+        if self.is_busy_set.get() {
+            status |= STATUS_BUSY;
+        }
+        if self.is_write_enable_set.get() {
+            status |= STATUS_WRITE_ENABLE;
+        }
+        status
+    }
+
+    /// Set the BUSY bit, as done when receiving a command that is handled
+    /// in software.
+    pub fn set_busy(&self) {
+        self.is_busy_set.set(true);
+    }
+
+    /// Set or clear the WRITE ENABLE bit.
+    pub fn set_write_enable(&self, enabled: bool) {
+        self.is_write_enable_set.set(enabled);
+    }
+
+    /// Set the address mode.
+    pub fn set_address_mode(&self, address_mode: AddressMode) {
+        self.address_mode.set(address_mode);
+    }
+
+    /// Read from the mailbox at `offset`.
+    pub fn read_mailbox(&self, offset: usize, buffer: &mut [u8]) {
+        copy_padded(&self.mailbox.borrow()[..], offset, buffer);
+    }
+
+    /// Read the JEDEC ID.
+    pub fn read_jedec_id(&self, buffer: &mut [u8]) {
+        copy_padded(&self.jedec_id.borrow(), 0, buffer);
+    }
+
+    /// Read from the SFDP table at `offset`.
+    pub fn read_sfdp(&self, offset: usize, buffer: &mut [u8]) {
+        copy_padded(&self.sfdp.borrow(), offset, buffer);
+    }
+
+    fn clear_status(&self, clear_busy: bool, clear_write_enable: bool) {
+        if clear_busy {
+            self.is_busy_set.set(false);
+        }
+        if clear_write_enable {
+            self.is_write_enable_set.set(false);
+        }
+    }
+}
+
+impl SpiDevice for FakeSpiDevice {
+    fn is_busy_set(&self) -> bool {
+        self.is_busy_set.get()
+    }
+
+    fn is_write_enable_set(&self) -> bool {
+        self.is_write_enable_set.get()
+    }
+
+    fn end_transaction(&self) {
+    }
+
+    fn end_transaction_with_status(&self, clear_busy: bool, clear_write_enable: bool) -> TockResult<()> {
+        self.clear_status(clear_busy, clear_write_enable);
+        Ok(())
+    }
+
+    fn end_transaction_with_data(&self, write_buffer: &mut[u8], clear_busy: bool, clear_write_enable: bool)
+        -> TockResult<()> {
+        let mut mailbox = self.mailbox.borrow_mut();
+        let target = mailbox.get_mut(..write_buffer.len()).ok_or(TockError::Format)?;
+        target.copy_from_slice(write_buffer);
+        self.clear_status(clear_busy, clear_write_enable);
+        Ok(())
+    }
+
+    fn get_address_mode(&self) -> AddressMode {
+        self.address_mode.get()
+    }
+
+    fn set_jedec_id(&self, data: &mut[u8]) -> TockResult<()> {
+        *self.jedec_id.borrow_mut() = data.to_vec();
+        Ok(())
+    }
+
+    fn set_sfdp(&self, data: &mut[u8]) -> TockResult<()> {
+        *self.sfdp.borrow_mut() = data.to_vec();
+        Ok(())
+    }
+}
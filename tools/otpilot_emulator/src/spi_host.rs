@@ -0,0 +1,176 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Replacement for the SPI host driver with an in-memory SPI flash chip
+//! attached to it.
+
+use core::cell::Cell;
+use core::cell::RefCell;
+
+use libtock::println;
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::protocol::flash::Address;
+use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::flash::Header;
+use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::wire::WireEnum;
+
+pub const MAX_READ_BUFFER_LENGTH: usize = 128;
+
+// Erase sizes of the emulated SPI flash chip.
+const SECTOR_SIZE: usize = 4 * 1024;
+const BLOCK_32KB_SIZE: usize = 32 * 1024;
+const BLOCK_64KB_SIZE: usize = 64 * 1024;
+
+pub trait SpiHost {
+    /// Send `read_write_length` bytes from `write_buffer` to the SPI flash.
+    fn read_write_bytes(&self, write_buffer: &mut[u8], read_write_length: usize) -> TockResult<()>;
+
+    /// Wait for the current transaction to complete.
+    fn wait_read_write_done(&self);
+}
+
+// Get the static SpiHost object.
+pub fn get() -> &'static dyn SpiHost {
+    get_fake()
+}
+
+/// The SPI flash chip attached to the SPI host.
+pub struct FakeSpiFlash {
+    data: RefCell<Vec<u8>>,
+    address_mode: Cell<AddressMode>,
+    write_enabled: Cell<bool>,
+    reset_enabled: Cell<bool>,
+}
+
+thread_local! {
+    static SPI_FLASH: &'static FakeSpiFlash = Box::leak(Box::new(FakeSpiFlash {
+        data: RefCell::new(Vec::new()),
+        address_mode: Cell::new(AddressMode::ThreeByte),
+        write_enabled: Cell::new(false),
+        reset_enabled: Cell::new(false),
+    }));
+}
+
+// Get the static FakeSpiFlash object.
+pub fn get_fake() -> &'static FakeSpiFlash {
+    SPI_FLASH.with(|flash| *flash)
+}
+
+impl FakeSpiFlash {
+    /// Replace the flash contents with `image`.
+    pub fn set_image(&self, image: Vec<u8>) {
+        *self.data.borrow_mut() = image;
+    }
+
+    /// Set the address mode, as done by the 4-byte address mode commands.
+    pub fn set_address_mode(&self, address_mode: AddressMode) {
+        self.address_mode.set(address_mode);
+    }
+
+    /// Read from `address` into `buffer`, wrapping around at the end of flash.
+    pub fn read(&self, address: u32, buffer: &mut [u8]) {
+        let data = self.data.borrow();
+        if data.is_empty() {
+            return;
+        }
+        for (idx, val) in buffer.iter_mut().enumerate() {
+            *val = data[(address as usize + idx) % data.len()];
+        }
+    }
+
+    /// Program `bytes` at `address`, as done by PageProgram.
+    pub fn program(&self, address: usize, bytes: &[u8]) -> TockResult<()> {
+        let mut data = self.data.borrow_mut();
+        let target = data.get_mut(address..address + bytes.len()).ok_or(TockError::Format)?;
+        for (dst, src) in target.iter_mut().zip(bytes.iter()) {
+            *dst &= *src;
+        }
+        Ok(())
+    }
+
+    /// Erase the `size` bytes block that contains `address`.
+    pub fn erase(&self, address: usize, size: usize) -> TockResult<()> {
+        let start = address - address % size;
+        let mut data = self.data.borrow_mut();
+        let target = data.get_mut(start..start + size).ok_or(TockError::Format)?;
+        for val in target.iter_mut() {
+            *val = 0xff;
+        }
+        Ok(())
+    }
+
+    fn execute<AddrType: Address>(&self, header: &Header<AddrType>, data: &[u8]) -> TockResult<()> {
+        let address = header.get_address().unwrap_or(0) as usize;
+        // Reset is only accepted directly after ResetEnable.
+        let reset_enabled = self.reset_enabled.replace(header.opcode == OpCode::ResetEnable);
+        match header.opcode {
+            OpCode::WriteEnable => self.write_enabled.set(true),
+            OpCode::WriteDisable => self.write_enabled.set(false),
+            OpCode::Enter4ByteAddressMode => self.address_mode.set(AddressMode::FourByte),
+            OpCode::Exit4ByteAddressMode => self.address_mode.set(AddressMode::ThreeByte),
+            OpCode::ResetEnable => {}
+            OpCode::Reset if reset_enabled => self.write_enabled.set(false),
+            OpCode::PageProgram | OpCode::SectorErase | OpCode::BlockErase32KB
+                | OpCode::BlockErase64KB | OpCode::ChipErase | OpCode::ChipErase2
+                | OpCode::PageProgram4B | OpCode::SectorErase4B | OpCode::BlockErase64KB4B => {
+                if !self.write_enabled.get() {
+                    println!("SPI flash: {:?} without write enable", header.opcode);
+                    return Ok(());
+                }
+                self.write_enabled.set(false);
+                match header.opcode {
+                    OpCode::PageProgram | OpCode::PageProgram4B => self.program(address, data)?,
+                    OpCode::SectorErase | OpCode::SectorErase4B => self.erase(address, SECTOR_SIZE)?,
+                    OpCode::BlockErase32KB => self.erase(address, BLOCK_32KB_SIZE)?,
+                    OpCode::BlockErase64KB | OpCode::BlockErase64KB4B => self.erase(address, BLOCK_64KB_SIZE)?,
+                    _ => {
+                        let len = self.data.borrow().len();
+                        self.erase(0, len)?;
+                    }
+                }
+            }
+            _ => println!("SPI flash: ignoring {:?}", header.opcode),
+        }
+        Ok(())
+    }
+}
+
+impl SpiHost for FakeSpiFlash {
+    fn read_write_bytes(&self, write_buffer: &mut[u8], read_write_length: usize) -> TockResult<()> {
+        let mut data = write_buffer.get(..read_write_length).ok_or(TockError::Format)?;
+        let address_mode = data.first()
+            .and_then(|opcode| OpCode::from_wire_value(*opcode))
+            .map_or(self.address_mode.get(), |opcode| opcode.address_mode(self.address_mode.get()));
+        match address_mode {
+            AddressMode::ThreeByte => {
+                let header = Header::<ux::u24>::from_wire(&mut data).map_err(|_| TockError::Format)?;
+                self.execute(&header, data)
+            }
+            AddressMode::FourByte => {
+                let header = Header::<u32>::from_wire(&mut data).map_err(|_| TockError::Format)?;
+                self.execute(&header, data)
+            }
+        }
+    }
+
+    fn wait_read_write_done(&self) {
+        // All transactions complete synchronously.
+    }
+}
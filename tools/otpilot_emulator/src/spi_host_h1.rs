@@ -0,0 +1,41 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+
+//! Replacement for the H1-specific SPI host driver.
+
+use libtock::result::TockResult;
+
+pub trait SpiHostH1 {
+    /// Enable/disable waiting for BUSY bit to clear in transactions.
+    fn set_wait_busy_clear_in_transactions(&self, enabled: bool) -> TockResult<()>;
+}
+
+// Get the static SpiHostH1 object.
+pub fn get() -> &'static dyn SpiHostH1 {
+    &SPI_HOST_H1
+}
+
+struct SpiHostH1Impl;
+
+static SPI_HOST_H1: SpiHostH1Impl = SpiHostH1Impl;
+
+impl SpiHostH1 for SpiHostH1Impl {
+    // The emulated SPI flash completes all operations immediately.
+    fn set_wait_busy_clear_in_transactions(&self, _enabled: bool) -> TockResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+// The dcrypto firmware for ECDSA P-256 is shared with the U2F app.
+const DCRYPTO_P256_SOURCE: &str = "../u2f_app/p256_ecdsa.c";
+
+// Generate dcrypto_p256.rs from the dcrypto program in the C source.
+fn generate_dcrypto_p256(source: &str) -> String {
+    let mut words = Vec::new();
+    let mut p256init = None;
+    let mut p256sign = None;
+
+    let mut in_program = false;
+    for line in source.lines() {
+        let line = line.trim();
+        if line.starts_with("static uint32_t IMEM_dcrypto_p256[]") {
+            in_program = true;
+        } else if in_program && line.starts_with("};") {
+            break;
+        } else if in_program && line.starts_with("0x") {
+            let word = line.split(',').next().unwrap();
+            words.push(word.to_string());
+        } else if line.starts_with("#define ") {
+            let mut fields = line.split_whitespace().skip(1);
+            let (name, address) = (fields.next(), fields.next().map(str::to_string));
+            match name {
+                Some("CF_p256init_adr") => p256init = address,
+                Some("CF_p256sign_adr") => p256sign = address,
+                _ => (),
+            }
+        }
+    }
+    assert!(!words.is_empty(), "No dcrypto program in {}", DCRYPTO_P256_SOURCE);
+
+    let mut out = String::new();
+    writeln!(out, "pub const P256INIT: usize = {};",
+        p256init.expect("No CF_p256init_adr")).unwrap();
+    writeln!(out, "pub const P256SIGN: usize = {};",
+        p256sign.expect("No CF_p256sign_adr")).unwrap();
+    writeln!(out, "pub const PROGRAM_LEN: usize = {};", words.len()).unwrap();
+    writeln!(out, "pub static mut PROGRAM: [u32; PROGRAM_LEN] = [").unwrap();
+    for word in words {
+        writeln!(out, "    {},", word).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", DCRYPTO_P256_SOURCE);
+
+    let src = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(DCRYPTO_P256_SOURCE);
+    let dst = Path::new(&env::var("OUT_DIR").unwrap()).join("dcrypto_p256.rs");
+    fs::write(dst, generate_dcrypto_p256(&fs::read_to_string(src).unwrap())).unwrap();
+}
@@ -23,6 +23,10 @@ pub trait Alarm {
     // Get clock frequency in Hz.
     fn get_clock_frequency(&self) -> usize;
 
+    // Get the current value of the alarm clock in ticks.
+    // The value wraps around.
+    fn get_ticks(&self) -> TockResult<usize>;
+
     // Set alarm to occur after `ticks`.
     fn set(&self, ticks: usize) -> TockResult<()>;
 
@@ -43,10 +47,15 @@ const DRIVER_NUMBER: usize = 0x00000;
 mod command_nr {
     pub const CHECK_IF_PRESENT: usize = 0;
     pub const GET_CLOCK_FREQUENCY: usize = 1;
+    pub const GET_CLOCK_VALUE: usize = 2;
     pub const STOP_ALARM: usize = 3;
     pub const SET_RELATIVE_ALARM: usize = 5;
 }
 
+// The lowest error code returned by the kernel (ENOACK). The clock value is
+// misread as an error in the few ticks before it wraps around.
+const MIN_RETURN_CODE: isize = -13;
+
 mod subscribe_nr {
     pub const ALARM_EXPIRED: usize = 0;
 }
@@ -117,6 +126,17 @@ impl Alarm for AlarmImpl {
         self.clock_frequency
     }
 
+    fn get_ticks(&self) -> TockResult<usize> {
+        match syscalls::command(DRIVER_NUMBER, command_nr::GET_CLOCK_VALUE, 0, 0) {
+            Ok(ticks) => Ok(ticks),
+            // The clock value uses the full range of the return value, so
+            // syscalls::command also rejects values with the top bit set.
+            // Only the values of the kernel's error codes are errors.
+            Err(err) if err.return_code < MIN_RETURN_CODE => Ok(err.return_code as usize),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     fn set(&self, ticks: usize) -> TockResult<()> {
         self.alarm_expired.set(false);
         self.alarm_id.set(None);
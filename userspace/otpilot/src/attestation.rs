@@ -0,0 +1,329 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cerberus attestation: GetDigests, GetCertificate and Challenge.
+//!
+//! The device certificate and the personality key are taken from the
+//! personality data. The certificate chain consists of the device
+//! certificate only, and slot 0 is the only supported slot. Challenge
+//! responses are signed with the personality key (ECDSA P-256 with SHA-256)
+//! and report PMR0, the digest of the active RW. The personality key is
+//! derived from the key ladder for each signature and wiped afterwards.
+
+use crate::ecdsa;
+use crate::key_ladder;
+use crate::manticore_support::error_nr;
+use crate::manticore_support::write_error;
+use crate::manticore_support::ERROR_LEN;
+use crate::personality;
+use crate::rng;
+
+use libtock::println;
+
+use spiutils::crypto::p256::PrivateKey;
+use spiutils::crypto::p256::MAX_DER_SIGNATURE_LEN;
+use spiutils::crypto::p256::SCALAR_LEN;
+use spiutils::crypto::wipe;
+use spiutils::driver::personality::CERTIFICATE_HASH_OFFSET;
+use spiutils::driver::personality::CERTIFICATE_LEN_OFFSET;
+use spiutils::driver::personality::CERTIFICATE_OFFSET;
+use spiutils::driver::personality::CHECKSUM_OFFSET;
+use spiutils::driver::personality::MAX_CERTIFICATE_LEN;
+use spiutils::driver::personality::PERSONALITY_LEN;
+use spiutils::driver::personality::PUB_X_OFFSET;
+use spiutils::driver::personality::PUB_Y_OFFSET;
+use spiutils::driver::personality::SALT_OFFSET;
+use spiutils::protocol::integrity::Sha256;
+use spiutils::protocol::integrity::SHA256_LEN;
+
+use manticore::protocol::CommandType;
+
+// The only supported slot.
+const SLOT: u8 = 0;
+
+// The protocol version reported in challenge responses.
+const PROTOCOL_VERSION: u8 = 1;
+
+// The length of the challenge nonces.
+const NONCE_LEN: usize = 32;
+
+// The personality data does not fit on the stack.
+static mut PERSONALITY_DATA: [u8; PERSONALITY_LEN] = [0; PERSONALITY_LEN];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AttestationError {
+    // The request could not be parsed.
+    Malformed,
+
+    // The request refers to an unsupported slot or certificate.
+    InvalidSlot,
+
+    // There is no valid personality data.
+    NotProvisioned,
+
+    // A driver failed.
+    Tock,
+
+    // The response does not fit into the output buffer.
+    Serialization,
+}
+
+pub type AttestationResult<T> = Result<T, AttestationError>;
+
+// The identity loaded from the personality data.
+struct Personality {
+    certificate: &'static [u8],
+    certificate_hash: [u8; SHA256_LEN],
+
+    // The key ladder input of the personality key.
+    salt: [u8; SCALAR_LEN],
+}
+
+pub struct Attestation {
+    // None if the device is not provisioned.
+    personality: Option<Personality>,
+
+    // The digest of the active RW.
+    pmr0: [u8; SHA256_LEN],
+}
+
+// Copy 32 bytes from data at offset.
+fn get_bytes(data: &[u8], offset: usize) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&data[offset..offset + 32]);
+    out
+}
+
+// The public key coordinates are stored as little-endian numbers.
+fn get_coordinate(data: &[u8], offset: usize) -> [u8; SCALAR_LEN] {
+    let mut out = get_bytes(data, offset);
+    out.reverse();
+    out
+}
+
+// Derive the personality key from salt.
+fn derive_key(salt: &[u8; SCALAR_LEN]) -> AttestationResult<PrivateKey> {
+    let mut seed = key_ladder::get().derive_attest(salt).map_err(|_| AttestationError::Tock)?;
+    let key = PrivateKey::from_seed(&seed);
+    wipe(&mut seed);
+    Ok(key)
+}
+
+fn load_personality() -> AttestationResult<Personality> {
+    // TODO(osk): We need the unsafe block since we're accessing PERSONALITY_DATA as &mut.
+    let data = unsafe { &mut PERSONALITY_DATA };
+    personality::get().read(data).map_err(|_| AttestationError::Tock)?;
+    let data: &'static [u8] = data;
+
+    let certificate_len = u32::from_le_bytes([
+        data[CERTIFICATE_LEN_OFFSET],
+        data[CERTIFICATE_LEN_OFFSET + 1],
+        data[CERTIFICATE_LEN_OFFSET + 2],
+        data[CERTIFICATE_LEN_OFFSET + 3]]) as usize;
+    if certificate_len == 0 || certificate_len > MAX_CERTIFICATE_LEN {
+        return Err(AttestationError::NotProvisioned);
+    }
+    let certificate = &data[CERTIFICATE_OFFSET..CERTIFICATE_OFFSET + certificate_len];
+
+    let certificate_hash = get_bytes(data, CERTIFICATE_HASH_OFFSET);
+    if Sha256::new().add(certificate).finish() != certificate_hash {
+        return Err(AttestationError::NotProvisioned);
+    }
+
+    // The checksum binds the certificate to this chip.
+    let checksum = key_ladder::get().derive_attest(&certificate_hash)
+        .map_err(|_| AttestationError::Tock)?;
+    if checksum != get_bytes(data, CHECKSUM_OFFSET) {
+        return Err(AttestationError::NotProvisioned);
+    }
+
+    let salt = get_bytes(data, SALT_OFFSET);
+    let public_key = derive_key(&salt)?.public_key();
+    if public_key.x != get_coordinate(data, PUB_X_OFFSET)
+        || public_key.y != get_coordinate(data, PUB_Y_OFFSET) {
+        return Err(AttestationError::NotProvisioned);
+    }
+
+    Ok(Personality {
+        certificate,
+        certificate_hash,
+        salt,
+    })
+}
+
+/// Check whether the Manticore command is answered by Attestation.
+pub fn is_attestation_command(command: CommandType) -> bool {
+    matches!(command, CommandType::GetDigests | CommandType::GetCert | CommandType::Challenge)
+}
+
+impl Attestation {
+    /// Load the device certificate and the personality key.
+    /// pmr0: The digest of the active RW.
+    pub fn load(pmr0: [u8; SHA256_LEN]) -> Attestation {
+        let personality = match load_personality() {
+            Ok(personality) => {
+                println!("Attestation: certificate of {} bytes", personality.certificate.len());
+                Some(personality)
+            }
+            Err(why) => {
+                println!("Attestation: not available: {:?}", why);
+                None
+            }
+        };
+        Attestation {
+            personality,
+            pmr0,
+        }
+    }
+
+    fn get_personality(&self) -> AttestationResult<&Personality> {
+        self.personality.as_ref().ok_or(AttestationError::NotProvisioned)
+    }
+
+    /// Answer the attestation request `command` with the payload in request.
+    /// request_header: The serialized Cerberus header of the request.
+    /// response_header: The serialized Cerberus header of the response.
+    /// Returns the command and the length of the response payload written
+    /// to response. Requests that cannot be answered get an error message.
+    pub fn process_request(&self, command: CommandType, request_header: &[u8], request: &[u8],
+                           response_header: &[u8], response: &mut [u8])
+                           -> AttestationResult<(CommandType, usize)> {
+        if response.len() < ERROR_LEN {
+            return Err(AttestationError::Serialization);
+        }
+
+        // TODO: Parse the requests and serialize the responses with the
+        // manticore::protocol message types once this has been built against
+        // the pinned third_party/manticore. The layouts below are written by
+        // hand after the Cerberus specification.
+        let result = match command {
+            CommandType::GetDigests => self.get_digests(request, response),
+            CommandType::GetCert => self.get_certificate(request, response),
+            CommandType::Challenge => self.challenge(
+                (request_header, request), (response_header, response)),
+            _ => return Err(AttestationError::Malformed),
+        };
+
+        let error_code = match result {
+            Ok(len) => return Ok((command, len)),
+            Err(why @ AttestationError::Malformed)
+            | Err(why @ AttestationError::Serialization) => return Err(why),
+            Err(AttestationError::InvalidSlot) => error_nr::INVALID_REQUEST,
+            Err(AttestationError::NotProvisioned)
+            | Err(AttestationError::Tock) => error_nr::UNSPECIFIED,
+        };
+        Ok((CommandType::Error, write_error(response, error_code, 0)))
+    }
+
+    // Request: slot (1), key exchange algorithm (1).
+    // Response: number of digests (1), digests (32 each).
+    fn get_digests(&self, request: &[u8], response: &mut [u8]) -> AttestationResult<usize> {
+        if request.len() != 2 {
+            return Err(AttestationError::Malformed);
+        }
+        if request[0] != SLOT {
+            return Err(AttestationError::InvalidSlot);
+        }
+        let personality = self.get_personality()?;
+
+        let len = 1 + SHA256_LEN;
+        if response.len() < len {
+            return Err(AttestationError::Serialization);
+        }
+        response[0] = 1;
+        response[1..len].copy_from_slice(&personality.certificate_hash);
+        Ok(len)
+    }
+
+    // Request: slot (1), certificate number (1), offset (2), length (2).
+    // Response: slot (1), certificate number (1), certificate data.
+    // A length of 0 requests the remainder of the certificate.
+    fn get_certificate(&self, request: &[u8], response: &mut [u8]) -> AttestationResult<usize> {
+        if request.len() != 6 {
+            return Err(AttestationError::Malformed);
+        }
+        if request[0] != SLOT || request[1] != 0 {
+            return Err(AttestationError::InvalidSlot);
+        }
+        let certificate = self.get_personality()?.certificate;
+
+        let offset = u16::from_le_bytes([request[2], request[3]]) as usize;
+        if offset > certificate.len() {
+            return Err(AttestationError::Malformed);
+        }
+        let mut len = u16::from_le_bytes([request[4], request[5]]) as usize;
+        if len == 0 || len > certificate.len() - offset {
+            len = certificate.len() - offset;
+        }
+        if response.len() < 2 {
+            return Err(AttestationError::Serialization);
+        }
+        // Return as much as fits into the response.
+        len = core::cmp::min(len, response.len() - 2);
+
+        response[0] = request[0];
+        response[1] = request[1];
+        response[2..2 + len].copy_from_slice(&certificate[offset..offset + len]);
+        Ok(2 + len)
+    }
+
+    // Request: slot (1), reserved (1), nonce (32).
+    // Response: slot (1), slot mask (1), min/max protocol version (2),
+    //           reserved (2), nonce (32), number of components (1),
+    //           PMR0 length (1), PMR0, DER signature.
+    // The signature covers the request and the response messages, headers
+    // included, up to the signature.
+    fn challenge(&self, (request_header, request): (&[u8], &[u8]),
+                 (response_header, response): (&[u8], &mut [u8])) -> AttestationResult<usize> {
+        if request.len() != 2 + NONCE_LEN {
+            return Err(AttestationError::Malformed);
+        }
+        if request[0] != SLOT {
+            return Err(AttestationError::InvalidSlot);
+        }
+        let personality = self.get_personality()?;
+
+        let signed_len = 6 + NONCE_LEN + 2 + SHA256_LEN;
+        if response.len() < signed_len + MAX_DER_SIGNATURE_LEN {
+            return Err(AttestationError::Serialization);
+        }
+        response[0] = request[0];
+        response[1] = 1 << SLOT;
+        response[2] = PROTOCOL_VERSION;
+        response[3] = PROTOCOL_VERSION;
+        response[4] = 0;
+        response[5] = 0;
+        rng::get().fill(&mut response[6..6 + NONCE_LEN]).map_err(|_| AttestationError::Tock)?;
+        response[6 + NONCE_LEN] = 1;
+        response[7 + NONCE_LEN] = SHA256_LEN as u8;
+        response[8 + NONCE_LEN..signed_len].copy_from_slice(&self.pmr0);
+
+        let digest = Sha256::new()
+            .add(request_header)
+            .add(request)
+            .add(response_header)
+            .add(&response[..signed_len])
+            .finish();
+        let key = derive_key(&personality.salt)?;
+        let signature = ecdsa::get().sign(&key, &digest)
+            .map_err(|_| AttestationError::Tock)?;
+
+        let mut der = [0u8; MAX_DER_SIGNATURE_LEN];
+        let der_len = signature.to_der(&mut der);
+        response[signed_len..signed_len + der_len].copy_from_slice(&der[..der_len]);
+        Ok(signed_len + der_len)
+    }
+}
@@ -18,19 +18,29 @@ use crate::console_reader;
 use crate::firmware_controller;
 use crate::globalsec;
 use crate::gpio_processor::GpioProcessor;
+use crate::measured_boot::MeasuredBoot;
+use crate::reboot_scheduler::RebootScheduler;
 use crate::reset;
+use crate::write_protect::WriteProtect;
 
 use libtock::println;
 use libtock::result::TockResult;
 
 pub struct ConsoleProcessor<'a> {
-    gpio_processor: &'a GpioProcessor,
+    gpio_processor: &'a GpioProcessor<'a>,
+    reboot: &'a RebootScheduler,
+    measured_boot: &'a MeasuredBoot,
+    write_protect: &'a WriteProtect,
 }
 
 impl<'a> ConsoleProcessor<'a> {
-    pub fn new(gpio_processor: &'a GpioProcessor) -> ConsoleProcessor<'a> {
+    pub fn new(gpio_processor: &'a GpioProcessor<'a>, reboot: &'a RebootScheduler,
+               measured_boot: &'a MeasuredBoot, write_protect: &'a WriteProtect) -> ConsoleProcessor<'a> {
         ConsoleProcessor {
             gpio_processor: gpio_processor,
+            reboot: reboot,
+            measured_boot: measured_boot,
+            write_protect: write_protect,
         }
     }
 
@@ -44,6 +54,13 @@ impl<'a> ConsoleProcessor<'a> {
         println!("@ : Deassert BMC_SRST.");
         println!("i : Read firmware info.");
         println!("R : Reset chip.");
+        println!("p : Show pending reboot.");
+        println!("c : Cancel pending reboot.");
+        println!("m : Show measured boot and recovery status.");
+        println!("v : Allow provisioning the measured boot manifest via the mailbox.");
+        println!("V : Unlock the measured boot manifest.");
+        println!("w : Allow provisioning the write protection policy via the mailbox.");
+        println!("W : Unlock the write protection policy.");
 
         Ok(())
     }
@@ -83,6 +100,43 @@ impl<'a> ConsoleProcessor<'a> {
                 println!("resetting ...");
                 reset::get().reset()?;
             }
+            'p' => {
+                if self.reboot.is_pending() {
+                    match self.reboot.get_remaining_msecs()? {
+                        Some(msecs) => println!("reboot pending, timeout in {} ms", msecs),
+                        None => println!("reboot pending, no timeout"),
+                    }
+                } else {
+                    println!("no reboot pending");
+                }
+            },
+            'c' => {
+                println!("cancelling pending reboot");
+                self.reboot.cancel();
+            },
+            'm' => {
+                let manifest = self.measured_boot.manifest();
+                println!("measured boot: {:?}, {} region(s), result {:?}, failed region {}",
+                    manifest.mode, manifest.regions().len(), self.measured_boot.result(),
+                    self.measured_boot.failed_region());
+                let status = self.measured_boot.recovery_status();
+                println!("recovery: {:?}, {} attempt(s), copied 0x{:x} of 0x{:x} bytes",
+                    status.result, status.attempts, status.bytes_copied, status.bytes_total);
+            },
+            'v' => {
+                println!("measured boot manifest provisioning enabled");
+                self.measured_boot.enable_provisioning();
+            },
+            'V' => {
+                println!("unlocking measured boot manifest: {:?}", self.measured_boot.unlock());
+            },
+            'w' => {
+                println!("write protection policy provisioning enabled");
+                self.write_protect.enable_provisioning();
+            },
+            'W' => {
+                println!("unlocking write protection policy: {:?}", self.write_protect.unlock());
+            },
             _ => (),
         }
 
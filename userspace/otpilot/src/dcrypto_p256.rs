@@ -0,0 +1,27 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The dcrypto firmware for ECDSA P-256.
+//!
+//! build.rs generates the program from userspace/u2f_app/p256_ecdsa.c,
+//! which the U2F app runs.
+//!
+//! - P256INIT and P256SIGN: entry points into PROGRAM, as word offsets.
+//! - PROGRAM_LEN: the number of instructions in PROGRAM.
+//! - PROGRAM: the program. It is kept in RAM, since the kernel only
+//!   accepts allowed buffers there.
+
+include!(concat!(env!("OUT_DIR"), "/dcrypto_p256.rs"));
@@ -0,0 +1,144 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use libtock::result::TockResult;
+use libtock::syscalls;
+
+// The digest modes of the kernel digest driver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DigestMode {
+    Sha256 = 1,
+}
+
+pub trait Digest {
+    // Start a new digest in the specified mode.
+    // The digest engine is reserved for this app until `finalize` is called.
+    fn initialize(&self, mode: DigestMode) -> TockResult<()>;
+
+    // Add data to the digest.
+    // buffer: Buffer with data to add.
+    // len: Number of bytes from buffer to add. Must be <= buffer.len().
+    fn update(&self, buffer: &mut[u8], len: usize) -> TockResult<()>;
+
+    // Finish the digest and write it to output.
+    // output: Buffer for the digest. Must be large enough for the digest mode.
+    fn finalize(&self, output: &mut[u8]) -> TockResult<()>;
+
+    // Run a key ladder step with the specified hardware certificate.
+    // input: Data to add to the step, if the certificate takes any.
+    // output: Buffer for the 32 byte result. If None, the result is only
+    //         kept in the key ladder.
+    fn certificate_step(&self, certificate: u32, input: Option<&mut[u8]>,
+                        output: Option<&mut[u8]>) -> TockResult<()>;
+}
+
+// Get the static Digest object.
+pub fn get() -> &'static dyn Digest {
+    get_impl()
+}
+
+const DRIVER_NUMBER: usize = 0x40003;
+
+mod command_nr {
+    pub const CHECK_IF_PRESENT: usize = 0;
+    pub const INITIALIZE: usize = 1;
+    pub const UPDATE: usize = 2;
+    pub const FINALIZE: usize = 3;
+    pub const CERTIFICATE_INITIALIZE: usize = 5;
+}
+
+mod allow_nr {
+    pub const INPUT_BUFFER: usize = 0;
+    pub const OUTPUT_BUFFER: usize = 1;
+}
+
+struct DigestImpl {}
+
+static mut DIGEST: DigestImpl = DigestImpl {};
+
+static mut IS_INITIALIZED: bool = false;
+
+fn get_impl() -> &'static DigestImpl {
+    unsafe {
+        if !IS_INITIALIZED {
+            if DigestImpl::initialize(&mut DIGEST).is_err() {
+                panic!("Could not initialize Digest");
+            }
+            IS_INITIALIZED = true;
+        }
+        &DIGEST
+    }
+}
+
+impl DigestImpl {
+    fn initialize(&'static mut self) -> TockResult<()> {
+        syscalls::command(DRIVER_NUMBER, command_nr::CHECK_IF_PRESENT, 0, 0)?;
+
+        Ok(())
+    }
+}
+
+impl Digest for DigestImpl {
+    fn initialize(&self, mode: DigestMode) -> TockResult<()> {
+        syscalls::command(DRIVER_NUMBER, command_nr::INITIALIZE, mode as usize, 0)?;
+
+        Ok(())
+    }
+
+    fn update(&self, buffer: &mut[u8], len: usize) -> TockResult<()> {
+        // We want this to go out of scope after executing the command
+        let _buffer_share = syscalls::allow(DRIVER_NUMBER, allow_nr::INPUT_BUFFER, buffer)?;
+
+        syscalls::command(DRIVER_NUMBER, command_nr::UPDATE, len, 0)?;
+
+        Ok(())
+    }
+
+    fn finalize(&self, output: &mut[u8]) -> TockResult<()> {
+        // We want this to go out of scope after executing the command
+        let _buffer_share = syscalls::allow(DRIVER_NUMBER, allow_nr::OUTPUT_BUFFER, output)?;
+
+        syscalls::command(DRIVER_NUMBER, command_nr::FINALIZE, 0, 0)?;
+
+        Ok(())
+    }
+
+    fn certificate_step(&self, certificate: u32, input: Option<&mut[u8]>,
+                        output: Option<&mut[u8]>) -> TockResult<()> {
+        // The kernel releases the engine right after initializing the
+        // certificate if no input buffer is shared, and finalizes without
+        // revealing the result if no output buffer is shared.
+        let input_len = input.as_ref().map(|buffer| buffer.len());
+
+        // We want these to go out of scope after executing the commands
+        let _input_share = match input {
+            Some(buffer) => Some(syscalls::allow(DRIVER_NUMBER, allow_nr::INPUT_BUFFER, buffer)?),
+            None => None,
+        };
+        let _output_share = match output {
+            Some(buffer) => Some(syscalls::allow(DRIVER_NUMBER, allow_nr::OUTPUT_BUFFER, buffer)?),
+            None => None,
+        };
+
+        syscalls::command(DRIVER_NUMBER, command_nr::CERTIFICATE_INITIALIZE, certificate as usize, 0)?;
+        if let Some(len) = input_len {
+            syscalls::command(DRIVER_NUMBER, command_nr::UPDATE, len, 0)?;
+            syscalls::command(DRIVER_NUMBER, command_nr::FINALIZE, 0, 0)?;
+        }
+
+        Ok(())
+    }
+}
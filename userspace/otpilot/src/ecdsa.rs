@@ -0,0 +1,233 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::dcrypto_p256;
+use crate::rng;
+
+use core::cell::Cell;
+use core::mem::size_of_val;
+use core::slice;
+
+use libtock::result::TockError;
+use libtock::result::TockResult;
+use libtock::syscalls;
+use libtock::syscalls::raw::yieldk;
+
+use spiutils::crypto::p256::PrivateKey;
+use spiutils::crypto::p256::Signature;
+use spiutils::crypto::p256::SCALAR_LEN;
+use spiutils::crypto::wipe;
+use spiutils::protocol::integrity::SHA256_LEN;
+
+pub trait Ecdsa {
+    // Sign the SHA-256 digest with key on the dcrypto engine.
+    // Waits (yieldk) until the signature is done.
+    fn sign(&self, key: &PrivateKey, digest: &[u8; SHA256_LEN]) -> TockResult<Signature>;
+}
+
+// Get the static Ecdsa object.
+pub fn get() -> &'static dyn Ecdsa {
+    get_impl()
+}
+
+const DRIVER_NUMBER: usize = 0x40004;
+
+mod command_nr {
+    pub const CHECK_IF_PRESENT: usize = 0;
+    pub const RUN: usize = 1;
+}
+
+mod subscribe_nr {
+    pub const RUN_DONE: usize = 0;
+}
+
+mod allow_nr {
+    pub const DATA: usize = 0;
+    pub const PROGRAM: usize = 1;
+}
+
+// The cells of the dcrypto data memory that hold the parameters of the
+// program. Cell 0 holds the cell index of each parameter, in this order.
+mod cell {
+    pub const K: usize = 1;
+    pub const RND: usize = 2;
+    pub const MSG: usize = 3;
+    pub const R: usize = 4;
+    pub const S: usize = 5;
+    pub const X: usize = 6;
+    pub const Y: usize = 7;
+    pub const D: usize = 8;
+}
+
+const CELL_LEN: usize = 32;
+
+const DATA_LEN: usize = (cell::D + 1) * CELL_LEN;
+
+// The dcrypto data memory. The kernel copies it back when the program is done.
+static mut DATA: [u8; DATA_LEN] = [0; DATA_LEN];
+
+struct EcdsaImpl {
+    // The error reported for the last run, if it is done.
+    run_error: Cell<Option<usize>>,
+}
+
+static mut ECDSA: EcdsaImpl = EcdsaImpl {
+    run_error: Cell::new(None),
+};
+
+static mut IS_INITIALIZED: bool = false;
+
+fn get_impl() -> &'static EcdsaImpl {
+    unsafe {
+        if !IS_INITIALIZED {
+            if ECDSA.initialize().is_err() {
+                panic!("Could not initialize Ecdsa");
+            }
+            IS_INITIALIZED = true;
+        }
+        &ECDSA
+    }
+}
+
+impl EcdsaImpl {
+    fn initialize(&'static mut self) -> TockResult<()> {
+        syscalls::command(DRIVER_NUMBER, command_nr::CHECK_IF_PRESENT, 0, 0)?;
+
+        syscalls::subscribe_fn(
+            DRIVER_NUMBER,
+            subscribe_nr::RUN_DONE,
+            EcdsaImpl::run_done_trampoline,
+            0)?;
+
+        Ok(())
+    }
+
+    extern "C"
+    fn run_done_trampoline(arg1: usize, arg2: usize, arg3: usize, _data: usize) {
+        get_impl().run_done(arg1, arg2, arg3);
+    }
+
+    fn run_done(&self, error: usize, _fault: usize, _: usize) {
+        self.run_error.set(Some(error));
+    }
+
+    // Set up the parameter cells, as the program expects.
+    fn init_cells(&self, data: &mut [u8; DATA_LEN]) {
+        for index in cell::K..=cell::D {
+            let word = (index as u32).to_le_bytes();
+            let pointer = (index - 1) * 4;
+            data[pointer..pointer + 4].copy_from_slice(&word);
+            // Overwrite the first word of each cell to ensure pairwise mismatch.
+            data[index * CELL_LEN..index * CELL_LEN + 4].copy_from_slice(&word);
+        }
+    }
+
+    // Store the big-endian value in the cell, as a little-endian number.
+    fn set_cell(&self, data: &mut [u8; DATA_LEN], index: usize, value: &[u8; SCALAR_LEN]) {
+        for (dst, src) in data[index * CELL_LEN..(index + 1) * CELL_LEN].iter_mut()
+            .zip(value.iter().rev()) {
+            *dst = *src;
+        }
+    }
+
+    // Get the value of the cell as a big-endian number.
+    fn get_cell(&self, data: &[u8; DATA_LEN], index: usize) -> [u8; SCALAR_LEN] {
+        let mut value = [0u8; SCALAR_LEN];
+        for (dst, src) in value.iter_mut()
+            .zip(data[index * CELL_LEN..(index + 1) * CELL_LEN].iter().rev()) {
+            *dst = *src;
+        }
+        value
+    }
+
+    // Run the program from the specified instruction and wait until it is done.
+    fn run(&self, data: &mut [u8; DATA_LEN], instruction: usize) -> TockResult<()> {
+        // TODO(osk): We need the unsafe block since we're accessing PROGRAM as &mut.
+        let program = unsafe {
+            slice::from_raw_parts_mut(
+                dcrypto_p256::PROGRAM.as_mut_ptr() as *mut u8,
+                size_of_val(&dcrypto_p256::PROGRAM))
+        };
+
+        {
+            // We want this to go out of scope after the program is done
+            let _data_share = syscalls::allow(DRIVER_NUMBER, allow_nr::DATA, data)?;
+            let _program_share = syscalls::allow(DRIVER_NUMBER, allow_nr::PROGRAM, program)?;
+
+            self.run_error.set(None);
+            syscalls::command(DRIVER_NUMBER, command_nr::RUN, instruction, 0)?;
+            while self.run_error.get().is_none() { unsafe { yieldk(); } }
+        }
+
+        if self.run_error.get() != Some(0) {
+            return Err(TockError::Format);
+        }
+        Ok(())
+    }
+
+    // The nonce and the copy of the key are returned in k and d,
+    // so that the caller can wipe them.
+    fn sign_with_data(&self, data: &mut [u8; DATA_LEN], k: &mut [u8; SCALAR_LEN],
+                      d: &mut [u8; SCALAR_LEN], key: &PrivateKey,
+                      digest: &[u8; SHA256_LEN]) -> TockResult<Signature> {
+        self.init_cells(data);
+        self.run(data, dcrypto_p256::P256INIT)?;
+
+        // Pick the nonce in [1, n - 1] by testing candidates (FIPS 186-4,
+        // B.5.2).
+        loop {
+            rng::get().fill(k)?;
+            if PrivateKey::from_bytes(k).is_ok() {
+                break;
+            }
+        }
+        self.set_cell(data, cell::K, k);
+
+        let mut rnd = [0u8; SCALAR_LEN];
+        rng::get().fill(&mut rnd)?;
+        self.set_cell(data, cell::RND, &rnd);
+
+        self.set_cell(data, cell::MSG, digest);
+        *d = key.to_bytes();
+        self.set_cell(data, cell::D, d);
+
+        self.run(data, dcrypto_p256::P256SIGN)?;
+
+        Ok(Signature {
+            r: self.get_cell(data, cell::R),
+            s: self.get_cell(data, cell::S),
+        })
+    }
+}
+
+impl Ecdsa for EcdsaImpl {
+    fn sign(&self, key: &PrivateKey, digest: &[u8; SHA256_LEN]) -> TockResult<Signature> {
+        // TODO(osk): We need the unsafe block since we're accessing DATA as &mut.
+        let data = unsafe { &mut DATA };
+        let mut k = [0u8; SCALAR_LEN];
+        let mut d = [0u8; SCALAR_LEN];
+        let result = self.sign_with_data(data, &mut k, &mut d, key, digest);
+
+        // Wipe the key and the nonce, also if signing failed.
+        wipe(&mut k);
+        wipe(&mut d);
+        for index in [cell::K, cell::D, cell::X, cell::Y].iter() {
+            wipe(&mut data[index * CELL_LEN..(index + 1) * CELL_LEN]);
+        }
+
+        result
+    }
+}
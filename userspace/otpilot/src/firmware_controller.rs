@@ -14,6 +14,7 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::digest;
 use crate::flash;
 
 
@@ -26,6 +27,7 @@ use spiutils::compat::firmware::BUILD_INFO_OFFSET;
 use spiutils::compat::firmware::BuildInfo;
 use spiutils::driver::firmware::SegmentInfo;
 use spiutils::driver::firmware::UNKNOWN_SEGMENT;
+use spiutils::protocol::integrity::SHA256_LEN;
 use spiutils::protocol::wire::FromWire;
 
 #[derive(Copy, Clone, Debug)]
@@ -34,6 +36,8 @@ pub enum FirmwareControllerError {
     FlashReadError,
     FlashWriteError,
     FlashOperationFailed,
+    DigestError,
+    OutOfRange,
     Format(core::fmt::Error),
 }
 
@@ -176,6 +180,72 @@ impl FirmwareController {
         self.verify_segment_chunk()
     }
 
+    // Read data.len() bytes at offset within the segment into data.
+    // The offset does not need to be aligned.
+    pub fn read_segment_chunk(&self, segment: SegmentInfo, offset: usize, data: &mut [u8]) -> FirmwareControllerResult<()> {
+        let end = offset + data.len();
+        if end > segment.size as usize {
+            return Err(FirmwareControllerError::OutOfRange);
+        }
+
+        let mut read_buf = [0u8; flash::MAX_BUFFER_LENGTH];
+        let mut pos = offset;
+        while pos < end {
+            // Flash reads must be word aligned and a multiple of 4 bytes.
+            let skip = pos % 4;
+            let chunk_length = core::cmp::min(end - pos, flash::MAX_BUFFER_LENGTH - skip);
+            let read_length = (skip + chunk_length + 3) & !3;
+            if flash::get().read(segment.address as usize + pos - skip, &mut read_buf, read_length).is_err() {
+                println!("flash read failed");
+                return Err(FirmwareControllerError::FlashReadError);
+            }
+            data[pos - offset..pos - offset + chunk_length].copy_from_slice(&read_buf[skip..skip + chunk_length]);
+            pos += chunk_length;
+        }
+
+        Ok(())
+    }
+
+    // Compute the SHA-256 digest of the first `length` bytes of the segment.
+    pub fn compute_segment_digest(&self, segment: SegmentInfo, length: usize) -> FirmwareControllerResult<[u8; SHA256_LEN]> {
+        if length > segment.size as usize {
+            return Err(FirmwareControllerError::OutOfRange);
+        }
+
+        if digest::get().initialize(digest::DigestMode::Sha256).is_err() {
+            println!("digest initialize failed");
+            return Err(FirmwareControllerError::DigestError);
+        }
+
+        let mut read_buf = [0u8; flash::MAX_BUFFER_LENGTH];
+        let mut offset = 0;
+        while offset < length {
+            let chunk_length = core::cmp::min(length - offset, flash::MAX_BUFFER_LENGTH);
+            // Flash reads must be a multiple of 4 bytes.
+            let read_length = (chunk_length + 3) & !3;
+            if flash::get().read(segment.address as usize + offset, &mut read_buf, read_length).is_err() {
+                println!("flash read failed");
+                // Release the digest engine.
+                let _ = digest::get().finalize(&mut [0u8; SHA256_LEN]);
+                return Err(FirmwareControllerError::FlashReadError);
+            }
+            if digest::get().update(&mut read_buf, chunk_length).is_err() {
+                println!("digest update failed");
+                let _ = digest::get().finalize(&mut [0u8; SHA256_LEN]);
+                return Err(FirmwareControllerError::DigestError);
+            }
+            offset += chunk_length;
+        }
+
+        let mut result = [0u8; SHA256_LEN];
+        if digest::get().finalize(&mut result).is_err() {
+            println!("digest finalize failed");
+            return Err(FirmwareControllerError::DigestError);
+        }
+
+        Ok(result)
+    }
+
     pub fn get_max_write_chunk_length(&self) -> usize {
         flash::MAX_BUFFER_LENGTH
     }
@@ -0,0 +1,427 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! The state machine of firmware updates.
+//!
+//! Updates via the firmware protocol and via the Cerberus firmware update
+//! commands share one FirmwareUpdate, so that they follow the same rules:
+//! - Only the inactive segments can be updated.
+//! - The inactive segments must not change while a delayed reboot into them
+//!   is pending.
+//!
+//! The firmware protocol writes chunks at any offset of an inactive segment;
+//! its clients erase the segment with UpdatePrepare first.
+//!
+//! The Cerberus commands update the inactive RW: Prepare Firmware Update
+//! erases it, Update Firmware appends the image data, Activate Firmware
+//! Update checks that the whole image was written and schedules a delayed
+//! reboot, and Update Status reports the outcome of the last step. Data can
+//! only be appended after Prepare Firmware Update.
+
+use crate::firmware_controller::FirmwareController;
+use crate::globalsec;
+use crate::manticore_support::error_nr;
+use crate::manticore_support::write_error;
+use crate::manticore_support::ERROR_LEN;
+use crate::reboot_scheduler::RebootScheduler;
+
+use core::cell::Cell;
+
+use libtock::println;
+
+use spiutils::driver::firmware::SegmentInfo;
+use spiutils::protocol::firmware::SegmentAndLocation;
+
+use manticore::protocol::CommandType;
+
+// Cerberus firmware update status codes.
+mod status_nr {
+    pub const SUCCESS: u32 = 0;
+    pub const INCOMPLETE_IMAGE: u32 = 4;
+    pub const UPDATE_FAILED: u32 = 12;
+    pub const NONE_STARTED: u32 = 23;
+    pub const STAGING_PREP_FAIL: u32 = 24;
+    pub const STAGING_WRITE_FAIL: u32 = 26;
+    pub const REQUEST_BLOCKED: u32 = 28;
+}
+
+// The update type of firmware in Update Status requests.
+const UPDATE_TYPE_FIRMWARE: u8 = 0;
+
+// The length of Prepare Firmware Update requests: image length (4).
+const PREPARE_LEN: usize = 4;
+
+// The maximum length of Update Status requests: update type (1), port (1).
+const MAX_STATUS_LEN: usize = 2;
+
+// The length of Update Status responses: update status (4).
+const STATUS_LEN: usize = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UpdateError {
+    /// The segment is not an inactive segment.
+    InvalidSegment,
+
+    /// The segment is not being updated.
+    NotPrepared,
+
+    /// The offset is outside of the segment.
+    InvalidOffset,
+
+    /// The data does not fit into the segment, the image or a single write.
+    DataTooLong,
+
+    /// The data does not end on a word boundary and is not the end of the image.
+    Unaligned,
+
+    /// The data read back after writing differs.
+    CompareFailed,
+
+    /// Not all data of the image has been written.
+    Incomplete,
+
+    /// A delayed reboot into the inactive segments is pending.
+    Blocked,
+
+    /// Erasing or writing the flash failed.
+    Flash,
+
+    /// Scheduling the reboot into the new image failed.
+    Reboot,
+
+    /// The Cerberus request could not be parsed.
+    Malformed,
+
+    /// The Cerberus request is well-formed, but asks for something that is
+    /// not supported: an update type other than firmware or an empty image.
+    InvalidRequest,
+
+    /// The Cerberus response does not fit into the output buffer.
+    Serialization,
+}
+
+pub type UpdateResult<T> = Result<T, UpdateError>;
+
+/// The outcome of the last update step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UpdateStatus {
+    /// No update was started since boot.
+    NotStarted,
+
+    /// The last step succeeded.
+    Success,
+
+    /// Preparing the segment failed.
+    PrepareFailed(UpdateError),
+
+    /// Writing data failed.
+    WriteFailed(UpdateError),
+
+    /// Completing the update failed.
+    CompleteFailed(UpdateError),
+}
+
+/// Get the segment that may be updated for `identifier`.
+pub fn get_inactive_segment(identifier: SegmentAndLocation) -> Option<SegmentInfo> {
+    if identifier == globalsec::get().get_inactive_rw().identifier {
+        Some(globalsec::get().get_inactive_rw())
+    } else if identifier == globalsec::get().get_inactive_ro().identifier {
+        Some(globalsec::get().get_inactive_ro())
+    } else {
+        None
+    }
+}
+
+/// Check whether the Manticore command is a Cerberus firmware update command.
+pub fn is_update_command(command: CommandType) -> bool {
+    matches!(command,
+        CommandType::PrepareFirmwareUpdate | CommandType::UpdateFirmware
+        | CommandType::UpdateStatus | CommandType::ActivateFirmwareUpdate)
+}
+
+pub struct FirmwareUpdate<'a> {
+    /// Blocks updates while a reboot is pending.
+    reboot: &'a RebootScheduler,
+
+    /// The segment being updated, if it has been prepared.
+    segment: Cell<Option<SegmentInfo>>,
+
+    /// The length of the image, if it was announced when preparing.
+    image_length: Cell<Option<usize>>,
+
+    /// The end of the data written so far.
+    written: Cell<usize>,
+
+    /// The outcome of the last step.
+    status: Cell<UpdateStatus>,
+}
+
+impl<'a> FirmwareUpdate<'a> {
+    pub fn new(reboot: &'a RebootScheduler) -> FirmwareUpdate<'a> {
+        FirmwareUpdate {
+            reboot,
+            segment: Cell::new(None),
+            image_length: Cell::new(None),
+            written: Cell::new(0),
+            status: Cell::new(UpdateStatus::NotStarted),
+        }
+    }
+
+    /// Erase the inactive segment `identifier` so that it takes data.
+    /// image_length: The length of the image, if known. Only updates that
+    ///               know it can `append` data.
+    pub fn prepare(&self, firmware: &mut FirmwareController, identifier: SegmentAndLocation,
+                   image_length: Option<usize>) -> UpdateResult<SegmentInfo> {
+        let result = self.prepare_segment(firmware, identifier, image_length);
+        self.status.set(match result {
+            Ok(_) => UpdateStatus::Success,
+            Err(why) => UpdateStatus::PrepareFailed(why),
+        });
+        result
+    }
+
+    fn prepare_segment(&self, firmware: &mut FirmwareController, identifier: SegmentAndLocation,
+                       image_length: Option<usize>) -> UpdateResult<SegmentInfo> {
+        if self.reboot.is_pending() {
+            return Err(UpdateError::Blocked);
+        }
+        let segment = get_inactive_segment(identifier).ok_or(UpdateError::InvalidSegment)?;
+        if let Some(length) = image_length {
+            if length > segment.size as usize {
+                return Err(UpdateError::DataTooLong);
+            }
+        }
+
+        // Whatever was written before is gone once erasing starts.
+        self.segment.set(None);
+        if let Err(why) = firmware.erase_segment(segment) {
+            println!("update prepare failed: {:?}", why);
+            return Err(UpdateError::Flash);
+        }
+
+        self.segment.set(Some(segment));
+        self.image_length.set(image_length);
+        self.written.set(0);
+        Ok(segment)
+    }
+
+    /// Write data at offset within the inactive segment `identifier`.
+    /// The segment does not need to be prepared.
+    pub fn write(&self, firmware: &mut FirmwareController, identifier: SegmentAndLocation,
+                 offset: usize, data: &[u8]) -> UpdateResult<()> {
+        let result = get_inactive_segment(identifier)
+            .ok_or(UpdateError::InvalidSegment)
+            .and_then(|segment| self.write_segment(firmware, segment, offset, data));
+        self.status.set(match result {
+            Ok(()) => UpdateStatus::Success,
+            Err(why) => UpdateStatus::WriteFailed(why),
+        });
+        result
+    }
+
+    /// Write data right after the data written so far.
+    /// All data but the end of the image must be a multiple of 4 bytes long.
+    /// If writing the flash fails, the segment must be prepared again.
+    pub fn append(&self, firmware: &mut FirmwareController, data: &[u8]) -> UpdateResult<()> {
+        let result = self.append_segment(firmware, data);
+        self.status.set(match result {
+            Ok(()) => UpdateStatus::Success,
+            Err(why) => UpdateStatus::WriteFailed(why),
+        });
+        result
+    }
+
+    fn append_segment(&self, firmware: &mut FirmwareController, data: &[u8]) -> UpdateResult<()> {
+        if self.reboot.is_pending() {
+            return Err(UpdateError::Blocked);
+        }
+        let segment = self.segment.get().ok_or(UpdateError::NotPrepared)?;
+        let image_length = self.image_length.get().ok_or(UpdateError::NotPrepared)?;
+        let offset = self.written.get();
+        let end = offset + data.len();
+        if end > image_length {
+            return Err(UpdateError::DataTooLong);
+        }
+        if end & 3 != 0 && end != image_length {
+            return Err(UpdateError::Unaligned);
+        }
+
+        let mut buffer = [0xffu8; crate::flash::MAX_BUFFER_LENGTH];
+        let chunk_length = firmware.get_max_write_chunk_length();
+        for (index, chunk) in data.chunks(chunk_length).enumerate() {
+            // Pad the end of the image to a full word. Writing 0xff leaves the
+            // erased flash unchanged.
+            let padded_length = (chunk.len() + 3) & !3;
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            for byte in buffer[chunk.len()..padded_length].iter_mut() {
+                *byte = 0xff;
+            }
+            if let Err(why) = self.write_segment(firmware, segment, offset + index * chunk_length,
+                                                 &buffer[..padded_length]) {
+                // The failed chunk may be partially written, and flash words
+                // cannot be written again without erasing them first.
+                self.segment.set(None);
+                return Err(why);
+            }
+        }
+
+        // Appends that fail before writing can be retried from the same offset.
+        self.written.set(end);
+        Ok(())
+    }
+
+    fn write_segment(&self, firmware: &mut FirmwareController, segment: SegmentInfo,
+                     offset: usize, data: &[u8]) -> UpdateResult<()> {
+        if self.reboot.is_pending() {
+            return Err(UpdateError::Blocked);
+        }
+        if offset >= segment.size as usize {
+            return Err(UpdateError::InvalidOffset);
+        }
+        if offset + data.len() > segment.size as usize
+            || data.len() > firmware.get_max_write_chunk_length() {
+            return Err(UpdateError::DataTooLong);
+        }
+
+        match firmware.write_and_verify_segment_chunk(segment, offset, data) {
+            Err(why) => {
+                println!("update write failed: {:?}", why);
+                Err(UpdateError::Flash)
+            },
+            Ok(false) => Err(UpdateError::CompareFailed),
+            Ok(true) => Ok(()),
+        }
+    }
+
+    /// Finish the update. Fails unless the segment was prepared with an image
+    /// length and that much data has been written. The segment takes no more
+    /// data afterwards.
+    pub fn complete(&self) -> UpdateResult<SegmentInfo> {
+        let result = self.complete_segment();
+        self.status.set(match result {
+            Ok(_) => UpdateStatus::Success,
+            Err(why) => UpdateStatus::CompleteFailed(why),
+        });
+        result
+    }
+
+    fn complete_segment(&self) -> UpdateResult<SegmentInfo> {
+        let segment = self.segment.get().ok_or(UpdateError::NotPrepared)?;
+        // Without an image length, there is no telling whether the image is
+        // complete.
+        let length = self.image_length.get().ok_or(UpdateError::Incomplete)?;
+        if self.written.get() < length {
+            return Err(UpdateError::Incomplete);
+        }
+        self.segment.set(None);
+        Ok(segment)
+    }
+
+    /// Answer the Cerberus firmware update request `command` with the payload
+    /// in request. The Cerberus header has already been parsed.
+    /// Returns the command type and the length of the response payload
+    /// written to response.
+    pub fn process_request(&self, firmware: &mut FirmwareController, command: CommandType,
+                           request: &[u8], response: &mut [u8])
+                           -> UpdateResult<(CommandType, usize)> {
+        if response.len() < ERROR_LEN {
+            return Err(UpdateError::Serialization);
+        }
+
+        // TODO: Use the manticore::protocol message types for these commands
+        // where the pinned third_party/manticore provides them. The layouts
+        // below are written by hand after the Cerberus specification.
+        let result = match command {
+            CommandType::PrepareFirmwareUpdate => {
+                // Request: image length (4).
+                if request.len() != PREPARE_LEN {
+                    return Err(UpdateError::Malformed);
+                }
+                let image_length = u32::from_le_bytes(
+                    [request[0], request[1], request[2], request[3]]) as usize;
+                if image_length == 0 {
+                    Err(UpdateError::InvalidRequest)
+                } else {
+                    let identifier = globalsec::get().get_inactive_rw().identifier;
+                    self.prepare(firmware, identifier, Some(image_length)).map(|_| ())
+                }
+            },
+            CommandType::UpdateFirmware => {
+                // Request: image data (1 or more).
+                if request.is_empty() {
+                    return Err(UpdateError::Malformed);
+                }
+                self.append(firmware, request)
+            },
+            CommandType::UpdateStatus => {
+                // Request: update type (1), optionally followed by a port (1).
+                if request.is_empty() || request.len() > MAX_STATUS_LEN {
+                    return Err(UpdateError::Malformed);
+                }
+                if request[0] == UPDATE_TYPE_FIRMWARE {
+                    response[..STATUS_LEN].copy_from_slice(&self.get_cerberus_status().to_le_bytes());
+                    return Ok((CommandType::UpdateStatus, STATUS_LEN));
+                }
+                Err(UpdateError::InvalidRequest)
+            },
+            CommandType::ActivateFirmwareUpdate => {
+                // Request: no payload.
+                if !request.is_empty() {
+                    return Err(UpdateError::Malformed);
+                }
+                self.activate()
+            },
+            _ => return Err(UpdateError::Malformed),
+        };
+
+        // The other commands are answered with an error message.
+        let error_code = match result {
+            Ok(()) => error_nr::NO_ERROR,
+            Err(UpdateError::Blocked) => error_nr::BUSY,
+            Err(UpdateError::InvalidRequest)
+            | Err(UpdateError::NotPrepared)
+            | Err(UpdateError::Incomplete)
+            | Err(UpdateError::DataTooLong)
+            | Err(UpdateError::Unaligned) => error_nr::INVALID_REQUEST,
+            Err(_) => error_nr::UNSPECIFIED,
+        };
+        Ok((CommandType::Error, write_error(response, error_code, self.get_cerberus_status())))
+    }
+
+    // Complete the update and reboot into the new image after a delay.
+    fn activate(&self) -> UpdateResult<()> {
+        self.complete()?;
+        if let Err(why) = self.reboot.schedule() {
+            println!("update activate failed: {:?}", why);
+            self.status.set(UpdateStatus::CompleteFailed(UpdateError::Reboot));
+            return Err(UpdateError::Reboot);
+        }
+        Ok(())
+    }
+
+    fn get_cerberus_status(&self) -> u32 {
+        match self.status.get() {
+            UpdateStatus::NotStarted => status_nr::NONE_STARTED,
+            UpdateStatus::Success => status_nr::SUCCESS,
+            UpdateStatus::PrepareFailed(UpdateError::Blocked)
+            | UpdateStatus::WriteFailed(UpdateError::Blocked) => status_nr::REQUEST_BLOCKED,
+            UpdateStatus::PrepareFailed(_) => status_nr::STAGING_PREP_FAIL,
+            UpdateStatus::WriteFailed(_) => status_nr::STAGING_WRITE_FAIL,
+            UpdateStatus::CompleteFailed(UpdateError::Incomplete) => status_nr::INCOMPLETE_IMAGE,
+            UpdateStatus::CompleteFailed(_) => status_nr::UPDATE_FAILED,
+        }
+    }
+}
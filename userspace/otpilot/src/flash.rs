@@ -22,6 +22,9 @@ use libtock::syscalls::raw::yieldk;
 
 pub const MAX_BUFFER_LENGTH: usize = 128;
 
+// The size of a flash page.
+pub const PAGE_SIZE: usize = 2048;
+
 pub trait Flash {
     // Read from flash.
     // offset: Location relative to flash start to read from. Must be word (4 bytes) aligned.
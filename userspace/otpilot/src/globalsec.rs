@@ -35,6 +35,12 @@ pub trait GlobalSec {
 
     /// Get segment information for inactive RW.
     fn get_inactive_rw(&self) -> SegmentInfo;
+
+    /// Get segment information for the NVMEM in the active bank.
+    fn get_active_nv(&self) -> SegmentInfo;
+
+    /// Get segment information for the NVMEM in the inactive bank.
+    fn get_inactive_nv(&self) -> SegmentInfo;
 }
 
 // Get the static GlobalSec object.
@@ -119,4 +125,12 @@ impl GlobalSec for GlobalSecImpl {
     fn get_inactive_rw(&self) -> SegmentInfo {
         self.runtime_segment_info.inactive_rw
     }
+
+    fn get_active_nv(&self) -> SegmentInfo {
+        self.runtime_segment_info.active_nv
+    }
+
+    fn get_inactive_nv(&self) -> SegmentInfo {
+        self.runtime_segment_info.inactive_nv
+    }
 }
@@ -18,6 +18,8 @@ use crate::alarm;
 use crate::gpio::GpioValue;
 use crate::gpio_control;
 use crate::gpio_control::GpioPin;
+use crate::measured_boot::MeasuredBoot;
+use crate::reboot_scheduler::RebootScheduler;
 use crate::spi_device;
 use crate::spi_host_h1;
 use crate::spi_host_helper::SpiHostHelper;
@@ -29,35 +31,46 @@ use libtock::result::TockResult;
 
 use spiutils::protocol::flash::AddressMode;
 
-pub struct GpioProcessor {
-    /// Whether to ignore bmc_rstmon_n events
-    ignore_bmc_rstmon_n_events: Cell<bool>,
+pub struct GpioProcessor<'a> {
+    /// Pending reboot to execute on BMC or system reset
+    reboot: &'a RebootScheduler,
+
+    /// Verifies the SPI flash chip before the BMC is let out of reset
+    measured_boot: &'a MeasuredBoot,
+
+    /// Alarm clock value when we started to ignore bmc_rstmon_n events,
+    /// or None if they are not ignored
+    ignore_bmc_rstmon_n_since: Cell<Option<usize>>,
 
     /// The initial address mode after resetting the BMC.
     initial_address_mode: AddressMode,
 
-    /// Alarm ticks
-    alarm_ticks: usize,
+    /// Ticks for which bmc_rstmon_n events are ignored after releasing a reset
+    ignore_ticks: usize,
 }
 
-const ALARM_MSECS: u64 = 62;
+const IGNORE_MSECS: u64 = 62;
 const MSECS_IN_SEC: u64 = 1000;
 
-impl GpioProcessor {
-    pub fn new() -> GpioProcessor {
-        let alarm_ticks: u64 =
-            ((alarm::get().get_clock_frequency() as u64) * ALARM_MSECS) / MSECS_IN_SEC;
+impl<'a> GpioProcessor<'a> {
+    pub fn new(reboot: &'a RebootScheduler, measured_boot: &'a MeasuredBoot) -> GpioProcessor<'a> {
+        let ignore_ticks: u64 =
+            ((alarm::get().get_clock_frequency() as u64) * IGNORE_MSECS) / MSECS_IN_SEC;
 
         GpioProcessor {
-            ignore_bmc_rstmon_n_events: Cell::new(false),
+            reboot: reboot,
+            measured_boot: measured_boot,
+            ignore_bmc_rstmon_n_since: Cell::new(None),
             initial_address_mode: spi_device::get().get_address_mode(),
-            alarm_ticks: alarm_ticks as usize,
+            ignore_ticks: ignore_ticks as usize,
         }
     }
 
-    fn set_alarm(&self) -> TockResult<()> {
-        self.ignore_bmc_rstmon_n_events.set(true);
-        alarm::get().set(self.alarm_ticks)
+    // Ignore bmc_rstmon_n events for `ignore_ticks`.
+    // The main loop arms the alarm for the end of this window.
+    fn ignore_bmc_rstmon_n(&self) -> TockResult<()> {
+        self.ignore_bmc_rstmon_n_since.set(Some(alarm::get().get_ticks()?));
+        Ok(())
     }
 
     pub fn set_bmc_cpu_rst(&self, asserted: bool) -> TockResult<()> {
@@ -65,7 +78,7 @@ impl GpioProcessor {
             gpio_control::get().set(GpioPin::BMC_CPU_RST_N, GpioValue::Low)?;
         } else  {
             gpio_control::get().set(GpioPin::BMC_CPU_RST_N, GpioValue::High)?;
-            self.set_alarm()?;
+            self.ignore_bmc_rstmon_n()?;
         }
 
         Ok(())
@@ -76,27 +89,28 @@ impl GpioProcessor {
             gpio_control::get().set(GpioPin::BMC_SRST_N, GpioValue::Low)?;
         } else  {
             gpio_control::get().set(GpioPin::BMC_SRST_N, GpioValue::High)?;
-            self.set_alarm()?;
+            self.ignore_bmc_rstmon_n()?;
         }
 
         Ok(())
     }
 
-    fn handle_bmc_rstmon(&self) -> TockResult<()> {
-        // Put BMC into reset
-        self.set_bmc_cpu_rst(true)?;
-
+    fn measure_spi_flash(&self) -> TockResult<bool> {
         // Disable SPI passthrough
         spi_host_h1::get().set_passthrough(false)?;
 
-        // Read some stuff from the SPI host
-        // TODO: Do something more useful with the data (e.g. checksum) here.
+        // Measure the SPI flash chip through the SPI host
         let host_helper = SpiHostHelper {};
         host_helper.enter_4b()?;
-        host_helper.read_and_print_data(0x0)?;
+        let mut allowed = self.measured_boot.verify(
+            |start, size| host_helper.compute_digest(start, size));
+        if !allowed {
+            allowed = self.measured_boot.recover(
+                |start, size| host_helper.compute_digest(start, size),
+                |source, destination, size| host_helper.copy(source, destination, size));
+        }
 
         // Set expected initial address mode
-        let host_helper = SpiHostHelper {};
         match self.initial_address_mode {
             AddressMode::ThreeByte => host_helper.exit_4b()?,
             AddressMode::FourByte => host_helper.enter_4b()?,
@@ -106,11 +120,40 @@ impl GpioProcessor {
         // Enable SPI passthrough
         spi_host_h1::get().set_passthrough(true)?;
 
+        Ok(allowed)
+    }
+
+    /// Verify the SPI flash chip against the measured boot manifest and
+    /// recover it from the recovery region if it does not match.
+    /// The BMC must be held in reset.
+    /// Returns whether the BMC may be let out of reset.
+    pub fn verify_spi_flash(&self) -> bool {
+        if !self.measured_boot.is_enabled() {
+            return true;
+        }
+        let allowed = match self.measure_spi_flash() {
+            Ok(allowed) => allowed,
+            Err(_) => self.measured_boot.record_error(),
+        };
+        if !allowed {
+            println!("Measured boot: Holding BMC in reset");
+        }
+        allowed
+    }
+
+    fn handle_bmc_rstmon(&self) -> TockResult<()> {
+        // Put BMC into reset
+        self.set_bmc_cpu_rst(true)?;
+
+        let allowed = self.verify_spi_flash();
+
         // We don't care about any events that may have happened during reset.
         gpio_control::get().clear_event(GpioPin::BMC_RSTMON_N);
 
         // Let BMC out of reset
-        self.set_bmc_cpu_rst(false)?;
+        if allowed {
+            self.set_bmc_cpu_rst(false)?;
+        }
 
         Ok(())
     }
@@ -118,8 +161,11 @@ impl GpioProcessor {
     pub fn process_gpio_events(&self) -> TockResult<()> {
         let bmc_rstmon_n = gpio_control::get().consume_event(GpioPin::BMC_RSTMON_N);
         if bmc_rstmon_n {
-            if self.ignore_bmc_rstmon_n_events.get() {
+            if self.ignore_bmc_rstmon_n_since.get().is_some() {
                 println!("Ignored bmc_rstmon_n");
+            } else if self.reboot.is_pending() {
+                println!("Handling bmc_rstmon_n: pending reboot");
+                self.reboot.execute()?;
             } else {
                 println!("Handling bmc_rstmon_n");
                 self.handle_bmc_rstmon()?;
@@ -128,15 +174,36 @@ impl GpioProcessor {
 
         let sys_rstmon_n = gpio_control::get().consume_event(GpioPin::SYS_RSTMON_N);
         if sys_rstmon_n {
-            println!("Ignored sys_rstmon_n");
+            if self.reboot.is_pending() {
+                println!("Handling sys_rstmon_n: pending reboot");
+                self.reboot.execute()?;
+            } else {
+                println!("Ignored sys_rstmon_n");
+            }
         }
 
         Ok(())
     }
 
+    /// Get the ticks until bmc_rstmon_n events are no longer ignored.
+    /// Returns None if they are not ignored.
+    pub fn get_remaining_ticks(&self) -> TockResult<Option<usize>> {
+        Ok(match self.ignore_bmc_rstmon_n_since.get() {
+            Some(since) => {
+                let elapsed = alarm::get().get_ticks()?.wrapping_sub(since);
+                Some(self.ignore_ticks.saturating_sub(elapsed))
+            },
+            None => None,
+        })
+    }
+
+    /// Handle the alarm shared with the RebootScheduler and the uptime.
+    /// Stops ignoring bmc_rstmon_n events once their window has passed.
     pub fn alarm_expired(&self) -> TockResult<()> {
-        println!("GPIO: alarm expired");
-        self.ignore_bmc_rstmon_n_events.set(false);
-        alarm::get().clear()
+        if self.get_remaining_ticks()? == Some(0) {
+            println!("GPIO: alarm expired");
+            self.ignore_bmc_rstmon_n_since.set(None);
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,114 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+// Derives device-bound secrets with the H1 key ladder.
+// The steps match the u2f app, so that both derive the same secrets from
+// the same personality.
+
+use crate::digest;
+use crate::rng;
+
+use libtock::result::TockResult;
+
+/// The length of a key ladder input or secret, in bytes.
+pub const SECRET_LEN: usize = 32;
+
+// Value is SHA256("ISR2_SEED").
+const ISR2_SEED: [u32; 8] = [
+    0x704e9863, 0xf61c70d3, 0xd26f32e7, 0x294297e2, 0x4d1e939c, 0x64b3b6a8, 0xb5a31836, 0x1c1f1d7e,
+];
+
+// Value is SHA256("KL_SEED_ATTEST").
+const KL_SEED_ATTEST: [u32; 8] = [
+    0x40640139, 0xcbfacf4a, 0xc2c2c27b, 0x9f2d9cba, 0x8e3d41c3, 0x43bfe954, 0x81cd534f, 0x23804b05,
+];
+
+mod certificate_nr {
+    pub const STIR_RSR: u32 = 28;
+    pub const HCC2: [u32; 7] = [0, 3, 4, 5, 7, 15, 20];
+    pub const HCC2_ROUND: u32 = 25;
+    pub const HCC2_ROUNDS: usize = 255;
+    pub const ISR2: u32 = 34;
+    pub const ISR2_TO_USR0: u32 = 35;
+    pub const HMAC: u32 = 38;
+}
+
+pub trait KeyLadder {
+    // Derive the attestation secret for input.
+    fn derive_attest(&self, input: &[u8; SECRET_LEN]) -> TockResult<[u8; SECRET_LEN]>;
+}
+
+// Get the static KeyLadder object.
+pub fn get() -> &'static dyn KeyLadder {
+    get_impl()
+}
+
+struct KeyLadderImpl {}
+
+static mut KEY_LADDER: KeyLadderImpl = KeyLadderImpl {};
+
+static mut IS_INITIALIZED: bool = false;
+
+fn get_impl() -> &'static KeyLadderImpl {
+    unsafe {
+        if !IS_INITIALIZED {
+            if KEY_LADDER.initialize().is_err() {
+                panic!("Could not initialize KeyLadder");
+            }
+            IS_INITIALIZED = true;
+        }
+        &KEY_LADDER
+    }
+}
+
+// Get the in-memory bytes of a seed.
+fn seed_bytes(seed: &[u32; 8]) -> [u8; SECRET_LEN] {
+    let mut bytes = [0u8; SECRET_LEN];
+    for (chunk, word) in bytes.chunks_mut(4).zip(seed.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+impl KeyLadderImpl {
+    fn initialize(&'static mut self) -> TockResult<()> {
+        let mut salt = [0u8; SECRET_LEN];
+        rng::get().fill(&mut salt)?;
+        digest::get().certificate_step(certificate_nr::STIR_RSR, Some(&mut salt), None)?;
+
+        for certificate in certificate_nr::HCC2.iter() {
+            digest::get().certificate_step(*certificate, None, None)?;
+        }
+        for _ in 0..certificate_nr::HCC2_ROUNDS {
+            digest::get().certificate_step(certificate_nr::HCC2_ROUND, None, None)?;
+        }
+        digest::get().certificate_step(certificate_nr::ISR2, Some(&mut seed_bytes(&ISR2_SEED)), None)?;
+
+        Ok(())
+    }
+}
+
+impl KeyLadder for KeyLadderImpl {
+    fn derive_attest(&self, input: &[u8; SECRET_LEN]) -> TockResult<[u8; SECRET_LEN]> {
+        digest::get().certificate_step(
+            certificate_nr::ISR2_TO_USR0, Some(&mut seed_bytes(&KL_SEED_ATTEST)), None)?;
+
+        let mut input = *input;
+        let mut output = [0u8; SECRET_LEN];
+        digest::get().certificate_step(certificate_nr::HMAC, Some(&mut input), Some(&mut output))?;
+        Ok(output)
+    }
+}
@@ -17,28 +17,49 @@
 #![no_std]
 
 mod alarm;
+mod attestation;
 mod console_processor;
 mod console_reader;
+mod dcrypto_p256;
+mod digest;
+mod ecdsa;
 mod firmware_controller;
+mod firmware_update;
 mod flash;
 mod fuse;
 mod globalsec;
 mod gpio;
 mod gpio_control;
 mod gpio_processor;
+mod key_ladder;
 mod manticore_support;
+mod measured_boot;
+mod nvmem;
+mod personality;
+mod reboot_scheduler;
 mod reset;
+mod reset_counter;
+mod rng;
 mod sfdp;
+mod spi_flash_identity;
 mod spi_host;
 mod spi_host_h1;
 mod spi_host_helper;
 mod spi_device;
 mod spi_processor;
+mod write_protect;
 
+use crate::attestation::Attestation;
 use crate::console_processor::ConsoleProcessor;
+use crate::firmware_update::FirmwareUpdate;
 use crate::gpio_processor::GpioProcessor;
+use crate::measured_boot::MeasuredBoot;
+use crate::reboot_scheduler::RebootScheduler;
+use crate::spi_flash_identity::UnknownPartPolicy;
 use crate::spi_host_helper::SpiHostHelper;
+use crate::spi_processor::SegmentReadAccess;
 use crate::spi_processor::SpiProcessor;
+use crate::write_protect::WriteProtect;
 
 use libtock::println;
 use libtock::result::TockError;
@@ -49,12 +70,26 @@ use spiutils::driver::firmware::SegmentInfo;
 use spiutils::driver::spi_device::AddressConfig;
 use spiutils::driver::spi_device::HandlerMode;
 use spiutils::io::Cursor;
+use spiutils::protocol::error;
 use spiutils::protocol::firmware::SegmentAndLocation;
 use spiutils::protocol::flash::AddressMode;
+use spiutils::protocol::integrity::SHA256_LEN;
+use spiutils::protocol::payload;
 use spiutils::protocol::wire::ToWire;
 
 libtock_core::stack_size! {2048}
 
+// Time after which a delayed reboot is executed if neither the BMC nor the
+// system was reset.
+const DELAYED_REBOOT_TIMEOUT_MSECS: Option<u32> = Some(30 * 60 * 1000);
+
+// Interval after which the uptime is updated while otpilot is idle.
+// This must be well within the wraparound of the alarm clock.
+const UPTIME_UPDATE_MSECS: u64 = 10 * 60 * 1000;
+
+// What to do if the SPI flash chip behind the SPI host cannot be identified.
+const UNKNOWN_SPI_FLASH_POLICY: UnknownPartPolicy = UnknownPartPolicy::UseDefault;
+
 //////////////////////////////////////////////////////////////////////////////
 
 fn run_host_helper_demo() -> TockResult<()> {
@@ -101,6 +136,56 @@ fn store_build_info(segment_info: SegmentInfo, mut buf: &mut[u8]) {
     }
 }
 
+// The alarm is shared by the GpioProcessor, the RebootScheduler and the
+// uptime. Handle whichever of them is due and arm the alarm for the earliest
+// remaining deadline. The alarm is only re-armed if it fired or if a deadline
+// comes before the armed one.
+// alarm_deadline: The alarm clock value at which the armed alarm fires.
+fn handle_alarm(gpio_processor: &GpioProcessor, reboot_scheduler: &RebootScheduler,
+                manticore_reset: &manticore_support::Reset, uptime_update_ticks: usize,
+                alarm_deadline: &mut Option<usize>)
+                -> TockResult<()> {
+    use core::cmp::max;
+    use core::cmp::min;
+
+    if alarm::get().is_expired() {
+        alarm::get().clear()?;
+        *alarm_deadline = None;
+    }
+    gpio_processor.alarm_expired()?;
+    reboot_scheduler.alarm_expired()?;
+
+    // Keep the alarm running so that no wraparound of the alarm clock is
+    // missed in the uptime.
+    manticore_reset.update_uptime();
+
+    let mut ticks = uptime_update_ticks;
+    let deadlines = [
+        gpio_processor.get_remaining_ticks()?,
+        reboot_scheduler.get_remaining_ticks()?,
+    ];
+    for remaining in deadlines.iter().flatten() {
+        ticks = min(ticks, *remaining);
+    }
+    // A deadline may pass right after it was checked. An alarm in the past
+    // would only fire once the alarm clock wraps around.
+    let ticks = max(ticks, 1);
+
+    let now = alarm::get().get_ticks()?;
+    if let Some(deadline) = *alarm_deadline {
+        // An armed alarm that already passed wraps around to a large value
+        // and is replaced.
+        if deadline.wrapping_sub(now) <= ticks {
+            return Ok(());
+        }
+    }
+    *alarm_deadline = None;
+    alarm::get().clear()?;
+    alarm::get().set(ticks)?;
+    *alarm_deadline = Some(now.wrapping_add(ticks));
+    Ok(())
+}
+
 fn run() -> TockResult<()> {
     use core::cmp::min;
 
@@ -108,6 +193,9 @@ fn run() -> TockResult<()> {
 
     run_host_helper_demo()?;
 
+    // Identify the SPI flash chip so that the BMC sees its geometry.
+    let spi_flash = spi_flash_identity::probe(UNKNOWN_SPI_FLASH_POLICY)?;
+
     //////////////////////////////////////////////////////////////////////////////
 
     // Initialize Manticore identity data.
@@ -138,14 +226,54 @@ fn run() -> TockResult<()> {
 
     //////////////////////////////////////////////////////////////////////////////
 
+    let reboot_scheduler = RebootScheduler::new(DELAYED_REBOOT_TIMEOUT_MSECS);
+    let measured_boot = MeasuredBoot::load();
+    let write_protect = WriteProtect::load();
+
+    let resets_since_power_on = reset_counter::update().unwrap_or_else(|_| {
+        println!("Could not update the reset count");
+        0
+    });
+    println!("Resets since power-on: {}", resets_since_power_on);
+    let manticore_reset = manticore_support::Reset::new(resets_since_power_on);
+    let uptime_update_ticks =
+        (alarm::get().get_clock_frequency() as u64 * UPTIME_UPDATE_MSECS / 1000) as usize;
+
+    // PMR0 for attestation is the digest of the active RW.
+    let firmware = firmware_controller::FirmwareController::new();
+    let active_rw = globalsec::get().get_active_rw();
+    let pmr0 = firmware.compute_segment_digest(active_rw, active_rw.size as usize)
+        .unwrap_or_else(|_| {
+            println!("Could not compute PMR0");
+            [0; SHA256_LEN]
+        });
+    let attestation = Attestation::load(pmr0);
+    let firmware_update = FirmwareUpdate::new(&reboot_scheduler);
+
     let mut spi_processor = SpiProcessor {
-        manticore_handler: manticore_support::Handler::new(&identity),
+        manticore_handler: manticore_support::Handler::new(
+            &identity, &manticore_reset, &attestation, &firmware_update),
         print_flash_headers: false,  // Enable to print incoming SPI flash headers
-        firmware: firmware_controller::FirmwareController::new(),
+        firmware,
+        update: &firmware_update,
+        read_access: SegmentReadAccess {
+            active_ro: true,
+            active_rw: true,
+            inactive_ro: true,
+            inactive_rw: true,
+        },
+        reboot: &reboot_scheduler,
+        reassembler: payload::Reassembler::new(),
+        fragmenter: payload::Fragmenter::new(),
+        integrity: None,
+        request: error::NO_REQUEST,
+        write_protect: &write_protect,
+        measured_boot: &measured_boot,
     };
 
-    let gpio_processor = GpioProcessor::new();
-    let console_processor = ConsoleProcessor::new(&gpio_processor);
+    let gpio_processor = GpioProcessor::new(&reboot_scheduler, &measured_boot);
+    let console_processor = ConsoleProcessor::new(
+        &gpio_processor, &reboot_scheduler, &measured_boot, &write_protect);
 
     //////////////////////////////////////////////////////////////////////////////
 
@@ -153,20 +281,16 @@ fn run() -> TockResult<()> {
     spi_device::get().configure_addresses(AddressConfig {
         flash_virtual_base: 0x0,
         flash_physical_base: 0x0,
-        flash_physical_size: spi_processor::SPI_FLASH_SIZE,
+        flash_physical_size: spi_flash.size,
         ram_virtual_base: spi_processor::SPI_MAILBOX_ADDRESS,
-        virtual_size: spi_processor::SPI_FLASH_SIZE,
+        virtual_size: spi_flash.size,
     })?;
 
     //////////////////////////////////////////////////////////////////////////////
 
-    // OpenTitan JEDEC ID
-    spi_device::get().set_jedec_id(&mut [
-        0x26, // Manufacturer (Visic, should actually be
-              // 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x26)
-        0x31, // Device (OpenTitan)
-        0x19, // Size (2^25 = 256 Mb)
-        ])?;
+    // JEDEC ID of the SPI flash chip, or the OpenTitan JEDEC ID if unknown.
+    let mut jedec_id = spi_flash.jedec_id;
+    spi_device::get().set_jedec_id(&mut jedec_id)?;
 
     //////////////////////////////////////////////////////////////////////////////
 
@@ -174,9 +298,10 @@ fn run() -> TockResult<()> {
         let mut sfdp = [0xff; 128];
         sfdp::get_table(
             &mut sfdp,
-            spi_processor::SPI_FLASH_SIZE * 8, // image_size_bits
+            spi_flash.size as u64 * 8, // image_size_bits
             spi_device::get().get_address_mode(), // startup_address_mode
-            spi_device::get().get_address_mode() == AddressMode::ThreeByte, // support_address_mode_switch
+            spi_device::get().get_address_mode() == AddressMode::ThreeByte
+                && spi_flash.supports_address_mode_switch(), // support_address_mode_switch
             spi_processor::SPI_MAILBOX_ADDRESS, // mailbox_offset
             spi_device::MAX_READ_BUFFER_SIZE as u32, // mailbox_size
             0 // google_capabilities
@@ -191,18 +316,28 @@ fn run() -> TockResult<()> {
 
     //////////////////////////////////////////////////////////////////////////////
 
-    // We assume that we've already done all boot-time checks at this point.
+    // We assume that we've already done all other boot-time checks at this point.
+    let bmc_allowed = gpio_processor.verify_spi_flash();
 
     // Deassert BMC resets.
     // TODO(osk): Do something with the result codes.
-    let _ = gpio_processor.set_bmc_cpu_rst(false);
+    if bmc_allowed {
+        let _ = gpio_processor.set_bmc_cpu_rst(false);
+    }
     let _ = gpio_processor.set_bmc_srst(false);
 
     //////////////////////////////////////////////////////////////////////////////
 
     console_reader::get().allow_read(1)?;
 
+    let mut alarm_deadline = None;
     loop {
+        if let Err(_) = handle_alarm(&gpio_processor, &reboot_scheduler, &manticore_reset,
+                                     uptime_update_ticks, &mut alarm_deadline) {
+            // Ignore error from writeln. There's nothing we can do here anyway.
+            println!("Alarm: Error.");
+        }
+
         while !spi_device::get().have_transaction()
             && !console_reader::get().have_data()
             && !gpio_control::get().have_events()
@@ -252,16 +387,6 @@ fn run() -> TockResult<()> {
                 }
             }
         }
-
-        if alarm::get().is_expired() {
-            match gpio_processor.alarm_expired() {
-                Ok(()) => {}
-                Err(_) => {
-                    // Ignore error from writeln. There's nothing we can do here anyway.
-                    println!("GPIO processor (alarm): Error.");
-                }
-            }
-        }
     }
 }
 
@@ -14,8 +14,22 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::alarm;
+use crate::attestation::Attestation;
+use crate::attestation::AttestationError;
+use crate::attestation::is_attestation_command;
+use crate::digest;
+use crate::firmware_controller::FirmwareController;
+use crate::firmware_update::FirmwareUpdate;
+use crate::firmware_update::UpdateError;
+use crate::firmware_update::is_update_command;
+
+use core::cell::Cell;
+use core::convert::TryFrom;
 use core::time::Duration;
 
+use libtock::result::TockResult;
+
 use manticore::crypto::rsa;
 use manticore::hardware;
 use manticore::protocol::capabilities::*;
@@ -27,6 +41,10 @@ use manticore::protocol::wire::ToWireError;
 use manticore::server::pa_rot::Options;
 use manticore::server::pa_rot::PaRot;
 
+use spiutils::crypto::rsa as sw_rsa;
+use spiutils::crypto::rsa::RsaError;
+use spiutils::protocol::integrity::SHA256_LEN;
+
 const NETWORKING: Networking = Networking {
     max_message_size: 1024,
     max_packet_size: 256,
@@ -39,6 +57,27 @@ const TIMEOUTS: Timeouts = Timeouts {
     crypto: Duration::from_millis(200),
 };
 
+const NSECS_IN_SEC: u64 = 1_000_000_000;
+
+/// Cerberus error codes of error messages.
+pub mod error_nr {
+    pub const NO_ERROR: u8 = 0x00;
+    pub const INVALID_REQUEST: u8 = 0x01;
+    pub const BUSY: u8 = 0x02;
+    pub const UNSPECIFIED: u8 = 0x03;
+}
+
+/// The length of Cerberus error messages: error code (1), error data (4).
+pub const ERROR_LEN: usize = 5;
+
+/// Write a Cerberus error message with `code` and `data` to response, which
+/// must hold at least ERROR_LEN bytes. Returns the length of the message.
+pub fn write_error(response: &mut [u8], code: u8, data: u32) -> usize {
+    response[0] = code;
+    response[1..ERROR_LEN].copy_from_slice(&data.to_le_bytes());
+    ERROR_LEN
+}
+
 const DEVICE_ID: device_id::DeviceIdentifier =
     device_id::DeviceIdentifier {
         vendor_id: 1,
@@ -69,55 +108,157 @@ impl hardware::Identity for Identity {
     }
 }
 
-pub struct Reset;
+/// Reports the resets and the uptime to Manticore.
+///
+/// The uptime is counted in alarm clock ticks. `update_uptime` must be called
+/// at least once per wraparound of the alarm clock.
+pub struct Reset {
+    /// Resets since the last power-on reset.
+    resets_since_power_on: u32,
+
+    /// Alarm clock value at the last update.
+    last_ticks: Cell<usize>,
+
+    /// Alarm clock ticks since boot.
+    uptime_ticks: Cell<u64>,
+}
+
+impl Reset {
+    pub fn new(resets_since_power_on: u32) -> Reset {
+        // The alarm clock starts when the chip boots.
+        let ticks = alarm::get().get_ticks().unwrap_or(0);
+        Reset {
+            resets_since_power_on,
+            last_ticks: Cell::new(ticks),
+            uptime_ticks: Cell::new(ticks as u64),
+        }
+    }
+
+    /// Add the alarm clock ticks since the last update to the uptime.
+    pub fn update_uptime(&self) {
+        if let Ok(ticks) = alarm::get().get_ticks() {
+            let elapsed = ticks.wrapping_sub(self.last_ticks.get());
+            self.last_ticks.set(ticks);
+            self.uptime_ticks.set(self.uptime_ticks.get() + elapsed as u64);
+        }
+    }
+}
+
 impl hardware::Reset for Reset {
     fn resets_since_power_on(&self) -> u32 {
-        0
+        self.resets_since_power_on
     }
     fn uptime(&self) -> Duration {
-        Duration::from_millis(1)
+        self.update_uptime();
+        let frequency = alarm::get().get_clock_frequency() as u64;
+        let ticks = self.uptime_ticks.get();
+        Duration::new(ticks / frequency, ((ticks % frequency) * NSECS_IN_SEC / frequency) as u32)
+    }
+}
+
+/// An RSA public key for verifying signatures in Manticore.
+pub struct RsaPubKey {
+    key: sw_rsa::PublicKey,
+    len: rsa::ModulusLength,
+}
+
+impl TryFrom<sw_rsa::PublicKey> for RsaPubKey {
+    type Error = RsaError;
+
+    fn try_from(key: sw_rsa::PublicKey) -> Result<Self, RsaError> {
+        let len = match key.modulus_len() {
+            sw_rsa::MODULUS_2048_LEN => rsa::ModulusLength::Bits2048,
+            sw_rsa::MODULUS_3072_LEN => rsa::ModulusLength::Bits3072,
+            _ => return Err(RsaError::UnsupportedModulus),
+        };
+        Ok(RsaPubKey { key, len })
     }
 }
 
-pub struct NoRsaPubKey;
-impl rsa::PublicKey for NoRsaPubKey {
+impl rsa::PublicKey for RsaPubKey {
     fn len(&self) -> rsa::ModulusLength {
-        unreachable!()
+        self.len
     }
 }
 
-pub struct NoRsaEngine;
-impl rsa::Engine for NoRsaEngine {
-    type Error = ();
-    type Key = NoRsaPubKey;
+// The bignum scratch memory for RSA verification, which does not fit on the
+// stack. It is only borrowed for the duration of the `sw_rsa::verify` call in
+// `RsaEngine::verify_signature`, which does not call back into this module,
+// and the app has a single thread, so at most one reference exists at a time.
+static mut RSA_WORKSPACE: sw_rsa::Workspace = sw_rsa::Workspace::new();
+
+// The number of message bytes passed to the digest driver at a time.
+const DIGEST_CHUNK_LEN: usize = 128;
+
+#[derive(Copy, Clone, Debug)]
+pub enum RsaEngineError {
+    DigestError,
+    Verify(RsaError),
+}
+
+/// Verifies RSA PKCS#1 v1.5 signatures of SHA-256 digests.
+pub struct RsaEngine {
+    key: sw_rsa::PublicKey,
+}
+
+impl RsaEngine {
+    // Compute the SHA-256 digest of message with the digest engine.
+    fn compute_digest(message: &[u8]) -> TockResult<[u8; SHA256_LEN]> {
+        digest::get().initialize(digest::DigestMode::Sha256)?;
+
+        let mut chunk_buf = [0u8; DIGEST_CHUNK_LEN];
+        for chunk in message.chunks(DIGEST_CHUNK_LEN) {
+            chunk_buf[..chunk.len()].copy_from_slice(chunk);
+            if let Err(err) = digest::get().update(&mut chunk_buf, chunk.len()) {
+                // Release the digest engine.
+                let _ = digest::get().finalize(&mut [0u8; SHA256_LEN]);
+                return Err(err);
+            }
+        }
+
+        let mut result = [0u8; SHA256_LEN];
+        digest::get().finalize(&mut result)?;
+        Ok(result)
+    }
+}
+
+impl rsa::Engine for RsaEngine {
+    type Error = RsaEngineError;
+    type Key = RsaPubKey;
 
     fn verify_signature(
         &mut self,
-        _signature: &[u8],
-        _message: &[u8],
-    ) -> Result<(), rsa::Error<()>> {
-        Err(manticore::crypto::rsa::Error::Custom(()))
+        signature: &[u8],
+        message: &[u8],
+    ) -> Result<(), rsa::Error<RsaEngineError>> {
+        let digest = RsaEngine::compute_digest(message)
+            .map_err(|_| rsa::Error::Custom(RsaEngineError::DigestError))?;
+        let result = unsafe {
+            // Safe since this is the only place that borrows RSA_WORKSPACE (see above).
+            sw_rsa::verify(&self.key, signature, &digest, &mut RSA_WORKSPACE)
+        };
+        result.map_err(|err| rsa::Error::Custom(RsaEngineError::Verify(err)))
     }
 }
 
-pub struct NoRsa;
-impl rsa::Builder for NoRsa {
-    type Engine = NoRsaEngine;
+pub struct Rsa;
+impl rsa::Builder for Rsa {
+    type Engine = RsaEngine;
 
-    fn supports_modulus(&self, _: rsa::ModulusLength) -> bool {
-        true
+    fn supports_modulus(&self, len: rsa::ModulusLength) -> bool {
+        matches!(len, rsa::ModulusLength::Bits2048 | rsa::ModulusLength::Bits3072)
     }
 
-    fn new_engine(&self, _key: NoRsaPubKey) -> Result<NoRsaEngine, rsa::Error<()>> {
-        Err(manticore::crypto::rsa::Error::Custom(()))
+    fn new_engine(&self, key: RsaPubKey) -> Result<RsaEngine, rsa::Error<RsaEngineError>> {
+        Ok(RsaEngine { key: key.key })
     }
 }
 
-pub fn get_pa_rot(identity: &Identity) -> PaRot<Identity, Reset, NoRsa> {
+pub fn get_pa_rot<'a>(identity: &'a Identity, reset: &'a Reset) -> PaRot<'a, Identity, Reset, Rsa> {
     PaRot::new(Options {
         identity: &identity,
-        reset: &Reset,
-        rsa: &NoRsa,
+        reset,
+        rsa: &Rsa,
         device_id: DEVICE_ID,
         networking: NETWORKING,
         timeouts: TIMEOUTS,
@@ -132,6 +273,8 @@ pub enum HandlerError {
     FromWire(FromWireError),
     ToWire(ToWireError),
     Manticore(manticore::server::Error),
+    Attestation(AttestationError),
+    Update(UpdateError),
     NoResponse,
 }
 
@@ -153,28 +296,52 @@ impl From<manticore::server::Error> for HandlerError {
     }
 }
 
+impl From<AttestationError> for HandlerError {
+    fn from(err: AttestationError) -> Self {
+        HandlerError::Attestation(err)
+    }
+}
+
+impl From<UpdateError> for HandlerError {
+    fn from(err: UpdateError) -> Self {
+        HandlerError::Update(err)
+    }
+}
+
 pub type HandlerResult<T> = Result<T, HandlerError>;
 
 pub struct Handler<'a> {
     // The Handler protocol server.
-    server: PaRot<'a, Identity, Reset, NoRsa>,
+    server: PaRot<'a, Identity, Reset, Rsa>,
+
+    // Answers the attestation requests, which the server does not handle.
+    attestation: &'a Attestation,
+
+    // Answers the Cerberus firmware update requests, which the server does not handle.
+    update: &'a FirmwareUpdate<'a>,
 }
 
 impl<'a> Handler<'a> {
 
-    pub fn new(identity: &'a Identity) -> Self {
+    pub fn new(identity: &'a Identity, reset: &'a Reset, attestation: &'a Attestation,
+               update: &'a FirmwareUpdate<'a>) -> Self {
         Self {
-            server: get_pa_rot(identity),
+            server: get_pa_rot(identity, reset),
+            attestation,
+            update,
         }
     }
 
-    pub fn process_request(&mut self, mut input: &[u8], output: &mut[u8]) -> HandlerResult<usize> {
+    /// firmware: Executes the Cerberus firmware update requests.
+    pub fn process_request(&mut self, request: &[u8], output: &mut[u8],
+                           firmware: &mut FirmwareController) -> HandlerResult<usize> {
         use manticore::mem::BumpArena;
         use manticore::net::InMemHost;
         use manticore::protocol::Header;
         use manticore::protocol::HEADER_LEN;
         use manticore::io::Cursor;
 
+        let mut input = request;
         let header = {
             unsafe {
                 let arena = BumpArena::new(&mut ARENA[..]);
@@ -182,6 +349,45 @@ impl<'a> Handler<'a> {
             }
         };
 
+        if is_attestation_command(header.command) {
+            if !header.is_request || output.len() < HEADER_LEN {
+                return Err(AttestationError::Malformed.into());
+            }
+            // Challenge responses sign the headers, so write the response
+            // header first.
+            let resp_header = Header {
+                command: header.command,
+                is_request: false,
+            };
+            let (resp_header_buf, resp_data) = output.split_at_mut(HEADER_LEN);
+            resp_header.to_wire(Cursor::new(resp_header_buf))?;
+            let req_header_buf = &request[..request.len() - input.len()];
+            let (resp_command, resp_data_len) = self.attestation.process_request(
+                header.command, req_header_buf, input, resp_header_buf, resp_data)?;
+            if resp_command != header.command {
+                let resp_header = Header {
+                    command: resp_command,
+                    is_request: false,
+                };
+                resp_header.to_wire(Cursor::new(resp_header_buf))?;
+            }
+            return Ok(resp_data_len + HEADER_LEN);
+        }
+
+        if is_update_command(header.command) {
+            if !header.is_request || output.len() < HEADER_LEN {
+                return Err(UpdateError::Malformed.into());
+            }
+            let (resp_command, resp_data_len) = self.update.process_request(
+                firmware, header.command, input, &mut output[HEADER_LEN..])?;
+            let resp_header = Header {
+                command: resp_command,
+                is_request: false,
+            };
+            resp_header.to_wire(Cursor::new(output))?;
+            return Ok(resp_data_len + HEADER_LEN);
+        }
+
         let resp_header: Header;
         let resp_data_len: usize;
         {
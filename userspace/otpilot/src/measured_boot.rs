@@ -0,0 +1,325 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::nvmem;
+
+use core::cell::Cell;
+use core::cmp::min;
+
+use libtock::println;
+use libtock::result::TockResult;
+
+use spiutils::io::Cursor as SpiutilsCursor;
+use spiutils::protocol::integrity::SHA256_LEN;
+use spiutils::protocol::measured_boot::GetRecoveryStatusResponse;
+use spiutils::protocol::measured_boot::Manifest;
+use spiutils::protocol::measured_boot::RecoveryResult;
+use spiutils::protocol::measured_boot::SetManifestResult;
+use spiutils::protocol::measured_boot::VerifyMode;
+use spiutils::protocol::measured_boot::VerifyResult;
+use spiutils::protocol::measured_boot::DISABLED_MANIFEST;
+use spiutils::protocol::measured_boot::MANIFEST_LEN;
+use spiutils::protocol::measured_boot::NO_FAILED_REGION;
+use spiutils::protocol::wire::FromWire;
+use spiutils::protocol::wire::ToWire;
+
+// The number of bytes copied at a time during recovery.
+const RECOVERY_BLOCK_LEN: u32 = 0x10000;
+
+// The number of bytes copied between progress messages during recovery.
+const RECOVERY_PROGRESS_LEN: u32 = 0x100000;
+
+// The manifest used if a provisioned manifest cannot be read.
+// Its regions are unknown, so verification fails until a valid manifest is
+// provisioned.
+fn unreadable_manifest() -> Manifest {
+    let mut manifest = DISABLED_MANIFEST;
+    manifest.mode = VerifyMode::Enforce;
+    manifest
+}
+
+/// Verifies the SPI flash chip against the measured boot manifest before
+/// the BMC is let out of reset.
+///
+/// The manifest is shared by the SpiProcessor, which provisions it, and the
+/// GpioProcessor, which verifies the SPI flash chip on BMC reset. The BMC can
+/// only provision a manifest after provisioning has been enabled on the
+/// console, which also offers to unlock a locked manifest.
+pub struct MeasuredBoot {
+    manifest: Cell<Manifest>,
+
+    /// Whether the manifest was read successfully.
+    manifest_valid: Cell<bool>,
+
+    /// The result of the most recent verification.
+    result: Cell<VerifyResult>,
+
+    /// The index of the first region that failed the most recent verification.
+    failed_region: Cell<u8>,
+
+    /// The status of the most recent recovery.
+    recovery_status: Cell<GetRecoveryStatusResponse>,
+
+    /// Whether the next SetManifest request is accepted.
+    provisioning: Cell<bool>,
+}
+
+impl MeasuredBoot {
+    /// Load the manifest persisted in NVMEM.
+    /// Verification is disabled if no manifest has been provisioned.
+    pub fn load() -> MeasuredBoot {
+        let mut buf = [0u8; MANIFEST_LEN];
+        let (manifest, manifest_valid) = match nvmem::read(nvmem::Slot::MeasuredBootManifest, &mut buf) {
+            Ok(Some(len)) => match Manifest::from_wire(&buf[..len]) {
+                Ok(manifest) => (manifest, true),
+                Err(why) => {
+                    println!("Measured boot: Invalid manifest: {:?}", why);
+                    (unreadable_manifest(), false)
+                }
+            },
+            Ok(None) => (DISABLED_MANIFEST, true),
+            Err(_) => {
+                println!("Measured boot: Could not read manifest");
+                (unreadable_manifest(), false)
+            }
+        };
+        println!("Measured boot: {:?}, {} region(s), {}",
+            manifest.mode,
+            manifest.regions().len(),
+            if manifest.locked { "locked" } else { "unlocked" });
+        MeasuredBoot {
+            manifest: Cell::new(manifest),
+            manifest_valid: Cell::new(manifest_valid),
+            result: Cell::new(VerifyResult::Unknown),
+            failed_region: Cell::new(NO_FAILED_REGION),
+            recovery_status: Cell::new(GetRecoveryStatusResponse {
+                result: RecoveryResult::Unknown,
+                attempts: 0,
+                bytes_copied: 0,
+                bytes_total: 0,
+            }),
+            provisioning: Cell::new(false),
+        }
+    }
+
+    /// The active manifest.
+    pub fn manifest(&self) -> Manifest {
+        self.manifest.get()
+    }
+
+    /// The result of the most recent verification.
+    pub fn result(&self) -> VerifyResult {
+        if !self.is_enabled() {
+            return VerifyResult::Disabled;
+        }
+        self.result.get()
+    }
+
+    /// The index of the first region that failed the most recent
+    /// verification, or NO_FAILED_REGION.
+    pub fn failed_region(&self) -> u8 {
+        self.failed_region.get()
+    }
+
+    /// The status of the most recent recovery.
+    pub fn recovery_status(&self) -> GetRecoveryStatusResponse {
+        self.recovery_status.get()
+    }
+
+    /// Whether the SPI flash chip needs to be measured on BMC reset.
+    pub fn is_enabled(&self) -> bool {
+        self.manifest.get().mode != VerifyMode::Disabled
+    }
+
+    /// Accept the next SetManifest request.
+    /// Only the console may call this.
+    pub fn enable_provisioning(&self) {
+        self.provisioning.set(true);
+    }
+
+    // Persist `manifest` in NVMEM and activate it.
+    fn store_manifest(&self, manifest: Manifest) -> SetManifestResult {
+        let mut buf = [0u8; MANIFEST_LEN];
+        let mut cursor = SpiutilsCursor::new(&mut buf);
+        if manifest.to_wire(&mut cursor).is_err() {
+            return SetManifestResult::StorageError;
+        }
+        let len = cursor.consumed_len();
+        if nvmem::write(nvmem::Slot::MeasuredBootManifest, &buf[..len]).is_err() {
+            println!("Measured boot: Could not persist manifest");
+            return SetManifestResult::StorageError;
+        }
+
+        self.manifest.set(manifest);
+        self.manifest_valid.set(true);
+        SetManifestResult::Success
+    }
+
+    /// Persist `manifest` in NVMEM and activate it.
+    /// Fails unless provisioning has been enabled, or if the active manifest
+    /// is locked. Provisioning is disabled again once a manifest is stored.
+    /// The new manifest is used on the next BMC reset.
+    pub fn set_manifest(&self, manifest: Manifest) -> SetManifestResult {
+        if !self.provisioning.get() {
+            return SetManifestResult::NotProvisioning;
+        }
+        if self.manifest.get().locked {
+            return SetManifestResult::Locked;
+        }
+
+        let result = self.store_manifest(manifest);
+        if result == SetManifestResult::Success {
+            self.provisioning.set(false);
+            self.result.set(VerifyResult::Unknown);
+            self.failed_region.set(NO_FAILED_REGION);
+            println!("Measured boot: Provisioned {:?} with {} region(s)",
+                manifest.mode, manifest.regions().len());
+        }
+        result
+    }
+
+    /// Unlock the active manifest so that it can be replaced.
+    /// This is the recovery path for a locked manifest and is only offered on
+    /// the console.
+    pub fn unlock(&self) -> SetManifestResult {
+        let mut manifest = self.manifest.get();
+        if !manifest.locked {
+            return SetManifestResult::Success;
+        }
+        manifest.locked = false;
+        self.store_manifest(manifest)
+    }
+
+    // Record the result of a verification.
+    // Returns whether the BMC may be let out of reset.
+    fn finish(&self, result: VerifyResult, failed_region: u8) -> bool {
+        self.result.set(result);
+        self.failed_region.set(failed_region);
+        match self.manifest.get().mode {
+            VerifyMode::Disabled | VerifyMode::LogOnly => true,
+            VerifyMode::Enforce => result == VerifyResult::Match,
+        }
+    }
+
+    /// Record that the SPI flash chip could not be accessed for verification.
+    /// Returns whether the BMC may be let out of reset.
+    // The otpilot emulator has no SPI host passthrough that could fail.
+    #[allow(dead_code)]
+    pub fn record_error(&self) -> bool {
+        println!("Measured boot: Could not access SPI flash");
+        self.finish(VerifyResult::Error, NO_FAILED_REGION)
+    }
+
+    /// Verify the SPI flash chip against the manifest.
+    /// `measure` computes the SHA-256 digest of `size` bytes at `start`.
+    /// Returns whether the BMC may be let out of reset.
+    pub fn verify<F>(&self, mut measure: F) -> bool
+        where F: FnMut(u32, u32) -> TockResult<[u8; SHA256_LEN]>
+    {
+        let manifest = self.manifest.get();
+        if manifest.mode == VerifyMode::Disabled {
+            return self.finish(VerifyResult::Disabled, NO_FAILED_REGION);
+        }
+        if !self.manifest_valid.get() {
+            println!("Measured boot: No valid manifest");
+            return self.finish(VerifyResult::Error, NO_FAILED_REGION);
+        }
+
+        for (idx, region) in manifest.regions().iter().enumerate() {
+            let result = match measure(region.start, region.size) {
+                Ok(digest) if digest == region.digest => continue,
+                Ok(_) => VerifyResult::Mismatch,
+                Err(_) => VerifyResult::Error,
+            };
+            println!("Measured boot: {:?} in region {} at 0x{:x}", result, idx, region.start);
+            return self.finish(result, idx as u8);
+        }
+
+        println!("Measured boot: {} region(s) match", manifest.regions().len());
+        self.finish(VerifyResult::Match, NO_FAILED_REGION)
+    }
+
+    // Record the result of a recovery.
+    // Returns whether the BMC may be let out of reset.
+    fn finish_recovery(&self, result: RecoveryResult, allowed: bool) -> bool {
+        let mut status = self.recovery_status.get();
+        status.result = result;
+        self.recovery_status.set(status);
+        println!("Recovery: {:?}", result);
+        allowed
+    }
+
+    /// Restore the image from the recovery region of the manifest after
+    /// `verify` failed, then verify the image again.
+    /// The known-good copy is verified before the image is modified.
+    /// `measure` computes the SHA-256 digest of `size` bytes at `start`.
+    /// `copy` copies `size` bytes from `source` to `destination`. All
+    /// arguments are multiples of RECOVERY_ALIGNMENT.
+    /// Returns whether the BMC may be let out of reset.
+    pub fn recover<M, C>(&self, mut measure: M, mut copy: C) -> bool
+        where M: FnMut(u32, u32) -> TockResult<[u8; SHA256_LEN]>,
+              C: FnMut(u32, u32, u32) -> TockResult<()>
+    {
+        let manifest = self.manifest.get();
+        let recovery = *manifest.recovery();
+        let mut status = self.recovery_status.get();
+        status.attempts = status.attempts.wrapping_add(1);
+        status.bytes_copied = 0;
+        status.bytes_total = recovery.size;
+        self.recovery_status.set(status);
+
+        if !self.manifest_valid.get() || !recovery.is_enabled() {
+            return self.finish_recovery(RecoveryResult::NotConfigured, false);
+        }
+
+        println!("Recovery: Verifying known-good copy at 0x{:x}", recovery.source);
+        for region in manifest.regions() {
+            // Regions outside of the image are not restored and only
+            // checked by the final verification.
+            let source_region = match recovery.source_region(region) {
+                Some(source_region) => source_region,
+                None => continue,
+            };
+            match measure(source_region.start, source_region.size) {
+                Ok(digest) if digest == source_region.digest => {}
+                Ok(_) => return self.finish_recovery(RecoveryResult::InvalidSource, false),
+                Err(_) => return self.finish_recovery(RecoveryResult::FlashError, false),
+            }
+        }
+
+        println!("Recovery: Copying 0x{:x} bytes to 0x{:x}", recovery.size, recovery.destination);
+        let mut offset = 0;
+        while offset < recovery.size {
+            let len = min(recovery.size - offset, RECOVERY_BLOCK_LEN);
+            if copy(recovery.source + offset, recovery.destination + offset, len).is_err() {
+                println!("Recovery: Copying 0x{:x} failed", recovery.destination + offset);
+                return self.finish_recovery(RecoveryResult::FlashError, false);
+            }
+            offset += len;
+            status.bytes_copied = offset;
+            self.recovery_status.set(status);
+            if offset % RECOVERY_PROGRESS_LEN == 0 || offset == recovery.size {
+                println!("Recovery: Copied 0x{:x} of 0x{:x} bytes", offset, recovery.size);
+            }
+        }
+
+        if self.verify(measure) {
+            self.finish_recovery(RecoveryResult::Success, true)
+        } else {
+            self.finish_recovery(RecoveryResult::VerifyFailed, false)
+        }
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+// NVMEM stores small records in H1 flash that survive reboots and firmware
+// updates.
+//
+// Each bank ends with an NVMEM segment that holds one flash page per slot.
+// Only the NVMEM in the inactive bank is writable, so every write goes
+// there, tagged with a generation one higher than that of the newest record
+// in either bank. Reads return the newest valid record.
+
+use crate::flash;
+use crate::globalsec;
+
+use core::cmp::min;
+
+use libtock::println;
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::driver::firmware::SegmentInfo;
+use spiutils::protocol::integrity::Crc32;
+
+/// An NVMEM slot, each of which holds one record.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    /// The SPI flash write protection policy.
+    WriteProtectPolicy = 0,
+
+    /// The measured boot manifest.
+    MeasuredBootManifest = 1,
+
+    /// The number of resets since the last power-on reset.
+    ResetCount = 2,
+}
+
+/// The maximum length of the data in a record.
+pub const MAX_DATA_LEN: usize = 256;
+
+// Record layout, all fields little endian:
+// - magic: u32
+// - generation: u32
+// - data length: u32
+// - data, padded with 0xff to a multiple of 4 bytes
+// - CRC-32 of all preceding bytes: u32
+const RECORD_MAGIC: u32 = 0x4e56_4d31;
+const RECORD_HEADER_LEN: usize = 12;
+const RECORD_CRC_LEN: usize = 4;
+const MAX_RECORD_LEN: usize = RECORD_HEADER_LEN + MAX_DATA_LEN + RECORD_CRC_LEN;
+
+// TODO(osk): We need to have this buffer somewhere, but putting it on the
+// stack doesn't work, since that's currently limited to 2048 bytes.
+static mut RECORD_BUF: [u8; MAX_RECORD_LEN] = [0xff; MAX_RECORD_LEN];
+
+// Returns the length of a record with `data_len` bytes of data.
+fn record_len(data_len: usize) -> usize {
+    RECORD_HEADER_LEN + ((data_len + 3) & !3) + RECORD_CRC_LEN
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut val = [0u8; 4];
+    val.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(val)
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, val: u32) {
+    buf[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+}
+
+// Returns the page number of `slot` in `segment`.
+fn get_page(segment: SegmentInfo, slot: Slot) -> TockResult<usize> {
+    if slot as u32 >= segment.page_count {
+        return Err(TockError::Format);
+    }
+    Ok(segment.start_page as usize + slot as usize)
+}
+
+// Returns the flash offset of `slot` in `segment`.
+fn get_offset(segment: SegmentInfo, slot: Slot) -> TockResult<usize> {
+    get_page(segment, slot)?;
+    Ok(segment.address as usize + slot as usize * flash::PAGE_SIZE)
+}
+
+// Read `buf.len()` bytes at `offset`. The length must be a multiple of 4.
+fn read_flash(offset: usize, buf: &mut [u8]) -> TockResult<()> {
+    let mut pos = 0;
+    while pos < buf.len() {
+        let len = min(buf.len() - pos, flash::MAX_BUFFER_LENGTH);
+        flash::get().read(offset + pos, &mut buf[pos..], len)?;
+        pos += len;
+    }
+    Ok(())
+}
+
+fn check_operation_result() -> TockResult<()> {
+    flash::get().wait_operation_done();
+    let result = flash::get().get_operation_result();
+    flash::get().clear_operation();
+    if result < 0 {
+        println!("NVMEM: flash operation error {}", result);
+        return Err(TockError::Format);
+    }
+    Ok(())
+}
+
+// Read the record of `slot` in `segment` into RECORD_BUF.
+// Returns the generation and the data length if the record is valid.
+fn read_record(segment: SegmentInfo, slot: Slot) -> Option<(u32, usize)> {
+    let offset = get_offset(segment, slot).ok()?;
+    unsafe {
+        // TODO(osk): We need the unsafe block since we're accessing RECORD_BUF as &mut.
+        read_flash(offset, &mut RECORD_BUF[..RECORD_HEADER_LEN]).ok()?;
+        if get_u32(&RECORD_BUF, 0) != RECORD_MAGIC {
+            return None;
+        }
+        let generation = get_u32(&RECORD_BUF, 4);
+        let data_len = get_u32(&RECORD_BUF, 8) as usize;
+        if data_len > MAX_DATA_LEN {
+            return None;
+        }
+
+        let len = record_len(data_len);
+        read_flash(offset + RECORD_HEADER_LEN, &mut RECORD_BUF[RECORD_HEADER_LEN..len]).ok()?;
+        let crc = Crc32::init().add(&RECORD_BUF[..len - RECORD_CRC_LEN]).get();
+        if get_u32(&RECORD_BUF, len - RECORD_CRC_LEN) != crc {
+            return None;
+        }
+        Some((generation, data_len))
+    }
+}
+
+// Find the newest valid record of `slot`.
+// Returns its segment and generation.
+fn find_newest(slot: Slot) -> Option<(SegmentInfo, u32)> {
+    let mut newest: Option<(SegmentInfo, u32)> = None;
+    for segment in [globalsec::get().get_active_nv(), globalsec::get().get_inactive_nv()].iter() {
+        if let Some((generation, _)) = read_record(*segment, slot) {
+            if newest.map_or(true, |(_, newest_generation)| generation > newest_generation) {
+                newest = Some((*segment, generation));
+            }
+        }
+    }
+    newest
+}
+
+/// Read the newest record of `slot` into `data`.
+/// Returns the length of the record's data, or None if the slot is empty.
+/// Fails if `data` is too small.
+pub fn read(slot: Slot, data: &mut [u8]) -> TockResult<Option<usize>> {
+    let segment = match find_newest(slot) {
+        Some((segment, _)) => segment,
+        None => return Ok(None),
+    };
+    let (_, data_len) = read_record(segment, slot).ok_or(TockError::Format)?;
+    if data_len > data.len() {
+        return Err(TockError::Format);
+    }
+    unsafe {
+        // TODO(osk): We need the unsafe block since we're accessing RECORD_BUF.
+        data[..data_len].copy_from_slice(&RECORD_BUF[RECORD_HEADER_LEN..RECORD_HEADER_LEN + data_len]);
+    }
+    Ok(Some(data_len))
+}
+
+/// Write `data` as the newest record of `slot`.
+pub fn write(slot: Slot, data: &[u8]) -> TockResult<()> {
+    if data.len() > MAX_DATA_LEN {
+        return Err(TockError::Format);
+    }
+    let generation = match find_newest(slot) {
+        Some((_, generation)) => generation.checked_add(1).ok_or(TockError::Format)?,
+        None => 0,
+    };
+
+    let segment = globalsec::get().get_inactive_nv();
+    let offset = get_offset(segment, slot)?;
+    flash::get().erase(get_page(segment, slot)?)?;
+    check_operation_result()?;
+
+    let len = record_len(data.len());
+    unsafe {
+        // TODO(osk): We need the unsafe block since we're accessing RECORD_BUF as &mut.
+        for val in RECORD_BUF.iter_mut() {
+            *val = 0xff;
+        }
+        put_u32(&mut RECORD_BUF, 0, RECORD_MAGIC);
+        put_u32(&mut RECORD_BUF, 4, generation);
+        put_u32(&mut RECORD_BUF, 8, data.len() as u32);
+        RECORD_BUF[RECORD_HEADER_LEN..RECORD_HEADER_LEN + data.len()].copy_from_slice(data);
+        let crc = Crc32::init().add(&RECORD_BUF[..len - RECORD_CRC_LEN]).get();
+        put_u32(&mut RECORD_BUF, len - RECORD_CRC_LEN, crc);
+
+        let mut pos = 0;
+        while pos < len {
+            let chunk_len = min(len - pos, flash::MAX_BUFFER_LENGTH);
+            flash::get().write(offset + pos, &mut RECORD_BUF[pos..pos + chunk_len], chunk_len)?;
+            check_operation_result()?;
+            pos += chunk_len;
+        }
+    }
+
+    // Read the record back to make sure it is valid.
+    match read_record(segment, slot) {
+        Some((read_generation, read_len)) if read_generation == generation && read_len == data.len() => Ok(()),
+        _ => {
+            println!("NVMEM: verifying slot {:?} failed", slot);
+            Err(TockError::Format)
+        }
+    }
+}
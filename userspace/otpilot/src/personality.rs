@@ -0,0 +1,78 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use libtock::result::TockResult;
+use libtock::syscalls;
+
+use spiutils::driver::personality::PERSONALITY_LEN;
+
+pub trait Personality {
+    // Read the personality data (the kernel's PersonalityData) into buffer.
+    fn read(&self, buffer: &mut[u8; PERSONALITY_LEN]) -> TockResult<()>;
+}
+
+// Get the static Personality object.
+pub fn get() -> &'static dyn Personality {
+    get_impl()
+}
+
+const DRIVER_NUMBER: usize = 0x5000b;
+
+mod command_nr {
+    pub const CHECK_IF_PRESENT: usize = 0;
+    pub const READ: usize = 1;
+}
+
+mod allow_nr {
+    pub const BUFFER: usize = 0;
+}
+
+struct PersonalityImpl {}
+
+static mut PERSONALITY: PersonalityImpl = PersonalityImpl {};
+
+static mut IS_INITIALIZED: bool = false;
+
+fn get_impl() -> &'static PersonalityImpl {
+    unsafe {
+        if !IS_INITIALIZED {
+            if PERSONALITY.initialize().is_err() {
+                panic!("Could not initialize Personality");
+            }
+            IS_INITIALIZED = true;
+        }
+        &PERSONALITY
+    }
+}
+
+impl PersonalityImpl {
+    fn initialize(&'static mut self) -> TockResult<()> {
+        syscalls::command(DRIVER_NUMBER, command_nr::CHECK_IF_PRESENT, 0, 0)?;
+
+        Ok(())
+    }
+}
+
+impl Personality for PersonalityImpl {
+    fn read(&self, buffer: &mut[u8; PERSONALITY_LEN]) -> TockResult<()> {
+        // We want this to go out of scope after executing the command
+        let _buffer_share = syscalls::allow(DRIVER_NUMBER, allow_nr::BUFFER, buffer)?;
+
+        syscalls::command(DRIVER_NUMBER, command_nr::READ, 0, 0)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::alarm;
+use crate::reset;
+
+use core::cell::Cell;
+
+use libtock::println;
+use libtock::result::TockResult;
+
+const MSECS_IN_SEC: u64 = 1000;
+
+/// Keeps track of a delayed reboot.
+///
+/// A pending reboot is executed when the BMC or the system is reset, or after
+/// an optional timeout. The timeout shares the alarm with the GpioProcessor:
+/// the main loop arms the alarm for the earliest of their remaining ticks and
+/// calls `alarm_expired` whenever it expires.
+pub struct RebootScheduler {
+    /// Ticks after which a pending reboot is executed without a reset.
+    timeout_ticks: Option<usize>,
+
+    /// Whether a reboot is pending.
+    pending: Cell<bool>,
+
+    /// Alarm clock value when the reboot was scheduled.
+    scheduled_ticks: Cell<usize>,
+}
+
+impl RebootScheduler {
+    pub fn new(timeout_msecs: Option<u32>) -> RebootScheduler {
+        let timeout_ticks = timeout_msecs.map(|msecs| {
+            let ticks: u64 =
+                ((alarm::get().get_clock_frequency() as u64) * (msecs as u64)) / MSECS_IN_SEC;
+            // The elapsed time is calculated from the wrapping clock value,
+            // so the timeout must be well within its range.
+            core::cmp::min(ticks, (core::usize::MAX / 2) as u64) as usize
+        });
+
+        RebootScheduler {
+            timeout_ticks: timeout_ticks,
+            pending: Cell::new(false),
+            scheduled_ticks: Cell::new(0),
+        }
+    }
+
+    /// Schedule a reboot for the next reset or when the timeout expires.
+    /// Restarts the timeout if a reboot is already pending.
+    pub fn schedule(&self) -> TockResult<()> {
+        self.scheduled_ticks.set(alarm::get().get_ticks()?);
+        self.pending.set(true);
+        Ok(())
+    }
+
+    /// Cancel a pending reboot.
+    pub fn cancel(&self) {
+        self.pending.set(false);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.get()
+    }
+
+    /// Get the ticks until a pending reboot times out.
+    /// Returns None if no reboot is pending or if there is no timeout.
+    pub fn get_remaining_ticks(&self) -> TockResult<Option<usize>> {
+        if !self.pending.get() {
+            return Ok(None);
+        }
+
+        Ok(match self.timeout_ticks {
+            Some(ticks) => {
+                let elapsed = alarm::get().get_ticks()?.wrapping_sub(self.scheduled_ticks.get());
+                Some(ticks.saturating_sub(elapsed))
+            },
+            None => None,
+        })
+    }
+
+    /// Get the time until a pending reboot times out in msecs.
+    /// Returns None if no reboot is pending or if there is no timeout.
+    pub fn get_remaining_msecs(&self) -> TockResult<Option<u32>> {
+        Ok(self.get_remaining_ticks()?.map(|ticks| {
+            let msecs = ((ticks as u64) * MSECS_IN_SEC) / (alarm::get().get_clock_frequency() as u64);
+            core::cmp::min(msecs, core::u32::MAX as u64) as u32
+        }))
+    }
+
+    /// Execute the pending reboot. Called when a reset was observed.
+    pub fn execute(&self) -> TockResult<()> {
+        if !self.pending.get() {
+            return Ok(());
+        }
+
+        println!("Executing pending reboot");
+        reset::get().reset()
+    }
+
+    /// Handle the alarm shared with the GpioProcessor and the uptime.
+    /// Executes the pending reboot once its timeout has passed.
+    pub fn alarm_expired(&self) -> TockResult<()> {
+        if self.get_remaining_ticks()? == Some(0) {
+            println!("Pending reboot timed out");
+            return self.execute();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+// Counts the resets since the last power-on reset in NVMEM.
+
+use crate::nvmem;
+use crate::reset;
+
+use libtock::result::TockResult;
+
+// The length of the count in NVMEM, in bytes.
+const COUNT_LEN: usize = 4;
+
+// Read the count persisted in NVMEM.
+// Returns None if there is no count or it cannot be read.
+fn read_count() -> Option<u32> {
+    let mut buf = [0u8; COUNT_LEN];
+    match nvmem::read(nvmem::Slot::ResetCount, &mut buf) {
+        Ok(Some(COUNT_LEN)) => Some(u32::from_le_bytes(buf)),
+        _ => None,
+    }
+}
+
+/// Count the reset the app is starting from.
+/// The count is cleared on power-on reset.
+/// Returns the number of resets since the last power-on reset.
+pub fn update() -> TockResult<u32> {
+    let stored = read_count();
+    let count = if reset::get().get_reset_source()?.power_on_reset {
+        0
+    } else {
+        stored.unwrap_or(0).saturating_add(1)
+    };
+
+    // Repeated power cycles do not wear out the flash.
+    if stored != Some(count) {
+        nvmem::write(nvmem::Slot::ResetCount, &count.to_le_bytes())?;
+    }
+    Ok(count)
+}
@@ -6,13 +6,22 @@ pub enum SfdpTableError {
 
 pub fn get_table(
     data: &mut[u8],
-    image_size_bits : u32,
+    image_size_bits : u64,
     startup_address_mode : AddressMode,
     support_address_mode_switch : bool,
     mailbox_offset: u32,
     mailbox_size: u32,
     google_capabilities: u32) -> Result<(), SfdpTableError> {
 
+    // The density is encoded as N, where the density is N+1 bits up to
+    // 2 gibibits and 2^N bits above.
+    let density_above_2gib = image_size_bits > 1 << 31;
+    let density = if density_above_2gib {
+        63 - image_size_bits.leading_zeros()
+    } else {
+        image_size_bits.saturating_sub(1) as u32
+    };
+
     // JESD216A
     let sfdp : [u8; 104] = [
         // SFDP Header 1st DWORD
@@ -107,12 +116,12 @@ pub fn get_table(
         // <30:0> : N, where:
         //           - if =< 2 gibibits, flash memory density is N+1 bits
         //           - if > 2 gibibits, flash memory density is 2^N bits
-        ((image_size_bits >> 0) & 0xff) as u8,
-        ((image_size_bits >> 8) & 0xff) as u8,
-        ((image_size_bits >> 16) & 0xff) as u8,
-        ((image_size_bits >> 24) & 0x7f) as u8 |
+        ((density >> 0) & 0xff) as u8,
+        ((density >> 8) & 0xff) as u8,
+        ((density >> 16) & 0xff) as u8,
+        ((density >> 24) & 0x7f) as u8 |
         // <31>   : Density greater than 2 gibibits
-        0 << 7,
+        (density_above_2gib as u8) << 7,
 
 
         // Basic Flash Parameter Table v1.0 3rd DWORD
@@ -0,0 +1,158 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::spi_host_helper::SpiHostHelper;
+use crate::spi_processor::SPI_FLASH_SIZE;
+
+use libtock::println;
+use libtock::result::TockError;
+use libtock::result::TockResult;
+
+use spiutils::protocol::sfdp::Sfdp;
+
+/// The length of a JEDEC ID, in bytes.
+pub const JEDEC_ID_LEN: usize = 3;
+
+/// The JEDEC ID advertised for an unknown SPI flash chip.
+pub const DEFAULT_JEDEC_ID: [u8; JEDEC_ID_LEN] = [
+    0x26, // Manufacturer (Visic, should actually be
+          // 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x7F, 0x26)
+    0x31, // Device (OpenTitan)
+    0x19, // Size (2^25 = 256 Mb)
+];
+
+/// What to do if the SPI flash chip cannot be identified.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnknownPartPolicy {
+    /// Advertise the default JEDEC ID and size.
+    UseDefault,
+
+    /// Fail, leaving the BMC in reset and SPI passthrough disabled.
+    Refuse,
+}
+
+/// The identity of the SPI flash chip behind the SPI host, as advertised to
+/// the BMC.
+#[derive(Clone, Copy, Debug)]
+pub struct SpiFlashIdentity {
+    /// The JEDEC ID.
+    pub jedec_id: [u8; JEDEC_ID_LEN],
+
+    /// The size of the SPI flash chip, in bytes.
+    pub size: u32,
+
+    /// The SFDP of the SPI flash chip.
+    /// None if the identity is the default.
+    pub sfdp: Option<Sfdp>,
+}
+
+impl SpiFlashIdentity {
+    /// The identity used for an unknown SPI flash chip.
+    pub fn unknown() -> SpiFlashIdentity {
+        SpiFlashIdentity {
+            jedec_id: DEFAULT_JEDEC_ID,
+            size: SPI_FLASH_SIZE,
+            sfdp: None,
+        }
+    }
+
+    /// Returns true iff the host can switch the SPI flash chip between
+    /// 3 and 4 byte address mode.
+    pub fn supports_address_mode_switch(&self) -> bool {
+        self.sfdp.map_or(true, |sfdp| sfdp.basic.supports_address_mode_switch())
+    }
+}
+
+// Check whether a JEDEC ID was read from an SPI flash chip.
+// A missing chip reads as all 0x00 or all 0xff.
+fn is_valid_jedec_id(jedec_id: &[u8; JEDEC_ID_LEN]) -> bool {
+    jedec_id.iter().any(|val| *val != 0x00 && *val != 0xff)
+}
+
+// Get the size of the SPI flash chip from its SFDP.
+// Returns None if the size cannot be used for the address configuration,
+// which requires a power of 2.
+fn get_size(sfdp: &Sfdp) -> Option<u32> {
+    let size = sfdp.basic.density_bytes();
+    if size.is_power_of_two() && size <= u32::max_value() as u64 {
+        Some(size as u32)
+    } else {
+        None
+    }
+}
+
+// Identify the SPI flash chip via ReadJedec and SFDP.
+// Returns None if it cannot be identified.
+fn identify() -> Option<SpiFlashIdentity> {
+    let host_helper = SpiHostHelper {};
+
+    let jedec_id = match host_helper.read_jedec_id() {
+        Ok(jedec_id) if is_valid_jedec_id(&jedec_id) => jedec_id,
+        Ok(jedec_id) => {
+            println!("SPI flash: Invalid JEDEC ID {:02x?}", jedec_id);
+            return None;
+        }
+        Err(_) => {
+            println!("SPI flash: Could not read JEDEC ID");
+            return None;
+        }
+    };
+
+    let sfdp = match host_helper.get_sfdp() {
+        Ok(sfdp) => sfdp,
+        Err(_) => {
+            println!("SPI flash: Could not read SFDP");
+            return None;
+        }
+    };
+
+    let size = match get_size(&sfdp) {
+        Some(size) => size,
+        None => {
+            println!("SPI flash: Unsupported size {} bytes", sfdp.basic.density_bytes());
+            return None;
+        }
+    };
+
+    println!("SPI flash: JEDEC ID {:02x?}, SFDP {}.{}, {} bytes, {:?}, erase {:?}",
+        jedec_id, sfdp.major_revision, sfdp.minor_revision, size,
+        sfdp.basic.address_bytes, sfdp.basic.smallest_erase_type());
+    Some(SpiFlashIdentity {
+        jedec_id,
+        size,
+        sfdp: Some(sfdp),
+    })
+}
+
+/// Probe the SPI flash chip behind the SPI host and return the identity to
+/// advertise to the BMC.
+/// SPI passthrough must be disabled.
+pub fn probe(policy: UnknownPartPolicy) -> TockResult<SpiFlashIdentity> {
+    match identify() {
+        Some(identity) => Ok(identity),
+        None => match policy {
+            UnknownPartPolicy::UseDefault => {
+                println!("WARNING: Unknown SPI flash. Using default JEDEC ID and size 0x{:x}.",
+                    SPI_FLASH_SIZE);
+                Ok(SpiFlashIdentity::unknown())
+            }
+            UnknownPartPolicy::Refuse => {
+                println!("ERROR: Unknown SPI flash.");
+                Err(TockError::Format)
+            }
+        },
+    }
+}
@@ -14,14 +14,51 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::digest;
+use crate::spi_flash_identity::JEDEC_ID_LEN;
 use crate::spi_host;
 
 
+use core::cmp::min;
+
 use libtock::println;
+use libtock::result::TockError;
 use libtock::result::TockResult;
 
+use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::integrity::SHA256_LEN;
+use spiutils::protocol::sfdp;
+use spiutils::protocol::sfdp::Sfdp;
+
 pub struct SpiHostHelper;
 
+// The number of SFDP bytes read from the SPI flash chip.
+// This covers the basic flash parameter table and the Google parameter table
+// of common parts.
+const SFDP_READ_LEN: usize = 256;
+
+// The length of a Read header in 4 byte address mode: op code and address.
+const READ_HEADER_LEN: usize = 5;
+
+// The size of a page of the SPI flash chip.
+// A PageProgram must not cross a page boundary.
+const PAGE_SIZE: u32 = 256;
+
+// The sizes of the SectorErase and BlockErase64KB commands.
+const SECTOR_SIZE: u32 = 0x1000;
+const BLOCK_64KB_SIZE: u32 = 0x10000;
+
+// The write in progress bit of the status register.
+const STATUS_WIP: u8 = 0x01;
+
+// The maximum number of status register reads while waiting for a program
+// or erase command to finish. Each read takes a few microseconds, which
+// leaves plenty of time for a block erase.
+const MAX_STATUS_POLLS: u32 = 1_000_000;
+
+// The length of a ReadSfdp header: op code, 3 byte address and dummy byte.
+const SFDP_HEADER_LEN: usize = 5;
+
 static mut TXBUFFER: [u8; spi_host::MAX_READ_BUFFER_LENGTH] = [0xff; spi_host::MAX_READ_BUFFER_LENGTH];
 
 impl SpiHostHelper {
@@ -57,10 +94,179 @@ impl SpiHostHelper {
         Ok(&spi_host::get().get_read_buffer()[tx_len..])
     }
 
+    // Read the status register of the SPI flash chip.
+    pub fn read_status(&self) -> TockResult<u8> {
+        spi_host::get().read_write_bytes(&mut [OpCode::ReadStatusRegister as u8, 0xff], 2)?;
+        spi_host::get().wait_read_write_done();
+        Ok(spi_host::get().get_read_buffer()[1])
+    }
+
+    pub fn write_enable(&self) -> TockResult<()> {
+        spi_host::get().read_write_bytes(&mut [OpCode::WriteEnable as u8], 1)?;
+        spi_host::get().wait_read_write_done();
+        Ok(())
+    }
+
+    // Wait until the SPI flash chip has finished a program or erase command.
+    fn wait_write_done(&self) -> TockResult<()> {
+        for _ in 0..MAX_STATUS_POLLS {
+            if self.read_status()? & STATUS_WIP == 0 {
+                return Ok(());
+            }
+        }
+        println!("Host: Timeout waiting for SPI flash");
+        Err(TockError::Format)
+    }
+
+    // Erase `size` bytes at `addr`.
+    // Both must be a multiple of the sector size.
+    // The SPI flash chip must be in 4 byte address mode.
+    pub fn erase(&self, addr: u32, size: u32) -> TockResult<()> {
+        if addr % SECTOR_SIZE != 0 || size % SECTOR_SIZE != 0 {
+            return Err(TockError::Format);
+        }
+        let mut offset = 0;
+        while offset < size {
+            let erase_addr = addr + offset;
+            let (opcode, erase_size) =
+                if erase_addr % BLOCK_64KB_SIZE == 0 && size - offset >= BLOCK_64KB_SIZE {
+                    (OpCode::BlockErase64KB, BLOCK_64KB_SIZE)
+                } else {
+                    (OpCode::SectorErase, SECTOR_SIZE)
+                };
+            self.write_enable()?;
+            let tx_len = self.create_tx_buf(opcode as u8, erase_addr);
+            unsafe {
+                // TODO(osk): We need the unsafe block since we're accessing TXBUFFER as &mut.
+                spi_host::get().read_write_bytes(&mut TXBUFFER, tx_len)?;
+            }
+            spi_host::get().wait_read_write_done();
+            self.wait_write_done()?;
+            offset += erase_size;
+        }
+        Ok(())
+    }
+
+    // Program `data` at `addr`.
+    // The SPI flash chip must be in 4 byte address mode.
+    pub fn program(&self, addr: u32, data: &[u8]) -> TockResult<()> {
+        let mut offset = 0;
+        while offset < data.len() {
+            let program_addr = addr + offset as u32;
+            let len = min(min(data.len() - offset, (PAGE_SIZE - program_addr % PAGE_SIZE) as usize),
+                spi_host::MAX_READ_BUFFER_LENGTH - READ_HEADER_LEN);
+            self.write_enable()?;
+            let tx_len = self.create_tx_buf(OpCode::PageProgram as u8, program_addr);
+            unsafe {
+                // TODO(osk): We need the unsafe block since we're accessing TXBUFFER as &mut.
+                TXBUFFER[tx_len..tx_len + len].copy_from_slice(&data[offset..offset + len]);
+                spi_host::get().read_write_bytes(&mut TXBUFFER, tx_len + len)?;
+            }
+            spi_host::get().wait_read_write_done();
+            self.wait_write_done()?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    // Copy `size` bytes from `source` to `destination`.
+    // Both addresses and the size must be a multiple of the sector size.
+    // The SPI flash chip must be in 4 byte address mode.
+    pub fn copy(&self, source: u32, destination: u32, size: u32) -> TockResult<()> {
+        self.erase(destination, size)?;
+
+        let mut chunk = [0u8; spi_host::MAX_READ_BUFFER_LENGTH - READ_HEADER_LEN];
+        let mut offset = 0;
+        while offset < size {
+            let chunk_len = min((size - offset) as usize, chunk.len());
+            let data = self.read_data(source + offset, chunk_len)?;
+            chunk[..chunk_len].copy_from_slice(&data[..chunk_len]);
+            // The destination is erased, so there is no need to program 0xff.
+            if chunk[..chunk_len].iter().any(|val| *val != 0xff) {
+                self.program(destination + offset, &chunk[..chunk_len])?;
+            }
+            offset += chunk_len as u32;
+        }
+        Ok(())
+    }
+
+    // Read the JEDEC ID of the SPI flash chip.
+    pub fn read_jedec_id(&self) -> TockResult<[u8; JEDEC_ID_LEN]> {
+        let mut tx_buf = [0xff; 1 + JEDEC_ID_LEN];
+        tx_buf[0] = OpCode::ReadJedec as u8;
+        spi_host::get().read_write_bytes(&mut tx_buf, 1 + JEDEC_ID_LEN)?;
+        spi_host::get().wait_read_write_done();
+
+        let mut jedec_id = [0u8; JEDEC_ID_LEN];
+        jedec_id.copy_from_slice(&spi_host::get().get_read_buffer()[1..1 + JEDEC_ID_LEN]);
+        Ok(jedec_id)
+    }
+
+    // Read SFDP data starting at `addr` into `data`.
+    // SFDP always uses a 3 byte address, regardless of the address mode.
+    pub fn read_sfdp(&self, addr: u32, data: &mut [u8]) -> TockResult<()> {
+        let mut offset = 0;
+        while offset < data.len() {
+            let rx_len = min(data.len() - offset, spi_host::MAX_READ_BUFFER_LENGTH - SFDP_HEADER_LEN);
+            let sfdp_addr = addr + offset as u32;
+            unsafe {
+                // TODO(osk): We need the unsafe block since we're accessing TXBUFFER as &mut.
+                TXBUFFER[0] = OpCode::ReadSfdp as u8;
+                TXBUFFER[1..4].copy_from_slice(&sfdp_addr.to_be_bytes()[1..]);
+                for idx in 4..TXBUFFER.len() {
+                    TXBUFFER[idx] = 0xff;
+                }
+                spi_host::get().read_write_bytes(&mut TXBUFFER, SFDP_HEADER_LEN + rx_len)?;
+            }
+            spi_host::get().wait_read_write_done();
+            data[offset..offset + rx_len].copy_from_slice(
+                &spi_host::get().get_read_buffer()[SFDP_HEADER_LEN..SFDP_HEADER_LEN + rx_len]);
+            offset += rx_len;
+        }
+        Ok(())
+    }
+
+    // Read and parse the SFDP of the SPI flash chip.
+    pub fn get_sfdp(&self) -> TockResult<Sfdp> {
+        let mut data = [0xff; SFDP_READ_LEN];
+        self.read_sfdp(0, &mut data)?;
+        sfdp::parse(&data).map_err(|why| {
+            println!("Host: Invalid SFDP: {:?}", why);
+            TockError::Format
+        })
+    }
+
     pub fn read_and_print_data(&self, addr: u32) -> TockResult<()> {
 
         let rx_buf = self.read_data(addr, 8)?;
         println!("Host: Result: {:02x?}", rx_buf);
         Ok(())
     }
+
+    // Compute the SHA-256 digest of `size` bytes at `addr`.
+    // The SPI flash chip must be in 4 byte address mode.
+    pub fn compute_digest(&self, addr: u32, size: u32) -> TockResult<[u8; SHA256_LEN]> {
+        digest::get().initialize(digest::DigestMode::Sha256)?;
+
+        let mut chunk = [0u8; spi_host::MAX_READ_BUFFER_LENGTH - READ_HEADER_LEN];
+        let mut offset = 0;
+        while offset < size {
+            let chunk_len = min((size - offset) as usize, chunk.len());
+            let result = self.read_data(addr + offset, chunk_len).and_then(|data| {
+                chunk[..chunk_len].copy_from_slice(&data[..chunk_len]);
+                digest::get().update(&mut chunk, chunk_len)
+            });
+            if result.is_err() {
+                println!("Host: Could not measure 0x{:x}", addr + offset);
+                // Release the digest engine.
+                let _ = digest::get().finalize(&mut [0u8; SHA256_LEN]);
+                return Err(TockError::Format);
+            }
+            offset += chunk_len as u32;
+        }
+
+        let mut result = [0u8; SHA256_LEN];
+        digest::get().finalize(&mut result)?;
+        Ok(result)
+    }
 }
@@ -14,13 +14,20 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::firmware_controller;
 use crate::firmware_controller::FirmwareController;
+use crate::firmware_update;
+use crate::firmware_update::FirmwareUpdate;
+use crate::firmware_update::UpdateError;
 use crate::globalsec;
 use crate::manticore_support;
+use crate::measured_boot::MeasuredBoot;
+use crate::reboot_scheduler::RebootScheduler;
 use crate::reset;
 use crate::spi_host;
 use crate::spi_host_h1;
 use crate::spi_device;
+use crate::write_protect::WriteProtect;
 
 use core::cmp::min;
 use core::convert::TryFrom;
@@ -28,9 +35,12 @@ use core::convert::TryFrom;
 use libtock::println;
 use libtock::result::TockError;
 
+use spiutils::compat::firmware::BuildInfo;
 use spiutils::io::Cursor as SpiutilsCursor;
 use spiutils::io::Write as SpiutilsWrite;
 use spiutils::driver::firmware::SegmentInfo;
+use spiutils::protocol::capabilities;
+use spiutils::protocol::capabilities::Message as CapabilitiesMessage;
 use spiutils::protocol::error;
 use spiutils::protocol::error::Message as ErrorMessage;
 use spiutils::protocol::firmware;
@@ -39,15 +49,20 @@ use spiutils::protocol::flash as spi_flash;
 use spiutils::protocol::flash::Address;
 use spiutils::protocol::flash::AddressMode;
 use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::integrity;
+use spiutils::protocol::measured_boot;
+use spiutils::protocol::measured_boot::Message as MeasuredBootMessage;
 use spiutils::protocol::payload;
 use spiutils::protocol::wire::FromWire;
 use spiutils::protocol::wire::FromWireError;
 use spiutils::protocol::wire::ToWire;
 use spiutils::protocol::wire::ToWireError;
+use spiutils::protocol::wire::WireEnum;
+use spiutils::protocol::write_protect;
+use spiutils::protocol::write_protect::Message as WriteProtectMessage;
 
-// Size of the SPI flash chip.
-// Hard-coded to 64 MiB for now.
-// TODO(osenft): Modify this to be read from the actual SPI flash chip at runtime.
+// Default size of the SPI flash chip.
+// Used if the SPI flash chip cannot be identified.
 pub const SPI_FLASH_SIZE: u32 = 0x4000000;
 
 // The location of the mailbox.
@@ -57,6 +72,18 @@ pub const SPI_MAILBOX_ADDRESS: u32 = 0x80000;
 // The size of the mailbox.
 const SPI_MAILBOX_SIZE: u32 = spi_device::MAX_READ_BUFFER_SIZE as u32;
 
+// The firmware operations handled by `SpiProcessor::process_firmware`.
+const SUPPORTED_FIRMWARE_CONTENT_TYPES: [firmware::ContentType; 8] = [
+    firmware::ContentType::InactiveSegmentsInfoRequest,
+    firmware::ContentType::UpdatePrepareRequest,
+    firmware::ContentType::WriteChunkRequest,
+    firmware::ContentType::RebootRequest,
+    firmware::ContentType::VerifySegmentRequest,
+    firmware::ContentType::ReadChunkRequest,
+    firmware::ContentType::FirmwareInfoRequest,
+    firmware::ContentType::PendingRebootRequest,
+];
+
 #[derive(Copy, Clone, Debug)]
 pub enum SpiProcessorError {
     FromWire(FromWireError),
@@ -64,8 +91,13 @@ pub enum SpiProcessorError {
     Tock,
     Manticore(manticore_support::HandlerError),
     UnsupportedFirmwareOperation(firmware::ContentType),
+    UnsupportedCapabilitiesOperation(capabilities::ContentType),
+    UnsupportedWriteProtectOperation(write_protect::ContentType),
+    UnsupportedMeasuredBootOperation(measured_boot::ContentType),
+    NoPendingFragment,
     UnsupportedOpCode(OpCode),
     InvalidAddress(Option<u32>),
+    Busy,
     Format(core::fmt::Error),
 }
 
@@ -101,6 +133,15 @@ impl From<core::fmt::Error> for SpiProcessorError {
 
 //////////////////////////////////////////////////////////////////////////////
 
+// Which segments may be read back with a ReadChunkRequest.
+#[derive(Copy, Clone, Debug)]
+pub struct SegmentReadAccess {
+    pub active_ro: bool,
+    pub active_rw: bool,
+    pub inactive_ro: bool,
+    pub inactive_rw: bool,
+}
+
 pub struct SpiProcessor<'a> {
     pub manticore_handler: manticore_support::Handler<'a>,
 
@@ -108,20 +149,116 @@ pub struct SpiProcessor<'a> {
     pub print_flash_headers: bool,
 
     pub firmware: FirmwareController,
+
+    // The update state shared with the Cerberus firmware update commands.
+    pub update: &'a FirmwareUpdate<'a>,
+
+    pub read_access: SegmentReadAccess,
+
+    // Executes delayed reboots.
+    pub reboot: &'a RebootScheduler,
+
+    // Reassembles fragmented requests in SPI_RX_MESSAGE_BUF.
+    pub reassembler: payload::Reassembler,
+
+    // Splits responses that do not fit into the mailbox into fragments.
+    pub fragmenter: payload::Fragmenter,
+
+    // The integrity mode of the request being processed.
+    // Responses are sent in the same mode.
+    pub integrity: Option<integrity::IntegrityMode>,
+
+    // The content type of the request being processed, or error::NO_REQUEST.
+    // Error replies report it.
+    pub request: u8,
+
+    // Decides which PageProgram and erase commands are passed through to
+    // the SPI flash chip.
+    pub write_protect: &'a WriteProtect,
+
+    // Verifies the SPI flash chip before the BMC is let out of reset.
+    pub measured_boot: &'a MeasuredBoot,
 }
 
-const SPI_TX_BUF_SIZE : usize = 512;
+// The maximum length of a message, which may span multiple mailbox transactions.
+const MAX_MESSAGE_LEN : usize = 2048;
+
+// The maximum length of the data in a single fragment.
+// Leaves room for an extended header and integrity trailer.
+const MAX_FRAGMENT_DATA_LEN : usize = SPI_MAILBOX_SIZE as usize - payload::HEADER_LEN
+    - payload::EXTENDED_HEADER_LEN - integrity::MAX_TRAILER_LEN - payload::FRAGMENT_HEADER_LEN;
+
+const SPI_TX_BUF_SIZE : usize = payload::HEADER_LEN + MAX_MESSAGE_LEN;
 
 // TODO(osk): We need to have this tx_buf somewhere, but putting it on the stack
 // doesn't work, since that's currently limited to 2048 bytes. Declaring it
 // static here for now until we have a better place for it to live.
 static mut SPI_TX_BUF : [u8; SPI_TX_BUF_SIZE] = [0xff; SPI_TX_BUF_SIZE];
 
+// Buffer for reassembling fragmented requests.
+static mut SPI_RX_MESSAGE_BUF : [u8; MAX_MESSAGE_LEN] = [0xff; MAX_MESSAGE_LEN];
+
+// The maximum length of the data in a ReadChunkResponse.
+// The response must fit into a single mailbox transaction.
+const MAX_READ_CHUNK_LEN : usize = SPI_MAILBOX_SIZE as usize - payload::HEADER_LEN
+    - payload::EXTENDED_HEADER_LEN - integrity::MAX_TRAILER_LEN
+    - firmware::HEADER_LEN - firmware::READ_CHUNK_RESPONSE_LEN;
+
+// Buffer for the data of a ReadChunkResponse.
+static mut READ_CHUNK_BUF : [u8; MAX_READ_CHUNK_LEN] = [0xff; MAX_READ_CHUNK_LEN];
+
+// Buffer for a single fragment of a response in SPI_TX_BUF.
+static mut SPI_FRAGMENT_BUF : [u8; SPI_MAILBOX_SIZE as usize] = [0xff; SPI_MAILBOX_SIZE as usize];
+
 pub type SpiProcessorResult<T> = Result<T, SpiProcessorError>;
 
 impl<'a> SpiProcessor<'a> {
 
     fn send_data(&mut self, content_type: payload::ContentType, content_len: u16, tx_buf: &mut[u8]) -> SpiProcessorResult<()> {
+        // The response in SPI_TX_BUF replaces any fragmented response in progress.
+        self.fragmenter.reset();
+        let overhead = self.integrity.map_or(0, payload::extended_overhead);
+        if payload::HEADER_LEN + overhead + content_len as usize > SPI_MAILBOX_SIZE as usize {
+            // The response does not fit into the mailbox. Send it in fragments.
+            self.fragmenter.start(content_type, content_len as usize);
+            return self.send_next_fragment();
+        }
+        self.send_frame(content_type, content_len, tx_buf)
+    }
+
+    // Move the content in tx_buf behind an extended header and append the integrity trailer.
+    // Returns the content type and length of the resulting `Extended` payload.
+    fn add_integrity(&self, mode: integrity::IntegrityMode, content_type: payload::ContentType, content_len: usize, tx_buf: &mut[u8])
+        -> SpiProcessorResult<(payload::ContentType, u16)> {
+        let start = payload::HEADER_LEN;
+        let content_start = start + payload::EXTENDED_HEADER_LEN;
+        let trailer_start = content_start + content_len;
+        let end = trailer_start + mode.trailer_len();
+        if end > tx_buf.len() {
+            return Err(SpiProcessorError::ToWire(ToWireError::Io(spiutils::io::Error::BufferExhausted)));
+        }
+
+        tx_buf.copy_within(start..start + content_len, content_start);
+        let extended = payload::ExtendedHeader {
+            version: payload::EXTENDED_HEADER_VERSION,
+            content: content_type,
+            integrity: mode,
+        };
+        extended.to_wire(SpiutilsCursor::new(&mut tx_buf[start..content_start]))?;
+        let trailer = integrity::compute_trailer(mode, &tx_buf[start..trailer_start]);
+        tx_buf[trailer_start..end].copy_from_slice(&trailer[..mode.trailer_len()]);
+
+        let extended_len = u16::try_from(end - start)
+            .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
+        Ok((payload::ContentType::Extended, extended_len))
+    }
+
+    fn send_frame(&mut self, content_type: payload::ContentType, content_len: u16, tx_buf: &mut[u8]) -> SpiProcessorResult<()> {
+        let (content_type, content_len) = match self.integrity {
+            Some(mode) => self.add_integrity(mode, content_type, content_len as usize, tx_buf)?,
+            None => (content_type, content_len),
+        };
+
         let mut header = payload::Header {
             content: content_type,
             content_len: content_len,
@@ -140,25 +277,120 @@ impl<'a> SpiProcessor<'a> {
         Ok(())
     }
 
-    fn send_error<'m, M: ErrorMessage<'m>>(&mut self, msg: M) -> SpiProcessorResult<()> {
+    fn send_fragment(&mut self, fragment: &payload::FragmentHeader, data: &[u8]) -> SpiProcessorResult<()> {
+        let payload_len : u16;
+        unsafe {
+            // TODO(osk): We need the unsafe block since we're accessing SPI_FRAGMENT_BUF as &mut.
+            let mut tx_cursor = SpiutilsCursor::new(&mut SPI_FRAGMENT_BUF[payload::HEADER_LEN..]);
+            fragment.to_wire(&mut tx_cursor)?;
+            tx_cursor.write_bytes(data)
+                .map_err(|err| SpiProcessorError::ToWire(ToWireError::Io(err)))?;
+            payload_len = u16::try_from(tx_cursor.consumed_len())
+                .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
+        }
+        unsafe {
+            // TODO(osk): We need the unsafe block since we're accessing SPI_FRAGMENT_BUF as &mut.
+            self.send_frame(payload::ContentType::Fragment, payload_len, &mut SPI_FRAGMENT_BUF)
+        }
+    }
+
+    // Send the next fragment of the response in SPI_TX_BUF.
+    fn send_next_fragment(&mut self) -> SpiProcessorResult<()> {
+        let (fragment, range) = self.fragmenter.next(MAX_FRAGMENT_DATA_LEN)
+            .ok_or(SpiProcessorError::NoPendingFragment)?;
+        unsafe {
+            // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF.
+            let data = &SPI_TX_BUF[payload::HEADER_LEN + range.start..payload::HEADER_LEN + range.end];
+            self.send_fragment(&fragment, data)
+        }
+    }
+
+    // Send `header` followed by `message` as a payload of type `content_type`.
+    fn send_message<H: ToWire, M: ToWire>(&mut self, content_type: payload::ContentType, header: H, message: M)
+        -> SpiProcessorResult<()> {
         let payload_len : u16;
         unsafe {
             // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF as &mut.
             let mut tx_cursor = SpiutilsCursor::new(&mut SPI_TX_BUF[payload::HEADER_LEN..]);
-
-            let header = error::Header {
-                content: M::TYPE
-            };
             header.to_wire(&mut tx_cursor)?;
-            msg.to_wire(&mut tx_cursor)?;
+            message.to_wire(&mut tx_cursor)?;
             payload_len = u16::try_from(tx_cursor.consumed_len())
-                    .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
+                .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
         }
         unsafe {
             // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF as &mut.
-            self.send_data(payload::ContentType::Error, payload_len, &mut SPI_TX_BUF)?;
+            self.send_data(content_type, payload_len, &mut SPI_TX_BUF)
+        }
+    }
+
+    fn send_error<'m, M: ErrorMessage<'m>>(&mut self, msg: M) -> SpiProcessorResult<()> {
+        self.send_message(payload::ContentType::Error, error::Header { content: M::TYPE }, msg)
+    }
+
+    // Send the error message that corresponds to `err`.
+    // request: The payload content type of the failed request or error::NO_REQUEST.
+    fn send_error_reply(&mut self, request: u8, err: SpiProcessorError) -> SpiProcessorResult<()> {
+        use crate::attestation::AttestationError;
+        use manticore_support::HandlerError;
+
+        let internal_error = |code| error::InternalError { request, code };
+        match err {
+            SpiProcessorError::FromWire(_)
+            | SpiProcessorError::Manticore(HandlerError::FromWire(_))
+            | SpiProcessorError::Manticore(HandlerError::Attestation(AttestationError::Malformed))
+            | SpiProcessorError::Manticore(HandlerError::Update(UpdateError::Malformed))
+            | SpiProcessorError::InvalidAddress(_) => {
+                self.send_error(error::MalformedPayload { request })
+            }
+            SpiProcessorError::UnsupportedFirmwareOperation(content) => {
+                self.send_error(error::UnsupportedOperation { request, operation: content as u8 })
+            }
+            SpiProcessorError::UnsupportedCapabilitiesOperation(content) => {
+                self.send_error(error::UnsupportedOperation { request, operation: content as u8 })
+            }
+            SpiProcessorError::UnsupportedWriteProtectOperation(content) => {
+                self.send_error(error::UnsupportedOperation { request, operation: content as u8 })
+            }
+            SpiProcessorError::UnsupportedMeasuredBootOperation(content) => {
+                self.send_error(error::UnsupportedOperation { request, operation: content as u8 })
+            }
+            SpiProcessorError::UnsupportedOpCode(opcode) => {
+                self.send_error(error::UnsupportedOperation { request, operation: opcode as u8 })
+            }
+            SpiProcessorError::Busy => {
+                self.send_error(error::Busy { request })
+            }
+            SpiProcessorError::ToWire(_)
+            | SpiProcessorError::Manticore(HandlerError::ToWire(_))
+            | SpiProcessorError::Manticore(HandlerError::Attestation(AttestationError::Serialization))
+            | SpiProcessorError::Manticore(HandlerError::Update(UpdateError::Serialization)) => {
+                self.send_error(internal_error(error::InternalErrorCode::Serialization))
+            }
+            SpiProcessorError::Tock => {
+                self.send_error(internal_error(error::InternalErrorCode::Driver))
+            }
+            SpiProcessorError::Manticore(_) => {
+                self.send_error(internal_error(error::InternalErrorCode::Manticore))
+            }
+            SpiProcessorError::NoPendingFragment => {
+                self.send_error(internal_error(error::InternalErrorCode::NoPendingFragment))
+            }
+            SpiProcessorError::Format(_) => {
+                self.send_error(internal_error(error::InternalErrorCode::Format))
+            }
+        }
+    }
+
+    // Answer with an error message if processing a request failed, so that
+    // the host always gets a response.
+    fn reply_on_error(&mut self, result: SpiProcessorResult<()>) -> SpiProcessorResult<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(why) => {
+                println!("SPI processor: Request 0x{:02x} failed: {:?}", self.request, why);
+                self.send_error_reply(self.request, why)
+            }
         }
-        Ok(())
     }
 
     fn process_manticore(&mut self, data: &[u8]) -> SpiProcessorResult<()> {
@@ -166,7 +398,8 @@ impl<'a> SpiProcessor<'a> {
         {
             unsafe {
                 // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF as &mut.
-                let manticore_len = self.manticore_handler.process_request(&data, &mut SPI_TX_BUF[payload::HEADER_LEN..])?;
+                let manticore_len = self.manticore_handler.process_request(
+                    &data, &mut SPI_TX_BUF[payload::HEADER_LEN..], &mut self.firmware)?;
                 payload_len = u16::try_from(manticore_len)
                     .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
             }
@@ -179,24 +412,7 @@ impl<'a> SpiProcessor<'a> {
     }
 
     fn send_firmware_response<'m, M: Message<'m>>(&mut self, response: M) -> SpiProcessorResult<()> {
-        let payload_len : u16;
-        unsafe {
-            // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF as &mut.
-            let mut tx_cursor = SpiutilsCursor::new(&mut SPI_TX_BUF[payload::HEADER_LEN..]);
-
-            let fw_header = firmware::Header {
-                content: M::TYPE
-            };
-            fw_header.to_wire(&mut tx_cursor)?;
-            response.to_wire(&mut tx_cursor)?;
-            payload_len = u16::try_from(tx_cursor.consumed_len())
-                .map_err(|_| SpiProcessorError::FromWire(FromWireError::OutOfRange))?;
-        }
-        unsafe {
-            // TODO(osk): We need the unsafe block since we're accessing SPI_TX_BUF as &mut.
-            self.send_data(payload::ContentType::Firmware, payload_len, &mut SPI_TX_BUF)?;
-        }
-        Ok(())
+        self.send_message(payload::ContentType::Firmware, firmware::Header { content: M::TYPE }, response)
     }
 
     fn process_firmware_inactive_segments(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
@@ -211,40 +427,20 @@ impl<'a> SpiProcessor<'a> {
 
     fn process_firmware_update_prepare(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
         let req = firmware::UpdatePrepareRequest::from_wire(&mut data)?;
-        let segment: SegmentInfo;
-
-        if req.segment_and_location == globalsec::get().get_inactive_rw().identifier {
-            segment = globalsec::get().get_inactive_rw();
-        } else if req.segment_and_location == globalsec::get().get_inactive_ro().identifier {
-            segment = globalsec::get().get_inactive_ro();
-        } else {
-            let response = firmware::UpdatePrepareResponse {
-                segment_and_location: req.segment_and_location,
-                max_chunk_length: 0,
-                result: firmware::UpdatePrepareResult::InvalidSegmentAndLocation,
-            };
-            return self.send_firmware_response(response);
-        }
-
-        match self.firmware.erase_segment(segment) {
-            Ok(()) => {
-                let response = firmware::UpdatePrepareResponse {
-                    segment_and_location: req.segment_and_location,
-                    max_chunk_length: self.firmware.get_max_write_chunk_length() as u16,
-                    result: firmware::UpdatePrepareResult::Success,
-                };
-                self.send_firmware_response(response)
-            },
-            Err(why) => {
-                println!("update_prepare failed: {:?}", why);
-                let response = firmware::UpdatePrepareResponse {
-                    segment_and_location: req.segment_and_location,
-                    max_chunk_length: 0,
-                    result: firmware::UpdatePrepareResult::Error,
-                };
-                self.send_firmware_response(response)
-            }
-        }
+
+        let (max_chunk_length, result) = match self.update.prepare(&mut self.firmware, req.segment_and_location, None) {
+            Ok(_) => (self.firmware.get_max_write_chunk_length() as u16, firmware::UpdatePrepareResult::Success),
+            Err(UpdateError::Blocked) => return Err(SpiProcessorError::Busy),
+            Err(UpdateError::InvalidSegment) => (0, firmware::UpdatePrepareResult::InvalidSegmentAndLocation),
+            Err(_) => (0, firmware::UpdatePrepareResult::Error),
+        };
+
+        let response = firmware::UpdatePrepareResponse {
+            segment_and_location: req.segment_and_location,
+            max_chunk_length,
+            result,
+        };
+        self.send_firmware_response(response)
     }
 
     fn send_firmware_write_chunk_response(&mut self, req: &firmware::WriteChunkRequest, result: firmware::WriteChunkResult) -> SpiProcessorResult<()> {
@@ -261,28 +457,15 @@ impl<'a> SpiProcessor<'a> {
         {
             req = firmware::WriteChunkRequest::from_wire(&mut data)?;
         }
-        let segment: SegmentInfo;
 
-        if req.segment_and_location == globalsec::get().get_inactive_rw().identifier {
-            segment = globalsec::get().get_inactive_rw();
-        } else if req.segment_and_location == globalsec::get().get_inactive_ro().identifier {
-            segment = globalsec::get().get_inactive_ro();
-        } else {
-            return self.send_firmware_write_chunk_response(&req, firmware::WriteChunkResult::InvalidSegmentAndLocation);
-        }
-
-        if req.offset >= segment.size {
-            return self.send_firmware_write_chunk_response(&req, firmware::WriteChunkResult::InvalidOffset);
-        }
-
-        if req.offset + req.data.len() as u32 > segment.size || req.data.len() > self.firmware.get_max_write_chunk_length() {
-            return self.send_firmware_write_chunk_response(&req, firmware::WriteChunkResult::DataTooLong);
-        }
-
-        let result = match self.firmware.write_and_verify_segment_chunk(segment, req.offset as usize, req.data) {
-            Err(_why) => firmware::WriteChunkResult::Error,
-            Ok(false) => firmware::WriteChunkResult::CompareFailed,
-            Ok(true) => firmware::WriteChunkResult::Success,
+        let result = match self.update.write(&mut self.firmware, req.segment_and_location, req.offset as usize, req.data) {
+            Ok(()) => firmware::WriteChunkResult::Success,
+            Err(UpdateError::Blocked) => return Err(SpiProcessorError::Busy),
+            Err(UpdateError::InvalidSegment) => firmware::WriteChunkResult::InvalidSegmentAndLocation,
+            Err(UpdateError::InvalidOffset) => firmware::WriteChunkResult::InvalidOffset,
+            Err(UpdateError::DataTooLong) => firmware::WriteChunkResult::DataTooLong,
+            Err(UpdateError::CompareFailed) => firmware::WriteChunkResult::CompareFailed,
+            Err(_) => firmware::WriteChunkResult::Error,
         };
 
         self.send_firmware_write_chunk_response(&req, result)
@@ -311,14 +494,167 @@ impl<'a> SpiProcessor<'a> {
                 }
             },
             firmware::RebootTime::Delayed => {
-                // TODO(https://github.com/google/tock-on-titan/issues/236): Implement this.
-                firmware::RebootResult::Error
+                if let Err(_) = self.reboot.schedule() {
+                    firmware::RebootResult::Error
+                } else {
+                    firmware::RebootResult::Success
+                }
             },
         };
 
         self.send_firmware_reboot_response(&req, result)
     }
 
+    fn process_firmware_pending_reboot(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let req = firmware::PendingRebootRequest::from_wire(&mut data)?;
+
+        let pending = self.reboot.is_pending();
+        let timeout_msecs = self.reboot.get_remaining_msecs()?
+            .unwrap_or(firmware::NO_REBOOT_TIMEOUT);
+        if req.action == firmware::PendingRebootAction::Cancel {
+            self.reboot.cancel();
+        }
+
+        let response = firmware::PendingRebootResponse {
+            action: req.action,
+            pending: pending,
+            timeout_msecs: timeout_msecs,
+        };
+        self.send_firmware_response(response)
+    }
+
+    fn send_firmware_verify_segment_response(&mut self, req: &firmware::VerifySegmentRequest, result: firmware::VerifySegmentResult, digest: [u8; integrity::SHA256_LEN]) -> SpiProcessorResult<()> {
+        let response = firmware::VerifySegmentResponse {
+            segment_and_location: req.segment_and_location,
+            length: req.length,
+            result: result,
+            digest: digest,
+        };
+        self.send_firmware_response(response)
+    }
+
+    fn process_firmware_verify_segment(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let req = firmware::VerifySegmentRequest::from_wire(&mut data)?;
+        let segment = match firmware_update::get_inactive_segment(req.segment_and_location) {
+            Some(segment) => segment,
+            None => {
+                return self.send_firmware_verify_segment_response(&req, firmware::VerifySegmentResult::InvalidSegmentAndLocation, [0u8; integrity::SHA256_LEN]);
+            }
+        };
+
+        if req.length > segment.size {
+            return self.send_firmware_verify_segment_response(&req, firmware::VerifySegmentResult::InvalidLength, [0u8; integrity::SHA256_LEN]);
+        }
+
+        match self.firmware.compute_segment_digest(segment, req.length as usize) {
+            Ok(digest) => {
+                let result = if digest == req.digest {
+                    firmware::VerifySegmentResult::Match
+                } else {
+                    firmware::VerifySegmentResult::Mismatch
+                };
+                self.send_firmware_verify_segment_response(&req, result, digest)
+            },
+            Err(why) => {
+                println!("verify_segment failed: {:?}", why);
+                self.send_firmware_verify_segment_response(&req, firmware::VerifySegmentResult::Error, [0u8; integrity::SHA256_LEN])
+            }
+        }
+    }
+
+    // Get the segment for the identifier if it may be read.
+    fn get_readable_segment(&self, identifier: firmware::SegmentAndLocation) -> Result<SegmentInfo, firmware::ReadChunkResult> {
+        let segments = [
+            (globalsec::get().get_active_ro(), self.read_access.active_ro),
+            (globalsec::get().get_active_rw(), self.read_access.active_rw),
+            (globalsec::get().get_inactive_ro(), self.read_access.inactive_ro),
+            (globalsec::get().get_inactive_rw(), self.read_access.inactive_rw),
+        ];
+        for (segment, readable) in segments.iter() {
+            if segment.identifier == identifier {
+                if !readable {
+                    return Err(firmware::ReadChunkResult::AccessDenied);
+                }
+                return Ok(*segment);
+            }
+        }
+        Err(firmware::ReadChunkResult::InvalidSegmentAndLocation)
+    }
+
+    fn send_firmware_read_chunk_response(&mut self, req: &firmware::ReadChunkRequest, result: firmware::ReadChunkResult, data: &[u8]) -> SpiProcessorResult<()> {
+        let response = firmware::ReadChunkResponse {
+            segment_and_location: req.segment_and_location,
+            offset: req.offset,
+            result: result,
+            data: data,
+        };
+        self.send_firmware_response(response)
+    }
+
+    fn process_firmware_read_chunk(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let req = firmware::ReadChunkRequest::from_wire(&mut data)?;
+
+        let segment = match self.get_readable_segment(req.segment_and_location) {
+            Ok(segment) => segment,
+            Err(result) => return self.send_firmware_read_chunk_response(&req, result, &[]),
+        };
+
+        if req.offset >= segment.size {
+            return self.send_firmware_read_chunk_response(&req, firmware::ReadChunkResult::InvalidOffset, &[]);
+        }
+
+        // Truncate the read at the end of the segment and to what fits into the mailbox.
+        let length = min(min(req.length as usize, (segment.size - req.offset) as usize), MAX_READ_CHUNK_LEN);
+        unsafe {
+            // TODO(osk): We need the unsafe block since we're accessing READ_CHUNK_BUF as &mut.
+            match self.firmware.read_segment_chunk(segment, req.offset as usize, &mut READ_CHUNK_BUF[..length]) {
+                Ok(()) => {
+                    return self.send_firmware_read_chunk_response(&req, firmware::ReadChunkResult::Success, &READ_CHUNK_BUF[..length]);
+                },
+                Err(why) => {
+                    println!("read_chunk failed: {:?}", why);
+                }
+            }
+        }
+
+        self.send_firmware_read_chunk_response(&req, firmware::ReadChunkResult::Error, &[])
+    }
+
+    fn get_firmware_info_entry(&self, segment: SegmentInfo) -> firmware::FirmwareInfoEntry {
+        let (status, build_info) = match firmware_controller::get_build_info(segment) {
+            Ok(build_info) if build_info.is_blank() => (firmware::FirmwareInfoStatus::Blank, build_info),
+            Ok(build_info) => (firmware::FirmwareInfoStatus::Valid, build_info),
+            Err(_) => {
+                let build_info = BuildInfo {
+                    epoch: 0,
+                    major: 0,
+                    minor: 0,
+                    timestamp: 0,
+                };
+                (firmware::FirmwareInfoStatus::Unreadable, build_info)
+            },
+        };
+        firmware::FirmwareInfoEntry {
+            status: status,
+            info: firmware::FirmwareInfo {
+                segment_and_location: segment.identifier,
+                build_info: build_info,
+            },
+        }
+    }
+
+    fn process_firmware_info(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let _ = firmware::FirmwareInfoRequest::from_wire(&mut data)?;
+
+        let response = firmware::FirmwareInfoResponse {
+            active_ro: self.get_firmware_info_entry(globalsec::get().get_active_ro()),
+            active_rw: self.get_firmware_info_entry(globalsec::get().get_active_rw()),
+            inactive_ro: self.get_firmware_info_entry(globalsec::get().get_inactive_ro()),
+            inactive_rw: self.get_firmware_info_entry(globalsec::get().get_inactive_rw()),
+        };
+        self.send_firmware_response(response)
+    }
+
     fn process_firmware(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
         let header = firmware::Header::from_wire(&mut data)?;
 
@@ -335,6 +671,18 @@ impl<'a> SpiProcessor<'a> {
             firmware::ContentType::RebootRequest => {
                 self.process_firmware_reboot(&mut data)
             },
+            firmware::ContentType::VerifySegmentRequest => {
+                self.process_firmware_verify_segment(&mut data)
+            },
+            firmware::ContentType::ReadChunkRequest => {
+                self.process_firmware_read_chunk(&mut data)
+            },
+            firmware::ContentType::FirmwareInfoRequest => {
+                self.process_firmware_info(&mut data)
+            },
+            firmware::ContentType::PendingRebootRequest => {
+                self.process_firmware_pending_reboot(&mut data)
+            },
             _ => {
                 Err(SpiProcessorError::UnsupportedFirmwareOperation(header.content))
             }
@@ -343,19 +691,168 @@ impl<'a> SpiProcessor<'a> {
         result
     }
 
-    fn process_spi_payload(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
-        let header = payload::Header::from_wire(&mut data)?;
-        if header.checksum != payload::compute_checksum(&header, data) {
-            let error = error::BadChecksum {};
-            return self.send_error(error);
+    fn send_capabilities_response<'m, M: CapabilitiesMessage<'m>>(&mut self, response: M) -> SpiProcessorResult<()> {
+        self.send_message(payload::ContentType::Capabilities, capabilities::Header { content: M::TYPE }, response)
+    }
+
+    fn process_capabilities_request(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let _ = capabilities::CapabilitiesRequest::from_wire(&mut data)?;
+
+        let response = capabilities::CapabilitiesResponse {
+            protocol_version: capabilities::PROTOCOL_VERSION,
+            firmware_content_types: capabilities::firmware_content_type_mask(&SUPPORTED_FIRMWARE_CONTENT_TYPES),
+            mailbox_size: SPI_MAILBOX_SIZE,
+            max_chunk_length: self.firmware.get_max_write_chunk_length() as u16,
+        };
+        self.send_capabilities_response(response)
+    }
+
+    fn process_capabilities(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let header = capabilities::Header::from_wire(&mut data)?;
+
+        match header.content {
+            capabilities::ContentType::CapabilitiesRequest => {
+                self.process_capabilities_request(&mut data)
+            },
+            _ => {
+                Err(SpiProcessorError::UnsupportedCapabilitiesOperation(header.content))
+            }
+        }
+    }
+
+    fn send_write_protect_response<'m, M: WriteProtectMessage<'m>>(&mut self, response: M) -> SpiProcessorResult<()> {
+        self.send_message(payload::ContentType::WriteProtect, write_protect::Header { content: M::TYPE }, response)
+    }
+
+    fn process_write_protect_get_policy(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let _ = write_protect::GetPolicyRequest::from_wire(&mut data)?;
+
+        let response = write_protect::GetPolicyResponse {
+            policy: self.write_protect.policy(),
+            denied_count: self.write_protect.denied_count(),
+        };
+        self.send_write_protect_response(response)
+    }
+
+    fn process_write_protect_set_policy(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let req = write_protect::SetPolicyRequest::from_wire(&mut data)?;
+
+        let response = write_protect::SetPolicyResponse {
+            result: self.write_protect.set_policy(req.policy),
+        };
+        self.send_write_protect_response(response)
+    }
+
+    fn process_write_protect(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let header = write_protect::Header::from_wire(&mut data)?;
+
+        match header.content {
+            write_protect::ContentType::GetPolicyRequest => {
+                self.process_write_protect_get_policy(&mut data)
+            },
+            write_protect::ContentType::SetPolicyRequest => {
+                self.process_write_protect_set_policy(&mut data)
+            },
+            _ => {
+                Err(SpiProcessorError::UnsupportedWriteProtectOperation(header.content))
+            }
         }
+    }
+
+    fn send_measured_boot_response<'m, M: MeasuredBootMessage<'m>>(&mut self, response: M) -> SpiProcessorResult<()> {
+        self.send_message(payload::ContentType::MeasuredBoot, measured_boot::Header { content: M::TYPE }, response)
+    }
+
+    fn process_measured_boot_get_manifest(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let _ = measured_boot::GetManifestRequest::from_wire(&mut data)?;
+
+        let response = measured_boot::GetManifestResponse {
+            manifest: self.measured_boot.manifest(),
+            result: self.measured_boot.result(),
+            failed_region: self.measured_boot.failed_region(),
+        };
+        self.send_measured_boot_response(response)
+    }
+
+    fn process_measured_boot_set_manifest(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let req = measured_boot::SetManifestRequest::from_wire(&mut data)?;
+
+        let response = measured_boot::SetManifestResponse {
+            result: self.measured_boot.set_manifest(req.manifest),
+        };
+        self.send_measured_boot_response(response)
+    }
+
+    fn process_measured_boot_get_recovery_status(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let _ = measured_boot::GetRecoveryStatusRequest::from_wire(&mut data)?;
+
+        let response = self.measured_boot.recovery_status();
+        self.send_measured_boot_response(response)
+    }
+
+    fn process_measured_boot(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let header = measured_boot::Header::from_wire(&mut data)?;
 
         match header.content {
+            measured_boot::ContentType::GetManifestRequest => {
+                self.process_measured_boot_get_manifest(&mut data)
+            },
+            measured_boot::ContentType::SetManifestRequest => {
+                self.process_measured_boot_set_manifest(&mut data)
+            },
+            measured_boot::ContentType::GetRecoveryStatusRequest => {
+                self.process_measured_boot_get_recovery_status(&mut data)
+            },
+            _ => {
+                Err(SpiProcessorError::UnsupportedMeasuredBootOperation(header.content))
+            }
+        }
+    }
+
+    fn process_fragment(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let fragment = payload::FragmentHeader::from_wire(&mut data)?;
+
+        // The host acknowledges each fragment of a response to request the next one.
+        if self.fragmenter.is_ack(&fragment, data) {
+            return self.send_next_fragment();
+        }
+        self.fragmenter.reset();
+
+        let result = unsafe {
+            // TODO(osk): We need the unsafe block since we're accessing SPI_RX_MESSAGE_BUF as &mut.
+            self.reassembler.add(&mut SPI_RX_MESSAGE_BUF, &fragment, data)
+        };
+        match result {
+            Ok(None) => self.send_fragment(&fragment.ack(), &[]),
+            Ok(Some(len)) => unsafe {
+                // TODO(osk): We need the unsafe block since we're accessing SPI_RX_MESSAGE_BUF.
+                self.process_message(fragment.content, &SPI_RX_MESSAGE_BUF[..len])
+            },
+            Err(why) => {
+                println!("Fragment reassembly failed: {:?}", why);
+                let error = error::BadFragment {};
+                self.send_error(error)
+            }
+        }
+    }
+
+    fn process_message(&mut self, content: payload::ContentType, data: &[u8]) -> SpiProcessorResult<()> {
+        self.request = content as u8;
+        match content {
             payload::ContentType::Manticore => {
-                self.process_manticore(&data[..header.content_len as usize])
+                self.process_manticore(data)
             }
             payload::ContentType::Firmware => {
-                self.process_firmware(&data[..header.content_len as usize])
+                self.process_firmware(data)
+            }
+            payload::ContentType::Capabilities => {
+                self.process_capabilities(data)
+            }
+            payload::ContentType::WriteProtect => {
+                self.process_write_protect(data)
+            }
+            payload::ContentType::MeasuredBoot => {
+                self.process_measured_boot(data)
             }
             _ => {
                 let error = error::ContentTypeNotSupported {};
@@ -364,6 +861,60 @@ impl<'a> SpiProcessor<'a> {
         }
     }
 
+    fn process_spi_payload(&mut self, data: &[u8]) -> SpiProcessorResult<()> {
+        // The first byte of the payload header is the content type.
+        self.request = data.first().copied().unwrap_or(error::NO_REQUEST);
+        self.integrity = None;
+        let result = self.process_spi_payload_content(data);
+        self.reply_on_error(result)
+    }
+
+    fn process_spi_payload_content(&mut self, mut data: &[u8]) -> SpiProcessorResult<()> {
+        let header = payload::Header::from_wire(&mut data)?;
+        if header.content_len as usize > data.len() {
+            return Err(SpiProcessorError::FromWire(FromWireError::OutOfRange));
+        }
+        if header.checksum != payload::compute_checksum(&header, data) {
+            let error = error::BadChecksum {};
+            return self.send_error(error);
+        }
+
+        let mut content_type = header.content;
+        let mut content = &data[..header.content_len as usize];
+        if content_type == payload::ContentType::Extended {
+            match payload::parse_extended(content) {
+                Ok((extended, extended_content)) => {
+                    self.integrity = Some(extended.integrity);
+                    content_type = extended.content;
+                    content = extended_content;
+                }
+                Err(payload::ExtendedError::BadIntegrity(mode)) => {
+                    self.integrity = Some(mode);
+                    let error = error::BadChecksum {};
+                    return self.send_error(error);
+                }
+                Err(payload::ExtendedError::UnsupportedVersion(version)) => {
+                    println!("Unsupported extended header version: {}", version);
+                    let error = error::ContentTypeNotSupported {};
+                    return self.send_error(error);
+                }
+                Err(payload::ExtendedError::FromWire(why)) => {
+                    // The extended header or the trailer is truncated or corrupt.
+                    return Err(SpiProcessorError::FromWire(why));
+                }
+            }
+        }
+
+        if content_type == payload::ContentType::Fragment {
+            return self.process_fragment(content);
+        }
+
+        // An unfragmented request aborts any fragmented transfer in progress.
+        self.reassembler.reset();
+        self.fragmenter.reset();
+        self.process_message(content_type, content)
+    }
+
     // Send data via the SPI host.
     // The transaction is split into smaller transactions that fit into the SPI host's buffer.
     // The write enable status bit is set before each transaction is executed.
@@ -382,12 +933,8 @@ impl<'a> SpiProcessor<'a> {
             let data_len_to_send : usize;
             {
                 let mut tx_cursor = SpiutilsCursor::new(&mut tx_buf);
+                // This includes any dummy bytes required by the op code.
                 header.to_wire(&mut tx_cursor)?;
-                if header.opcode.has_dummy_byte() {
-                    // Skip one dummy byte (send 0x0)
-                    tx_cursor.write_bytes(&[0x0; 1])
-                        .map_err(|err| SpiProcessorError::ToWire(ToWireError::Io(err)))?;
-                }
 
                 data_len_to_send = min(spi_host::MAX_READ_BUFFER_LENGTH - tx_cursor.consumed_len(), data.len());
                 tx_cursor.write_bytes(&data[..data_len_to_send])
@@ -447,13 +994,10 @@ impl<'a> SpiProcessor<'a> {
 
     fn process_spi_header<AddrType>(&mut self, header: &spi_flash::Header::<AddrType>, rx_buf: &[u8]) -> SpiProcessorResult<()>
     where AddrType: Address {
-        let mut data: &[u8] = rx_buf;
-        if header.opcode.has_dummy_byte() {
-            // Skip dummy byte
-            data = &rx_buf[1..];
-        }
+        // The header has already consumed any dummy bytes.
+        let data: &[u8] = rx_buf;
         match header.opcode {
-            OpCode::PageProgram => {
+            OpCode::PageProgram | OpCode::PageProgram4B => {
                 match header.get_address() {
                     Some(addr) if self.is_mailbox_address(addr) => {
                         if spi_device::get().is_write_enable_set() {
@@ -462,7 +1006,8 @@ impl<'a> SpiProcessor<'a> {
                         self.clear_device_status(true, true)
                     }
                     Some(addr) if !self.is_mailbox_address(addr) => {
-                        if spi_device::get().is_write_enable_set() {
+                        if spi_device::get().is_write_enable_set()
+                            && self.write_protect.allows(header.opcode, Some(addr), data.len()) {
                             // Pass through to SPI host
                             self.spi_host_write(header, data)?;
                         }
@@ -471,14 +1016,16 @@ impl<'a> SpiProcessor<'a> {
                     _ => return Err(SpiProcessorError::InvalidAddress(header.get_address())),
                 }
             }
-            OpCode::SectorErase | OpCode::BlockErase32KB | OpCode::BlockErase64KB => {
+            OpCode::SectorErase | OpCode::BlockErase32KB | OpCode::BlockErase64KB
+                | OpCode::SectorErase4B | OpCode::BlockErase64KB4B => {
                 match header.get_address() {
                     Some(addr) if self.is_mailbox_address(addr) => {
                         // Nothing to do.
                         self.clear_device_status(true, true)
                     }
                     Some(addr) if !self.is_mailbox_address(addr) => {
-                        if spi_device::get().is_write_enable_set() {
+                        if spi_device::get().is_write_enable_set()
+                            && self.write_protect.allows(header.opcode, Some(addr), data.len()) {
                             // Pass through to SPI host
                             self.spi_host_write(header, data)?;
                         }
@@ -488,18 +1035,45 @@ impl<'a> SpiProcessor<'a> {
                 }
             }
             OpCode::ChipErase | OpCode::ChipErase2 => {
-                if spi_device::get().is_write_enable_set() {
+                if spi_device::get().is_write_enable_set()
+                    && self.write_protect.allows(header.opcode, None, data.len()) {
                     // Pass through to SPI host
                     self.spi_host_write(header, data)?;
                 }
                 self.clear_device_status(true, true)
             }
+            OpCode::ResetEnable | OpCode::DeepPowerDown => {
+                // Pass through to SPI host
+                self.spi_host_send(header, data, &|| Ok(()))?;
+                self.clear_device_status(true, false)
+            }
+            OpCode::Reset => {
+                // Pass through to SPI host
+                self.spi_host_send(header, data, &|| Ok(()))?;
+
+                // The reset also aborts any mailbox transfer in progress.
+                self.reassembler.reset();
+                self.fragmenter.reset();
+                self.clear_device_status(true, true)
+            }
+            OpCode::ReadStatusRegister2 | OpCode::ReadStatusRegister3 | OpCode::ReadFlagStatusRegister
+                | OpCode::FastReadQuadOutput | OpCode::FastReadQuadIO
+                | OpCode::FastReadQuadOutput4B | OpCode::FastReadQuadIO4B => {
+                // Reads are served by the SPI flash via passthrough.
+                // Nothing to do.
+                self.clear_device_status(true, false)
+            }
             _ => return Err(SpiProcessorError::UnsupportedOpCode(header.opcode)),
         }
     }
 
-    pub fn process_spi_packet(&mut self, mut rx_buf: &[u8]) -> SpiProcessorResult<()> {
-        match spi_device::get().get_address_mode() {
+    fn process_spi_flash_packet(&mut self, mut rx_buf: &[u8]) -> SpiProcessorResult<()> {
+        // Some op codes use a fixed address length regardless of the address mode.
+        let address_mode = rx_buf.first()
+            .and_then(|opcode| OpCode::from_wire_value(*opcode))
+            .map_or(spi_device::get().get_address_mode(),
+                |opcode| opcode.address_mode(spi_device::get().get_address_mode()));
+        match address_mode {
             AddressMode::ThreeByte => {
                 let header = spi_flash::Header::<ux::u24>::from_wire(&mut rx_buf)?;
                 if self.print_flash_headers {
@@ -516,4 +1090,12 @@ impl<'a> SpiProcessor<'a> {
             }
         }
     }
+
+    // Errors of SPI flash commands are not answered in the mailbox, which may
+    // hold the pending response to a mailbox request. The caller reports them
+    // by ending the transaction with the busy bit cleared. Mailbox requests
+    // have already been answered when an error is returned here.
+    pub fn process_spi_packet(&mut self, rx_buf: &[u8]) -> SpiProcessorResult<()> {
+        self.process_spi_flash_packet(rx_buf)
+    }
 }
@@ -0,0 +1,184 @@
+// Copyright 2021 lowRISC contributors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::nvmem;
+
+use core::cell::Cell;
+
+use libtock::println;
+
+use spiutils::io::Cursor as SpiutilsCursor;
+use spiutils::protocol::flash::OpCode;
+use spiutils::protocol::wire::FromWire;
+use spiutils::protocol::wire::ToWire;
+use spiutils::protocol::write_protect::Policy;
+use spiutils::protocol::write_protect::Range;
+use spiutils::protocol::write_protect::RangeKind;
+use spiutils::protocol::write_protect::SetPolicyResult;
+use spiutils::protocol::write_protect::POLICY_LEN;
+use spiutils::protocol::write_protect::UNRESTRICTED_POLICY;
+
+// The policy used if a provisioned policy cannot be read.
+// Denies all PageProgram and erase commands until a valid policy is
+// provisioned.
+fn deny_all_policy() -> Policy {
+    let mut policy = UNRESTRICTED_POLICY;
+    policy.deny_chip_erase = true;
+    // This leaves out the last byte of the 32-bit address space, which is
+    // beyond the largest supported SPI flash chip.
+    let _ = policy.add_range(Range {
+        kind: RangeKind::ReadOnly,
+        start: 0,
+        size: u32::max_value(),
+    });
+    policy
+}
+
+/// Enforces the write protection policy on PageProgram and erase commands
+/// passed through to the SPI flash chip.
+///
+/// The BMC can only provision a policy after provisioning has been enabled
+/// on the console, which also offers to unlock a locked policy.
+pub struct WriteProtect {
+    policy: Cell<Policy>,
+
+    // The number of commands denied since boot.
+    denied_count: Cell<u32>,
+
+    // Whether the next SetPolicy request is accepted.
+    provisioning: Cell<bool>,
+}
+
+impl WriteProtect {
+    /// Load the policy persisted in NVMEM.
+    /// All commands are allowed if no policy has been provisioned.
+    pub fn load() -> WriteProtect {
+        let mut buf = [0u8; POLICY_LEN];
+        let policy = match nvmem::read(nvmem::Slot::WriteProtectPolicy, &mut buf) {
+            Ok(Some(len)) => match Policy::from_wire(&buf[..len]) {
+                Ok(policy) => policy,
+                Err(why) => {
+                    println!("Write protect: Invalid policy: {:?}", why);
+                    deny_all_policy()
+                }
+            },
+            Ok(None) => UNRESTRICTED_POLICY,
+            Err(_) => {
+                println!("Write protect: Could not read policy");
+                deny_all_policy()
+            }
+        };
+        println!("Write protect: {} range(s), chip erase {}, {}",
+            policy.ranges().len(),
+            if policy.allows_chip_erase() { "allowed" } else { "denied" },
+            if policy.locked { "locked" } else { "unlocked" });
+        WriteProtect {
+            policy: Cell::new(policy),
+            denied_count: Cell::new(0),
+            provisioning: Cell::new(false),
+        }
+    }
+
+    /// The active policy.
+    pub fn policy(&self) -> Policy {
+        self.policy.get()
+    }
+
+    /// The number of commands denied since boot.
+    pub fn denied_count(&self) -> u32 {
+        self.denied_count.get()
+    }
+
+    /// Accept the next SetPolicy request.
+    /// Only the console may call this.
+    pub fn enable_provisioning(&self) {
+        self.provisioning.set(true);
+    }
+
+    // Persist `policy` in NVMEM and activate it.
+    fn store_policy(&self, policy: Policy) -> SetPolicyResult {
+        let mut buf = [0u8; POLICY_LEN];
+        let mut cursor = SpiutilsCursor::new(&mut buf);
+        if policy.to_wire(&mut cursor).is_err() {
+            return SetPolicyResult::StorageError;
+        }
+        let len = cursor.consumed_len();
+        if nvmem::write(nvmem::Slot::WriteProtectPolicy, &buf[..len]).is_err() {
+            println!("Write protect: Could not persist policy");
+            return SetPolicyResult::StorageError;
+        }
+
+        self.policy.set(policy);
+        SetPolicyResult::Success
+    }
+
+    /// Persist `policy` in NVMEM and activate it.
+    /// Fails unless provisioning has been enabled, or if the active policy
+    /// is locked. Provisioning is disabled again once a policy is stored.
+    pub fn set_policy(&self, policy: Policy) -> SetPolicyResult {
+        if !self.provisioning.get() {
+            return SetPolicyResult::NotProvisioning;
+        }
+        if self.policy.get().locked {
+            return SetPolicyResult::Locked;
+        }
+
+        let result = self.store_policy(policy);
+        if result == SetPolicyResult::Success {
+            self.provisioning.set(false);
+            println!("Write protect: Provisioned {} range(s)", policy.ranges().len());
+        }
+        result
+    }
+
+    /// Unlock the active policy so that it can be replaced.
+    /// This is the recovery path for a locked policy and is only offered on
+    /// the console.
+    pub fn unlock(&self) -> SetPolicyResult {
+        let mut policy = self.policy.get();
+        if !policy.locked {
+            return SetPolicyResult::Success;
+        }
+        policy.locked = false;
+        self.store_policy(policy)
+    }
+
+    /// Check whether the policy allows the command `opcode` with `data_len`
+    /// bytes of data at `address`.
+    /// Denied commands are counted and logged.
+    pub fn allows(&self, opcode: OpCode, address: Option<u32>, data_len: usize) -> bool {
+        let policy = self.policy.get();
+        let allowed = match opcode {
+            OpCode::ChipErase | OpCode::ChipErase2 => policy.allows_chip_erase(),
+            _ => match (address, opcode.erase_size()) {
+                (Some(addr), Some(size)) => policy.allows_erase(addr & !(size - 1), size),
+                (Some(addr), None) => policy.allows_program(addr, data_len as u32),
+                (None, _) => false,
+            },
+        };
+        if !allowed {
+            let denied_count = self.denied_count.get().wrapping_add(1);
+            self.denied_count.set(denied_count);
+            match address {
+                Some(addr) => println!("Write protect: Denied {:?} at 0x{:x} ({} denied)",
+                    opcode, addr, denied_count),
+                None => println!("Write protect: Denied {:?} ({} denied)",
+                    opcode, denied_count),
+            }
+        }
+        allowed
+    }
+}